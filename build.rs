@@ -18,13 +18,17 @@ fn main() -> Result<()> {
     paths_to_copy.push("res/config/meshes.ron");
     paths_to_copy.push("res/config/textures.ron");
     paths_to_copy.push("res/config/models.ron");
+    paths_to_copy.push("res/config/pipelines.ron");
     paths_to_copy.push("res/meshes");
+    paths_to_copy.push("res/shaders");
     paths_to_copy.push("res/textures");
     copy_items(&paths_to_copy, out_dir.clone(), &copy_options)?;
 
+    // copy_items copies each item into OUT_DIR by its file name, without the source
+    // directories: the configuration is at OUT_DIR/cfg.ron (not OUT_DIR/res/config/cfg.ron)
     let conf_path = String::from(
         Path::new(&out_dir)
-            .join("res/config/cfg.ron")
+            .join("cfg.ron")
             .to_owned()
             .to_str()
             .unwrap_or(""),
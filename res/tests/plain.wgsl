@@ -0,0 +1,13 @@
+// Trivial shader of the custom pipeline tests: a plain color, without buffer or bind group
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    let x = f32(i32(index & 1u) * 2 - 1);
+    let y = f32(i32(index >> 1u) * 2 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main() -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(1.0, 0.5, 0.0, 1.0);
+}
@@ -1,16 +1,35 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use serde::Deserialize;
 use winit::dpi::{PhysicalSize, Size};
 
+use crate::{
+    render::{error::ResourcesError, scene::camera::Easing},
+    APP_NAME,
+};
+
+use super::input::InputMap;
+
 static CONF_PATH: &'static str = env!("APP_CONF_FILE_PATH");
 
 /// Window configuration
-#[derive(Copy, Clone, Deserialize, Debug)]
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
 pub struct WindowConfig {
     pub height: u32,
     pub width: u32,
     pub maximized: bool,
+    pub title: String,
+    /// image file of the window icon
+    pub icon_path: Option<PathBuf>,
+    /// smallest inner size the window can be resized to
+    pub min_width: u32,
+    pub min_height: u32,
+    /// width / height ratio kept by the resizes, free if not set
+    pub lock_aspect: Option<f32>,
 }
 
 impl Default for WindowConfig {
@@ -19,6 +38,33 @@ impl Default for WindowConfig {
             height: 300,
             width: 400,
             maximized: false,
+            title: APP_NAME.to_string(),
+            icon_path: None,
+            min_width: 200,
+            min_height: 200,
+            lock_aspect: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn min_size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.min_width, self.min_height)
+    }
+
+    /// The largest size of the locked aspect ratio fitting in the requested one (unchanged if
+    /// the aspect is not locked or the size is empty)
+    pub fn locked_size(&self, size: PhysicalSize<u32>) -> PhysicalSize<u32> {
+        let aspect = match self.lock_aspect {
+            Some(aspect) if aspect > 0.0 && size.width > 0 && size.height > 0 => aspect,
+            _ => return size,
+        };
+        let width = (size.height as f32 * aspect).round() as u32;
+        if width <= size.width {
+            PhysicalSize::new(width.max(1), size.height)
+        } else {
+            let height = (size.width as f32 / aspect).round() as u32;
+            PhysicalSize::new(size.width, height.clamp(1, size.height))
         }
     }
 }
@@ -40,11 +86,14 @@ impl From<WindowConfig> for Size {
 /// - textures for textures images
 /// - configuration files listing the resources and their information
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct ResourcesConfig {
     /// textures as images
     pub textures_directory: String,
     /// meshes as (.obj) files
     pub meshes_directory: String,
+    /// WGSL files of the pipelines descriptors
+    pub shaders_directory: String,
     /// file path of the list of models (use mesh + material ...)
     pub models_cfg: String,
     /// file path of the list of meshes
@@ -53,31 +102,542 @@ pub struct ResourcesConfig {
     pub materials_cfg: String,
     /// file path of the list of textures
     pub textures_cfg: String,
+    /// file path of the list of pipelines (built at runtime from a WGSL file)
+    pub pipelines_cfg: String,
+    /// load every described model when the scene starts, rather than on first use
+    pub preload_models: bool,
+    /// exit when some of the scene objects cannot be loaded, rather than drawing the others
+    pub abort_on_object_failure: bool,
 }
 
 fn tostring(pathbuf: PathBuf) -> String {
     pathbuf.into_os_string().into_string().unwrap()
 }
 
+impl ResourcesConfig {
+    /// Check that the resources directories and descriptors files exist.
+    /// The error lists every missing path.
+    pub fn validate(&self) -> Result<(), ResourcesError> {
+        let directories = [
+            ("textures directory", &self.textures_directory),
+            ("meshes directory", &self.meshes_directory),
+            ("shaders directory", &self.shaders_directory),
+        ];
+        let files = [
+            ("models file", &self.models_cfg),
+            ("meshes file", &self.meshes_cfg),
+            ("materials file", &self.materials_cfg),
+            ("textures file", &self.textures_cfg),
+            ("pipelines file", &self.pipelines_cfg),
+        ];
+        let missing: Vec<String> = directories
+            .iter()
+            .filter(|(_, path)| !Path::new(path).is_dir())
+            .chain(files.iter().filter(|(_, path)| !Path::new(path).is_file()))
+            .map(|(kind, path)| format!("{} {}", kind, path))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(ResourcesError::MissingPaths { missing })
+        }
+    }
+}
+
 impl Default for ResourcesConfig {
     fn default() -> Self {
         let out_dir: PathBuf = PathBuf::from(env!("OUT_DIR").to_string());
         Self {
             textures_directory: tostring(out_dir.join("textures")),
             meshes_directory: tostring(out_dir.join("meshes")),
+            shaders_directory: tostring(out_dir.join("shaders")),
             models_cfg: tostring(out_dir.join("models.ron")),
             meshes_cfg: tostring(out_dir.join("meshes.ron")),
             materials_cfg: tostring(out_dir.join("materials.ron")),
             textures_cfg: tostring(out_dir.join("textures.ron")),
+            pipelines_cfg: tostring(out_dir.join("pipelines.ron")),
+            preload_models: false,
+            abort_on_object_failure: false,
+        }
+    }
+}
+
+/// Anti-aliasing method
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntiAliasing {
+    #[default]
+    None,
+    /// multisampling with the given samples count, set at startup: the scene pipelines are
+    /// built for it
+    Msaa(u32),
+    /// screen space smoothing, cheap on integrated GPUs
+    Fxaa,
+}
+
+impl AntiAliasing {
+    /// Samples count of the scene color and depth targets: 4 for any multisampling, the only
+    /// count the render passes support besides 1
+    pub fn sample_count(self) -> u32 {
+        match self {
+            AntiAliasing::Msaa(samples) if samples > 1 => {
+                if samples != 4 {
+                    log::warn!("MSAA x{} not supported, using x4", samples);
+                }
+                4
+            }
+            _ => 1,
+        }
+    }
+}
+
+/// Surface presentation mode
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentMode {
+    /// vertical sync
+    Fifo,
+    /// no tearing, the latest frame is shown (falls back to `Fifo` if unsupported)
+    Mailbox,
+    /// no vertical sync, tearing (falls back to `Fifo` if unsupported)
+    #[default]
+    Immediate,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+/// Render options adjustable while running (see the ui performance panel)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub present_mode: PresentMode,
+    pub anti_aliasing: AntiAliasing,
+    /// linear rgb
+    pub clear_color: [f32; 3],
+    /// frames per second limit, unlimited if not set
+    pub max_fps: Option<u32>,
+}
+
+/// Depth buffer format
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthFormat {
+    #[default]
+    Depth32Float,
+    /// usually preferred by the mobile (tiler) GPUs
+    Depth24Plus,
+    Depth24PlusStencil8,
+}
+
+impl From<DepthFormat> for wgpu::TextureFormat {
+    fn from(format: DepthFormat) -> Self {
+        match format {
+            DepthFormat::Depth32Float => wgpu::TextureFormat::Depth32Float,
+            DepthFormat::Depth24Plus => wgpu::TextureFormat::Depth24Plus,
+            DepthFormat::Depth24PlusStencil8 => wgpu::TextureFormat::Depth24PlusStencil8,
+        }
+    }
+}
+
+/// Depth bias of a pipeline, in the units of `wgpu::DepthBiasState`: negative values pull the
+/// geometries towards the camera
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct DepthBiasConfig {
+    /// in smallest depth steps
+    pub constant: i32,
+    /// times the depth slope of the triangle, the grazing surfaces need more bias
+    pub slope_scale: f32,
+    /// largest bias applied, unclamped if 0
+    pub clamp: f32,
+}
+
+/// Pulls the decals in front of the coplanar surface they are laid on
+impl Default for DepthBiasConfig {
+    fn default() -> Self {
+        Self {
+            constant: -2,
+            slope_scale: -2.0,
+            clamp: 0.0,
+        }
+    }
+}
+
+impl From<DepthBiasConfig> for wgpu::DepthBiasState {
+    fn from(bias: DepthBiasConfig) -> Self {
+        Self {
+            constant: bias.constant,
+            slope_scale: bias.slope_scale,
+            clamp: bias.clamp,
+        }
+    }
+}
+
+/// Window corner
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Ui colors theme
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Ui look configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UiConfig {
+    pub theme: Theme,
+    /// selection and links color (srgb), the theme one if not set
+    pub accent_color: Option<[u8; 3]>,
+    /// body text size (points), the other text styles are scaled alike
+    pub font_size: Option<f32>,
+    /// multiplies the window scale factor
+    pub scale: f32,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            accent_color: None,
+            font_size: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl UiConfig {
+    pub fn style(&self) -> egui::Style {
+        let mut visuals = match self.theme {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        };
+        if let Some([r, g, b]) = self.accent_color {
+            let accent = egui::Color32::from_rgb(r, g, b);
+            visuals.selection.bg_fill = accent;
+            visuals.hyperlink_color = accent;
+        }
+        egui::Style {
+            visuals,
+            ..Default::default()
+        }
+    }
+
+    pub fn font_definitions(&self) -> egui::FontDefinitions {
+        let mut fonts = egui::FontDefinitions::default();
+        let body_size = fonts
+            .family_and_size
+            .get(&egui::TextStyle::Body)
+            .map(|(_, size)| *size);
+        if let (Some(font_size), Some(body_size)) = (self.font_size, body_size) {
+            let ratio = font_size / body_size;
+            fonts
+                .family_and_size
+                .values_mut()
+                .for_each(|(_, size)| *size *= ratio);
+        }
+        fonts
+    }
+
+    /// Ui scale factor for the given window scale factor (an invalid scale is ignored)
+    pub fn scale_factor(&self, window_scale_factor: f64) -> f64 {
+        if self.scale > 0.0 {
+            window_scale_factor * self.scale as f64
+        } else {
+            window_scale_factor
+        }
+    }
+}
+
+/// Axes indicator (gnomon) configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GnomonConfig {
+    pub enabled: bool,
+    /// side length (pixels)
+    pub size: u32,
+    pub corner: Corner,
+}
+
+impl Default for GnomonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            size: 100,
+            // the top right corner holds the ui menu
+            corner: Corner::BottomLeft,
+        }
+    }
+}
+
+/// Debug normals lines configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DebugNormalsConfig {
+    /// lines length (model space)
+    pub length: f32,
+    /// also draw the tangents and bitangents
+    pub tangents: bool,
+}
+
+impl Default for DebugNormalsConfig {
+    fn default() -> Self {
+        Self {
+            length: 0.5,
+            tangents: false,
+        }
+    }
+}
+
+/// Width of the debug lines (normals, bounding boxes)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum LineWidth {
+    /// constant on screen, whatever the distance
+    Pixels(f32),
+    /// in world units, thinner with the distance
+    World(f32),
+}
+
+/// Debug lines configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LinesConfig {
+    pub width: LineWidth,
+}
+
+impl Default for LinesConfig {
+    fn default() -> Self {
+        Self {
+            width: LineWidth::Pixels(2.0),
         }
     }
 }
 
+/// Objects names labels configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LabelsConfig {
+    /// shown at startup
+    pub enabled: bool,
+    /// label height (world units), the width follows the text
+    pub height: f32,
+    /// label center height above the object origin (world units)
+    pub offset: f32,
+    /// rasterized text size (pixels)
+    pub font_size: f32,
+}
+
+impl Default for LabelsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: 1.5,
+            offset: 4.0,
+            font_size: 32.0,
+        }
+    }
+}
+
+/// Periodic scene statistics summary in the logs (info level)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StatsLogConfig {
+    pub enabled: bool,
+    /// seconds between two summaries
+    pub interval: f32,
+}
+
+impl Default for StatsLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 5.0,
+        }
+    }
+}
+
+/// Initial camera view, restored when the camera is reset
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    /// vertical field of view (degrees)
+    pub fovy: f32,
+    /// near and far clip planes distances
+    pub znear: f32,
+    pub zfar: f32,
+    /// lowest height of the camera above the ground, unlimited if not set
+    pub min_height: Option<f32>,
+    /// progress curve of the glide back to this view
+    pub reset_easing: Easing,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            position: [100.0, 100.0, 50.0],
+            target: [0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0],
+            fovy: 45.0,
+            znear: 1.0,
+            zfar: 1000.0,
+            min_height: None,
+            reset_easing: Easing::default(),
+        }
+    }
+}
+
+/// Scene light options
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LightConfig {
+    /// constant, linear and quadratic distance attenuation terms, (1, 0, 0) for none
+    pub attenuation: [f32; 3],
+}
+
+impl Default for LightConfig {
+    fn default() -> Self {
+        Self {
+            attenuation: [1.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// GPU setup configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// graphics API: "vulkan", "dx12", "metal", "gl", "primary" or "all"
+    pub backend: String,
+    /// GPU choice on multi GPU systems: "low" (integrated), "high" (discrete) or "default"
+    pub power_preference: String,
+    /// measure the passes GPU durations (if supported), stalls each frame until the GPU is done
+    pub gpu_timings: bool,
+    /// draw the opaque models depth before shading them (helps with expensive shading only)
+    pub depth_prepass: bool,
+    pub anti_aliasing: AntiAliasing,
+    pub present_mode: PresentMode,
+    /// background color (linear rgb)
+    pub clear_color: [f32; 3],
+    /// frames per second limit, unlimited if not set
+    pub max_fps: Option<u32>,
+    /// format of the depth texture and of the pipelines testing it
+    pub depth_format: DepthFormat,
+    /// depth bias of the decals pipeline, drawing over coplanar geometries
+    pub decal_bias: DepthBiasConfig,
+    /// darkening of the frame corners, 0 (none) to 1 (black), no vignette pass if not set
+    pub vignette: Option<f32>,
+    pub gnomon: GnomonConfig,
+    pub camera: CameraConfig,
+    pub light: LightConfig,
+    pub debug_normals: DebugNormalsConfig,
+    pub lines: LinesConfig,
+    pub labels: LabelsConfig,
+    pub stats_log: StatsLogConfig,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            backend: "primary".to_string(),
+            power_preference: "default".to_string(),
+            gpu_timings: false,
+            depth_prepass: false,
+            anti_aliasing: AntiAliasing::None,
+            present_mode: PresentMode::default(),
+            clear_color: [0.0, 0.05, 0.1],
+            max_fps: None,
+            depth_format: DepthFormat::default(),
+            decal_bias: Default::default(),
+            vignette: None,
+            gnomon: Default::default(),
+            camera: Default::default(),
+            light: Default::default(),
+            debug_normals: Default::default(),
+            lines: Default::default(),
+            labels: Default::default(),
+            stats_log: Default::default(),
+        }
+    }
+}
+
+impl RenderConfig {
+    /// The runtime adjustable options, as configured
+    pub fn settings(&self) -> RenderSettings {
+        RenderSettings {
+            present_mode: self.present_mode,
+            anti_aliasing: self.anti_aliasing,
+            clear_color: self.clear_color,
+            max_fps: self.max_fps,
+        }
+    }
+
+    /// Backends the wgpu instance is created with (primary ones if the configuration is invalid)
+    pub fn backends(&self) -> wgpu::Backends {
+        parse_backends(&self.backend).unwrap_or_else(|| {
+            log::warn!("Unknown backend {:?}, using the primary ones", self.backend);
+            wgpu::Backends::PRIMARY
+        })
+    }
+
+    /// Adapter power preference (default one if the configuration is invalid)
+    pub fn power_preference(&self) -> wgpu::PowerPreference {
+        parse_power_preference(&self.power_preference).unwrap_or_else(|| {
+            log::warn!(
+                "Unknown power preference {:?}, using the default one",
+                self.power_preference
+            );
+            wgpu::PowerPreference::default()
+        })
+    }
+}
+
+fn parse_power_preference(power_preference: &str) -> Option<wgpu::PowerPreference> {
+    match power_preference.to_lowercase().as_str() {
+        "low" => Some(wgpu::PowerPreference::LowPower),
+        "high" => Some(wgpu::PowerPreference::HighPerformance),
+        "default" => Some(wgpu::PowerPreference::default()),
+        _ => None,
+    }
+}
+
+fn parse_backends(backend: &str) -> Option<wgpu::Backends> {
+    match backend.to_lowercase().as_str() {
+        "vulkan" => Some(wgpu::Backends::VULKAN),
+        "dx12" => Some(wgpu::Backends::DX12),
+        "metal" => Some(wgpu::Backends::METAL),
+        "gl" => Some(wgpu::Backends::GL),
+        "primary" => Some(wgpu::Backends::PRIMARY),
+        "all" => Some(wgpu::Backends::all()),
+        _ => None,
+    }
+}
+
 /// Initial application configuration
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub struct AppConfig {
     pub window: WindowConfig,
     pub resources: ResourcesConfig,
+    #[serde(default)]
+    pub render: RenderConfig,
+    #[serde(default)]
+    pub input: InputMap,
+    #[serde(default)]
+    pub ui: UiConfig,
 }
 
 /// Load configuration from default local file.
@@ -101,11 +661,123 @@ pub fn load_conf() -> AppConfig {
     }
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            window: Default::default(),
-            resources: Default::default(),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_font_size_scales_the_text_styles() {
+        let size = |fonts: &egui::FontDefinitions, style| fonts.family_and_size[&style].1;
+        let default = UiConfig::default().font_definitions();
+        let config = UiConfig {
+            font_size: Some(20.0),
+            ..Default::default()
+        };
+        let fonts = config.font_definitions();
+        assert_eq!(size(&fonts, egui::TextStyle::Body), 20.0);
+        // the other styles keep their ratio to the body
+        let ratio = 20.0 / size(&default, egui::TextStyle::Body);
+        let heading = size(&default, egui::TextStyle::Heading) * ratio;
+        assert!((size(&fonts, egui::TextStyle::Heading) - heading).abs() < 1e-4);
+    }
+
+    #[test]
+    fn missing_meshes_directory_is_reported() {
+        let config = ResourcesConfig {
+            meshes_directory: "no/such/meshes".to_string(),
+            ..Default::default()
+        };
+        assert!(ResourcesConfig::default().validate().is_ok());
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "Missing resources: meshes directory no/such/meshes"
+        );
+    }
+
+    #[test]
+    fn locked_aspect_corrects_the_off_ratio_sizes() {
+        let config = WindowConfig {
+            lock_aspect: Some(16.0 / 9.0),
+            ..Default::default()
+        };
+        let size = PhysicalSize::new(1600, 900);
+        assert_eq!(config.locked_size(size), size);
+        // too tall: the width is kept
+        assert_eq!(
+            config.locked_size(PhysicalSize::new(1600, 1000)),
+            PhysicalSize::new(1600, 900)
+        );
+        // too wide: the height is kept
+        assert_eq!(
+            config.locked_size(PhysicalSize::new(2000, 900)),
+            PhysicalSize::new(1600, 900)
+        );
+        let free = WindowConfig::default();
+        assert_eq!(
+            free.locked_size(PhysicalSize::new(1600, 1000)),
+            PhysicalSize::new(1600, 1000)
+        );
+    }
+
+    #[test]
+    fn only_msaa_multisamples_the_scene() {
+        assert_eq!(AntiAliasing::Msaa(4).sample_count(), 4);
+        assert_eq!(AntiAliasing::Msaa(8).sample_count(), 4);
+        assert_eq!(AntiAliasing::Msaa(1).sample_count(), 1);
+        assert_eq!(AntiAliasing::Fxaa.sample_count(), 1);
+        assert_eq!(AntiAliasing::None.sample_count(), 1);
+        let config: RenderConfig = ron::from_str("(anti_aliasing:Msaa(4))").unwrap();
+        assert_eq!(config.anti_aliasing, AntiAliasing::Msaa(4));
+    }
+
+    #[test]
+    fn backends_parse_case_insensitively() {
+        assert_eq!(parse_backends("vulkan"), Some(wgpu::Backends::VULKAN));
+        assert_eq!(parse_backends("DX12"), Some(wgpu::Backends::DX12));
+        assert_eq!(parse_backends("Metal"), Some(wgpu::Backends::METAL));
+        assert_eq!(parse_backends("gl"), Some(wgpu::Backends::GL));
+        assert_eq!(parse_backends("primary"), Some(wgpu::Backends::PRIMARY));
+        assert_eq!(parse_backends("all"), Some(wgpu::Backends::all()));
+        assert_eq!(parse_backends("opengl"), None);
+        assert_eq!(parse_backends(""), None);
+    }
+
+    #[test]
+    fn power_preference_parses_case_insensitively() {
+        assert_eq!(
+            parse_power_preference("low"),
+            Some(wgpu::PowerPreference::LowPower)
+        );
+        assert_eq!(
+            parse_power_preference("High"),
+            Some(wgpu::PowerPreference::HighPerformance)
+        );
+        assert_eq!(
+            parse_power_preference("DEFAULT"),
+            Some(wgpu::PowerPreference::default())
+        );
+        assert_eq!(parse_power_preference("fast"), None);
+    }
+
+    #[test]
+    fn decal_pipeline_is_biased_towards_the_camera() {
+        let bias: wgpu::DepthBiasState = RenderConfig::default().decal_bias.into();
+        assert!(bias.constant < 0);
+        let options = crate::render::scene::pipeline::PipelineOptions::new(
+            wgpu::TextureFormat::Depth32Float,
+            1,
+        )
+        .decal(bias);
+        assert_eq!(options.depth_bias, bias);
+        assert_ne!(options.depth_bias.constant, 0);
+
+        let bias: DepthBiasConfig = ron::from_str("(constant:-8)").unwrap();
+        assert_eq!(
+            bias,
+            DepthBiasConfig {
+                constant: -8,
+                ..Default::default()
+            }
+        );
     }
 }
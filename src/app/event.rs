@@ -3,6 +3,8 @@ use std::sync::Mutex;
 use anyhow::Result;
 use winit::event_loop::EventLoop;
 
+use super::config::RenderSettings;
+
 /// Application events.
 ///
 /// Used to notify:
@@ -14,6 +16,45 @@ pub enum PomarinEvent {
     SomeEvent,
     EguiRequestRedraw,
     CloseApp,
+    /// Write the assets dependencies graph to a graphviz file
+    DumpAssetsGraph,
+    /// Pause or resume the scene animation
+    SetPaused(bool),
+    /// Scale the scene animation speed (1.0 is real time)
+    SetTimeScale(f32),
+    /// Tune the spray emitter: particles per second and their lifetime (seconds)
+    SetSpray {
+        spawn_rate: f32,
+        lifetime: f32,
+    },
+    /// Show the depth buffer instead of the scene
+    ToggleDepthView,
+    /// Draw the models edges over their shaded surface
+    ToggleWireframe,
+    /// Draw the models in a flat color of their pipeline
+    TogglePipelineColors,
+    /// Write the current scene layout to a RON file
+    SaveScene,
+    /// Restore the camera initial view
+    ResetCamera,
+    /// Switch to the next registered camera controller
+    CycleCamera,
+    /// Show or hide the objects names labels
+    ToggleLabels,
+    /// Draw the lights positions
+    ToggleLightGizmos,
+    /// Switch the light between its orbit and the camera position
+    ToggleHeadlight,
+    /// Switch the dock floodlight off or back on
+    ToggleFloodlight,
+    /// Switch the window between windowed and borderless fullscreen
+    ToggleFullscreen,
+    /// Draw the normals of the next loaded model (none after the last one)
+    CycleDebugNormals,
+    /// Only draw the objects of the masked layers
+    SetLayerMask(u32),
+    /// Apply the runtime adjustable render options
+    SetRenderSettings(RenderSettings),
 }
 
 pub trait EventEmitter<T> {
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, KeyboardInput, VirtualKeyCode},
+};
+
+use super::event::PomarinEvent;
+
+/// Named actions triggered by keyboard shortcuts
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    CloseApp,
+    DumpAssetsGraph,
+    ToggleDepthView,
+    CycleCamera,
+    ToggleHeadlight,
+    ToggleFullscreen,
+}
+
+/// Keyboard shortcuts bindings.
+///
+/// Deserialized from the app configuration as a map of key codes to actions:
+///
+/// ```ron
+/// input: (
+///     bindings: {
+///         Escape: CloseApp,
+///         F2: DumpAssetsGraph,
+///         F3: ToggleDepthView,
+///         F4: CycleCamera,
+///         F5: ToggleHeadlight,
+///         F11: ToggleFullscreen,
+///     }
+/// )
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct InputMap {
+    bindings: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            bindings: HashMap::from([
+                (VirtualKeyCode::Escape, Action::CloseApp),
+                (VirtualKeyCode::F2, Action::DumpAssetsGraph),
+                (VirtualKeyCode::F3, Action::ToggleDepthView),
+                (VirtualKeyCode::F4, Action::CycleCamera),
+                (VirtualKeyCode::F5, Action::ToggleHeadlight),
+                (VirtualKeyCode::F11, Action::ToggleFullscreen),
+            ]),
+        }
+    }
+}
+
+impl InputMap {
+    /// Get the action bound to the pressed key, if any.
+    /// Releasing a key triggers nothing.
+    pub fn process_keyboard(&self, input: &KeyboardInput) -> Option<Action> {
+        match input {
+            KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                ..
+            } => self.action(*key),
+            _ => None,
+        }
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+}
+
+/// Actions are dispatched as app events
+impl From<Action> for PomarinEvent {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::CloseApp => PomarinEvent::CloseApp,
+            Action::DumpAssetsGraph => PomarinEvent::DumpAssetsGraph,
+            Action::ToggleDepthView => PomarinEvent::ToggleDepthView,
+            Action::CycleCamera => PomarinEvent::CycleCamera,
+            Action::ToggleHeadlight => PomarinEvent::ToggleHeadlight,
+            Action::ToggleFullscreen => PomarinEvent::ToggleFullscreen,
+        }
+    }
+}
+
+/// Convert a cursor position (`WindowEvent::CursorMoved`) to normalized device coordinates.
+///
+/// The window left and right borders map to x = -1 and 1, the top and bottom borders to y = 1
+/// and -1 (wgpu NDC are Y-up, the window coordinates Y-down). Positions outside of the window are
+/// clamped to its borders.
+pub fn physical_to_ndc(position: PhysicalPosition<f64>, size: PhysicalSize<u32>) -> (f32, f32) {
+    let width = size.width.max(1) as f64;
+    let height = size.height.max(1) as f64;
+    let x = position.x.clamp(0.0, width) / width;
+    let y = position.y.clamp(0.0, height) / height;
+    ((x * 2.0 - 1.0) as f32, (1.0 - y * 2.0) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(deprecated)] // the modifiers field
+    fn key(key: VirtualKeyCode, state: ElementState) -> KeyboardInput {
+        KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn pressed_keys_trigger_their_bound_action() {
+        let input = InputMap::default();
+        assert_eq!(
+            input.process_keyboard(&key(VirtualKeyCode::F4, ElementState::Pressed)),
+            Some(Action::CycleCamera)
+        );
+        assert_eq!(
+            input.process_keyboard(&key(VirtualKeyCode::F4, ElementState::Released)),
+            None
+        );
+        assert_eq!(
+            input.process_keyboard(&key(VirtualKeyCode::A, ElementState::Pressed)),
+            None
+        );
+    }
+
+    #[test]
+    fn configured_bindings_replace_the_default_ones() {
+        let input: InputMap = ron::from_str("(bindings: { Q: CloseApp })").unwrap();
+        assert_eq!(
+            input.process_keyboard(&key(VirtualKeyCode::Q, ElementState::Pressed)),
+            Some(Action::CloseApp)
+        );
+        assert_eq!(
+            input.process_keyboard(&key(VirtualKeyCode::Escape, ElementState::Pressed)),
+            None
+        );
+    }
+
+    #[test]
+    fn window_corners_and_center_map_to_ndc() {
+        let size = PhysicalSize::new(800, 600);
+        let ndc = |x, y| physical_to_ndc(PhysicalPosition::new(x, y), size);
+        assert_eq!(ndc(0.0, 0.0), (-1.0, 1.0));
+        assert_eq!(ndc(800.0, 0.0), (1.0, 1.0));
+        assert_eq!(ndc(0.0, 600.0), (-1.0, -1.0));
+        assert_eq!(ndc(800.0, 600.0), (1.0, -1.0));
+        assert_eq!(ndc(400.0, 300.0), (0.0, 0.0));
+        // clamped to the window
+        assert_eq!(ndc(-50.0, 900.0), (-1.0, -1.0));
+    }
+}
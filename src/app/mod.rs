@@ -2,5 +2,7 @@
 pub mod config;
 /// App event structures and traits
 pub mod event;
+/// Keyboard shortcuts to actions mapping
+pub mod input;
 /// App module managing the rendering
 pub mod render;
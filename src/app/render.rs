@@ -1,26 +1,100 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use winit::{
-    dpi::PhysicalSize,
-    event::{Event, WindowEvent},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, Event, MouseButton, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Fullscreen, Icon, WindowBuilder},
 };
 
-use crate::{
-    render::{
-        egui::{pass::EguiWgpuPass, ui::EguiRoutine},
-        scene::pass::ScenePass,
-        state::WgpuState,
-    },
-    APP_NAME,
+use crate::render::{
+    config::scene::SceneDescriptor,
+    egui::{pass::EguiWgpuPass, ui::EguiRoutine},
+    gpu_timer::GpuTimer,
+    rpass::WgpuRpass,
+    scene::{command::SceneCommand, pass::ScenePass},
+    state::WgpuState,
 };
 
 use super::{
     config::AppConfig,
     event::{Emitter, PomarinEvent},
+    input::physical_to_ndc,
 };
 
+/// File the assets graph is dumped to (see `PomarinEvent::DumpAssetsGraph`)
+const ASSETS_GRAPH_FILE: &str = "assets.dot";
+/// File the scene layout is saved to (see `PomarinEvent::SaveScene`), and loaded from at start
+const SCENE_FILE: &str = "scene.ron";
+
+/// A pass of the frame, the built-in ones are owned by the event loop
+#[derive(Debug, PartialEq)]
+pub enum FramePass<P = Box<dyn WgpuRpass>> {
+    /// clears the surface and draws the 3d scene
+    Scene,
+    /// composites the ui over the previous passes
+    Ui,
+    Custom(P),
+}
+
+/// Insert a custom pass at the `index` of the frame passes (appended if the index is past the
+/// end)
+fn insert_pass<P>(passes: &mut Vec<FramePass<P>>, index: usize, pass: P) {
+    let index = index.min(passes.len());
+    passes.insert(index, FramePass::Custom(pass));
+}
+
+/// Latest window size, applied on the next redraw: a drag resize emits many intermediate sizes,
+/// the surface and the depth texture are only recreated for the last one
+#[derive(Debug, Default)]
+struct PendingResize(Option<PhysicalSize<u32>>);
+
+impl PendingResize {
+    /// Replace the size to apply
+    fn request(&mut self, size: PhysicalSize<u32>) {
+        self.0 = Some(size);
+    }
+
+    /// The latest requested size, once
+    fn take(&mut self) -> Option<PhysicalSize<u32>> {
+        self.0.take()
+    }
+}
+
+/// How the window is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    /// covers the current monitor, without changing its video mode
+    Borderless,
+}
+
+impl WindowMode {
+    /// The other mode
+    pub fn toggled(self) -> Self {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Windowed,
+        }
+    }
+
+    /// The winit fullscreen setting of the mode
+    pub fn fullscreen(self) -> Option<Fullscreen> {
+        match self {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(None)),
+        }
+    }
+}
+
 /// App render manager.
 ///
 /// This struct takes a config from which it defines the window and its content.
@@ -30,76 +104,173 @@ use super::{
 /// To notify the rendering with app event, get an emitter with `get_emitter_handle()` and call
 /// `emit(app_event)` on it.
 ///
+/// To drive the scene objects from another thread, get a sender with `get_scene_commands_sender()`.
+///
+/// The frames are made of the scene pass then the ui pass, other passes can be inserted between
+/// or after them with `insert_pass()`.
+///
 pub struct AppRender {
     app_config: AppConfig,
     event_loop: EventLoop<PomarinEvent>,
-    initial_size: PhysicalSize<u32>,
-    _max_fps: u64,
-    _fps_time: Duration,
+    commands_sender: Sender<SceneCommand>,
+    commands_receiver: Receiver<SceneCommand>,
+    /// rendered in order, each pass draws over the previous ones
+    passes: Vec<FramePass>,
+    /// the window starts windowed, `PomarinEvent::ToggleFullscreen` switches the mode
+    window_mode: WindowMode,
 }
 
 impl AppRender {
     pub fn new(app_config: AppConfig) -> Self {
-        let max_fps = 120;
-        let _fps_time = Duration::from_millis(1_000 / max_fps);
         let event_loop = EventLoop::<PomarinEvent>::with_user_event();
+        let (commands_sender, commands_receiver) = mpsc::channel();
         Self {
             app_config,
             event_loop,
-            initial_size: PhysicalSize::new(200, 200),
-            _max_fps: max_fps,
-            _fps_time,
+            commands_sender,
+            commands_receiver,
+            passes: vec![FramePass::Scene, FramePass::Ui],
+            window_mode: WindowMode::Windowed,
         }
     }
 
+    /// Insert a pass at the `index` of the frame passes (appended if the index is past the end).
+    ///
+    /// The frame starts with the scene (index 0) then the ui (index 1): a pass inserted at 1 is
+    /// drawn over the scene and under the ui.
+    pub fn insert_pass(&mut self, index: usize, pass: Box<dyn WgpuRpass>) {
+        insert_pass(&mut self.passes, index, pass);
+    }
+
     /// Get the event emitter (Arc wrapped) that will enable sending app event to the event loop
     pub fn get_emitter_handle(&self) -> Arc<Emitter<PomarinEvent>> {
         Arc::new(Emitter::new(&self.event_loop))
     }
 
+    /// Get a sender of commands applied by the scene before each frame
+    pub fn get_scene_commands_sender(&self) -> Sender<SceneCommand> {
+        self.commands_sender.clone()
+    }
+
     /// Run the winit event loop.
     /// Once this loop is started, it will be closed either by a `PomarinEvent::CloseApp` or a `Event::CloseRequested` event.
     pub fn run(self) {
         let window = WindowBuilder::new().build(&self.event_loop).unwrap();
-        window.set_title(APP_NAME);
+        window.set_title(&self.app_config.window.title);
+        if let Some(icon_path) = &self.app_config.window.icon_path {
+            match load_icon(icon_path) {
+                Ok(icon) => window.set_window_icon(Some(icon)),
+                Err(e) => log::warn!("Cannot load window icon {:?}: {}", icon_path, e),
+            }
+        }
         window.set_decorations(false);
         window.set_maximized(true);
         window.set_visible(true);
-        window.set_min_inner_size(Some(self.initial_size));
+        window.set_min_inner_size(Some(self.app_config.window.min_size()));
 
         // wgpu state
-        let mut wgpu = WgpuState::init(&window, &self.app_config.resources);
+        let mut wgpu =
+            WgpuState::init(&window, &self.app_config.resources, &self.app_config.render);
         // render egui ui
-        let mut egui = EguiWgpuPass::new(&wgpu, &window, &self.event_loop, EguiRoutine::default());
+        let mut egui = EguiWgpuPass::new(
+            &wgpu,
+            &window,
+            &self.event_loop,
+            &self.app_config.ui,
+            EguiRoutine::default().with_scene_commands(self.commands_sender.clone()),
+        );
         // render 3d scene
-        let mut rend = ScenePass::new(&wgpu, &window, &self.event_loop);
+        let saved_scene = load_saved_scene(Path::new(SCENE_FILE));
+        let mut rend = match ScenePass::new(
+            &wgpu,
+            &window,
+            &self.event_loop,
+            self.commands_receiver,
+            saved_scene,
+        ) {
+            Ok((rend, None)) => rend,
+            Ok((rend, Some(failures))) => {
+                if self.app_config.resources.abort_on_object_failure {
+                    log::error!("Cannot create the scene: {}", failures);
+                    return;
+                }
+                log::warn!("{}", failures);
+                rend
+            }
+            Err(e) => {
+                log::error!("Cannot create the scene: {}", e);
+                return;
+            }
+        };
+        // runtime adjustable options, the ui shows them as applied
+        let render_settings = rend.apply_settings(&wgpu, self.app_config.render.settings());
+        egui.set_render_settings(render_settings);
+        let mut max_fps = render_settings.max_fps;
+        // earliest next frame start when the frame rate is capped
+        let mut next_frame: Option<Instant> = None;
+        // keyboard shortcuts are dispatched as app events
+        let input_map = self.app_config.input.clone();
+        let actions_emitter = Emitter::new(&self.event_loop);
+        let mut pending_size = PendingResize::default();
+        // latest cursor position, clicking selects the object under it
+        let mut cursor = PhysicalPosition::new(0.0, 0.0);
+        let window_config = self.app_config.window.clone();
+        // passes GPU durations, only measured on request
+        let mut passes = self.passes;
+        let mut window_mode = self.window_mode;
+        let mut gpu_timer = if self.app_config.render.gpu_timings {
+            GpuTimer::new(&wgpu.device, &wgpu.queue)
+        } else {
+            None
+        };
 
         log::info!("Starting event loop");
 
         self.event_loop.run(move |event, _, control_flow| {
             egui.handle_event(&event);
-            *control_flow = ControlFlow::Wait;
+            *control_flow = next_frame.map_or(ControlFlow::Wait, ControlFlow::WaitUntil);
             match event {
                 Event::WindowEvent {
                     ref event,
                     window_id,
-                } => {
-                    if window_id == window.id() {
-                        match event {
-                            WindowEvent::CloseRequested => {
-                                log::info!(target: "event", "Window CloseRequest");
-                                *control_flow = ControlFlow::Exit;
+                } if window_id == window.id() => {
+                    match event {
+                        WindowEvent::CloseRequested => {
+                            log::info!(target: "event", "Window CloseRequest");
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        WindowEvent::KeyboardInput { input, .. }
+                            if !egui.wants_keyboard_input() =>
+                        {
+                            if let Some(action) = input_map.process_keyboard(input) {
+                                log::info!(target: "event", "Action {:?}", action);
+                                if let Err(e) = actions_emitter.emit(action.into()) {
+                                    log::error!("Cannot dispatch {:?}: {}", action, e);
+                                }
                             }
-                            WindowEvent::Resized(size) => {
-                                let size = *size;
-                                wgpu.pre_resize(size);
-                                rend.resize(&wgpu);
-                                wgpu.post_resize(size);
-
-                                log::info!(target: "event", "Window resized to {:?}", size);
+                        }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            cursor = *position;
+                        }
+                        WindowEvent::MouseInput {
+                            state: ElementState::Pressed,
+                            button: MouseButton::Left,
+                            ..
+                        } if !egui.wants_pointer_input() => {
+                            rend.pick(physical_to_ndc(cursor, window.inner_size()));
+                        }
+                        WindowEvent::Resized(size) => {
+                            // the window is asked to follow the locked aspect ratio, the
+                            // surface is configured with it in any case
+                            let size = window_config.locked_size(*size);
+                            // the fullscreen window covers the monitor whatever the ratio
+                            if window_mode == WindowMode::Windowed && window.inner_size() != size {
+                                window.set_inner_size(size);
                             }
-                            _ => {}
+                            log::debug!(target: "event", "Window resize to {:?} pending", size);
+                            pending_size.request(size);
                         }
+                        _ => {}
                     }
                 }
                 Event::UserEvent(event) => {
@@ -116,12 +287,71 @@ impl AppRender {
                             log::info!(target: "event", "App close requested");
                             *control_flow = ControlFlow::Exit;
                         }
+                        PomarinEvent::SetPaused(_)
+                        | PomarinEvent::SetLayerMask(_)
+                        | PomarinEvent::SetTimeScale(_)
+                        | PomarinEvent::SetSpray { .. }
+                        | PomarinEvent::ToggleDepthView
+                        | PomarinEvent::ToggleWireframe
+                        | PomarinEvent::TogglePipelineColors
+                        | PomarinEvent::CycleDebugNormals
+                        | PomarinEvent::ToggleLabels
+                        | PomarinEvent::ToggleLightGizmos
+                        | PomarinEvent::ToggleHeadlight
+                        | PomarinEvent::ToggleFloodlight
+                        | PomarinEvent::ResetCamera
+                        | PomarinEvent::CycleCamera => {
+                            // handled by the scene
+                        }
+                        PomarinEvent::ToggleFullscreen => {
+                            window_mode = window_mode.toggled();
+                            window.set_fullscreen(window_mode.fullscreen());
+                            // reconfigure the surface even if no resize event follows
+                            pending_size.request(window_config.locked_size(window.inner_size()));
+                            log::info!(target: "event", "Window mode {:?}", window_mode);
+                        }
+                        PomarinEvent::SetRenderSettings(requested) => {
+                            wgpu.set_present_mode(requested.present_mode.into());
+                            let applied = rend.apply_settings(&wgpu, requested);
+                            max_fps = applied.max_fps;
+                            next_frame = None;
+                            log::info!(target: "event", "Render settings applied {:?}", applied);
+                            egui.set_render_settings(applied);
+                        }
+                        PomarinEvent::DumpAssetsGraph => {
+                            match fs::write(ASSETS_GRAPH_FILE, wgpu.assets.to_dot()) {
+                                Ok(()) => {
+                                    log::info!("Assets graph written to {}", ASSETS_GRAPH_FILE)
+                                }
+                                Err(e) => log::error!("Cannot write assets graph: {}", e),
+                            }
+                        }
+                        PomarinEvent::SaveScene => {
+                            match rend
+                                .to_descriptor()
+                                .to_ron()
+                                .and_then(|ron| Ok(fs::write(SCENE_FILE, ron)?))
+                            {
+                                Ok(()) => log::info!("Scene written to {}", SCENE_FILE),
+                                Err(e) => log::error!("Cannot write scene: {}", e),
+                            }
+                        }
                     }
                 }
-                Event::MainEventsCleared => {
+                Event::MainEventsCleared
+                    if next_frame.is_none_or(|next| Instant::now() >= next) =>
+                {
                     window.request_redraw();
                 }
                 Event::RedrawRequested(window_id) if window_id == window.id() => {
+                    let frame_start = Instant::now();
+                    if let Some(size) = pending_size.take() {
+                        wgpu.pre_resize(size);
+                        rend.resize(&wgpu);
+                        wgpu.post_resize(size);
+
+                        log::info!(target: "event", "Window resized to {:?}", size);
+                    }
                     let output_frame = match wgpu.surface.get_current_texture() {
                         Ok(frame) => frame,
                         Err(wgpu::SurfaceError::Outdated) => {
@@ -139,22 +369,125 @@ impl AppRender {
                         .texture
                         .create_view(&wgpu::TextureViewDescriptor::default());
 
-                    let encoder =
+                    let mut encoder =
                         wgpu.device
                             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                                 label: Some("encoder"),
                             });
 
-                    // the last one wins, we want the ui above the 3d scene
-                    let encoder = rend.render(&wgpu, &window, &output_view, encoder);
-                    let encoder = egui.render(&wgpu, &window, &output_view, encoder);
+                    // the passes write the surface texture (output_view) in order:
+                    // - scene: clears and draws the 3d scene (resolved into output_view)
+                    // - egui: loads the previous passes image and composites the ui above it
+                    for pass in passes.iter_mut() {
+                        encoder = match pass {
+                            FramePass::Scene => {
+                                if let Some(timer) = &gpu_timer {
+                                    timer.scene_start(&mut encoder);
+                                }
+                                let mut encoder =
+                                    rend.render(&wgpu, &window, &output_view, encoder);
+                                if let Some(timer) = &gpu_timer {
+                                    timer.scene_end(&mut encoder);
+                                }
+                                encoder
+                            }
+                            FramePass::Ui => {
+                                let mut encoder =
+                                    egui.render(&wgpu, &window, &output_view, encoder);
+                                if let Some(timer) = &gpu_timer {
+                                    timer.ui_end(&mut encoder);
+                                }
+                                encoder
+                            }
+                            FramePass::Custom(pass) => {
+                                pass.render(&wgpu, &window, &output_view, encoder)
+                            }
+                        };
+                    }
                     wgpu.queue.submit(std::iter::once(encoder.finish()));
+                    if let Some(timer) = &mut gpu_timer {
+                        timer.read_back(&wgpu.device);
+                    }
+                    // shown in the next frame ui
+                    egui.set_gpu_timings(gpu_timer.as_ref().and_then(GpuTimer::timings));
 
                     // Redraw
                     output_frame.present();
+                    next_frame = max_fps
+                        .filter(|fps| *fps > 0)
+                        .map(|fps| frame_start + Duration::from_secs_f64(1.0 / fps as f64));
                 }
                 _ => {}
             };
         });
     }
 }
+
+/// The scene layout saved with `PomarinEvent::SaveScene`, `None` if there is none or if it
+/// cannot be read
+fn load_saved_scene(path: &Path) -> Option<SceneDescriptor> {
+    if !path.exists() {
+        return None;
+    }
+    match fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|ron| SceneDescriptor::from_ron(&ron))
+    {
+        Ok(scene) => {
+            log::info!("Loading the scene saved in {:?}", path);
+            Some(scene)
+        }
+        Err(e) => {
+            log::warn!("Cannot read the saved scene {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn load_icon(path: &Path) -> anyhow::Result<Icon> {
+    let image = image::open(path)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_twice_restores_the_window_mode() {
+        for mode in [WindowMode::Windowed, WindowMode::Borderless] {
+            assert_ne!(mode.toggled(), mode);
+            assert_eq!(mode.toggled().toggled(), mode);
+        }
+        assert_eq!(WindowMode::Windowed.fullscreen(), None);
+    }
+
+    #[test]
+    fn only_the_last_pending_resize_is_applied() {
+        let mut pending_size = PendingResize::default();
+        pending_size.request(PhysicalSize::new(800, 600));
+        pending_size.request(PhysicalSize::new(810, 605));
+        pending_size.request(PhysicalSize::new(820, 610));
+        assert_eq!(pending_size.take(), Some(PhysicalSize::new(820, 610)));
+        assert_eq!(pending_size.take(), None);
+    }
+
+    #[test]
+    fn passes_are_drawn_in_registration_order() {
+        let mut passes = vec![FramePass::Scene, FramePass::Ui];
+        insert_pass(&mut passes, 1, "hud");
+        insert_pass(&mut passes, 10, "overlay");
+        insert_pass(&mut passes, 1, "mirror");
+        assert_eq!(
+            passes,
+            vec![
+                FramePass::Scene,
+                FramePass::Custom("mirror"),
+                FramePass::Custom("hud"),
+                FramePass::Ui,
+                FramePass::Custom("overlay"),
+            ]
+        );
+    }
+}
@@ -2,8 +2,15 @@ mod app;
 /// Module managing rendering (scene and ui)
 mod render;
 
-use crate::app::{config::load_conf, event::PomarinEvent, render::AppRender};
-use std::{thread, time::Duration};
+use crate::{
+    app::{config::load_conf, event::PomarinEvent, render::AppRender},
+    render::scene::{command::SceneCommand, vignette::VignettePass},
+};
+use cgmath::Rotation3;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
 
 pub const APP_NAME: &'static str = "Pomarin";
 
@@ -21,7 +28,13 @@ fn main() {
     env_logger::init();
     log::info!("Initialized environment and logger");
 
-    let ui = AppRender::new(load_conf());
+    let config = load_conf();
+    let vignette = config.render.vignette;
+    let mut ui = AppRender::new(config);
+    // over the scene, under the ui
+    if let Some(strength) = vignette {
+        ui.insert_pass(1, Box::new(VignettePass::new(strength)));
+    }
     let emitter = ui.get_emitter_handle();
 
     thread::spawn(move || loop {
@@ -32,5 +45,22 @@ fn main() {
         thread::sleep(Duration::new(1, 0));
     });
 
+    // rock the zodiac from another thread, stops when the scene is gone
+    let commands = ui.get_scene_commands_sender();
+    thread::spawn(move || {
+        let start = Instant::now();
+        loop {
+            let roll = cgmath::Deg(5.0 * (start.elapsed().as_secs_f32() * 1.5).sin());
+            let command = SceneCommand::SetOrientation {
+                name: "zodiac".to_string(),
+                orientation: cgmath::Quaternion::from_angle_z(roll),
+            };
+            if commands.send(command).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(30));
+        }
+    });
+
     ui.run();
 }
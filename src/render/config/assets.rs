@@ -1,11 +1,15 @@
-use crate::render::names::{MaterialName, MeshName, ModelName, NamedHandle, TextureName};
+use crate::render::{
+    names::{MaterialName, MeshName, ModelName, NamedHandle, PipelineName, TextureName},
+    scene::model::Model,
+    state::WgpuState,
+};
 use anyhow::{anyhow, Result};
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, rc::Rc, str::FromStr};
 use thiserror::Error;
 
 use super::{
     material::MaterialDescriptor, mesh::MeshDescriptor, model::ModelDescriptor,
-    texture::TextureDescriptor,
+    pipeline::PipelineDescriptor, texture::TextureDescriptor, WgpuResourceLoader,
 };
 
 #[derive(Debug)]
@@ -14,17 +18,26 @@ pub enum AssetDescriptor {
     Material(MaterialDescriptor),
     Mesh(MeshDescriptor),
     Model(ModelDescriptor),
+    Pipeline(PipelineDescriptor),
 }
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+/// Assets keys, ordered by kind then name.
+///
+/// The kinds are declared in their dependency order: the models use pipelines, meshes and
+/// materials, the materials use textures.
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub enum AssetName {
     Texture(TextureName),
     Material(MaterialName),
     Mesh(MeshName),
+    Pipeline(PipelineName),
     Model(ModelName),
 }
 
-/// Store assets descriptor to reuse on demand
+/// Store assets descriptor to reuse on demand.
+///
+/// The bulk operations go through the descriptors in the `AssetName` order, their logs and
+/// results do not depend on the map layout.
 #[derive(Debug)]
 pub struct AssetsDescriptors(HashMap<AssetName, AssetDescriptor>);
 
@@ -51,9 +64,20 @@ impl AssetsDescriptors {
             AssetDescriptor::Model(ref model) => {
                 self.0.insert(AssetName::Model(model.name()), descriptor);
             }
+            AssetDescriptor::Pipeline(ref pipeline) => {
+                self.0
+                    .insert(AssetName::Pipeline(pipeline.name()), descriptor);
+            }
         }
     }
 
+    /// The descriptors in the dependencies order (see `AssetName`)
+    pub fn descriptors(&self) -> impl Iterator<Item = &AssetDescriptor> {
+        let mut descriptors: Vec<(&AssetName, &AssetDescriptor)> = self.0.iter().collect();
+        descriptors.sort_by_key(|(name, _)| *name);
+        descriptors.into_iter().map(|(_, descriptor)| descriptor)
+    }
+
     pub fn find<K: Into<AssetName>>(&self, key: K) -> Option<&AssetDescriptor> {
         self.0.get(&key.into())
     }
@@ -69,6 +93,163 @@ impl AssetsDescriptors {
             |descriptor| Ok(descriptor),
         )
     }
+
+    /// Check that every asset reference resolves to a descriptor of the expected kind:
+    /// - models mesh and materials
+    /// - texture materials textures
+    ///
+    /// This does not need any wgpu resource and is meant to be run right after the descriptors
+    /// are read.
+    pub fn validate(&self) -> Vec<AssetError> {
+        let mut errors = vec![];
+        let mut check = |asset: &AssetDescriptor, dependency: AssetName| {
+            if !self.0.contains_key(&dependency) {
+                errors.push(AssetError::MissingDependency {
+                    asset: asset.name(),
+                    dependency,
+                });
+            }
+        };
+        for descriptor in self.descriptors() {
+            match descriptor {
+                AssetDescriptor::Model(model) => {
+                    check(descriptor, model.mesh().clone().into());
+                    for (_, material) in model.geometries_materials() {
+                        check(descriptor, material.clone().into());
+                    }
+                }
+                AssetDescriptor::Material(material) => {
+                    for (_, texture) in material.textures() {
+                        check(descriptor, texture.into());
+                    }
+                }
+                AssetDescriptor::Mesh(_)
+                | AssetDescriptor::Texture(_)
+                | AssetDescriptor::Pipeline(_) => {}
+            }
+        }
+        errors
+    }
+
+    /// The described models, in name order
+    fn models(&self) -> impl Iterator<Item = &ModelDescriptor> {
+        self.descriptors()
+            .filter_map(|descriptor| match descriptor {
+                AssetDescriptor::Model(model) => Some(model),
+                _ => None,
+            })
+    }
+
+    /// Names of the models using the material for one of their geometries
+    pub fn models_using_material(&self, material: &MaterialName) -> Vec<ModelName> {
+        self.models()
+            .filter(|model| {
+                model
+                    .geometries_materials()
+                    .iter()
+                    .any(|(_, used)| used == material)
+            })
+            .map(|model| model.name())
+            .collect()
+    }
+
+    /// Names of the models drawing the mesh
+    pub fn models_using_mesh(&self, mesh: &MeshName) -> Vec<ModelName> {
+        self.models()
+            .filter(|model| model.mesh() == mesh)
+            .map(|model| model.name())
+            .collect()
+    }
+
+    /// Names of the models using the texture through one of their materials
+    pub fn models_using_texture(&self, texture: &TextureName) -> Vec<ModelName> {
+        let materials: Vec<MaterialName> = self
+            .descriptors()
+            .filter_map(|descriptor| match descriptor {
+                AssetDescriptor::Material(material)
+                    if material.textures().iter().any(|(_, used)| used == texture) =>
+                {
+                    Some(material.name())
+                }
+                _ => None,
+            })
+            .collect();
+        self.models()
+            .filter(|model| {
+                model
+                    .geometries_materials()
+                    .iter()
+                    .any(|(_, used)| materials.contains(used))
+            })
+            .map(|model| model.name())
+            .collect()
+    }
+
+    /// Load every described model, by name, with the outcome of each load
+    pub fn load_models(&self, wgpu_state: &WgpuState) -> Vec<(ModelName, Result<Rc<Model>>)> {
+        self.models()
+            .map(|model| (model.name(), model.load(wgpu_state)))
+            .collect()
+    }
+
+    /// Build a graphviz digraph of the assets dependencies:
+    /// - model -> mesh -> geometries
+    /// - model -> materials (labeled with the geometry using it)
+    /// - material -> textures (labeled with the texture usage)
+    ///
+    /// Render it with `dot -Tsvg assets.dot -o assets.svg`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph assets {\n    rankdir=LR;\n");
+        for descriptor in self.descriptors() {
+            let node = dot_id(descriptor.name());
+            match descriptor {
+                AssetDescriptor::Model(model) => {
+                    dot.push_str(&format!("    {} -> {};\n", node, dot_id(model.mesh())));
+                    for (geometry, material) in model.geometries_materials() {
+                        dot.push_str(&format!(
+                            "    {} -> {} [label={}];\n",
+                            node,
+                            dot_id(material),
+                            dot_id(geometry.as_str())
+                        ));
+                    }
+                }
+                AssetDescriptor::Mesh(mesh) => {
+                    for geometry in mesh.geometries_names() {
+                        // geometries names are only unique within their mesh
+                        let geometry_node = dot_id(format!("{}.{}", mesh.name(), geometry));
+                        dot.push_str(&format!(
+                            "    {} [label={}];\n    {} -> {};\n",
+                            geometry_node,
+                            dot_id(&geometry),
+                            node,
+                            geometry_node
+                        ));
+                    }
+                }
+                AssetDescriptor::Material(material) => {
+                    for (usage, texture) in material.textures() {
+                        dot.push_str(&format!(
+                            "    {} -> {} [label={}];\n",
+                            node,
+                            dot_id(&texture),
+                            dot_id(usage)
+                        ));
+                    }
+                }
+                AssetDescriptor::Texture(_) | AssetDescriptor::Pipeline(_) => {
+                    dot.push_str(&format!("    {};\n", node));
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quoted graphviz node id
+fn dot_id<D: Display>(name: D) -> String {
+    format!("\"{}\"", name.to_string().replace('"', "\\\""))
 }
 
 #[derive(Error, Debug)]
@@ -80,6 +261,13 @@ pub enum AssetError {
     },
     #[error("{key} not found")]
     AssetNotFound { key: String },
+    #[error("{asset} references {dependency} which is not described")]
+    MissingDependency {
+        asset: AssetName,
+        dependency: AssetName,
+    },
+    #[error("Cannot parse asset name {input} (expected Kind:name or Kind(name), Kind being Texture, Material, Mesh, Model or Pipeline)")]
+    InvalidAssetName { input: String },
 }
 
 pub trait TryAsRef<T> {
@@ -143,6 +331,20 @@ impl TryAsRef<MaterialDescriptor> for AssetDescriptor {
     }
 }
 
+impl TryAsRef<PipelineDescriptor> for AssetDescriptor {
+    type Error = anyhow::Error;
+    fn try_as_ref(&self) -> Result<&PipelineDescriptor, Self::Error> {
+        if let AssetDescriptor::Pipeline(p) = self {
+            Ok(p)
+        } else {
+            Err(anyhow!(AssetError::TryAsRefFailed {
+                target_type: "PipelineDescriptor".to_string(),
+                descriptor: self.name(),
+            }))
+        }
+    }
+}
+
 impl NamedHandle<AssetName> for AssetDescriptor {
     fn name(&self) -> AssetName {
         match self {
@@ -150,6 +352,7 @@ impl NamedHandle<AssetName> for AssetDescriptor {
             AssetDescriptor::Material(m) => AssetName::Material(m.name()),
             AssetDescriptor::Mesh(m) => AssetName::Mesh(m.name()),
             AssetDescriptor::Model(m) => AssetName::Model(m.name()),
+            AssetDescriptor::Pipeline(p) => AssetName::Pipeline(p.name()),
         }
     }
 }
@@ -167,54 +370,291 @@ impl Display for AssetName {
             AssetName::Material(m) => write!(f, "{}", m),
             AssetName::Mesh(m) => write!(f, "{}", m),
             AssetName::Model(m) => write!(f, "{}", m),
+            AssetName::Pipeline(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+/// Parse `Kind:name`, or `Kind(name)` as written by `Display`
+impl FromStr for AssetName {
+    type Err = AssetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AssetError::InvalidAssetName {
+            input: s.to_string(),
+        };
+        let (kind, name) = match s.split_once(':') {
+            Some(kind_name) => kind_name,
+            None => s
+                .strip_suffix(')')
+                .and_then(|s| s.split_once('('))
+                .ok_or_else(invalid)?,
+        };
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        match kind {
+            "Texture" => Ok(AssetName::Texture(TextureName::from(name))),
+            "Material" => Ok(AssetName::Material(MaterialName::from(name))),
+            "Mesh" => Ok(AssetName::Mesh(MeshName::from(name))),
+            "Model" => Ok(AssetName::Model(ModelName::from(name))),
+            "Pipeline" => Ok(AssetName::Pipeline(PipelineName::from(name))),
+            _ => Err(invalid()),
         }
     }
 }
 
-impl Into<AssetDescriptor> for TextureDescriptor {
-    fn into(self) -> AssetDescriptor {
-        AssetDescriptor::Texture(self)
+impl From<TextureDescriptor> for AssetDescriptor {
+    fn from(texture: TextureDescriptor) -> Self {
+        AssetDescriptor::Texture(texture)
+    }
+}
+
+impl From<MaterialDescriptor> for AssetDescriptor {
+    fn from(material: MaterialDescriptor) -> Self {
+        AssetDescriptor::Material(material)
+    }
+}
+
+impl From<MeshDescriptor> for AssetDescriptor {
+    fn from(mesh: MeshDescriptor) -> Self {
+        AssetDescriptor::Mesh(mesh)
+    }
+}
+
+impl From<ModelDescriptor> for AssetDescriptor {
+    fn from(model: ModelDescriptor) -> Self {
+        AssetDescriptor::Model(model)
     }
 }
 
-impl Into<AssetDescriptor> for MaterialDescriptor {
-    fn into(self) -> AssetDescriptor {
-        AssetDescriptor::Material(self)
+impl From<PipelineDescriptor> for AssetDescriptor {
+    fn from(pipeline: PipelineDescriptor) -> Self {
+        AssetDescriptor::Pipeline(pipeline)
     }
 }
 
-impl Into<AssetDescriptor> for MeshDescriptor {
-    fn into(self) -> AssetDescriptor {
-        AssetDescriptor::Mesh(self)
+impl From<TextureName> for AssetName {
+    fn from(texture: TextureName) -> Self {
+        AssetName::Texture(texture)
     }
 }
 
-impl Into<AssetDescriptor> for ModelDescriptor {
-    fn into(self) -> AssetDescriptor {
-        AssetDescriptor::Model(self)
+impl From<MaterialName> for AssetName {
+    fn from(material: MaterialName) -> Self {
+        AssetName::Material(material)
     }
 }
 
-impl Into<AssetName> for TextureName {
-    fn into(self) -> AssetName {
-        AssetName::Texture(self)
+impl From<MeshName> for AssetName {
+    fn from(mesh: MeshName) -> Self {
+        AssetName::Mesh(mesh)
     }
 }
 
-impl Into<AssetName> for MaterialName {
-    fn into(self) -> AssetName {
-        AssetName::Material(self)
+impl From<ModelName> for AssetName {
+    fn from(model: ModelName) -> Self {
+        AssetName::Model(model)
     }
 }
 
-impl Into<AssetName> for MeshName {
-    fn into(self) -> AssetName {
-        AssetName::Mesh(self)
+impl From<PipelineName> for AssetName {
+    fn from(pipeline: PipelineName) -> Self {
+        AssetName::Pipeline(pipeline)
     }
 }
 
-impl Into<AssetName> for ModelName {
-    fn into(self) -> AssetName {
-        AssetName::Model(self)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A textured and colored model, its mesh, materials and texture; but the skipped assets
+    fn assets(skipped: &[AssetName]) -> AssetsDescriptors {
+        let texture: TextureDescriptor =
+            ron::from_str(r#"(name:"d_wall", path:"wall_diffuse.jpg", kind:Diffuse)"#).unwrap();
+        let materials: Vec<MaterialDescriptor> = ron::from_str(
+            r#"[
+                Texture((name:"wall", diffuse_texture:("d_wall"))),
+                Color((
+                    name:"grey",
+                    ambient:(0.1,0.1,0.1),
+                    diffuse:(0.2,0.2,0.2),
+                    specular:(0.3,0.3,0.3),
+                )),
+            ]"#,
+        )
+        .unwrap();
+        let mesh: MeshDescriptor = ron::from_str(
+            r#"(
+                name:"zodiac",
+                source:Obj("zodiac_001.obj"),
+                geometries:[(name:"hull"), (name:"inflatable")],
+            )"#,
+        )
+        .unwrap();
+        let model: ModelDescriptor = ron::from_str(
+            r#"(
+                name:"zod",
+                mesh:("zodiac"),
+                geometries_materials:[(("hull"),("wall")), (("inflatable"),("grey"))],
+                pipeline_name:"textures_pipeline",
+            )"#,
+        )
+        .unwrap();
+
+        let mut assets = AssetsDescriptors::new();
+        let descriptors: Vec<AssetDescriptor> = vec![texture.into(), mesh.into(), model.into()];
+        for descriptor in descriptors
+            .into_iter()
+            .chain(materials.into_iter().map(AssetDescriptor::from))
+            .filter(|descriptor| !skipped.contains(&descriptor.name()))
+        {
+            assets.push(descriptor);
+        }
+        assets
+    }
+
+    /// The assets, with a second model sharing the mesh and the materials of the first one
+    fn shared_assets() -> AssetsDescriptors {
+        let mut assets = assets(&[]);
+        let model: ModelDescriptor = ron::from_str(
+            r#"(
+                name:"zod_moored",
+                mesh:("zodiac"),
+                geometries_materials:[(("hull"),("wall")), (("inflatable"),("grey"))],
+                pipeline_name:"textures_pipeline",
+            )"#,
+        )
+        .unwrap();
+        assets.push(model);
+        assets
+    }
+
+    /// The missing dependencies found, as (asset, dependency)
+    fn missing(assets: &AssetsDescriptors) -> Vec<(AssetName, AssetName)> {
+        assets
+            .validate()
+            .into_iter()
+            .map(|error| match error {
+                AssetError::MissingDependency { asset, dependency } => (asset, dependency),
+                other => panic!("unexpected {}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dot_graph_of_the_assets() {
+        let expected = r#"digraph assets {
+    rankdir=LR;
+    "Texture(d_wall)";
+    "Material(wall)" -> "Texture(d_wall)" [label="diffuse"];
+    "Mesh(zodiac).Geometry(hull)" [label="Geometry(hull)"];
+    "Mesh(zodiac)" -> "Mesh(zodiac).Geometry(hull)";
+    "Mesh(zodiac).Geometry(inflatable)" [label="Geometry(inflatable)"];
+    "Mesh(zodiac)" -> "Mesh(zodiac).Geometry(inflatable)";
+    "Model(zod)" -> "Mesh(zodiac)";
+    "Model(zod)" -> "Material(wall)" [label="hull"];
+    "Model(zod)" -> "Material(grey)" [label="inflatable"];
+}
+"#;
+        assert_eq!(assets(&[]).to_dot(), expected);
+    }
+
+    #[test]
+    fn descriptors_come_in_dependency_order() {
+        let names: Vec<AssetName> = assets(&[])
+            .descriptors()
+            .map(|descriptor| descriptor.name())
+            .collect();
+        let expected: Vec<AssetName> = vec![
+            TextureName::from("d_wall").into(),
+            MaterialName::from("grey").into(),
+            MaterialName::from("wall").into(),
+            MeshName::from("zodiac").into(),
+            ModelName::from("zod").into(),
+        ];
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn shared_assets_are_used_by_both_models() {
+        let assets = shared_assets();
+        let sorted = |mut models: Vec<ModelName>| {
+            models.sort_by_key(|model| model.to_string());
+            models
+        };
+        let both = vec![ModelName::from("zod"), ModelName::from("zod_moored")];
+        assert_eq!(
+            sorted(assets.models_using_material(&MaterialName::from("grey"))),
+            both
+        );
+        assert_eq!(
+            sorted(assets.models_using_mesh(&MeshName::from("zodiac"))),
+            both
+        );
+        assert_eq!(
+            sorted(assets.models_using_texture(&TextureName::from("d_wall"))),
+            both
+        );
+        assert!(assets
+            .models_using_material(&MaterialName::from("chrome"))
+            .is_empty());
+    }
+
+    #[test]
+    fn complete_assets_are_valid() {
+        assert!(assets(&[]).validate().is_empty());
+    }
+
+    #[test]
+    fn missing_dependencies_are_reported() {
+        let zod = || AssetName::from(ModelName::from("zod"));
+        assert_eq!(
+            missing(&assets(&[MeshName::from("zodiac").into()])),
+            vec![(zod(), MeshName::from("zodiac").into())]
+        );
+        assert_eq!(
+            missing(&assets(&[MaterialName::from("grey").into()])),
+            vec![(zod(), MaterialName::from("grey").into())]
+        );
+        assert_eq!(
+            missing(&assets(&[TextureName::from("d_wall").into()])),
+            vec![(
+                MaterialName::from("wall").into(),
+                TextureName::from("d_wall").into()
+            )]
+        );
+    }
+
+    #[test]
+    fn asset_names_parse_back_from_their_display() {
+        let names: Vec<AssetName> = vec![
+            TextureName::from("wall_diffuse").into(),
+            MaterialName::from("painted").into(),
+            MeshName::from("hull").into(),
+            ModelName::from("ship").into(),
+            PipelineName::from("textured").into(),
+        ];
+        for name in names {
+            assert_eq!(name.to_string().parse::<AssetName>().unwrap(), name);
+        }
+        assert_eq!(
+            "Mesh:hull".parse::<AssetName>().unwrap(),
+            AssetName::Mesh(MeshName::from("hull"))
+        );
+    }
+
+    #[test]
+    fn invalid_asset_names_are_rejected() {
+        for input in ["hull", "Mesh:", "Mesh()", "Sound(horn)", "Mesh(hull"] {
+            assert!(
+                matches!(
+                    input.parse::<AssetName>(),
+                    Err(AssetError::InvalidAssetName { .. })
+                ),
+                "{} parsed",
+                input
+            );
+        }
     }
 }
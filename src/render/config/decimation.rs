@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::{InnerSpace, Zero};
+
+use crate::render::scene::vertex::ModelVertex;
+
+use super::geometry::GeometryVertices;
+
+/// Finest grid tried, in cells along the longest side of the geometry box
+const MAX_GRID_RESOLUTION: u32 = 1024;
+
+/// Uniform grid over the box of a geometry, its cells merge the vertices they contain
+struct Grid {
+    min: [f32; 3],
+    max: [f32; 3],
+    cell_size: f32,
+    cells: [u32; 3],
+}
+
+impl Grid {
+    /// `resolution` cells along the longest side, `None` for a geometry without extent
+    fn new(min: [f32; 3], max: [f32; 3], resolution: u32) -> Option<Self> {
+        let extents = [0, 1, 2].map(|axis| max[axis] - min[axis]);
+        let longest = extents.iter().cloned().fold(0.0, f32::max);
+        if longest <= 0.0 {
+            return None;
+        }
+        let cell_size = longest / resolution as f32;
+        let cells = extents.map(|extent| ((extent / cell_size).ceil() as u32).max(1));
+        Some(Self {
+            min,
+            max,
+            cell_size,
+            cells,
+        })
+    }
+
+    fn cell(&self, position: [f32; 3]) -> [u32; 3] {
+        [0, 1, 2].map(|axis| {
+            let cell = ((position[axis] - self.min[axis]) / self.cell_size) as u32;
+            cell.min(self.cells[axis] - 1)
+        })
+    }
+}
+
+/// Triangles of the clustered geometry, as the cells of their corners: the triangles with two
+/// corners in the same cell collapse, the duplicates are dropped
+fn clustered_triangles(
+    vertices: &[ModelVertex],
+    indices: &[u32],
+    grid: &Grid,
+) -> Vec<[[u32; 3]; 3]> {
+    let mut seen = HashSet::new();
+    indices
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+                .map(|i| grid.cell(vertices[i as usize].position));
+            if a == b || b == c || c == a {
+                return None;
+            }
+            // same triangle whatever its first corner, the winding is kept
+            let first = [a, b, c]
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, cell)| **cell)?
+                .0;
+            let key = [a, b, c, a, b][first..first + 3].to_vec();
+            seen.insert(key).then_some([a, b, c])
+        })
+        .collect()
+}
+
+/// Accumulated attributes of the vertices of a cell
+struct Cluster {
+    count: u32,
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+    normal: cgmath::Vector3<f32>,
+    tangent: cgmath::Vector3<f32>,
+    bitangent: cgmath::Vector3<f32>,
+    /// the cell vertices on the sides of the geometry box, per axis
+    on_min: [bool; 3],
+    on_max: [bool; 3],
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            position: [0.0; 3],
+            tex_coords: [0.0; 2],
+            normal: cgmath::Vector3::zero(),
+            tangent: cgmath::Vector3::zero(),
+            bitangent: cgmath::Vector3::zero(),
+            on_min: [false; 3],
+            on_max: [false; 3],
+        }
+    }
+}
+
+impl Cluster {
+    fn add(&mut self, vertex: &ModelVertex, grid: &Grid) {
+        self.count += 1;
+        for axis in 0..3 {
+            self.position[axis] += vertex.position[axis];
+            self.on_min[axis] |= vertex.position[axis] <= grid.min[axis];
+            self.on_max[axis] |= vertex.position[axis] >= grid.max[axis];
+        }
+        self.tex_coords[0] += vertex.tex_coords[0];
+        self.tex_coords[1] += vertex.tex_coords[1];
+        self.normal += cgmath::Vector3::from(vertex.normal);
+        self.tangent += cgmath::Vector3::from(vertex.tangent);
+        self.bitangent += cgmath::Vector3::from(vertex.bitangent);
+    }
+
+    /// The mean vertex, kept on the sides of the geometry box its vertices were on so that the
+    /// box is preserved
+    fn vertex(&self, grid: &Grid) -> ModelVertex {
+        let count = self.count as f32;
+        let position = [0, 1, 2].map(|axis| {
+            if self.on_min[axis] {
+                grid.min[axis]
+            } else if self.on_max[axis] {
+                grid.max[axis]
+            } else {
+                self.position[axis] / count
+            }
+        });
+        let unit = |v: cgmath::Vector3<f32>| {
+            if v.magnitude2() > 0.0 {
+                v.normalize().into()
+            } else {
+                v.into()
+            }
+        };
+        ModelVertex {
+            position,
+            tex_coords: [self.tex_coords[0] / count, self.tex_coords[1] / count],
+            normal: unit(self.normal),
+            tangent: unit(self.tangent),
+            bitangent: unit(self.bitangent),
+        }
+    }
+}
+
+/// Simplified copy of the geometry with about `ratio` of its triangles (level of detail), by
+/// vertex clustering: the vertices are merged per cell of a uniform grid, the grid resolution
+/// being searched for the triangles count nearest to the target.
+///
+/// The merged vertices average their attributes: the texture seams are blurred, and the thin
+/// parts of the geometry may collapse. The bounding box is preserved. A `ratio` of 1 or more
+/// keeps the geometry as is.
+pub fn decimate(
+    geometry: &GeometryVertices<ModelVertex>,
+    ratio: f32,
+) -> GeometryVertices<ModelVertex> {
+    let unchanged = || {
+        GeometryVertices::new(
+            &geometry.name,
+            geometry.vertices.clone(),
+            geometry.indices.clone(),
+        )
+    };
+    let triangles_count = geometry.indices.len() / 3;
+    if ratio >= 1.0 || triangles_count == 0 {
+        return unchanged();
+    }
+    let target = (triangles_count as f32 * ratio.max(0.0)).round() as usize;
+    let bounds = geometry.bounding_box();
+    let grid_of = |resolution| Grid::new(bounds.min.into(), bounds.max.into(), resolution);
+    if grid_of(1).is_none() {
+        return unchanged();
+    }
+    let count = |resolution| {
+        grid_of(resolution).map_or(0, |grid| {
+            clustered_triangles(&geometry.vertices, &geometry.indices, &grid).len()
+        })
+    };
+
+    // the triangles count mostly grows with the resolution: smallest resolution reaching the
+    // target, or the one below if nearer
+    let (mut low, mut high) = (1, MAX_GRID_RESOLUTION);
+    while low < high {
+        let middle = (low + high) / 2;
+        if count(middle) >= target {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    let resolution =
+        if low > 1 && target.saturating_sub(count(low - 1)) < count(low).saturating_sub(target) {
+            low - 1
+        } else {
+            low
+        };
+    let grid = match grid_of(resolution) {
+        Some(grid) => grid,
+        None => return unchanged(),
+    };
+    let triangles = clustered_triangles(&geometry.vertices, &geometry.indices, &grid);
+
+    let mut clusters: HashMap<[u32; 3], Cluster> = HashMap::new();
+    for vertex in &geometry.vertices {
+        clusters
+            .entry(grid.cell(vertex.position))
+            .or_default()
+            .add(vertex, &grid);
+    }
+    // only the cells of the kept triangles become vertices
+    let mut cells_indices: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut vertices = vec![];
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+    for cell in triangles.iter().flatten() {
+        let index = *cells_indices.entry(*cell).or_insert_with(|| {
+            vertices.push(clusters[cell].vertex(&grid));
+            vertices.len() as u32 - 1
+        });
+        indices.push(index);
+    }
+    GeometryVertices::new(&geometry.name, vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Square plane of `n` x `n` quads (two triangles each) in the XZ plane, from 0 to 1
+    fn plane(n: u32) -> GeometryVertices<ModelVertex> {
+        let mut vertices = vec![];
+        for i in 0..=n {
+            for j in 0..=n {
+                vertices.push(ModelVertex {
+                    position: [i as f32 / n as f32, 0.0, j as f32 / n as f32],
+                    tex_coords: [i as f32 / n as f32, j as f32 / n as f32],
+                    normal: [0.0, 1.0, 0.0],
+                    tangent: [1.0, 0.0, 0.0],
+                    bitangent: [0.0, 0.0, 1.0],
+                });
+            }
+        }
+        let mut indices = vec![];
+        for i in 0..n {
+            for j in 0..n {
+                let corner = i * (n + 1) + j;
+                let (right, up, diagonal) = (corner + n + 1, corner + 1, corner + n + 2);
+                indices.extend([corner, up, diagonal, corner, diagonal, right]);
+            }
+        }
+        GeometryVertices::new("plane", vertices, indices)
+    }
+
+    #[test]
+    fn half_ratio_halves_the_triangles_and_keeps_the_box() {
+        let source = plane(20);
+        assert_eq!(source.indices.len() / 3, 800);
+        let lod = decimate(&source, 0.5);
+
+        let triangles = lod.indices.len() / 3;
+        assert!(
+            (320..=480).contains(&triangles),
+            "{} triangles kept of 800",
+            triangles
+        );
+        assert!(lod.vertices.len() < source.vertices.len());
+        let (bounds, lod_bounds) = (source.bounding_box(), lod.bounding_box());
+        assert_eq!(lod_bounds.min, bounds.min);
+        assert_eq!(lod_bounds.max, bounds.max);
+    }
+
+    #[test]
+    fn full_ratio_keeps_the_geometry() {
+        let source = plane(4);
+        let lod = decimate(&source, 1.0);
+        assert_eq!(lod.indices, source.indices);
+        assert_eq!(lod.vertices.len(), source.vertices.len());
+    }
+}
@@ -1,4 +1,13 @@
-use crate::render::scene::geometry::GeometryBuf;
+use crate::render::{
+    error::LimitError,
+    scene::{
+        bounds::{BoundingBox, BoundingSphere},
+        geometry::GeometryBuf,
+        vertex::ModelVertex,
+    },
+    state::{check_limit, MAX_BUFFER_SIZE},
+};
+use cgmath::{EuclideanSpace, InnerSpace};
 use serde::Deserialize;
 use wgpu::util::DeviceExt;
 
@@ -8,23 +17,27 @@ use wgpu::util::DeviceExt;
 ///
 /// ```
 /// GeometryDescriptor {
-///     name: "part_x"
+///     name: "part_x",
+///     // the load fails if the source file has another vertex count for this geometry
+///     expected_vertices: Some(1024),
 /// }
 /// ```
 ///
 /// Note: This struct should be used to
 /// handle more parameters (options) or sanity checks
-/// like the vertex count or the kind of vertex it
-/// is made of (with or without uv, normals...)
+/// like the kind of vertex it is made of (with or without uv, normals...)
 #[derive(Deserialize, Debug)]
 pub struct GeometryDescriptor {
     pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) expected_vertices: Option<u32>,
 }
 
 impl From<&str> for GeometryDescriptor {
     fn from(value: &str) -> Self {
         GeometryDescriptor {
             name: value.to_string(),
+            expected_vertices: None,
         }
     }
 }
@@ -33,8 +46,8 @@ impl From<&str> for GeometryDescriptor {
 ///
 /// Container of the raw vertices the geometry is made of.
 ///
-/// Is able to build a GeometryBuf from itself with the method to_wgpu_geometry_buffer(&device)
-///.
+/// Is able to build a GeometryBuf from itself with the method to_wgpu_geometry_buffer(&device),
+/// once checked against the device limits.
 pub struct GeometryVertices<T>
 where
     T: bytemuck::Pod,
@@ -56,22 +69,304 @@ where
         }
     }
 
-    pub fn to_wgpu_geometry_buffer(&self, device: &wgpu::Device) -> GeometryBuf {
+    /// 16 bits indices are enough (and half the size) when all the vertices can be addressed
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        if self.vertices.len() <= u16::MAX as usize {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+}
+
+impl GeometryVertices<ModelVertex> {
+    /// Sphere centered on the vertices bounding box, with the farthest vertex on its surface
+    /// (zero radius at the origin without vertices)
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_points(self.vertices.iter().map(|v| v.position.into()))
+    }
+
+    /// Axis aligned box of the vertices (empty at the origin without vertices)
+    pub fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::from_points(self.vertices.iter().map(|v| v.position.into()))
+    }
+
+    /// Triangles facing away from the point (outward) and toward it (inward), the front faces
+    /// being counter clockwise. The degenerate triangles are not counted.
+    pub fn facing_counts(&self, center: cgmath::Point3<f32>) -> (usize, usize) {
+        self.indices
+            .chunks_exact(3)
+            .fold((0, 0), |(outward, inward), triangle| {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+                    .map(|i| cgmath::Point3::from(self.vertices[i as usize].position));
+                let facing = (b - a).cross(c - a).dot(a.midpoint(b).midpoint(c) - center);
+                if facing > 0.0 {
+                    (outward + 1, inward)
+                } else if facing < 0.0 {
+                    (outward, inward + 1)
+                } else {
+                    (outward, inward)
+                }
+            })
+    }
+
+    /// Reverse the winding of the triangles: the front faces become the back faces
+    pub fn flip_winding(&mut self) {
+        self.indices
+            .chunks_exact_mut(3)
+            .for_each(|triangle| triangle.swap(1, 2));
+    }
+
+    /// Vertices whose normal points away from the point (outward) and toward it (inward)
+    pub fn normals_facing_counts(&self, center: cgmath::Point3<f32>) -> (usize, usize) {
+        self.vertices
+            .iter()
+            .fold((0, 0), |(outward, inward), vertex| {
+                let position = cgmath::Point3::from(vertex.position);
+                let facing = cgmath::Vector3::from(vertex.normal).dot(position - center);
+                if facing > 0.0 {
+                    (outward + 1, inward)
+                } else if facing < 0.0 {
+                    (outward, inward + 1)
+                } else {
+                    (outward, inward)
+                }
+            })
+    }
+
+    /// Reverse the normals, and the bitangents so that the tangent frames keep their handedness
+    pub fn flip_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = vertex.normal.map(|n| -n);
+            vertex.bitangent = vertex.bitangent.map(|b| -b);
+        }
+    }
+
+    /// Check the buffers of the geometry against the device limits
+    pub fn check_limits(&self, limits: &wgpu::Limits) -> Result<(), LimitError> {
+        let resource = format!("geometry {}", self.name);
+        let stride = std::mem::size_of::<ModelVertex>() as u64;
+        check_limit(
+            &resource,
+            "max_vertex_buffer_array_stride",
+            stride,
+            limits.max_vertex_buffer_array_stride as u64,
+        )?;
+        check_limit(
+            &resource,
+            "max_buffer_size",
+            self.vertices.len() as u64 * stride,
+            MAX_BUFFER_SIZE,
+        )?;
+        let index_size = match self.index_format() {
+            wgpu::IndexFormat::Uint16 => 2,
+            wgpu::IndexFormat::Uint32 => 4,
+        };
+        check_limit(
+            &resource,
+            "max_buffer_size",
+            self.indices.len() as u64 * index_size,
+            MAX_BUFFER_SIZE,
+        )
+    }
+
+    /// Upload the geometry, failing if its buffers exceed the device limits
+    pub fn to_wgpu_geometry_buffer(
+        &self,
+        device: &wgpu::Device,
+    ) -> Result<GeometryBuf, LimitError> {
+        self.check_limits(&device.limits())?;
+        let index_format = self.index_format();
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("{:?} Vertex Buffer", self.name.to_string())),
             contents: bytemuck::cast_slice(&self.vertices),
             usage: wgpu::BufferUsages::VERTEX,
         });
+        let indices_u16: Vec<u16>;
+        let indices_bytes: &[u8] = match index_format {
+            wgpu::IndexFormat::Uint16 => {
+                indices_u16 = self.indices.iter().map(|&i| i as u16).collect();
+                bytemuck::cast_slice(&indices_u16)
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(&self.indices),
+        };
+        // the buffer size is padded to the copy alignment by create_buffer_init
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("{:?} Index Buffer", self.name.to_string())),
-            contents: bytemuck::cast_slice(&self.indices),
+            contents: indices_bytes,
             usage: wgpu::BufferUsages::INDEX,
         });
-        GeometryBuf {
+        Ok(GeometryBuf {
             name: self.name.to_string(),
             vertex_buffer,
             index_buffer,
+            index_format,
             num_elements: self.indices.len() as u32,
+            bounding_sphere: self.bounding_sphere(),
+            bounding_box: self.bounding_box(),
+        })
+    }
+}
+
+/// Flip the winding of the geometries when most of their triangles face the centroid of their
+/// vertices: the mesh is assumed to be closed, this fixes inside-out imports. Returns whether
+/// the windings were flipped.
+///
+/// The normals are flipped along when most of them face the centroid too: an import with
+/// inverted windings but outward normals keeps its normals.
+pub fn fix_inverted_winding(geometries: &mut [GeometryVertices<ModelVertex>]) -> bool {
+    let positions: Vec<cgmath::Point3<f32>> = geometries
+        .iter()
+        .flat_map(|gv| gv.vertices.iter().map(|v| v.position.into()))
+        .collect();
+    if positions.is_empty() {
+        return false;
+    }
+    let centroid = cgmath::Point3::centroid(&positions);
+    let (outward, inward) = geometries
+        .iter()
+        .map(|gv| gv.facing_counts(centroid))
+        .fold((0, 0), |(o, i), (go, gi)| (o + go, i + gi));
+    if inward <= outward {
+        return false;
+    }
+    geometries.iter_mut().for_each(|gv| gv.flip_winding());
+    let (outward, inward) = geometries
+        .iter()
+        .map(|gv| gv.normals_facing_counts(centroid))
+        .fold((0, 0), |(o, i), (go, gi)| (o + go, i + gi));
+    if inward > outward {
+        geometries.iter_mut().for_each(|gv| gv.flip_normals());
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit cube centred on the origin, with a quad of 4 vertices per face, wound
+    /// counter-clockwise seen from outside and with outward normals
+    fn cube() -> GeometryVertices<ModelVertex> {
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for axis in 0..3 {
+            for side in [-1.0f32, 1.0] {
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                let first = vertices.len() as u32;
+                for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                    let mut position = [0.0; 3];
+                    position[axis] = side * 0.5;
+                    // the quad is seen mirrored from the negative side
+                    position[u] = a * side * 0.5;
+                    position[v] = b * 0.5;
+                    let mut normal = [0.0; 3];
+                    normal[axis] = side;
+                    let mut tangent = [0.0; 3];
+                    tangent[u] = side;
+                    let mut bitangent = [0.0; 3];
+                    bitangent[v] = 1.0;
+                    vertices.push(ModelVertex {
+                        position,
+                        tex_coords: [(a + 1.0) / 2.0, (b + 1.0) / 2.0],
+                        normal,
+                        tangent,
+                        bitangent,
+                    });
+                }
+                indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+        GeometryVertices::new("cube", vertices, indices)
+    }
+
+    fn origin() -> cgmath::Point3<f32> {
+        cgmath::Point3::new(0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn oversized_buffers_exceed_the_limits() {
+        let cube = cube();
+        assert_eq!(cube.check_limits(&wgpu::Limits::default()), Ok(()));
+        // a device whose vertex buffers can't hold a model vertex
+        let limits = wgpu::Limits {
+            max_vertex_buffer_array_stride: 16,
+            ..Default::default()
+        };
+        assert_eq!(
+            cube.check_limits(&limits),
+            Err(LimitError::Exceeded {
+                resource: "geometry cube".to_string(),
+                limit: "max_vertex_buffer_array_stride",
+                requested: std::mem::size_of::<ModelVertex>() as u64,
+                max: 16,
+            })
+        );
+        assert!(matches!(
+            check_limit(
+                "geometry",
+                "max_buffer_size",
+                MAX_BUFFER_SIZE + 1,
+                MAX_BUFFER_SIZE
+            ),
+            Err(LimitError::Exceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn cube_bounding_sphere_passes_through_the_corners() {
+        let mut cube = cube();
+        cube.vertices
+            .iter_mut()
+            .for_each(|vertex| vertex.position[0] += 10.0);
+        let sphere = cube.bounding_sphere();
+        assert_eq!(sphere.center, cgmath::Point3::new(10.0, 0.0, 0.0));
+        // half the space diagonal of the unit cube
+        assert!((sphere.radius - 3.0f32.sqrt() / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn outward_cube_is_kept() {
+        let mut geometries = [cube()];
+        assert_eq!(geometries[0].facing_counts(origin()), (12, 0));
+        assert!(!fix_inverted_winding(&mut geometries));
+        assert_eq!(geometries[0].indices, cube().indices);
+    }
+
+    #[test]
+    fn inverted_cube_is_turned_outward() {
+        let mut inverted = cube();
+        inverted.flip_winding();
+        inverted.flip_normals();
+        assert_eq!(inverted.facing_counts(origin()), (0, 12));
+        assert_eq!(inverted.normals_facing_counts(origin()), (0, 24));
+
+        let mut geometries = [inverted];
+        assert!(fix_inverted_winding(&mut geometries));
+        assert_eq!(geometries[0].facing_counts(origin()), (12, 0));
+        assert_eq!(geometries[0].normals_facing_counts(origin()), (24, 0));
+        let expected = cube();
+        for (vertex, expected) in geometries[0].vertices.iter().zip(&expected.vertices) {
+            assert_eq!(vertex.normal, expected.normal);
+            assert_eq!(vertex.bitangent, expected.bitangent);
         }
     }
+
+    #[test]
+    fn outward_normals_are_kept_when_only_the_winding_is_inverted() {
+        let mut inverted = cube();
+        inverted.flip_winding();
+        let mut geometries = [inverted];
+        assert!(fix_inverted_winding(&mut geometries));
+        assert_eq!(geometries[0].facing_counts(origin()), (12, 0));
+        assert_eq!(geometries[0].normals_facing_counts(origin()), (24, 0));
+    }
+
+    #[test]
+    fn index_format_fits_the_vertices_count() {
+        let vertex = cube().vertices[0];
+        let geometry = |count| GeometryVertices::new("grid", vec![vertex; count], vec![]);
+        assert_eq!(geometry(65535).index_format(), wgpu::IndexFormat::Uint16);
+        assert_eq!(geometry(65536).index_format(), wgpu::IndexFormat::Uint32);
+    }
 }
@@ -2,8 +2,11 @@ use crate::render::{
     config::{assets::TryAsRef, texture::TextureDescriptor},
     names::{NamedHandle, TextureName},
     scene::{
-        color_material::ColorMaterial, material::Material, texture::Texture,
+        color_material::{ColorMaterial, ColorUniform},
+        material::{BlendMode, Material},
+        texture::Texture,
         texture_material::TextureMaterial,
+        unlit_material::UnlitMaterial,
     },
     state::WgpuState,
 };
@@ -17,6 +20,23 @@ use super::WgpuResourceLoader;
 pub enum MaterialDescriptor {
     Texture(TextureMaterialDescriptor),
     Color(ColorMaterialDescriptor),
+    Unlit(UnlitMaterialDescriptor),
+}
+
+impl MaterialDescriptor {
+    /// Textures used by this material (with their usage)
+    pub fn textures(&self) -> Vec<(&'static str, TextureName)> {
+        match self {
+            MaterialDescriptor::Color(_) | MaterialDescriptor::Unlit(_) => vec![],
+            MaterialDescriptor::Texture(texture) => {
+                let mut textures = vec![("diffuse", texture.diffuse_texture.clone())];
+                if let Some(normal) = &texture.normal_texture {
+                    textures.push(("normal", normal.clone()));
+                }
+                textures
+            }
+        }
+    }
 }
 
 impl WgpuResourceLoader for MaterialDescriptor {
@@ -24,44 +44,81 @@ impl WgpuResourceLoader for MaterialDescriptor {
 
     fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output> {
         log::info!("load {}", self.name());
-        if wgpu_state.store.contains_material(&self.name()) {
+        if let Some(material) = wgpu_state.store.get_material(self.name().deref())? {
             log::info!("Hit wgpu store cache for {}", &self.name());
-            return Ok(wgpu_state.store.get_material(&self.name().deref()).unwrap());
+            return Ok(material);
         }
         let material: Rc<dyn Material> = match self {
             MaterialDescriptor::Color(color) => {
                 let material = ColorMaterial::new(
                     &wgpu_state.device,
+                    &wgpu_state.queue,
+                    &wgpu_state.color_materials,
                     color.name().deref(),
-                    color.ambient,
-                    color.diffuse,
-                    color.specular,
+                    ColorUniform::new(
+                        color.ambient,
+                        color.diffuse,
+                        color.specular,
+                        color.shininess,
+                    ),
                 );
                 Rc::new(material)
             }
+            MaterialDescriptor::Unlit(unlit) => Rc::new(UnlitMaterial::new(
+                &wgpu_state.device,
+                unlit.name().deref(),
+                unlit.color,
+            )),
             MaterialDescriptor::Texture(texture) => {
+                TextureMaterial::check_limits(texture.name().deref(), &wgpu_state.limits())?;
                 let diffuse: Rc<Texture> = wgpu_state
                     .assets
                     .get(texture.diffuse_texture.clone())
                     .and_then(|desc| desc.try_as_ref())
                     .and_then(|descriptor: &TextureDescriptor| descriptor.load(wgpu_state))?;
-                let normal: Rc<Texture> = wgpu_state
-                    .assets
-                    .get(texture.normal_texture.clone())
-                    .and_then(|desc| desc.try_as_ref())
-                    .and_then(|descriptor: &TextureDescriptor| descriptor.load(wgpu_state))?;
+                let normal: Rc<Texture> = match &texture.normal_texture {
+                    Some(normal_texture) => wgpu_state
+                        .assets
+                        .get(normal_texture.clone())
+                        .and_then(|desc| desc.try_as_ref())
+                        .and_then(|descriptor: &TextureDescriptor| descriptor.load(wgpu_state))?,
+                    None => flat_normal_texture(wgpu_state)?,
+                };
 
                 Rc::new(TextureMaterial::new(
                     &wgpu_state.device,
                     texture.name().deref(),
                     diffuse.as_ref(),
                     normal.as_ref(),
+                    texture.uv_scroll,
+                    texture.blend_mode,
+                    texture.derive_tangents,
                 ))
             }
         };
-        wgpu_state.store.add_material(material.clone());
+        wgpu_state.store.add_material(material.clone())?;
         Ok(material)
     }
+
+    fn unload(&self, wgpu_state: &WgpuState) -> Result<()> {
+        if wgpu_state.store.remove_material(&self.name())?.is_some() {
+            log::info!("Unloaded material {}", self.name());
+        }
+        Ok(())
+    }
+}
+
+/// Get the flat normal map shared by the texture materials without normal texture.
+/// It is created once and kept in the store.
+fn flat_normal_texture(wgpu_state: &WgpuState) -> Result<Rc<Texture>> {
+    if let Some(texture) = wgpu_state.store.get_texture(Texture::FLAT_NORMAL_NAME)? {
+        return Ok(texture);
+    }
+    let texture = Rc::new(Texture::flat_normal(&wgpu_state.device, &wgpu_state.queue)?);
+    wgpu_state
+        .store
+        .add_texture(Texture::FLAT_NORMAL_NAME, texture.clone())?;
+    Ok(texture)
 }
 
 #[derive(Deserialize, Debug)]
@@ -70,33 +127,168 @@ pub struct ColorMaterialDescriptor {
     pub ambient: [f32; 3],
     pub diffuse: [f32; 3],
     pub specular: [f32; 3],
+    /// specular exponent: the higher, the tighter the highlights (plastic around 32, polished
+    /// metal in the hundreds)
+    #[serde(default = "ColorMaterialDescriptor::default_shininess")]
+    pub shininess: f32,
 }
 
 impl ColorMaterialDescriptor {
+    pub const DEFAULT_SHININESS: f32 = 32.0;
+
     pub fn _new_(name: String, ambient: [f32; 3], diffuse: [f32; 3], specular: [f32; 3]) -> Self {
         Self {
             name,
             ambient,
             diffuse,
             specular,
+            shininess: Self::DEFAULT_SHININESS,
         }
     }
+
+    fn default_shininess() -> f32 {
+        Self::DEFAULT_SHININESS
+    }
+}
+
+/// Flat color (linear rgba) drawn without lighting (markers, gizmos...)
+#[derive(Deserialize, Debug)]
+pub struct UnlitMaterialDescriptor {
+    pub(crate) name: String,
+    pub color: [f32; 4],
 }
 
 #[derive(Deserialize, Debug)]
 pub struct TextureMaterialDescriptor {
     pub(crate) name: String,
     pub diffuse_texture: TextureName,
-    pub normal_texture: TextureName,
+    /// a flat normal map is used if not set
+    #[serde(default)]
+    pub normal_texture: Option<TextureName>,
+    /// texture coordinates scrolling speed (uv per second), for flowing water, conveyor belts...
+    #[serde(default)]
+    pub uv_scroll: Option<[f32; 2]>,
+    /// `Mask` discards the diffuse texture texels less opaque than the threshold (foliage
+    /// cutouts), `Blend` draws the geometry with the transparent pipeline
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+    /// build the normal map tangent frame in the shader rather than using the vertices
+    /// tangents, for the meshes with degenerate texture coordinates
+    #[serde(default)]
+    pub derive_tangents: bool,
 }
 
 //TODO: delete after having a better way to test
 impl TextureMaterialDescriptor {
-    pub fn _new_(name: String, diffuse_texture: TextureName, normal_texture: TextureName) -> Self {
+    pub fn _new_(
+        name: String,
+        diffuse_texture: TextureName,
+        normal_texture: Option<TextureName>,
+    ) -> Self {
         Self {
             name,
             diffuse_texture,
             normal_texture,
+            uv_scroll: None,
+            blend_mode: BlendMode::Opaque,
+            derive_tangents: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::scene::material::MaterialKind;
+
+    use super::*;
+
+    #[test]
+    fn shininess_defaults_when_omitted() {
+        let material: MaterialDescriptor = ron::from_str(
+            r#"Color((name:"grey", ambient:(0.1,0.1,0.1), diffuse:(0.2,0.2,0.2), specular:(0.3,0.3,0.3)))"#,
+        )
+        .unwrap();
+        match material {
+            MaterialDescriptor::Color(color) => {
+                assert_eq!(color.shininess, ColorMaterialDescriptor::DEFAULT_SHININESS)
+            }
+            other => panic!("grey deserialized as {:?}", other),
         }
+        let color: ColorMaterialDescriptor = ron::from_str(
+            r#"(name:"chrome", ambient:(0.1,0.1,0.1), diffuse:(0.2,0.2,0.2), specular:(0.3,0.3,0.3), shininess:256.0)"#,
+        )
+        .unwrap();
+        assert_eq!(color.shininess, 256.0);
+    }
+
+    #[test]
+    fn unlit_material_is_blended_when_translucent() {
+        let material: MaterialDescriptor =
+            ron::from_str(r#"Unlit((name:"marker", color:(1.0, 0.0, 0.0, 0.5)))"#).unwrap();
+        assert!(material.textures().is_empty());
+        let color = match material {
+            MaterialDescriptor::Unlit(unlit) => unlit.color,
+            other => panic!("marker deserialized as {:?}", other),
+        };
+
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let marker = UnlitMaterial::new(&device, "marker", color);
+        assert_eq!(marker.kind(), MaterialKind::Unlit);
+        assert_eq!(marker.blend_mode(), BlendMode::Blend);
+        let opaque = UnlitMaterial::new(&device, "buoy", [1.0, 0.5, 0.0, 1.0]);
+        assert_eq!(opaque.blend_mode(), BlendMode::Opaque);
+    }
+
+    #[test]
+    fn normal_texture_is_optional() {
+        let flat: MaterialDescriptor =
+            ron::from_str(r#"Texture((name:"wall", diffuse_texture:("d_wall")))"#).unwrap();
+        assert_eq!(flat.textures(), [("diffuse", TextureName::from("d_wall"))]);
+        let bumped: MaterialDescriptor = ron::from_str(
+            r#"Texture((name:"wall", diffuse_texture:("d_wall"), normal_texture:Some(("n_wall"))))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            bumped.textures(),
+            [
+                ("diffuse", TextureName::from("d_wall")),
+                ("normal", TextureName::from("n_wall"))
+            ]
+        );
+    }
+
+    fn blend_mode_of(descriptor: &str) -> BlendMode {
+        match ron::from_str(descriptor).unwrap() {
+            MaterialDescriptor::Texture(texture) => texture.blend_mode,
+            other => panic!("{} deserialized as {:?}", descriptor, other),
+        }
+    }
+
+    #[test]
+    fn materials_are_opaque_by_default() {
+        let blend_mode = blend_mode_of(r#"Texture((name:"hull", diffuse_texture:("d_hull")))"#);
+        assert_eq!(blend_mode, BlendMode::Opaque);
+        assert_eq!(blend_mode.alpha_cutoff(), 0.0);
+    }
+
+    #[test]
+    fn masked_material_discards_under_its_cutoff() {
+        let blend_mode = blend_mode_of(
+            r#"Texture((name:"foliage", diffuse_texture:("d_foliage"), blend_mode:Mask(0.4)))"#,
+        );
+        assert_eq!(blend_mode, BlendMode::Mask(0.4));
+        assert_eq!(blend_mode.alpha_cutoff(), 0.4);
+    }
+
+    #[test]
+    fn blended_material_discards_nothing() {
+        let blend_mode = blend_mode_of(
+            r#"Texture((name:"glass", diffuse_texture:("d_glass"), blend_mode:Blend))"#,
+        );
+        assert_eq!(blend_mode, BlendMode::Blend);
+        assert_eq!(blend_mode.alpha_cutoff(), 0.0);
     }
 }
@@ -1,15 +1,26 @@
-use anyhow::{anyhow, Result};
-use std::rc::Rc;
+use anyhow::Result;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use serde::Deserialize;
 
 use crate::render::{
-    names::{GeometryName, NamedHandle},
-    scene::mesh::MeshBuf,
+    error::MeshError,
+    names::{GeometryName, NamedHandle, TextureName},
+    scene::{material::BlendMode, mesh::MeshBuf, texture::TextureKind, vertex::ModelVertex},
     state::WgpuState,
 };
 
-use super::{geometry::GeometryDescriptor, vertex::VerticesSource, WgpuResourceLoader};
+use super::{
+    decimation::decimate,
+    geometry::{fix_inverted_winding, GeometryDescriptor, GeometryVertices},
+    material::{ColorMaterialDescriptor, MaterialDescriptor, TextureMaterialDescriptor},
+    texture::TextureDescriptor,
+    vertex::{ObjSplit, VerticesSource},
+    WgpuResourceLoader,
+};
 
 /// # Describe a mesh.
 ///
@@ -28,6 +39,16 @@ use super::{geometry::GeometryDescriptor, vertex::VerticesSource, WgpuResourceLo
 ///         GeometryDescriptor { name: "part_x" }
 ///         GeometryDescriptor { name: "part_y" }
 ///     ],
+///     // the file is Z-up
+///     import_transform: Some([-90.0, 0.0, 0.0]),
+///     // create the materials defined by the mtl file
+///     import_materials: true,
+///     // a geometry per object group
+///     split_by: ObjSplit::Group,
+///     // flip the triangles if most of them face inward
+///     auto_fix_winding: false,
+///     // two levels of detail generated, with half and a fifth of the triangles
+///     lod_ratios: vec![0.5, 0.2],
 /// }
 /// ```
 ///
@@ -36,6 +57,25 @@ pub struct MeshDescriptor {
     pub(crate) name: String,
     source: VerticesSource,
     geometries: Vec<GeometryDescriptor>,
+    /// rotation (euler angles in degrees) baked into the vertices on load
+    /// to convert the source coordinate system (Z-up...) to the scene one
+    #[serde(default)]
+    import_transform: Option<[f32; 3]>,
+    /// create the descriptors of the materials defined by the source file (see
+    /// `imported_materials()`)
+    #[serde(default)]
+    import_materials: bool,
+    /// a geometry per object group (default) or per material of the source file
+    #[serde(default)]
+    split_by: ObjSplit,
+    /// flip the windings if most triangles face the mesh centroid (inside-out imports), and the
+    /// normals if most of them face it too. A heuristic assuming a closed mesh: opt-in
+    #[serde(default)]
+    auto_fix_winding: bool,
+    /// triangles ratios (in ]0, 1[) of the levels of detail generated from the geometries on
+    /// load, finest first. None by default
+    #[serde(default)]
+    lod_ratios: Vec<f32>,
 }
 
 impl MeshDescriptor {
@@ -49,7 +89,69 @@ impl MeshDescriptor {
             name,
             source,
             geometries,
+            import_transform: None,
+            import_materials: false,
+            split_by: ObjSplit::Group,
+            auto_fix_winding: false,
+            lod_ratios: vec![],
+        }
+    }
+
+    pub fn imports_materials(&self) -> bool {
+        self.import_materials
+    }
+
+    /// Descriptors of the materials defined by the source file, named after the file
+    /// definitions:
+    /// - a material with a diffuse texture becomes a texture material
+    /// - otherwise it becomes a color material (ambient, diffuse and specular colors)
+    ///
+    /// The textures descriptors are named after the texture files, which are read relatively
+    /// to the meshes directory.
+    pub fn imported_materials(
+        &self,
+        meshes_directory: &Path,
+    ) -> Result<(Vec<MaterialDescriptor>, Vec<TextureDescriptor>)> {
+        let mut materials = vec![];
+        let mut textures = vec![];
+        let mut texture = |file: &str, kind: TextureKind| {
+            textures.push(TextureDescriptor::_new_(
+                file.to_string(),
+                meshes_directory.join(file),
+                kind,
+            ));
+            TextureName::from(file)
+        };
+        for material in self.source.materials(meshes_directory)? {
+            let descriptor = if material.diffuse_texture.is_empty() {
+                MaterialDescriptor::Color(ColorMaterialDescriptor {
+                    name: material.name,
+                    ambient: material.ambient,
+                    diffuse: material.diffuse,
+                    specular: material.specular,
+                    // 0 when the file sets no exponent (`Ns`)
+                    shininess: if material.shininess > 0.0 {
+                        material.shininess
+                    } else {
+                        ColorMaterialDescriptor::DEFAULT_SHININESS
+                    },
+                })
+            } else {
+                let diffuse_texture = texture(&material.diffuse_texture, TextureKind::Diffuse);
+                let normal_texture = (!material.normal_texture.is_empty())
+                    .then(|| texture(&material.normal_texture, TextureKind::Normal));
+                MaterialDescriptor::Texture(TextureMaterialDescriptor {
+                    name: material.name,
+                    diffuse_texture,
+                    normal_texture,
+                    uv_scroll: None,
+                    blend_mode: BlendMode::Opaque,
+                    derive_tangents: false,
+                })
+            };
+            materials.push(descriptor);
         }
+        Ok((materials, textures))
     }
 
     pub fn count_geometries(&self) -> usize {
@@ -59,6 +161,134 @@ impl MeshDescriptor {
     pub fn geometries_names(&self) -> Vec<GeometryName> {
         self.geometries.iter().map(|g| g.name()).collect()
     }
+
+    /// Compare the geometry vertex count with the one expected by its descriptor, if any
+    pub fn check_vertices_count(
+        &self,
+        geometry: &GeometryName,
+        vertices_count: usize,
+    ) -> Result<(), MeshError> {
+        let expected = self
+            .geometries
+            .iter()
+            .find(|g| &g.name() == geometry)
+            .and_then(|g| g.expected_vertices);
+        match expected {
+            Some(expected) if expected as usize != vertices_count => {
+                Err(MeshError::UnexpectedVertexCount {
+                    mesh: self.name(),
+                    geometry: geometry.clone(),
+                    expected,
+                    actual: vertices_count,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the geometries loaded from the source against the described ones: the source must
+    /// have faces and every loaded geometry must be described
+    pub fn check_geometries(
+        &self,
+        geometries: &[GeometryVertices<ModelVertex>],
+    ) -> Result<(), MeshError> {
+        if geometries.iter().all(|gv| gv.indices.is_empty()) {
+            return Err(MeshError::EmptyMesh { name: self.name() });
+        }
+        let described = self.geometries_names();
+        let loaded: Vec<GeometryName> = geometries.iter().map(|gv| gv.name()).collect();
+        let unexpected: Vec<String> = loaded
+            .iter()
+            .filter(|name| !described.contains(name))
+            .map(|name| name.to_string())
+            .collect();
+        if unexpected.is_empty() {
+            return Ok(());
+        }
+        Err(MeshError::GeometriesMismatch {
+            mesh: self.name(),
+            unexpected,
+            missing: described
+                .iter()
+                .filter(|name| !loaded.contains(name))
+                .map(|name| name.to_string())
+                .collect(),
+        })
+    }
+
+    /// Check the levels of detail ratios: each must drop some triangles, and keep some
+    pub fn check_lod_ratios(&self) -> Result<(), MeshError> {
+        match self
+            .lod_ratios
+            .iter()
+            .find(|ratio| !(**ratio > 0.0 && **ratio < 1.0))
+        {
+            Some(ratio) => Err(MeshError::InvalidLodRatio {
+                mesh: self.name(),
+                ratio: *ratio,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Simplified copies of the geometries per level of detail ratio, finest first
+    pub fn decimate(
+        &self,
+        geometries: &[GeometryVertices<ModelVertex>],
+    ) -> Vec<Vec<GeometryVertices<ModelVertex>>> {
+        self.lod_ratios
+            .iter()
+            .map(|ratio| geometries.iter().map(|gv| decimate(gv, *ratio)).collect())
+            .collect()
+    }
+
+    /// Vertices of the source geometries, with the import rotation applied and the windings
+    /// fixed if enabled
+    pub fn load_vertices(
+        &self,
+        wgpu_state: &WgpuState,
+    ) -> Result<Vec<GeometryVertices<ModelVertex>>> {
+        self.load_vertices_from(&PathBuf::from(&wgpu_state.settings.meshes_directory))
+    }
+
+    fn load_vertices_from(
+        &self,
+        meshes_directory: &Path,
+    ) -> Result<Vec<GeometryVertices<ModelVertex>>> {
+        let mut geometries_vertices = self.source.load_from(meshes_directory, self.split_by)?;
+        if let Some(rotation) = self.import_rotation() {
+            geometries_vertices
+                .iter_mut()
+                .flat_map(|gv| gv.vertices.iter_mut())
+                .for_each(|vertex| vertex.rotate(rotation));
+        }
+        if self.auto_fix_winding && fix_inverted_winding(&mut geometries_vertices) {
+            log::info!(
+                "{}: most triangles faced inward, windings flipped",
+                self.name()
+            );
+        }
+        Ok(geometries_vertices)
+    }
+
+    /// Whether the source file was modified since the mesh was loaded from it
+    pub fn is_outdated(&self, mesh: &MeshBuf, meshes_directory: &Path) -> bool {
+        match (self.source.modified(meshes_directory), mesh.source_modified) {
+            (Some(modified), Some(loaded)) => modified > loaded,
+            _ => false,
+        }
+    }
+
+    /// Rotation to apply to the source vertices
+    pub fn import_rotation(&self) -> Option<cgmath::Quaternion<f32>> {
+        self.import_transform.map(|[x, y, z]| {
+            cgmath::Quaternion::from(cgmath::Euler::new(
+                cgmath::Deg(x),
+                cgmath::Deg(y),
+                cgmath::Deg(z),
+            ))
+        })
+    }
 }
 
 /// Builds the MeshBuf using wgpu state
@@ -68,28 +298,262 @@ impl WgpuResourceLoader for MeshDescriptor {
     fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output> {
         log::info!("load {}", self.name());
 
-        if wgpu_state.store.contains_mesh(&self.name) {
-            return Ok(wgpu_state.store.get_mesh(&self.name).unwrap());
+        // the cached mesh is reused unless its source file was modified since
+        let meshes_directory = PathBuf::from(&wgpu_state.settings.meshes_directory);
+        if let Some(mesh) = wgpu_state.store.get_mesh(&self.name)? {
+            if !self.is_outdated(&mesh, &meshes_directory) {
+                return Ok(mesh);
+            }
+            log::info!(
+                "Source of mesh {} modified, reloading it (used by {})",
+                self.name(),
+                wgpu_state
+                    .assets
+                    .models_using_mesh(&self.name())
+                    .iter()
+                    .map(|model| model.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
         }
+        let source_modified = self.source.modified(&meshes_directory);
 
-        let geometries_vertices = self.source.load(wgpu_state)?;
-
+        self.check_lod_ratios()?;
+        let geometries_vertices = self.load_vertices(wgpu_state)?;
+        self.check_geometries(&geometries_vertices)?;
         let geometries = geometries_vertices
             .iter()
             .map(|gv| {
-                if self.geometries_names().contains(&gv.name()) {
-                    Ok(gv.to_wgpu_geometry_buffer(&wgpu_state.device))
-                } else {
-                    Err(anyhow!("Expected geometry does not match file loaded"))
-                }
+                self.check_vertices_count(&gv.name(), gv.vertices.len())?;
+                Ok(gv.to_wgpu_geometry_buffer(&wgpu_state.device)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let lods = self
+            .decimate(&geometries_vertices)
+            .iter()
+            .map(|lod| {
+                lod.iter()
+                    .map(|gv| Ok(gv.to_wgpu_geometry_buffer(&wgpu_state.device)?))
+                    .collect::<Result<Vec<_>>>()
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Result<Vec<_>>>()?;
 
         let mesh = Rc::new(MeshBuf {
             name: self.name.to_string(),
             geometries,
+            lods,
+            source_modified,
         });
-        wgpu_state.store.add_mesh(mesh.clone());
+        wgpu_state.store.add_mesh(mesh.clone())?;
         Ok(mesh)
     }
+
+    fn unload(&self, wgpu_state: &WgpuState) -> Result<()> {
+        if wgpu_state.store.remove_mesh(&self.name)?.is_some() {
+            log::info!("Unloaded mesh {}", self.name());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+
+    /// Directory of the test meshes
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("res/tests")
+    }
+
+    #[test]
+    fn sources_without_faces_or_undescribed_geometries_are_rejected() {
+        let descriptor: MeshDescriptor = ron::from_str(
+            r#"(name: "mixed", source: Obj("mixed.obj"), geometries: [(name: "hull")])"#,
+        )
+        .unwrap();
+        let geometry = |name, indices| GeometryVertices::<ModelVertex>::new(name, vec![], indices);
+        assert!(matches!(
+            descriptor.check_geometries(&[geometry("hull", vec![])]),
+            Err(MeshError::EmptyMesh { .. })
+        ));
+        assert!(descriptor
+            .check_geometries(&[geometry("hull", vec![0, 1, 2])])
+            .is_ok());
+        match descriptor.check_geometries(&[geometry("deck", vec![0, 1, 2])]) {
+            Err(MeshError::GeometriesMismatch {
+                unexpected,
+                missing,
+                ..
+            }) => {
+                assert_eq!(unexpected, ["Geometry(deck)"]);
+                assert_eq!(missing, ["Geometry(hull)"]);
+            }
+            other => panic!("deck checked as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn described_vertex_count_is_checked() {
+        let descriptor: MeshDescriptor = ron::from_str(
+            r#"(
+                name: "mixed",
+                source: Obj("mixed.obj"),
+                geometries: [(name: "hull", expected_vertices: Some(4)), (name: "inflatable")],
+            )"#,
+        )
+        .unwrap();
+        let hull = GeometryName::from("hull");
+        assert!(descriptor.check_vertices_count(&hull, 4).is_ok());
+        assert!(matches!(
+            descriptor.check_vertices_count(&hull, 5),
+            Err(MeshError::UnexpectedVertexCount {
+                expected: 4,
+                actual: 5,
+                ..
+            })
+        ));
+        // no expected count
+        let inflatable = GeometryName::from("inflatable");
+        assert!(descriptor.check_vertices_count(&inflatable, 99).is_ok());
+    }
+
+    #[test]
+    fn mtl_colors_import_as_color_materials() {
+        let descriptor = MeshDescriptor::_new_(
+            "mixed".to_string(),
+            VerticesSource::Obj("mixed.obj".to_string()),
+            vec![],
+        );
+        let (materials, textures) = descriptor.imported_materials(&fixtures()).unwrap();
+        assert_eq!(materials.len(), 2);
+        match &materials[1] {
+            MaterialDescriptor::Color(color) => {
+                assert_eq!(color.name, "rubber");
+                assert_eq!(color.diffuse, [0.8, 0.3, 0.1]);
+                assert_eq!(color.ambient, [0.1, 0.1, 0.1]);
+                // no `Ns` in the file
+                assert_eq!(color.shininess, ColorMaterialDescriptor::DEFAULT_SHININESS);
+            }
+            other => panic!("rubber imported as {:?}", other),
+        }
+        // the textured material keeps its texture instead
+        assert!(matches!(materials[0], MaterialDescriptor::Texture(_)));
+        assert_eq!(textures.len(), 1);
+    }
+
+    #[test]
+    fn split_by_material_merges_the_groups_of_a_material() {
+        let descriptor: MeshDescriptor = ron::from_str(
+            r#"(name: "mixed", source: Obj("mixed.obj"), geometries: [], split_by: Material)"#,
+        )
+        .unwrap();
+        let names = |geometries: Vec<GeometryVertices<ModelVertex>>| {
+            geometries
+                .iter()
+                .map(|gv| gv.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let by_material = descriptor
+            .source
+            .load_from(&fixtures(), descriptor.split_by)
+            .unwrap();
+        assert_eq!(names(by_material), ["painted", "rubber"]);
+        let by_group = descriptor
+            .source
+            .load_from(&fixtures(), ObjSplit::Group)
+            .unwrap();
+        assert_eq!(names(by_group), ["hull", "inflatable"]);
+
+        // two groups without material: a single geometry, the second indices shifted
+        let directory = std::env::temp_dir().join(format!("pomarin_split_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("pair.obj"),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvt 0 0\nvn 0 0 1\n\
+             g a\nf 1/1/1 2/1/1 3/1/1\ng b\nf 1/1/1 2/1/1 4/1/1\n",
+        )
+        .unwrap();
+        let source = VerticesSource::Obj("pair.obj".to_string());
+        let merged = source.load_from(&directory, ObjSplit::Material).unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].name, "default");
+        assert_eq!(merged[0].vertices.len(), 6);
+        assert_eq!(merged[0].indices, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            source.load_from(&directory, ObjSplit::Group).unwrap().len(),
+            2
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn import_transform_rotates_the_vertices() {
+        let descriptor: MeshDescriptor = ron::from_str(
+            r#"(
+                name: "mixed",
+                source: Obj("mixed.obj"),
+                geometries: [],
+                import_transform: Some((90.0, 0.0, 0.0)),
+            )"#,
+        )
+        .unwrap();
+        let source = descriptor
+            .source
+            .load_from(&fixtures(), ObjSplit::Group)
+            .unwrap();
+        let rotated = descriptor.load_vertices_from(&fixtures()).unwrap();
+        // +90° about X: (x, y, z) becomes (x, -z, y)
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        for (source, rotated) in source.iter().zip(&rotated) {
+            for (from, to) in source.vertices.iter().zip(&rotated.vertices) {
+                let [x, y, z] = from.position;
+                assert!(close(to.position, [x, -z, y]), "{:?}", to.position);
+                // the Y-up normals of the file now face +Z
+                assert!(close(to.normal, [0.0, 0.0, 1.0]), "{:?}", to.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn touched_source_outdates_the_loaded_mesh() {
+        let directory = std::env::temp_dir().join(format!("pomarin_mesh_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("triangle.obj");
+        fs::write(&file, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let descriptor = MeshDescriptor::_new_(
+            "triangle".to_string(),
+            VerticesSource::Obj("triangle.obj".to_string()),
+            vec![],
+        );
+        let loaded = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(loaded)
+            .unwrap();
+        let mesh = MeshBuf {
+            name: "triangle".to_string(),
+            geometries: vec![],
+            lods: vec![],
+            source_modified: descriptor.source.modified(&directory),
+        };
+        assert!(mesh.source_modified.is_some());
+        assert!(!descriptor.is_outdated(&mesh, &directory));
+
+        // touch
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::now())
+            .unwrap();
+        assert!(descriptor.is_outdated(&mesh, &directory));
+        fs::remove_dir_all(&directory).unwrap();
+    }
 }
@@ -5,6 +5,8 @@ use anyhow::Result;
 
 /// Module to handle all kind of asset as one type
 pub mod assets;
+/// Module simplifying the geometries (levels of detail)
+pub mod decimation;
 /// Module defining geometry configuration data
 pub mod geometry;
 /// Module defining asset kind names as strongly typed
@@ -13,6 +15,10 @@ pub mod material;
 pub mod mesh;
 /// Module defining model configuration data
 pub mod model;
+/// Module defining pipeline configuration data
+pub mod pipeline;
+/// Module defining the scene layout data
+pub mod scene;
 /// Module defining texture configuration data
 pub mod texture;
 /// Module defining vertex configuration data
@@ -32,18 +38,33 @@ pub trait WgpuResourceLoader {
     /// This method should save in the store the built resources if not present yet.
     /// Do nothing if the resource is already available in the store.
     fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output>;
+
+    /// Release the wgpu resource built by `load`: remove it from the store.
+    /// The GPU memory is freed once the users of the resource drop it too.
+    /// Do nothing by default, for the resources not kept in the store.
+    fn unload(&self, _wgpu_state: &WgpuState) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Default of the boolean descriptor fields enabled unless set
+fn default_true() -> bool {
+    true
 }
 
 pub mod utils {
     use anyhow::{Context, Result};
     use std::fs;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
-    use crate::{app::config::ResourcesConfig, render::config::assets::AssetsDescriptors};
+    use crate::{
+        app::config::ResourcesConfig,
+        render::{config::assets::AssetsDescriptors, names::NamedHandle},
+    };
 
     use super::{
         material::MaterialDescriptor, mesh::MeshDescriptor, model::ModelDescriptor,
-        texture::TextureDescriptor,
+        pipeline::PipelineDescriptor, texture::TextureDescriptor,
     };
 
     /// Given a ResourcesConfig, loads all available assets.
@@ -59,8 +80,35 @@ pub mod utils {
         materials_desc.into_iter().for_each(|t| ad.push(t));
 
         let meshes_desc = read_mesh_descriptors(&config.meshes_cfg)?;
+        // the materials defined in the configuration files prevail over the imported ones
+        let meshes_directory = PathBuf::from(&config.meshes_directory);
+        for mesh in meshes_desc.iter().filter(|mesh| mesh.imports_materials()) {
+            match mesh.imported_materials(&meshes_directory) {
+                Ok((materials, textures)) => {
+                    for texture in textures {
+                        if ad.find(texture.name()).is_none() {
+                            ad.push(texture);
+                        }
+                    }
+                    for material in materials {
+                        if ad.find(material.name()).is_none() {
+                            log::info!(
+                                "Imported material {} from {}",
+                                material.name(),
+                                mesh.name()
+                            );
+                            ad.push(material);
+                        }
+                    }
+                }
+                Err(e) => log::warn!("Cannot import the materials of {}: {}", mesh.name(), e),
+            }
+        }
         meshes_desc.into_iter().for_each(|t| ad.push(t));
 
+        let pipelines_desc = read_pipelines_descriptors(&config.pipelines_cfg)?;
+        pipelines_desc.into_iter().for_each(|t| ad.push(t));
+
         let model_desc = read_models_descriptors(&config.models_cfg)?;
         model_desc.into_iter().for_each(|t| ad.push(t));
 
@@ -90,4 +138,10 @@ pub mod utils {
             ron::from_str(&string_content).context("parsing models")?;
         Ok(list)
     }
+    pub fn read_pipelines_descriptors<P: AsRef<Path>>(file: P) -> Result<Vec<PipelineDescriptor>> {
+        let string_content = fs::read_to_string(file).context("reading pipelines file")?;
+        let list: Vec<PipelineDescriptor> =
+            ron::from_str(&string_content).context("parsing pipelines")?;
+        Ok(list)
+    }
 }
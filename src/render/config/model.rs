@@ -1,18 +1,24 @@
 use crate::render::{
     config::{
         assets::AssetDescriptor, assets::TryAsRef, material::MaterialDescriptor,
-        mesh::MeshDescriptor,
+        mesh::MeshDescriptor, pipeline::PipelineDescriptor,
     },
     error::ModelError,
-    names::{GeometryName, MaterialName, MeshName, NamedHandle},
-    scene::model::Model,
+    names::{GeometryName, MaterialName, MeshName, NamedHandle, PipelineName},
+    scene::{
+        instance::ModelInstances,
+        material::BlendMode,
+        model::Model,
+        overrides::{ModelOverrides, ModelOverridesBuf},
+        pipeline::NamedPipeline,
+    },
     state::WgpuState,
 };
 use anyhow::{anyhow, Result};
 use serde::Deserialize;
-use std::rc::Rc;
+use std::{path::PathBuf, rc::Rc};
 
-use super::WgpuResourceLoader;
+use super::{default_true, WgpuResourceLoader};
 
 /// Describe a model.
 ///
@@ -20,6 +26,12 @@ use super::WgpuResourceLoader;
 /// - what mesh to use by mesh name
 /// - what material apply to mesh's geometry
 /// - what pipeline will be used to handle the geometries and material bind groups
+/// - optionally, what pipeline will be used for some specific geometries instead of the model one
+/// - whether the model casts and receives shadows (both default to true, see `Model`)
+/// - optionally, how many instances the model may have in its own instances buffer, instead of
+///   sharing the scene instances buffer
+/// - optionally, overrides of its materials output (see `ModelOverrides`), so that models can
+///   share their materials
 ///
 /// This struct is deserlisable from ron string.
 ///
@@ -33,7 +45,11 @@ use super::WgpuResourceLoader;
 ///         (("model0_geometry0_name"),("model0_material0_name")),
 ///         (("model0_geometry1_name"),("model0_material1_name"))
 ///     ],
-///     pipeline_name:"model0_pipeline"
+///     pipeline_name:"model0_pipeline",
+///     geometries_pipelines:[
+///         (("model0_geometry1_name"),"model0_geometry1_pipeline"),
+///     ],
+///     overrides:(tint:(1.0, 0.5, 0.5, 1.0)),
 /// )";
 /// let model: ModelDescriptor = ron::from_str(&EXAMPLE)?;
 /// assert_eq!(mode.name(), ModelName::from("model0_name"));
@@ -46,6 +62,16 @@ pub struct ModelDescriptor {
     mesh: MeshName,
     geometries_materials: Vec<(GeometryName, MaterialName)>,
     pipeline_name: String, // Pipeline descriptor...
+    #[serde(default)]
+    geometries_pipelines: Vec<(GeometryName, String)>,
+    #[serde(default = "default_true")]
+    casts_shadow: bool,
+    #[serde(default = "default_true")]
+    receives_shadow: bool,
+    #[serde(default)]
+    max_instances: Option<u32>,
+    #[serde(default)]
+    overrides: ModelOverrides,
 }
 
 // TODO: should be moved in a test
@@ -61,6 +87,124 @@ impl ModelDescriptor {
             mesh, // mesh: MeshName(String)
             geometries_materials,
             pipeline_name,
+            geometries_pipelines: vec![],
+            casts_shadow: true,
+            receives_shadow: true,
+            max_instances: None,
+            overrides: Default::default(),
+        }
+    }
+}
+
+impl ModelDescriptor {
+    pub fn mesh(&self) -> &MeshName {
+        &self.mesh
+    }
+
+    /// Dedicated instances buffer of the model, if it declares `max_instances`
+    fn model_instances(&self, device: &wgpu::Device) -> Option<ModelInstances> {
+        self.max_instances
+            .map(|capacity| ModelInstances::new(device, &self.name, capacity))
+    }
+
+    pub fn geometries_materials(&self) -> &[(GeometryName, MaterialName)] {
+        &self.geometries_materials
+    }
+
+    /// The named pipeline: a built-in one from the store, else built from its descriptor
+    fn find_pipeline(&self, wgpu_state: &WgpuState, name: &str) -> Result<Rc<NamedPipeline>> {
+        if let Some(pipeline) = wgpu_state.store.get_pipeline(name)? {
+            return Ok(pipeline);
+        }
+        match wgpu_state.assets.find(PipelineName::from(name)) {
+            Some(descriptor) => {
+                let descriptor: &PipelineDescriptor = descriptor.try_as_ref()?;
+                descriptor.load(wgpu_state)
+            }
+            None => Err(anyhow!(ModelError::PipelineNotFoundInStore {
+                model: self.name(),
+                pipeline: name.to_string(),
+            })),
+        }
+    }
+
+    /// The materials of the geometries, in the order of the given (loaded) geometries: the
+    /// materials are paired with the geometries by name, the mesh file may order its
+    /// geometries differently than the descriptors
+    fn paired_materials(
+        &self,
+        geometries: &[GeometryName],
+    ) -> Result<Vec<MaterialName>, ModelError> {
+        let mut materials = vec![None; geometries.len()];
+        for (g_name, m_name) in &self.geometries_materials {
+            // does the mesh declares the same geometries we are setting materials to ?
+            match geometries.iter().position(|g| g == g_name) {
+                Some(index) => materials[index] = Some(m_name.clone()),
+                None => {
+                    return Err(ModelError::MaterialNotSetForGeometry {
+                        geometry: g_name.clone(),
+                        model: self.name(),
+                    })
+                }
+            }
+        }
+        geometries
+            .iter()
+            .zip(materials)
+            .map(|(g_name, m_name)| {
+                m_name.ok_or_else(|| ModelError::MaterialNotSetForGeometry {
+                    geometry: g_name.clone(),
+                    model: self.name(),
+                })
+            })
+            .collect()
+    }
+
+    /// Name of the pipeline drawing the geometry: the geometry specific one if any, else the
+    /// model one
+    fn geometry_pipeline_name(&self, geometry: &GeometryName) -> &str {
+        self.geometries_pipelines
+            .iter()
+            .find(|(g, _)| g == geometry)
+            .map_or(&self.pipeline_name, |(_, pipeline_name)| pipeline_name)
+    }
+
+    /// Pipeline to use for the geometry: the geometry specific one if any, else the model one
+    fn geometry_pipeline(
+        &self,
+        wgpu_state: &WgpuState,
+        geometry: &GeometryName,
+        model_pipeline: &Rc<NamedPipeline>,
+    ) -> Result<Rc<NamedPipeline>> {
+        match self.geometry_pipeline_name(geometry) {
+            name if name == self.pipeline_name => Ok(model_pipeline.clone()),
+            name => self.find_pipeline(wgpu_state, name),
+        }
+    }
+
+    /// Pipeline drawing a geometry of the given blend mode: the blended materials are drawn by
+    /// the transparent variant of the geometry pipeline, if it has one
+    fn blend_pipeline(
+        &self,
+        wgpu_state: &WgpuState,
+        pipeline: Rc<NamedPipeline>,
+        blend_mode: BlendMode,
+        geometry: &GeometryName,
+    ) -> Result<Rc<NamedPipeline>> {
+        if blend_mode != BlendMode::Blend || pipeline.is_transparent() {
+            return Ok(pipeline);
+        }
+        match pipeline.transparent_variant() {
+            Some(variant) => self.find_pipeline(wgpu_state, variant),
+            None => {
+                log::warn!(
+                    "{}: pipeline {} has no transparent variant, geometry {} is drawn opaque",
+                    self.name(),
+                    pipeline.name(),
+                    geometry
+                );
+                Ok(pipeline)
+            }
         }
     }
 }
@@ -75,16 +219,12 @@ impl WgpuResourceLoader for ModelDescriptor {
     /// - check that the materials associated to the geometries are valid with the pipeline
     /// - check that the geometries on which set materials are valid for this mesh
     /// - check that either all geometries have one material or all have none
+    /// - check that the geometries specific pipelines are valid with their materials
     fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output> {
         let store = &wgpu_state.store;
         let assets = &wgpu_state.assets;
         log::info!("load {}", self.name());
 
-        // already loaded ? -> return wgpu store cache
-        if store.contains_model(&self.name) {
-            log::info!("Hit wgpu store cache for {}", self.name());
-            return Ok(store.get_model(&self.name).unwrap());
-        }
         let model_name = self.name();
         let mesh_name = &self.mesh;
 
@@ -94,25 +234,54 @@ impl WgpuResourceLoader for ModelDescriptor {
             .get(mesh_name.clone())
             .and_then(|desc| desc.try_as_ref())?;
 
+        // already loaded ? -> return wgpu store cache, unless the mesh source was modified since
+        // (the model is then rebuilt on the reloaded mesh)
+        if let Some(model) = store.get_model(&self.name)? {
+            let meshes_directory = PathBuf::from(&wgpu_state.settings.meshes_directory);
+            if !mesh_descriptor.is_outdated(&model.mesh, &meshes_directory) {
+                log::info!("Hit wgpu store cache for {}", self.name());
+                return Ok(model);
+            }
+            log::info!("Mesh of {} modified, rebuilding the model", self.name());
+        }
+
         let pipeline_name = self.pipeline_name.clone();
 
-        // load mesh from store of add it to store from desc
-        let mesh = store
-            .get_mesh(&mesh_name)
-            .map_or_else(|| mesh_descriptor.load(wgpu_state), |f| Ok(f))?;
+        // load mesh from store (if up to date) or add it to store from desc
+        let mesh = mesh_descriptor.load(wgpu_state)?;
 
-        // load pipeline from store
-        let pipeline = store.get_pipeline(&pipeline_name).ok_or_else(|| {
-            ModelError::PipelineNotFoundInStore {
+        // load pipeline from store or from its descriptor
+        let pipeline = self.find_pipeline(wgpu_state, &pipeline_name)?;
+
+        // geometries specific pipelines must target this mesh's geometries
+        if let Some((g_name, _)) = self
+            .geometries_pipelines
+            .iter()
+            .find(|(g_name, _)| mesh.geometry(g_name).is_none())
+        {
+            return Err(anyhow!(ModelError::InvalidMaterialAndPipeline {
                 model: model_name.clone(),
                 pipeline: pipeline_name.clone(),
-            }
-        })?;
+                reason: format!("Pipeline set for unknown geometry {}", g_name),
+            }));
+        }
 
-        let model = match (self.geometries_materials.len(), pipeline.needs_material()) {
+        let mut model = match (self.geometries_materials.len(), pipeline.needs_material()) {
             (0, false) => {
                 // no material and pipeline does not use any
-                let model = Model::new(model_name.to_string(), pipeline, mesh.clone());
+                let mut model = Model::new(model_name.to_string(), pipeline.clone(), mesh.clone());
+                for geometry in &mesh.geometries {
+                    let geometry_pipeline =
+                        self.geometry_pipeline(wgpu_state, &geometry.name(), &pipeline)?;
+                    if geometry_pipeline.needs_material() {
+                        return Err(anyhow!(ModelError::InvalidMaterialAndPipeline {
+                            model: model_name.clone(),
+                            pipeline: geometry_pipeline.name(),
+                            reason: "Pipeline expects a material".to_string()
+                        }));
+                    }
+                    model.pipelines.push(geometry_pipeline);
+                }
                 Ok(model)
             }
             (_, false) => {
@@ -133,32 +302,259 @@ impl WgpuResourceLoader for ModelDescriptor {
             }
             _ => {
                 // maybe defer instanciation after all checks are ok ?
-                let mut model = Model::new(model_name.to_string(), pipeline, mesh.clone());
-                let geometries_names = mesh_descriptor.geometries_names();
-
-                for (g_name, m_name) in &self.geometries_materials {
-                    // does the mesh declares the same geometries we are setting materials to ?
-                    if !geometries_names.contains(&g_name) {
-                        return Err(anyhow!(ModelError::MaterialNotSetForGeometry {
-                            geometry: g_name.clone(),
-                            model: model_name.clone(),
-                        }));
-                    }
+                let mut model = Model::new(model_name.to_string(), pipeline.clone(), mesh.clone());
 
+                let geometries_names: Vec<GeometryName> =
+                    mesh.geometries.iter().map(|g| g.name()).collect();
+                let geometries_materials = self.paired_materials(&geometries_names)?;
+
+                // materials and pipelines are aligned with the mesh geometries (draw order)
+                for (g_name, m_name) in geometries_names.into_iter().zip(geometries_materials) {
                     let material = assets
                         .get(m_name.clone())
                         .and_then(|desc: &AssetDescriptor| desc.try_as_ref())
                         .and_then(|descriptor: &MaterialDescriptor| descriptor.load(wgpu_state))?;
 
+                    let geometry_pipeline =
+                        self.geometry_pipeline(wgpu_state, &g_name, &pipeline)?;
+                    if !geometry_pipeline.can_use(material.kind()) {
+                        return Err(anyhow!(ModelError::InvalidMaterialAndPipeline {
+                            model: model_name.clone(),
+                            pipeline: geometry_pipeline.name(),
+                            reason: format!(
+                                "Pipeline does not support {} used by geometry {}",
+                                String::from(material.kind()),
+                                g_name
+                            ),
+                        }));
+                    }
+                    if geometry_pipeline.uses_packed_materials() && material.packed().is_none() {
+                        return Err(anyhow!(ModelError::InvalidMaterialAndPipeline {
+                            model: model_name.clone(),
+                            pipeline: geometry_pipeline.name(),
+                            reason: format!(
+                                "Material {} used by geometry {} is not packed",
+                                material.name(),
+                                g_name
+                            ),
+                        }));
+                    }
+
+                    let geometry_pipeline = self.blend_pipeline(
+                        wgpu_state,
+                        geometry_pipeline,
+                        material.blend_mode(),
+                        &g_name,
+                    )?;
+
                     model.materials.push(material.clone());
+                    model.pipelines.push(geometry_pipeline);
                 }
 
                 Ok(model)
             }
         }?;
 
+        model.casts_shadow = self.casts_shadow;
+        model.receives_shadow = self.receives_shadow;
+        model.instances = self.model_instances(&wgpu_state.device);
+        if !model.materials.is_empty() {
+            model.overrides = Some(ModelOverridesBuf::new(
+                &wgpu_state.device,
+                &model.name,
+                self.overrides,
+            ));
+        }
+        if let Some(instances) = &model.instances {
+            log::info!(
+                "Model {} has its own buffer for {} instances",
+                model.name,
+                instances.capacity()
+            );
+        }
+
         let model = Rc::new(model);
-        store.add_model(model.clone());
+        store.add_model(model.clone())?;
         Ok(model)
     }
+
+    /// Also release the model mesh and materials, unless other models use them
+    fn unload(&self, wgpu_state: &WgpuState) -> Result<()> {
+        let store = &wgpu_state.store;
+        let model = match store.remove_model(&self.name)? {
+            Some(model) => model,
+            None => return Ok(()),
+        };
+        log::info!("Unloaded model {}", self.name());
+        if Rc::strong_count(&model) > 1 {
+            log::warn!("Model {} is unloaded while still in use", self.name());
+        }
+        // only referenced by the store and this model
+        if Rc::strong_count(&model.mesh) == 2 {
+            store.remove_mesh(&model.mesh.name)?;
+            log::info!("Unloaded mesh {} of model {}", model.mesh.name, self.name());
+        }
+        for material in &model.materials {
+            // the model may use the material for several geometries
+            let model_uses = model
+                .materials
+                .iter()
+                .filter(|m| Rc::ptr_eq(m, material))
+                .count();
+            if Rc::strong_count(material) == model_uses + 1
+                && store.remove_material(&material.name())?.is_some()
+            {
+                log::info!(
+                    "Unloaded material {} of model {}",
+                    material.name(),
+                    self.name()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::render::config::vertex::{ObjSplit, VerticesSource};
+    use crate::render::scene::pipeline::PipelineOptions;
+
+    use super::*;
+
+    /// Textured hull and colored inflatable of the test mesh (`res/tests/mixed.obj`), drawn by
+    /// the textures pipeline but the inflatable
+    const MIXED_MODEL: &str = r#"(
+        name:"mixed",
+        mesh:("mixed"),
+        geometries_materials:[
+            (("hull"),("painted")),
+            (("inflatable"),("rubber")),
+        ],
+        pipeline_name:"textures_pipeline",
+        geometries_pipelines:[
+            (("inflatable"),"colors_pipeline"),
+        ],
+    )"#;
+
+    #[test]
+    fn geometries_pipelines_override_the_model_one() {
+        let descriptor: ModelDescriptor = ron::from_str(MIXED_MODEL).unwrap();
+        let geometries = VerticesSource::Obj("mixed.obj".to_string())
+            .load_from(
+                &Path::new(env!("CARGO_MANIFEST_DIR")).join("res/tests"),
+                ObjSplit::Group,
+            )
+            .unwrap();
+        let pipelines: Vec<(String, &str)> = geometries
+            .iter()
+            .map(|g| (g.name.clone(), descriptor.geometry_pipeline_name(&g.name())))
+            .collect();
+        assert_eq!(
+            pipelines,
+            vec![
+                ("hull".to_string(), "textures_pipeline"),
+                ("inflatable".to_string(), "colors_pipeline"),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadow_flags_default_to_true() {
+        let descriptor: ModelDescriptor = ron::from_str(
+            r#"(
+                name:"boat",
+                mesh:("zodiac"),
+                geometries_materials:[],
+                pipeline_name:"colors_pipeline",
+            )"#,
+        )
+        .unwrap();
+        assert!(descriptor.casts_shadow);
+        assert!(descriptor.receives_shadow);
+
+        let descriptor: ModelDescriptor = ron::from_str(
+            r#"(
+                name:"sea_square",
+                mesh:("sea"),
+                geometries_materials:[],
+                pipeline_name:"textures_pipeline",
+                casts_shadow:false,
+            )"#,
+        )
+        .unwrap();
+        assert!(!descriptor.casts_shadow);
+        assert!(descriptor.receives_shadow);
+    }
+
+    #[test]
+    fn materials_are_paired_with_the_geometries_by_name() {
+        let descriptor: ModelDescriptor = ron::from_str(MIXED_MODEL).unwrap();
+        // the mesh file orders its geometries unlike the descriptor
+        let materials = descriptor
+            .paired_materials(&[GeometryName::from("inflatable"), GeometryName::from("hull")])
+            .unwrap();
+        assert_eq!(
+            materials,
+            vec![MaterialName::from("rubber"), MaterialName::from("painted")]
+        );
+
+        // a material set on a geometry the mesh does not have
+        assert!(matches!(
+            descriptor.paired_materials(&[GeometryName::from("hull")]),
+            Err(ModelError::MaterialNotSetForGeometry { geometry, .. }) if geometry == GeometryName::from("inflatable")
+        ));
+        // a geometry of the mesh without material
+        assert!(matches!(
+            descriptor.paired_materials(&[
+                GeometryName::from("hull"),
+                GeometryName::from("inflatable"),
+                GeometryName::from("deck"),
+            ]),
+            Err(ModelError::MaterialNotSetForGeometry { geometry, .. }) if geometry == GeometryName::from("deck")
+        ));
+    }
+
+    #[test]
+    fn max_instances_sizes_a_dedicated_buffer() {
+        let descriptor: ModelDescriptor = ron::from_str(MIXED_MODEL).unwrap();
+        assert_eq!(descriptor.max_instances, None);
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        assert!(descriptor.model_instances(&device).is_none());
+
+        let descriptor: ModelDescriptor = ron::from_str(
+            r#"(
+                name:"grass",
+                mesh:("grass"),
+                geometries_materials:[],
+                pipeline_name:"colors_pipeline",
+                max_instances:Some(500),
+            )"#,
+        )
+        .unwrap();
+        let instances = descriptor.model_instances(&device).unwrap();
+        assert_eq!(instances.capacity(), 500);
+        assert_eq!(instances.count(), 0);
+    }
+
+    #[test]
+    fn sea_is_drawn_without_back_face_culling() {
+        let models: Vec<ModelDescriptor> = ron::from_str(
+            &std::fs::read_to_string(
+                Path::new(env!("CARGO_MANIFEST_DIR")).join("res/config/models.ron"),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let sea = models.iter().find(|m| m.name == "sea_square").unwrap();
+        // visible from under the waterline
+        assert_eq!(sea.pipeline_name, "textures_double_sided_pipeline");
+        let options = PipelineOptions::new(wgpu::TextureFormat::Depth32Float, 1).double_sided();
+        assert_eq!(options.cull_mode, None);
+    }
 }
@@ -0,0 +1,266 @@
+use crate::render::{
+    scene::{
+        camera::CameraSystem,
+        color_material::ColorMaterial,
+        instance::InstanceRaw,
+        light,
+        material::MaterialKind,
+        overrides::ModelOverridesBuf,
+        pipeline::{create_custom_pipeline, NamedPipeline, PipelineOptions},
+        texture_material::TextureMaterial,
+        unlit_material::UnlitMaterial,
+        vertex::ModelVertex,
+    },
+    state::WgpuState,
+};
+use anyhow::Result;
+use serde::Deserialize;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::{default_true, WgpuResourceLoader};
+
+/// Faces not drawn
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CullMode {
+    None,
+    Front,
+    #[default]
+    Back,
+}
+
+impl From<CullMode> for Option<wgpu::Face> {
+    fn from(mode: CullMode) -> Self {
+        match mode {
+            CullMode::None => None,
+            CullMode::Front => Some(wgpu::Face::Front),
+            CullMode::Back => Some(wgpu::Face::Back),
+        }
+    }
+}
+
+/// How the fragments colors are written over the target ones
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Blend {
+    /// the fragment color replaces the target one
+    Replace,
+    /// the fragment color is blended over the target one by its alpha
+    #[default]
+    Alpha,
+}
+
+impl From<Blend> for Option<wgpu::BlendState> {
+    fn from(blend: Blend) -> Self {
+        match blend {
+            Blend::Replace => None,
+            Blend::Alpha => Some(wgpu::BlendState {
+                alpha: wgpu::BlendComponent::REPLACE,
+                color: wgpu::BlendComponent::OVER,
+            }),
+        }
+    }
+}
+
+/// Pipeline drawing the models with a WGSL shader read at runtime.
+///
+/// The shader entry points are `vs_main` and `fs_main`, it takes the `ModelVertex` and
+/// `InstanceRaw` vertex buffers and the bind groups:
+/// - 0: camera
+/// - 1: light, if `lit`
+/// - then the material, if the pipeline supports materials
+/// - then the model overrides (see `ModelOverrides`), if the pipeline supports materials
+///
+/// ```ron
+/// (
+///     name:"toon_pipeline",
+///     shader:"toon.wgsl",
+///     materials:[Color],
+///     cull_mode:Back,
+/// )
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct PipelineDescriptor {
+    pub(crate) name: String,
+    /// WGSL file, in the shaders directory
+    shader: PathBuf,
+    /// kinds of the materials bound, sharing a bind group layout
+    #[serde(default)]
+    materials: Vec<MaterialKind>,
+    #[serde(default = "default_true")]
+    lit: bool,
+    #[serde(default)]
+    cull_mode: CullMode,
+    #[serde(default)]
+    blend: Blend,
+    #[serde(default = "default_true")]
+    depth_write: bool,
+}
+
+impl PipelineDescriptor {
+    fn material_bind_group_layout(&self, device: &wgpu::Device) -> Option<wgpu::BindGroupLayout> {
+        self.materials.first().map(|kind| match kind {
+            MaterialKind::Texture => TextureMaterial::bind_group_layout(device),
+            MaterialKind::Color => ColorMaterial::bind_group_layout(device),
+            MaterialKind::Unlit => UnlitMaterial::bind_group_layout(device),
+        })
+    }
+
+    /// Build the pipeline, reading its shader from `shaders_directory`
+    fn build(
+        &self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shaders_directory: &Path,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Result<NamedPipeline> {
+        // identical layouts are deduplicated by wgpu: they match the scene bind groups
+        let camera_bgl = CameraSystem::bind_group_layout(device);
+        let light_bgl = light::bind_group_layout(device);
+        let material_bgl = self.material_bind_group_layout(device);
+        let mut bind_group_layouts = vec![&camera_bgl];
+        if self.lit {
+            bind_group_layouts.push(&light_bgl);
+        }
+        let overrides_bgl = ModelOverridesBuf::bind_group_layout(device);
+        if let Some(material_bgl) = &material_bgl {
+            bind_group_layouts.extend([material_bgl, &overrides_bgl]);
+        }
+
+        let options = PipelineOptions {
+            cull_mode: self.cull_mode.into(),
+            blend: self.blend.into(),
+            depth_write_enabled: self.depth_write,
+            ..PipelineOptions::new(depth_format, sample_count)
+        };
+        let shader = shaders_directory.join(&self.shader);
+        let pipeline = create_custom_pipeline(
+            device,
+            config,
+            &self.name,
+            shader,
+            &[ModelVertex::desc(), InstanceRaw::desc()],
+            &bind_group_layouts,
+            options,
+        )?;
+        let mut pipeline = NamedPipeline::new(&self.name, pipeline, self.materials.clone());
+        if !self.lit {
+            pipeline = pipeline.unlit();
+        }
+        Ok(pipeline)
+    }
+}
+
+impl WgpuResourceLoader for PipelineDescriptor {
+    type Output = Rc<NamedPipeline>;
+
+    fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output> {
+        if let Some(pipeline) = wgpu_state.store.get_pipeline(&self.name)? {
+            return Ok(pipeline);
+        }
+        log::info!("Load {}", self);
+        let pipeline = self.build(
+            &wgpu_state.device,
+            &wgpu_state.config,
+            Path::new(&wgpu_state.settings.shaders_directory),
+            wgpu_state.depth_format,
+            wgpu_state.sample_count,
+        )?;
+        let pipeline = Rc::new(pipeline);
+        wgpu_state.store.add_pipeline(pipeline.clone())?;
+        Ok(pipeline)
+    }
+
+    fn unload(&self, wgpu_state: &WgpuState) -> Result<()> {
+        if wgpu_state.store.remove_pipeline(&self.name)?.is_some() {
+            log::info!("Unloaded pipeline {}", self.name);
+        }
+        Ok(())
+    }
+}
+
+impl Display for PipelineDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PipelineDescriptor:\"{}\" from {:?}",
+            self.name, self.shader
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_settings_take_the_defaults() {
+        let descriptor: PipelineDescriptor = ron::from_str(
+            r#"(
+                name:"toon_pipeline",
+                shader:"toon.wgsl",
+                materials:[Color],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(descriptor.name, "toon_pipeline");
+        assert_eq!(descriptor.shader, PathBuf::from("toon.wgsl"));
+        assert_eq!(descriptor.materials, vec![MaterialKind::Color]);
+        assert!(descriptor.lit);
+        assert_eq!(descriptor.cull_mode, CullMode::Back);
+        assert_eq!(descriptor.blend, Blend::Alpha);
+        assert!(descriptor.depth_write);
+
+        let descriptor: PipelineDescriptor = ron::from_str(
+            r#"(
+                name:"glass_pipeline",
+                shader:"glass.wgsl",
+                lit:false,
+                cull_mode:None,
+                blend:Replace,
+                depth_write:false,
+            )"#,
+        )
+        .unwrap();
+        assert!(descriptor.materials.is_empty());
+        assert!(!descriptor.lit);
+        assert_eq!(descriptor.cull_mode, CullMode::None);
+        assert_eq!(descriptor.blend, Blend::Replace);
+        assert!(!descriptor.depth_write);
+    }
+
+    #[test]
+    fn configured_pipeline_builds() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
+        let descriptors: Vec<PipelineDescriptor> =
+            ron::from_str(&std::fs::read_to_string(root.join("config/pipelines.ron")).unwrap())
+                .unwrap();
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 4,
+            height: 4,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        for descriptor in descriptors {
+            let pipeline = descriptor
+                .build(
+                    &device,
+                    &config,
+                    &root.join("shaders"),
+                    wgpu::TextureFormat::Depth32Float,
+                    1,
+                )
+                .unwrap();
+            assert_eq!(pipeline.name(), descriptor.name);
+            assert_eq!(pipeline.is_lit(), descriptor.lit);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::Result;
+use cgmath::{Deg, Euler, Quaternion};
+use serde::{Deserialize, Serialize};
+
+use crate::render::{
+    names::ModelName,
+    scene::{
+        instance::InstanceUsage,
+        object::{Object, DEFAULT_LAYERS},
+    },
+};
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_layers() -> u32 {
+    DEFAULT_LAYERS
+}
+
+/// An object placed in the scene
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ObjectDescriptor {
+    pub name: String,
+    pub model: ModelName,
+    pub position: [f32; 3],
+    /// euler angles (degrees), applied in the x, y, z order
+    #[serde(default)]
+    pub orientation: [f32; 3],
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// layers bitmask
+    #[serde(default = "default_layers")]
+    pub layers: u32,
+    /// static objects are not meant to move
+    #[serde(default)]
+    pub usage: InstanceUsage,
+}
+
+impl ObjectDescriptor {
+    pub fn to_object(&self) -> Object {
+        let mut object = Object::new(self.name.clone(), self.model.clone());
+        let [x, y, z] = self.position;
+        object.set_position((x, y, z));
+        let [x, y, z] = self.orientation;
+        object.orientation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z)));
+        object.mesh_scale = self.scale;
+        object.layers = self.layers;
+        object.usage = self.usage;
+        object
+    }
+}
+
+impl From<&Object> for ObjectDescriptor {
+    fn from(object: &Object) -> Self {
+        // same convention as the loading conversion (see `to_object`)
+        let euler = Euler::from(object.orientation);
+        Self {
+            name: object.name(),
+            model: object.model(),
+            position: object.position.into(),
+            orientation: [
+                Deg::from(euler.x).0,
+                Deg::from(euler.y).0,
+                Deg::from(euler.z).0,
+            ],
+            scale: object.mesh_scale,
+            layers: object.layers,
+            usage: object.usage,
+        }
+    }
+}
+
+/// Many objects of one model (grass, rocks...) randomly placed in an area, the same seed
+/// always giving the same placements
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScatterDescriptor {
+    /// prefix of the objects names, suffixed with their index
+    pub name: String,
+    pub model: ModelName,
+    pub count: u32,
+    /// min and max corners of the box the objects are placed in
+    pub area: ([f32; 3], [f32; 3]),
+    pub seed: u64,
+    /// min and max scales
+    #[serde(default = "default_scale_range")]
+    pub scale_range: (f32, f32),
+    #[serde(default = "default_layers")]
+    pub layers: u32,
+    #[serde(default)]
+    pub usage: InstanceUsage,
+}
+
+fn default_scale_range() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+impl ScatterDescriptor {
+    /// The scattered objects, randomly turned around the vertical axis
+    pub fn to_objects(&self) -> Vec<Object> {
+        let mut rng = SplitMix64(self.seed);
+        let (min, max) = self.area;
+        let (min_scale, max_scale) = self.scale_range;
+        (0..self.count)
+            .map(|index| {
+                let mut position = [0.0; 3];
+                for (axis, p) in position.iter_mut().enumerate() {
+                    *p = min[axis] + rng.next_f32() * (max[axis] - min[axis]);
+                }
+                ObjectDescriptor {
+                    name: format!("{}_{}", self.name, index),
+                    model: self.model.clone(),
+                    position,
+                    orientation: [0.0, rng.next_f32() * 360.0, 0.0],
+                    scale: min_scale + rng.next_f32() * (max_scale - min_scale),
+                    layers: self.layers,
+                    usage: self.usage,
+                }
+                .to_object()
+            })
+            .collect()
+    }
+}
+
+/// Small seeded generator (splitmix64): the placements must not depend on a platform rng
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1[
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// The scene objects layout
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SceneDescriptor {
+    pub objects: Vec<ObjectDescriptor>,
+}
+
+impl SceneDescriptor {
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self> {
+        Ok(ron::from_str(ron)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROCKS: &str = r#"(
+        name: "rock",
+        model: ("rock"),
+        count: 50,
+        area: ((-10.0, 0.0, -5.0), (10.0, 2.0, 5.0)),
+        seed: 42,
+        scale_range: (0.5, 1.5),
+    )"#;
+
+    #[test]
+    fn saved_scene_loads_the_same_transforms() {
+        let mut boat = Object::new("boat".to_string(), ModelName::from("color_zod"));
+        boat.set_position((1.5, -2.0, 30.0));
+        boat.orientation = Quaternion::from(Euler::new(Deg(10.0), Deg(-35.0), Deg(120.0)));
+        boat.mesh_scale = 2.5;
+        let mut buoy = Object::new("buoy".to_string(), ModelName::from("toon_zod"));
+        buoy.orientation = Quaternion::from(Euler::new(Deg(0.0), Deg(200.0), Deg(0.0)));
+        buoy.usage = InstanceUsage::Static;
+        let objects = [boat, buoy];
+
+        let scene = SceneDescriptor {
+            objects: objects.iter().map(ObjectDescriptor::from).collect(),
+        };
+        let loaded = SceneDescriptor::from_ron(&scene.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded.objects.len(), objects.len());
+
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        for (object, descriptor) in objects.iter().zip(&loaded.objects) {
+            let loaded = descriptor.to_object();
+            assert_eq!(loaded.name(), object.name());
+            assert_eq!(loaded.model(), object.model());
+            assert_eq!(loaded.usage, object.usage);
+            assert!(close(loaded.mesh_scale, object.mesh_scale));
+            for axis in 0..3 {
+                assert!(close(loaded.position[axis], object.position[axis]));
+            }
+            // q and -q are the same rotation
+            let dot = cgmath::InnerSpace::dot(loaded.orientation, object.orientation);
+            assert!(close(dot.abs(), 1.0), "{:?}", loaded.orientation);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_placements() {
+        let rocks: ScatterDescriptor = ron::from_str(ROCKS).unwrap();
+        let objects = rocks.to_objects();
+        assert_eq!(objects.len(), 50);
+        assert_eq!(objects, rocks.to_objects());
+        assert_eq!(objects[7].name(), "rock_7");
+
+        let other_seed = ScatterDescriptor { seed: 43, ..rocks };
+        assert_ne!(objects, other_seed.to_objects());
+    }
+
+    #[test]
+    fn scattered_objects_stay_in_the_area() {
+        let rocks: ScatterDescriptor = ron::from_str(ROCKS).unwrap();
+        let (min, max) = rocks.area;
+        for object in rocks.to_objects() {
+            let position: [f32; 3] = object.position.into();
+            for axis in 0..3 {
+                assert!((min[axis]..=max[axis]).contains(&position[axis]));
+            }
+            assert!((0.5..=1.5).contains(&object.mesh_scale));
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::render::{
-    scene::texture::{Texture, TextureKind},
-    state::WgpuState,
+    scene::{
+        store::{Store, TextureFile},
+        texture::{Texture, TextureKind},
+    },
+    state::{check_limit, WgpuState},
 };
 use anyhow::Result;
 use serde::Deserialize;
@@ -12,13 +15,27 @@ use super::WgpuResourceLoader;
 #[derive(Deserialize, Debug, Clone)]
 pub struct TextureDescriptor {
     pub(crate) name: String,
+    /// image file, unused by the render targets
+    #[serde(default)]
     path: PathBuf,
     kind: TextureKind,
+    /// downscale the image on load if larger (the device limit applies anyway)
+    #[serde(default)]
+    max_dimension: Option<u32>,
+    /// decode the image in the background, a placeholder color is shown meanwhile
+    #[serde(default)]
+    streamed: bool,
 }
 
 impl TextureDescriptor {
     pub fn _new_(name: String, path: PathBuf, kind: TextureKind) -> Self {
-        Self { name, path, kind }
+        Self {
+            name,
+            path,
+            kind,
+            max_dimension: None,
+            streamed: false,
+        }
     }
 }
 
@@ -35,31 +52,120 @@ impl WgpuResourceLoader for TextureDescriptor {
             directory
         );
 
-        if wgpu_state.store.contains_texture(&self.name) {
-            return Ok(wgpu_state
-                .store
-                .get_texture(&self.name)
-                .expect("Impossible err 2"));
+        if let Some(texture) = wgpu_state.store.get_texture(&self.name)? {
+            return Ok(texture);
+        }
+        let target = match self.kind {
+            TextureKind::RenderTarget {
+                width,
+                height,
+                format,
+            } => Some((width, height, format.format(wgpu_state.config.format))),
+            TextureKind::ShadowMap { size } => Some((size, size, wgpu_state.depth_format)),
+            TextureKind::Diffuse | TextureKind::Normal => None,
+        };
+        if let Some((width, height, format)) = target {
+            let max_dimension = wgpu_state.limits().max_texture_dimension_2d as u64;
+            let resource = format!("render target {}", self.name);
+            check_limit(
+                &resource,
+                "max_texture_dimension_2d",
+                width as u64,
+                max_dimension,
+            )?;
+            check_limit(
+                &resource,
+                "max_texture_dimension_2d",
+                height as u64,
+                max_dimension,
+            )?;
+            let texture = Rc::new(match self.kind {
+                TextureKind::ShadowMap { size } => {
+                    Texture::create_shadow_map(&wgpu_state.device, &self.name, size, format)
+                }
+                _ => Texture::create_render_target(
+                    &wgpu_state.device,
+                    &self.name,
+                    width,
+                    height,
+                    format,
+                ),
+            });
+            wgpu_state.store.add_texture(&self.name, texture.clone())?;
+            return Ok(texture);
         }
         let is_normal_map = self.kind == TextureKind::Normal;
-        let texture = Texture::load(
-            &wgpu_state.device,
-            &wgpu_state.queue,
-            directory.join(&self.path),
-            is_normal_map,
-        )?;
-        let texture = Rc::new(texture);
-        wgpu_state.store.add_texture(&self.name, texture.clone());
-        Ok(texture.clone())
+        let device_max_dimension = wgpu_state.device.limits().max_texture_dimension_2d;
+        let max_dimension = self
+            .max_dimension
+            .map_or(device_max_dimension, |max| max.min(device_max_dimension));
+        let path = directory.join(&self.path);
+        // another descriptor may have loaded the same image
+        let file = TextureFile::new(&path, is_normal_map, max_dimension);
+        share_texture_file(&wgpu_state.store, &self.name, file, || {
+            // the compressed files are not decoded, they are loaded right away
+            if self.streamed && Texture::compressed_extension(&path).is_none() {
+                let placeholder = Texture::placeholder(
+                    &wgpu_state.device,
+                    &wgpu_state.queue,
+                    &path,
+                    is_normal_map,
+                    max_dimension,
+                )?;
+                wgpu_state
+                    .store
+                    .texture_stream
+                    .spawn(&self.name, path, max_dimension);
+                Ok(placeholder)
+            } else {
+                Texture::load(
+                    &wgpu_state.device,
+                    &wgpu_state.queue,
+                    path,
+                    is_normal_map,
+                    max_dimension,
+                )
+            }
+        })
+    }
+
+    fn unload(&self, wgpu_state: &WgpuState) -> Result<()> {
+        if wgpu_state.store.remove_texture(&self.name)?.is_some() {
+            log::info!("Unloaded texture {}", self.name);
+        }
+        Ok(())
     }
 }
 
+/// Store the texture of the file under `name`: the one another descriptor loaded from the file if
+/// still in use, else the one uploaded by `load`
+fn share_texture_file<F>(
+    store: &Store,
+    name: &str,
+    file: TextureFile,
+    load: F,
+) -> Result<Rc<Texture>>
+where
+    F: FnOnce() -> Result<Texture>,
+{
+    if let Some(texture) = store.get_texture_file(&file)? {
+        log::info!("Share texture file {:?} with {}", file, name);
+        store.add_texture(name, texture.clone())?;
+        return Ok(texture);
+    }
+    let texture = Rc::new(load()?);
+    store.add_texture_file(file, &texture)?;
+    store.add_texture(name, texture.clone())?;
+    Ok(texture)
+}
+
 impl Display for TextureDescriptor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let k = if self.kind == TextureKind::Diffuse {
-            "diffuse"
-        } else {
-            "normal"
+        let k = match self.kind {
+            TextureKind::Diffuse => "diffuse",
+            TextureKind::Normal => "normal",
+            TextureKind::RenderTarget { .. } => "render target",
+            TextureKind::ShadowMap { .. } => "shadow map",
         };
         write!(
             f,
@@ -68,3 +174,39 @@ impl Display for TextureDescriptor {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::path::Path;
+
+    use crate::render::state::tests::headless_device;
+
+    use super::*;
+
+    #[test]
+    fn descriptors_of_one_file_share_one_upload() {
+        let (device, queue) = match headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let store = Store::new();
+        let uploads = Cell::new(0);
+        let upload = || {
+            uploads.set(uploads.get() + 1);
+            Texture::flat_normal(&device, &queue)
+        };
+        let file = || TextureFile::new(Path::new("res/textures/hull.png"), false, 1024);
+
+        let hull = share_texture_file(&store, "hull", file(), upload).unwrap();
+        let deck = share_texture_file(&store, "deck", file(), upload).unwrap();
+        assert_eq!(uploads.get(), 1);
+        assert!(Rc::ptr_eq(&hull, &deck));
+        assert!(store.get_texture("deck").unwrap().is_some());
+
+        // decoded differently: another upload
+        let normal = TextureFile::new(Path::new("res/textures/hull.png"), true, 1024);
+        share_texture_file(&store, "hull_normal", normal, upload).unwrap();
+        assert_eq!(uploads.get(), 2);
+    }
+}
@@ -2,7 +2,70 @@ use super::{geometry::GeometryVertices, WgpuResourceLoader};
 use crate::render::{scene::vertex::ModelVertex, state::WgpuState};
 use anyhow::{Context, Result};
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// How the faces of an OBJ file are split into geometries
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjSplit {
+    /// a geometry per object or group, named after it
+    #[default]
+    Group,
+    /// a geometry per material, named after it (the faces without material make the
+    /// "default" geometry), whatever their groups
+    Material,
+}
+
+/// Geometry name of the faces without material, when split by material
+const NO_MATERIAL_GEOMETRY: &str = "default";
+
+/// Geometries of the loaded OBJ models.
+///
+/// tobj starts a new model at each material change, the models of a material are merged when
+/// splitting by material.
+fn obj_geometries(
+    obj_models: Vec<tobj::Model>,
+    materials: &[tobj::Material],
+    split: ObjSplit,
+) -> Vec<GeometryVertices<ModelVertex>> {
+    let mut geometries: Vec<GeometryVertices<ModelVertex>> = vec![];
+    for tobj_model in obj_models {
+        let mut vertices = Vec::new();
+        ModelVertex::fill_vertices_from_model(&mut vertices, &tobj_model);
+        let name = match split {
+            ObjSplit::Group => tobj_model.name.clone(),
+            ObjSplit::Material => tobj_model
+                .mesh
+                .material_id
+                .map(|id| {
+                    materials
+                        .get(id)
+                        .map_or_else(|| format!("material_{}", id), |m| m.name.clone())
+                })
+                .unwrap_or_else(|| NO_MATERIAL_GEOMETRY.to_string()),
+        };
+        match geometries
+            .iter_mut()
+            .find(|g| split == ObjSplit::Material && g.name == name)
+        {
+            Some(geometry) => {
+                let offset = geometry.vertices.len() as u32;
+                geometry.vertices.extend(vertices);
+                geometry
+                    .indices
+                    .extend(tobj_model.mesh.indices.iter().map(|i| i + offset));
+            }
+            None => geometries.push(GeometryVertices::new(
+                &name,
+                vertices,
+                tobj_model.mesh.indices,
+            )),
+        }
+    }
+    geometries
+}
 
 /// Describe the kind of file/source is a mesh from
 #[derive(Deserialize, Debug)]
@@ -11,15 +74,54 @@ pub enum VerticesSource {
     // one day...
 }
 
-impl WgpuResourceLoader for VerticesSource {
-    type Output = Vec<GeometryVertices<ModelVertex>>;
+impl VerticesSource {
+    /// Materials defined by the source material library (obj + mtl)
+    pub fn materials(&self, directory: &Path) -> Result<Vec<tobj::Material>> {
+        match &self {
+            VerticesSource::Obj(path) => {
+                let (_, materials) = tobj::load_obj(
+                    directory.join(path),
+                    &tobj::LoadOptions {
+                        triangulate: true,
+                        single_index: true,
+                        ..Default::default()
+                    },
+                )
+                .context(format!("Failed to load obj {} ({:?})", path, directory))?;
+                Ok(materials.context(format!("Failed to load the materials of {}", path))?)
+            }
+        }
+    }
 
-    fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output> {
+    /// Last modification time of the source file (None for the sources not read from a file or
+    /// if the file metadata are not available)
+    pub fn modified(&self, directory: &Path) -> Option<SystemTime> {
+        match &self {
+            VerticesSource::Obj(path) => std::fs::metadata(directory.join(path))
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+        }
+    }
+
+    /// Geometries of the source, split as requested
+    pub fn load_split(
+        &self,
+        wgpu_state: &WgpuState,
+        split: ObjSplit,
+    ) -> Result<Vec<GeometryVertices<ModelVertex>>> {
         let directory = PathBuf::from(wgpu_state.settings.meshes_directory.to_string());
+        self.load_from(&directory, split)
+    }
+
+    pub(crate) fn load_from(
+        &self,
+        directory: &Path,
+        split: ObjSplit,
+    ) -> Result<Vec<GeometryVertices<ModelVertex>>> {
         match &self {
             VerticesSource::Obj(path) => {
                 log::info!("Load obj {} ({:?})", path.to_string(), directory);
-                let (obj_models, _) = tobj::load_obj(
+                let (obj_models, materials) = tobj::load_obj(
                     directory.join(path),
                     &tobj::LoadOptions {
                         triangulate: true,
@@ -32,15 +134,24 @@ impl WgpuResourceLoader for VerticesSource {
                     path.to_string(),
                     directory
                 ))?;
-                Ok(obj_models
-                    .into_iter()
-                    .map(|tobj_model| {
-                        let mut vertices = Vec::new();
-                        ModelVertex::fill_vertices_from_model(&mut vertices, &tobj_model);
-                        GeometryVertices::new(&tobj_model.name, vertices, tobj_model.mesh.indices)
-                    })
-                    .collect())
+                // the material names are only needed to split by material
+                let materials = match (split, materials) {
+                    (ObjSplit::Material, Err(e)) => {
+                        log::warn!("Cannot load the materials of {}: {}", path, e);
+                        vec![]
+                    }
+                    (_, materials) => materials.unwrap_or_default(),
+                };
+                Ok(obj_geometries(obj_models, &materials, split))
             }
         }
     }
 }
+
+impl WgpuResourceLoader for VerticesSource {
+    type Output = Vec<GeometryVertices<ModelVertex>>;
+
+    fn load(&self, wgpu_state: &WgpuState) -> Result<Self::Output> {
+        self.load_split(wgpu_state, ObjSplit::Group)
+    }
+}
@@ -1,71 +1,246 @@
-use std::{sync::Arc, time::Instant};
+use std::{collections::HashMap, rc::Rc, sync::Arc, time::Instant};
 
-use egui::FontDefinitions;
 use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
 use egui_winit_platform::{Platform, PlatformDescriptor};
 use winit::{event_loop::EventLoop, window::Window};
 
 use crate::{
-    app::event::{Emitter, EventEmitter, PomarinEvent},
-    render::state::WgpuState,
+    app::{
+        config::{RenderSettings, UiConfig},
+        event::{Emitter, EventEmitter, PomarinEvent},
+    },
+    render::{
+        config::{assets::AssetDescriptor, material::MaterialDescriptor},
+        gpu_timer::GpuTimings,
+        names::{NamedHandle, TextureName},
+        rpass::WgpuRpass,
+        scene::texture::Texture,
+        state::WgpuState,
+    },
 };
 
+use super::ui::{AssetPreview, AssetsView, DiagnosticsView, Preview, RenderSettingsView};
+
 // retain egui state
 pub struct EguiWgpuPass<T>
 where
-    T: EventEmitter<PomarinEvent> + epi::App,
+    T: EventEmitter<PomarinEvent> + epi::App + AssetsView + DiagnosticsView + RenderSettingsView,
 {
     platform: Platform,
     rpass: egui_wgpu_backend::RenderPass,
     previous_frame_time: Option<f32>,
     repainter: Arc<dyn epi::backend::RepaintSignal>,
+    /// format the egui pipeline was created for, must be the one of the resolved scene target
+    target_format: wgpu::TextureFormat,
+    /// store textures registered in egui (by texture name) to be displayed as thumbnails
+    thumbnails: HashMap<String, (Rc<Texture>, egui::TextureId)>,
+    /// egui ids of the thumbnails of the unloaded textures, reused by the next thumbnails (the
+    /// egui backend cannot free a texture)
+    free_thumbnail_ids: Vec<egui::TextureId>,
+    /// ui scale factor, the window one multiplied by the configured ui scale
+    scale_factor: f64,
     gui: T,
 }
 
 impl<T> EguiWgpuPass<T>
 where
-    T: EventEmitter<PomarinEvent> + epi::App,
+    T: EventEmitter<PomarinEvent> + epi::App + AssetsView + DiagnosticsView + RenderSettingsView,
 {
     pub fn new(
         wgpu: &WgpuState,
         window: &Window,
         event_loop: &EventLoop<PomarinEvent>,
+        ui_config: &UiConfig,
         mut gui: T,
     ) -> EguiWgpuPass<T> {
         let repainter = Arc::new(Emitter::new(event_loop));
 
         let inner_size = window.inner_size();
+        let scale_factor = ui_config.scale_factor(window.scale_factor());
         let platform = Platform::new(PlatformDescriptor {
             physical_width: inner_size.width,
             physical_height: inner_size.height,
-            scale_factor: window.scale_factor(),
-            font_definitions: FontDefinitions::default(),
-            style: Default::default(),
+            scale_factor,
+            font_definitions: ui_config.font_definitions(),
+            style: ui_config.style(),
         });
 
         let rpass = RenderPass::new(&wgpu.device, wgpu.surface_format, 1);
 
         gui.set_emitter_from(event_loop);
+        gui.set_diagnostics(wgpu.diagnostics());
 
         EguiWgpuPass {
             platform,
             rpass,
+            target_format: wgpu.surface_format,
             previous_frame_time: None,
             repainter,
+            thumbnails: HashMap::new(),
+            free_thumbnail_ids: vec![],
+            scale_factor,
             gui,
         }
     }
+
+    /// Register the store textures in egui, or update the registration if the texture was
+    /// reloaded, and build the textures and materials previews.
+    ///
+    /// The thumbnails of the textures no longer stored (unloaded) are dropped, so that they do
+    /// not keep the textures alive.
+    fn assets_previews(&mut self, wgpu: &WgpuState) -> Vec<AssetPreview> {
+        let textures = match wgpu.store.textures() {
+            Ok(textures) => {
+                let free_ids = &mut self.free_thumbnail_ids;
+                self.thumbnails.retain(|name, (_, id)| {
+                    let stored = textures.iter().any(|(stored, _)| stored == name);
+                    if !stored {
+                        free_ids.push(*id);
+                    }
+                    stored
+                });
+                textures
+            }
+            Err(e) => {
+                log::error!("Cannot list the textures previews: {}", e);
+                vec![]
+            }
+        };
+        for (name, texture) in textures {
+            match self.thumbnails.get_mut(&name) {
+                Some((registered, _)) if Rc::ptr_eq(registered, &texture) => {}
+                Some((registered, id)) => {
+                    if let Err(e) = self.rpass.update_egui_texture_from_wgpu_texture(
+                        &wgpu.device,
+                        &texture.texture,
+                        wgpu::FilterMode::Linear,
+                        *id,
+                    ) {
+                        log::warn!("Cannot update thumbnail of {}: {}", name, e);
+                    }
+                    *registered = texture;
+                }
+                None => {
+                    let id = match self.free_thumbnail_ids.pop() {
+                        Some(id) => {
+                            if let Err(e) = self.rpass.update_egui_texture_from_wgpu_texture(
+                                &wgpu.device,
+                                &texture.texture,
+                                wgpu::FilterMode::Linear,
+                                id,
+                            ) {
+                                log::warn!("Cannot register thumbnail of {}: {}", name, e);
+                            }
+                            id
+                        }
+                        None => self.rpass.egui_texture_from_wgpu_texture(
+                            &wgpu.device,
+                            &texture.texture,
+                            wgpu::FilterMode::Linear,
+                        ),
+                    };
+                    self.thumbnails.insert(name, (texture, id));
+                }
+            }
+        }
+
+        let thumbnail = |texture: &TextureName| {
+            self.thumbnails
+                .get(texture.as_str())
+                .map_or(Preview::None, |(_, id)| Preview::Texture(*id))
+        };
+        let mut previews: Vec<AssetPreview> = wgpu
+            .assets
+            .descriptors()
+            .filter_map(|descriptor| {
+                let preview = match descriptor {
+                    AssetDescriptor::Texture(texture) => thumbnail(&texture.name()),
+                    AssetDescriptor::Material(MaterialDescriptor::Color(color)) => {
+                        Preview::Color(color.diffuse)
+                    }
+                    AssetDescriptor::Material(MaterialDescriptor::Unlit(unlit)) => {
+                        let [r, g, b, _] = unlit.color;
+                        Preview::Color([r, g, b])
+                    }
+                    AssetDescriptor::Material(MaterialDescriptor::Texture(texture)) => {
+                        thumbnail(&texture.diffuse_texture)
+                    }
+                    AssetDescriptor::Mesh(_)
+                    | AssetDescriptor::Model(_)
+                    | AssetDescriptor::Pipeline(_) => return None,
+                };
+                let used_by = match descriptor {
+                    AssetDescriptor::Texture(texture) => {
+                        wgpu.assets.models_using_texture(&texture.name())
+                    }
+                    AssetDescriptor::Material(material) => {
+                        wgpu.assets.models_using_material(&material.name())
+                    }
+                    _ => vec![],
+                };
+                Some(AssetPreview {
+                    name: descriptor.name().to_string(),
+                    preview,
+                    used_by: used_by.iter().map(|model| model.to_string()).collect(),
+                })
+            })
+            .collect();
+        previews.sort_by(|a, b| a.name.cmp(&b.name));
+        previews
+    }
     pub fn handle_event(&mut self, event: &winit::event::Event<PomarinEvent>) {
         self.platform.handle_event(event);
+        // the platform takes the new window scale factor as is
+        if let winit::event::Event::WindowEvent {
+            event: winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. },
+            ..
+        } = event
+        {
+            log::warn!("Window scale factor changed, the ui scale is reset");
+            self.scale_factor = *scale_factor;
+        }
+    }
+
+    /// Give the ui the passes GPU durations of the last frame
+    pub fn set_gpu_timings(&mut self, timings: Option<GpuTimings>) {
+        self.gui.set_gpu_timings(timings);
+    }
+
+    /// Give the ui the render settings as applied
+    pub fn set_render_settings(&mut self, settings: RenderSettings) {
+        self.gui.set_render_settings(settings);
+    }
+
+    /// Whether egui is using the keyboard (a text field has the focus...)
+    pub fn wants_keyboard_input(&self) -> bool {
+        self.platform.context().wants_keyboard_input()
     }
 
-    pub fn render(
+    /// Whether the pointer is over an egui area or dragging one
+    pub fn wants_pointer_input(&self) -> bool {
+        self.platform.context().wants_pointer_input()
+    }
+}
+
+impl<T> WgpuRpass for EguiWgpuPass<T>
+where
+    T: EventEmitter<PomarinEvent> + epi::App + AssetsView + DiagnosticsView + RenderSettingsView,
+{
+    /// Composite the ui over `output_view`.
+    ///
+    /// The view is loaded, not cleared: it must hold the resolved (single sampled) scene image,
+    /// never a multisampled target, egui renders with a sample count of 1.
+    fn render(
         &mut self,
         wgpu: &WgpuState,
         window: &winit::window::Window,
         output_view: &wgpu::TextureView,
         mut encoder: wgpu::CommandEncoder,
     ) -> wgpu::CommandEncoder {
+        assert_eq!(
+            self.target_format, wgpu.config.format,
+            "egui pipeline format does not match the resolved target format"
+        );
         let egui_start = Instant::now();
         self.platform.begin_frame();
         let app_output = epi::backend::AppOutput::default();
@@ -75,13 +250,18 @@ where
                 name: "egui",
                 web_info: None,
                 cpu_usage: self.previous_frame_time,
-                native_pixels_per_point: Some(window.scale_factor() as _),
+                native_pixels_per_point: Some(self.scale_factor as _),
                 prefer_dark_mode: None,
             },
             output: app_output,
             repaint_signal: self.repainter.clone(),
         });
 
+        if self.gui.assets_visible() {
+            let previews = self.assets_previews(wgpu);
+            self.gui.set_assets(previews);
+        }
+
         // draw gui
         self.gui.update(&self.platform.context(), &mut frame);
 
@@ -96,7 +276,7 @@ where
         let screen_descriptor = ScreenDescriptor {
             physical_width: wgpu.config.width,
             physical_height: wgpu.config.height,
-            scale_factor: window.scale_factor() as f32,
+            scale_factor: self.scale_factor as f32,
         };
         self.rpass.update_texture(
             &wgpu.device,
@@ -1,23 +1,500 @@
+use crate::app::config::{AntiAliasing, PresentMode, RenderSettings};
 use crate::app::event::{Emitter, EventEmitter, PomarinEvent};
+use crate::render::gpu_timer::GpuTimings;
+use crate::render::names::{GeometryName, ModelName};
+use crate::render::scene::command::{SceneCommand, Transform};
+use crate::render::scene::draw_ext::GeometryFilter;
+use crate::render::scene::object::ALL_LAYERS;
+use crate::render::scene::particles::ParticleEmitter;
 use anyhow::{anyhow, Result};
 use egui::Align2;
-use std::sync::Arc;
+use std::sync::{mpsc::Sender, Arc};
 use winit::event_loop::EventLoop;
 
+/// Thumbnail size of the assets browser previews
+const THUMBNAIL_SIZE: f32 = 32.0;
+
+/// Number of layers toggled in the layers panel
+const SHOWN_LAYERS: u32 = 8;
+
+/// Levels of detail selectable in the scene panel (0 for the source geometries)
+const SHOWN_LODS: usize = 3;
+
+/// How an asset is previewed in the assets browser
+#[derive(Debug, Clone)]
+pub enum Preview {
+    /// a texture registered in the egui render pass
+    Texture(egui::TextureId),
+    /// a color swatch (linear rgb)
+    Color([f32; 3]),
+    /// nothing to show (the asset is not loaded yet)
+    None,
+}
+
+/// An entry of the assets browser
+#[derive(Debug, Clone)]
+pub struct AssetPreview {
+    pub name: String,
+    pub preview: Preview,
+    /// names of the models depending on the asset
+    pub used_by: Vec<String>,
+}
+
+/// Implemented by egui routines able to display the loaded assets.
+///
+/// The egui pass owns the textures registration, so it builds the previews and gives them to the
+/// routine, only when the routine actually shows them.
+pub trait AssetsView {
+    fn assets_visible(&self) -> bool;
+    fn set_assets(&mut self, assets: Vec<AssetPreview>);
+}
+
+/// Implemented by egui routines displaying the GPU diagnostics (see `WgpuState::diagnostics()`)
+pub trait DiagnosticsView {
+    fn set_diagnostics(&mut self, diagnostics: Vec<(&'static str, String)>);
+    /// Last frame passes GPU durations, `None` if they are not measured
+    fn set_gpu_timings(&mut self, timings: Option<GpuTimings>);
+}
+
+/// Implemented by egui routines editing the runtime adjustable render options
+pub trait RenderSettingsView {
+    /// Settings as actually applied by the render (an unsupported option may have been dropped)
+    fn set_render_settings(&mut self, settings: RenderSettings);
+}
+
+/// Targets of the scene panel commands, as edited
+struct SceneControls {
+    object: String,
+    model: String,
+    geometry: String,
+    position: [f32; 3],
+    scale: f32,
+    show_bounds: bool,
+    lod: usize,
+}
+
+impl Default for SceneControls {
+    fn default() -> Self {
+        Self {
+            object: "buoy".to_string(),
+            model: "color_zod".to_string(),
+            geometry: String::new(),
+            position: [0.0; 3],
+            scale: 1.0,
+            show_bounds: false,
+            lod: 0,
+        }
+    }
+}
+
+impl SceneControls {
+    fn transform(&self) -> Transform {
+        Transform {
+            position: self.position.into(),
+            scale: self.scale,
+            ..Default::default()
+        }
+    }
+}
+
 pub struct EguiRoutine {
     emitter: Option<Arc<Emitter<PomarinEvent>>>,
+    /// `None` until set with `with_scene_commands()`
+    commands: Option<Sender<SceneCommand>>,
+    show_assets: bool,
+    assets: Vec<AssetPreview>,
+    selected_asset: Option<String>,
+    paused: bool,
+    time_scale: f32,
+    /// spray particles emitted per second and their lifetime (seconds)
+    spray_rate: f32,
+    spray_lifetime: f32,
+    show_diagnostics: bool,
+    diagnostics: Vec<(&'static str, String)>,
+    gpu_timings: Option<GpuTimings>,
+    show_render_settings: bool,
+    show_layers: bool,
+    layer_mask: u32,
+    show_scene: bool,
+    scene_controls: SceneControls,
+    /// settings applied by the render
+    render_settings: RenderSettings,
+    /// settings edited in the panel and not applied yet (dirty)
+    requested_settings: Option<RenderSettings>,
 }
 
 impl Default for EguiRoutine {
     fn default() -> Self {
-        Self { emitter: None }
+        Self {
+            emitter: None,
+            commands: None,
+            show_assets: false,
+            assets: vec![],
+            selected_asset: None,
+            paused: false,
+            time_scale: 1.0,
+            spray_rate: ParticleEmitter::default().spawn_rate,
+            spray_lifetime: ParticleEmitter::default().lifetime,
+            show_diagnostics: false,
+            diagnostics: vec![],
+            gpu_timings: None,
+            show_render_settings: false,
+            show_layers: false,
+            layer_mask: ALL_LAYERS,
+            show_scene: false,
+            scene_controls: SceneControls::default(),
+            render_settings: RenderSettings {
+                present_mode: PresentMode::default(),
+                anti_aliasing: AntiAliasing::default(),
+                clear_color: [0.0; 3],
+                max_fps: None,
+            },
+            requested_settings: None,
+        }
     }
 }
 
 impl EguiRoutine {
+    /// Send the scene panel commands with `commands`
+    pub fn with_scene_commands(mut self, commands: Sender<SceneCommand>) -> Self {
+        self.commands = Some(commands);
+        self
+    }
+
+    fn send(&self, command: SceneCommand) -> Result<()> {
+        self.commands
+            .as_ref()
+            .ok_or_else(|| anyhow!("No scene commands sender set for EguiRender"))?
+            .send(command)?;
+        Ok(())
+    }
+
     fn close_app(&self) -> Result<()> {
         self.emit(PomarinEvent::CloseApp)
     }
+
+    fn dump_assets_graph(&self) -> Result<()> {
+        self.emit(PomarinEvent::DumpAssetsGraph)
+    }
+
+    fn time_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let label = if self.paused { "Play" } else { "Pause" };
+            if ui.button(label).clicked() {
+                self.paused = !self.paused;
+                if let Err(e) = self.emit(PomarinEvent::SetPaused(self.paused)) {
+                    log::error!("{:?}", e);
+                }
+            }
+            let speed = ui.add(
+                egui::Slider::new(&mut self.time_scale, 0.0..=4.0)
+                    .text("speed")
+                    .fixed_decimals(1),
+            );
+            if speed.changed() {
+                if let Err(e) = self.emit(PomarinEvent::SetTimeScale(self.time_scale)) {
+                    log::error!("{:?}", e);
+                }
+            }
+        });
+    }
+
+    fn spray_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let rate = ui.add(
+                egui::Slider::new(&mut self.spray_rate, 0.0..=500.0)
+                    .text("spray rate")
+                    .fixed_decimals(0),
+            );
+            let lifetime = ui.add(
+                egui::Slider::new(&mut self.spray_lifetime, 0.1..=5.0)
+                    .text("spray lifetime")
+                    .fixed_decimals(1),
+            );
+            if rate.changed() || lifetime.changed() {
+                let spray = PomarinEvent::SetSpray {
+                    spawn_rate: self.spray_rate,
+                    lifetime: self.spray_lifetime,
+                };
+                if let Err(e) = self.emit(spray) {
+                    log::error!("{:?}", e);
+                }
+            }
+        });
+    }
+
+    fn assets_browser(&mut self, ctx: &egui::CtxRef) {
+        let assets = &self.assets;
+        let selected_asset = &mut self.selected_asset;
+        egui::Window::new("Assets")
+            .open(&mut self.show_assets)
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for asset in assets {
+                        ui.horizontal(|ui| {
+                            let size = egui::Vec2::splat(THUMBNAIL_SIZE);
+                            match asset.preview {
+                                Preview::Texture(texture_id) => {
+                                    ui.image(texture_id, size);
+                                }
+                                Preview::Color([r, g, b]) => {
+                                    let (rect, _) =
+                                        ui.allocate_exact_size(size, egui::Sense::hover());
+                                    ui.painter().rect_filled(
+                                        rect,
+                                        2.0,
+                                        egui::Rgba::from_rgb(r, g, b),
+                                    );
+                                }
+                                Preview::None => {
+                                    ui.allocate_exact_size(size, egui::Sense::hover());
+                                }
+                            }
+                            let is_selected = selected_asset.as_ref() == Some(&asset.name);
+                            let label = ui.selectable_label(is_selected, &asset.name);
+                            let label = if asset.used_by.is_empty() {
+                                label.on_hover_text("Not used by any model")
+                            } else {
+                                label.on_hover_text(format!("Used by {}", asset.used_by.join(", ")))
+                            };
+                            if label.clicked() {
+                                log::info!("Asset {} selected", asset.name);
+                                *selected_asset = Some(asset.name.clone());
+                            }
+                        });
+                    }
+                });
+            });
+    }
+}
+
+impl EguiRoutine {
+    fn diagnostics_panel(&mut self, ctx: &egui::CtxRef) {
+        let diagnostics = &self.diagnostics;
+        let (scene_time, ui_time) = match self.gpu_timings {
+            Some(timings) => (
+                format!("{:.2} ms", timings.scene.as_secs_f64() * 1000.0),
+                format!("{:.2} ms", timings.ui.as_secs_f64() * 1000.0),
+            ),
+            None => ("n/a".to_string(), "n/a".to_string()),
+        };
+        egui::Window::new("Diagnostics")
+            .open(&mut self.show_diagnostics)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("diagnostics_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (key, value) in diagnostics {
+                            ui.label(*key);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                        ui.label("gpu scene pass");
+                        ui.label(&scene_time);
+                        ui.end_row();
+                        ui.label("gpu ui pass");
+                        ui.label(&ui_time);
+                        ui.end_row();
+                    });
+            });
+    }
+}
+
+impl EguiRoutine {
+    fn render_settings_panel(&mut self, ctx: &egui::CtxRef) {
+        let mut edited = self.requested_settings.unwrap_or(self.render_settings);
+        egui::Window::new("Performance")
+            .open(&mut self.show_render_settings)
+            .collapsible(true)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("render_settings_grid").show(ui, |ui| {
+                    ui.label("present mode");
+                    egui::ComboBox::from_id_source("present_mode")
+                        .selected_text(format!("{:?}", edited.present_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in [
+                                PresentMode::Fifo,
+                                PresentMode::Mailbox,
+                                PresentMode::Immediate,
+                            ] {
+                                ui.selectable_value(
+                                    &mut edited.present_mode,
+                                    mode,
+                                    format!("{:?}", mode),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("falls back to Fifo if not supported by the surface");
+                    ui.end_row();
+
+                    ui.label("anti-aliasing");
+                    if let AntiAliasing::Msaa(samples) = edited.anti_aliasing {
+                        // the scene pipelines are built for it, only a restart changes it
+                        ui.label(format!("MSAA x{} (configured)", samples));
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut edited.anti_aliasing, AntiAliasing::None, "None");
+                            ui.radio_value(&mut edited.anti_aliasing, AntiAliasing::Fxaa, "FXAA");
+                        });
+                    }
+                    ui.end_row();
+
+                    ui.label("background");
+                    ui.color_edit_button_rgb(&mut edited.clear_color);
+                    ui.end_row();
+
+                    ui.label("fps cap");
+                    ui.horizontal(|ui| {
+                        let mut capped = edited.max_fps.is_some();
+                        ui.checkbox(&mut capped, "");
+                        let mut fps = edited.max_fps.unwrap_or(60);
+                        ui.add_enabled(capped, egui::Slider::new(&mut fps, 10..=240));
+                        edited.max_fps = capped.then_some(fps);
+                    });
+                    ui.end_row();
+                });
+            });
+        if edited != self.requested_settings.unwrap_or(self.render_settings) {
+            self.requested_settings = Some(edited);
+            if let Err(e) = self.emit(PomarinEvent::SetRenderSettings(edited)) {
+                log::error!("{:?}", e);
+            }
+        }
+    }
+}
+
+impl EguiRoutine {
+    fn layers_panel(&mut self, ctx: &egui::CtxRef) {
+        let mut layer_mask = self.layer_mask;
+        egui::Window::new("Layers")
+            .open(&mut self.show_layers)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for layer in 0..SHOWN_LAYERS {
+                    let mut visible = layer_mask & (1 << layer) != 0;
+                    if ui
+                        .checkbox(&mut visible, format!("layer {}", layer))
+                        .changed()
+                    {
+                        layer_mask ^= 1 << layer;
+                    }
+                }
+            });
+        if layer_mask != self.layer_mask {
+            self.layer_mask = layer_mask;
+            if let Err(e) = self.emit(PomarinEvent::SetLayerMask(layer_mask)) {
+                log::error!("{:?}", e);
+            }
+        }
+    }
+}
+
+impl EguiRoutine {
+    fn scene_panel(&mut self, ctx: &egui::CtxRef) {
+        let controls = &mut self.scene_controls;
+        let mut commands = vec![];
+        egui::Window::new("Scene")
+            .open(&mut self.show_scene)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("scene_grid").show(ui, |ui| {
+                    ui.label("object");
+                    ui.text_edit_singleline(&mut controls.object);
+                    ui.end_row();
+
+                    ui.label("position");
+                    ui.horizontal(|ui| {
+                        for coordinate in controls.position.iter_mut() {
+                            ui.add(egui::DragValue::new(coordinate).speed(0.1));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("scale");
+                    ui.add(
+                        egui::DragValue::new(&mut controls.scale)
+                            .speed(0.01)
+                            .clamp_range(0.01..=100.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("model");
+                    ui.text_edit_singleline(&mut controls.model);
+                    ui.end_row();
+
+                    ui.label("geometry");
+                    ui.text_edit_singleline(&mut controls.geometry);
+                    ui.end_row();
+                });
+                let model = ModelName::from(controls.model.as_str());
+                ui.horizontal(|ui| {
+                    if ui.button("Spawn").clicked() {
+                        commands.push(SceneCommand::Spawn {
+                            name: controls.object.clone(),
+                            model: model.clone(),
+                            transform: controls.transform(),
+                        });
+                    }
+                    if ui.button("Move").clicked() {
+                        commands.push(SceneCommand::SetTransform {
+                            name: controls.object.clone(),
+                            transform: controls.transform(),
+                        });
+                    }
+                    if ui.button("Despawn").clicked() {
+                        commands.push(SceneCommand::Despawn(controls.object.clone()));
+                    }
+                    if ui.button("Select").clicked() {
+                        commands.push(SceneCommand::Select(Some(controls.object.clone())));
+                    }
+                    if ui.button("Deselect").clicked() {
+                        commands.push(SceneCommand::Select(None));
+                    }
+                    if ui.checkbox(&mut controls.show_bounds, "bounds").changed() {
+                        commands.push(SceneCommand::ShowBounds {
+                            name: controls.object.clone(),
+                            show: controls.show_bounds,
+                        });
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Normals").clicked() {
+                        commands.push(SceneCommand::ShowNormals(Some(model.clone())));
+                    }
+                    if ui.button("Hide normals").clicked() {
+                        commands.push(SceneCommand::ShowNormals(None));
+                    }
+                    if ui.button("Unload model").clicked() {
+                        commands.push(SceneCommand::UnloadModel(model.clone()));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Isolate geometry").clicked() {
+                        commands.push(SceneCommand::IsolateGeometry(Some(GeometryFilter {
+                            model: model.clone(),
+                            geometry: GeometryName::from(controls.geometry.as_str()),
+                        })));
+                    }
+                    if ui.button("Show all").clicked() {
+                        commands.push(SceneCommand::IsolateGeometry(None));
+                    }
+                });
+                let lod =
+                    egui::Slider::new(&mut controls.lod, 0..=SHOWN_LODS).text("level of detail");
+                if ui.add(lod).changed() {
+                    commands.push(SceneCommand::ShowLod(controls.lod));
+                }
+            });
+        for command in commands {
+            if let Err(e) = self.send(command) {
+                log::error!("{:?}", e);
+            }
+        }
+    }
 }
 
 impl EventEmitter<PomarinEvent> for EguiRoutine {
@@ -33,6 +510,33 @@ impl EventEmitter<PomarinEvent> for EguiRoutine {
     }
 }
 
+impl AssetsView for EguiRoutine {
+    fn assets_visible(&self) -> bool {
+        self.show_assets
+    }
+
+    fn set_assets(&mut self, assets: Vec<AssetPreview>) {
+        self.assets = assets;
+    }
+}
+
+impl DiagnosticsView for EguiRoutine {
+    fn set_diagnostics(&mut self, diagnostics: Vec<(&'static str, String)>) {
+        self.diagnostics = diagnostics;
+    }
+
+    fn set_gpu_timings(&mut self, timings: Option<GpuTimings>) {
+        self.gpu_timings = timings;
+    }
+}
+
+impl RenderSettingsView for EguiRoutine {
+    fn set_render_settings(&mut self, settings: RenderSettings) {
+        self.render_settings = settings;
+        self.requested_settings = None;
+    }
+}
+
 // \\ // \\
 
 impl epi::App for EguiRoutine {
@@ -43,12 +547,94 @@ impl epi::App for EguiRoutine {
             .anchor(Align2::RIGHT_TOP, [-10.0, 10.0])
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.show_assets, "Assets");
+                    ui.checkbox(&mut self.show_diagnostics, "Diagnostics");
+                    ui.checkbox(&mut self.show_render_settings, "Performance");
+                    ui.checkbox(&mut self.show_layers, "Layers");
+                    ui.checkbox(&mut self.show_scene, "Scene");
+                    if ui.button("Assets graph").clicked() {
+                        if let Err(e) = self.dump_assets_graph() {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Depth view").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleDepthView) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Wireframe").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleWireframe) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Pipelines").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::TogglePipelineColors) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Labels").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleLabels) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Lights").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleLightGizmos) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Headlight").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleHeadlight) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Floodlight").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleFloodlight) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Normals").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::CycleDebugNormals) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Reset camera").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ResetCamera) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Fullscreen").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::ToggleFullscreen) {
+                            log::error!("{:?}", e);
+                        }
+                    }
+                    if ui.button("Save scene").clicked() {
+                        if let Err(e) = self.emit(PomarinEvent::SaveScene) {
+                            log::error!("{:?}", e);
+                        }
+                    }
                     if ui.button("Close").clicked() {
                         self.close_app().err().map(|e| log::error!("{:?}", e));
                         _frame.quit();
                     }
                 });
+                self.time_controls(ui);
+                self.spray_controls(ui);
             });
+        if self.show_assets {
+            self.assets_browser(ctx);
+        }
+        if self.show_diagnostics {
+            self.diagnostics_panel(ctx);
+        }
+        if self.show_render_settings {
+            self.render_settings_panel(ctx);
+        }
+        if self.show_layers {
+            self.layers_panel(ctx);
+        }
+        if self.show_scene {
+            self.scene_panel(ctx);
+        }
     }
 
     fn name(&self) -> &str {
@@ -20,6 +20,13 @@ pub enum TextureError {
         type_to_deser: String,
         input: String,
     },
+    #[error("Invalid compressed texture file: {reason}")]
+    InvalidCompressedFile { reason: String },
+    #[error("Compressed texture format {format:?} requires the unavailable device features {features:?}")]
+    UnsupportedCompressedFormat {
+        format: wgpu::TextureFormat,
+        features: wgpu::Features,
+    },
 }
 
 #[derive(ThisError, Debug)]
@@ -51,3 +58,97 @@ pub enum ModelError {
         model: ModelName,
     },
 }
+
+#[derive(ThisError, Debug)]
+pub enum MeshError {
+    #[error("{geometry} of {mesh} has {actual} vertices, {expected} expected")]
+    UnexpectedVertexCount {
+        mesh: MeshName,
+        geometry: GeometryName,
+        expected: u32,
+        actual: usize,
+    },
+    #[error("{name} source has no geometry with faces")]
+    EmptyMesh { name: MeshName },
+    #[error(
+        "Geometries of {mesh} do not match its source: [{}] not described, [{}] not found in the source",
+        .unexpected.join(", "),
+        .missing.join(", ")
+    )]
+    GeometriesMismatch {
+        mesh: MeshName,
+        /// loaded from the source but not described
+        unexpected: Vec<String>,
+        /// described but not loaded from the source
+        missing: Vec<String>,
+    },
+    #[error("Invalid level of detail ratio {ratio} of {mesh} (expected in ]0, 1[)")]
+    InvalidLodRatio { mesh: MeshName, ratio: f32 },
+}
+
+#[derive(ThisError, Debug, PartialEq)]
+pub enum CameraError {
+    #[error("Invalid field of view {fovy}° (expected in ]0, 180[)")]
+    InvalidFov { fovy: f32 },
+    #[error("Invalid clip planes: near {znear} and far {zfar} (expected 0 < near < far)")]
+    InvalidClipPlanes { znear: f32, zfar: f32 },
+}
+
+#[derive(ThisError, Debug)]
+pub enum SurfaceError {
+    #[error("No render attachment format among {candidates:?} is supported by the adapter")]
+    NoSupportedFormat {
+        candidates: Vec<wgpu::TextureFormat>,
+    },
+}
+
+#[derive(ThisError, Debug)]
+pub enum ResourcesError {
+    #[error("Missing resources: {}", .missing.join(", "))]
+    MissingPaths { missing: Vec<String> },
+}
+
+#[derive(ThisError, Debug, PartialEq)]
+pub enum StoreError {
+    #[error("Store {collection} are already borrowed (re-entrant store access)")]
+    AlreadyBorrowed { collection: &'static str },
+}
+
+#[derive(ThisError, Debug)]
+pub enum PipelineError {
+    #[error("Cannot read the shader {path} of pipeline {pipeline}: {reason}")]
+    ShaderNotReadable {
+        pipeline: String,
+        path: String,
+        reason: String,
+    },
+    #[error("Invalid pipeline {pipeline}: {reason}")]
+    InvalidPipeline { pipeline: String, reason: String },
+}
+
+#[derive(ThisError, Debug)]
+pub enum SceneError {
+    #[error(
+        "{} of {total} scene objects not loaded: {}",
+        .failures.len(),
+        .failures.iter().map(|(object, reason)| format!("{} ({})", object, reason)).collect::<Vec<_>>().join(", ")
+    )]
+    ObjectsNotLoaded {
+        total: usize,
+        /// object name and reason
+        failures: Vec<(String, String)>,
+    },
+    #[error("Cannot store the scene pipelines: {0}")]
+    PipelinesNotStored(#[from] StoreError),
+}
+
+#[derive(ThisError, Debug, PartialEq)]
+pub enum LimitError {
+    #[error("{resource} exceeds the device limit {limit}: {requested} requested, {max} allowed")]
+    Exceeded {
+        resource: String,
+        limit: &'static str,
+        requested: u64,
+        max: u64,
+    },
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+/// Timestamps written around the passes: scene start, scene end (ui start), ui end
+const TIMESTAMPS_COUNT: u32 = 3;
+
+/// GPU durations of the last frame passes
+#[derive(Debug, Clone, Copy)]
+pub struct GpuTimings {
+    pub scene: Duration,
+    pub ui: Duration,
+}
+
+/// Frame passes GPU durations, measured with timestamp queries.
+///
+/// Requires the device feature `TIMESTAMP_QUERY`. The timestamps are read back right after
+/// the frame submission, which waits for the GPU to finish the frame: only enable it to
+/// profile.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    /// the timestamps are resolved straight into the mappable buffer
+    read_buffer: wgpu::Buffer,
+    /// nanoseconds per timestamp tick
+    period: f32,
+    timings: Option<GpuTimings>,
+}
+
+impl GpuTimer {
+    /// `None` if the device does not support the timestamp queries
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            log::warn!("Timestamp queries not supported, no GPU timings");
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu_timer.query_set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMPS_COUNT,
+        });
+        let size = (TIMESTAMPS_COUNT as u64) * wgpu::QUERY_SIZE as u64;
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer.read_buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            read_buffer,
+            period: queue.get_timestamp_period(),
+            timings: None,
+        })
+    }
+
+    pub fn scene_start(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 0);
+    }
+
+    /// The scene end is the ui start
+    pub fn scene_end(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 1);
+    }
+
+    /// Write the last timestamp and resolve the timestamps into the readable buffer
+    pub fn ui_end(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 2);
+        encoder.resolve_query_set(&self.query_set, 0..TIMESTAMPS_COUNT, &self.read_buffer, 0);
+    }
+
+    /// Read the timestamps of the submitted frame (blocks until the GPU is done with it)
+    pub fn read_back(&mut self, device: &wgpu::Device) {
+        let slice = self.read_buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        if let Err(e) = pollster::block_on(mapping) {
+            log::warn!("Cannot read the GPU timestamps: {}", e);
+            self.timings = None;
+            return;
+        }
+        {
+            let data = slice.get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            let elapsed = |start: u64, end: u64| {
+                Duration::from_nanos((end.saturating_sub(start) as f64 * self.period as f64) as u64)
+            };
+            self.timings = Some(GpuTimings {
+                scene: elapsed(timestamps[0], timestamps[1]),
+                ui: elapsed(timestamps[1], timestamps[2]),
+            });
+        }
+        self.read_buffer.unmap();
+    }
+
+    /// GPU durations of the last read frame
+    pub fn timings(&self) -> Option<GpuTimings> {
+        self.timings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_timer_without_the_timestamp_feature() {
+        // the test device is requested without any optional feature
+        let (device, queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        assert!(!device.features().contains(wgpu::Features::TIMESTAMP_QUERY));
+        assert!(GpuTimer::new(&device, &queue).is_none());
+    }
+}
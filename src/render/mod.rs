@@ -5,6 +5,8 @@
 //! - scene: render 3d objects
 //! - shaders
 //! - error: errors related to this module
+//! - gpu_timer: passes GPU durations
+//! - rpass: the render passes drawing the frames
 //! - state: wgpu state struct with data available to egui and the objects renderer
 
 pub mod config;
@@ -12,5 +14,7 @@ pub mod egui;
 pub mod scene;
 
 pub mod error;
+pub mod gpu_timer;
 pub mod names;
+pub mod rpass;
 pub mod state;
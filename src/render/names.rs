@@ -7,9 +7,13 @@ use serde::{Deserialize, Serialize};
 use super::{
     config::{
         geometry::{GeometryDescriptor, GeometryVertices},
-        material::{ColorMaterialDescriptor, MaterialDescriptor, TextureMaterialDescriptor},
+        material::{
+            ColorMaterialDescriptor, MaterialDescriptor, TextureMaterialDescriptor,
+            UnlitMaterialDescriptor,
+        },
         mesh::MeshDescriptor,
         model::ModelDescriptor,
+        pipeline::PipelineDescriptor,
         texture::TextureDescriptor,
     },
     scene::{geometry::GeometryBuf, model::Model},
@@ -71,6 +75,9 @@ pub struct MaterialName(String);
 #[derive(Deserialize, Serialize, Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Hash)]
 pub struct MeshName(String);
 
+#[derive(Deserialize, Serialize, Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Hash)]
+pub struct PipelineName(String);
+
 // Geometry
 
 impl From<&str> for GeometryName {
@@ -175,6 +182,7 @@ impl NamedHandle<MaterialName> for MaterialDescriptor {
         match self {
             MaterialDescriptor::Color(color) => color.name(),
             MaterialDescriptor::Texture(texture) => texture.name(),
+            MaterialDescriptor::Unlit(unlit) => unlit.name(),
         }
     }
 }
@@ -191,6 +199,12 @@ impl NamedHandle<MaterialName> for ColorMaterialDescriptor {
     }
 }
 
+impl NamedHandle<MaterialName> for UnlitMaterialDescriptor {
+    fn name(&self) -> MaterialName {
+        MaterialName(self.name.clone())
+    }
+}
+
 // Mesh
 
 impl Display for MeshName {
@@ -252,3 +266,31 @@ impl NamedHandle<TextureName> for TextureDescriptor {
         TextureName(self.name.to_string())
     }
 }
+
+// Pipeline
+
+impl Display for PipelineName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Pipeline({})", self.0)
+    }
+}
+
+impl From<&str> for PipelineName {
+    fn from(value: &str) -> Self {
+        PipelineName(value.to_string())
+    }
+}
+
+impl Deref for PipelineName {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl NamedHandle<PipelineName> for PipelineDescriptor {
+    fn name(&self) -> PipelineName {
+        PipelineName(self.name.clone())
+    }
+}
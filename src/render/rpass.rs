@@ -0,0 +1,17 @@
+use crate::render::state::WgpuState;
+
+/// A render pass drawing into the window surface texture.
+///
+/// The app renders its passes one after the other each frame, in their order (see
+/// `AppRender::insert_pass`), recording them into the same command encoder.
+pub trait WgpuRpass {
+    /// Record the pass drawing into `output_view` (the surface texture), the encoder is handed
+    /// over to the next pass
+    fn render(
+        &mut self,
+        wgpu: &WgpuState,
+        window: &winit::window::Window,
+        output_view: &wgpu::TextureView,
+        encoder: wgpu::CommandEncoder,
+    ) -> wgpu::CommandEncoder;
+}
@@ -0,0 +1,337 @@
+use std::rc::Rc;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use anyhow::{anyhow, Result};
+use cgmath::{InnerSpace, Vector3};
+
+use super::{pipeline::create_billboard_pipeline, texture::Texture};
+
+/// Font of the labels, one of the egui default fonts
+const LABEL_FONT: &str = "Ubuntu-Light";
+/// Margin around the label text (pixels)
+const LABEL_PADDING: u32 = 4;
+/// Label background (srgba)
+const LABEL_BACKGROUND: [u8; 4] = [0, 0, 0, 128];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BillboardVertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+impl BillboardVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<BillboardVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}
+
+/// A textured quad facing the camera
+pub struct Billboard {
+    /// center of the quad (world space)
+    pub position: [f32; 3],
+    /// width and height (world units)
+    pub size: [f32; 2],
+    pub texture: Rc<Texture>,
+}
+
+/// Camera right and up directions (world space) of a view matrix
+pub fn camera_axes(view: cgmath::Matrix4<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    // the rows of the view rotation are the camera axes
+    let right = Vector3::new(view.x.x, view.y.x, view.z.x).normalize();
+    let up = Vector3::new(view.x.y, view.y.y, view.z.y).normalize();
+    (right, up)
+}
+
+/// Corners of a quad centered on `position`, in the bottom left, bottom right, top right, top left
+/// order
+pub fn billboard_corners(
+    position: [f32; 3],
+    size: [f32; 2],
+    right: Vector3<f32>,
+    up: Vector3<f32>,
+) -> [[f32; 3]; 4] {
+    let center = Vector3::from(position);
+    let right = right * size[0] * 0.5;
+    let up = up * size[1] * 0.5;
+    [
+        (center - right - up).into(),
+        (center + right - up).into(),
+        (center + right + up).into(),
+        (center - right + up).into(),
+    ]
+}
+
+/// Quads facing the camera (labels, markers...), built on the cpu each frame.
+///
+/// The quads are blended over the scene and tested against its depth, without writing it.
+pub struct BillboardPass {
+    pipeline: wgpu::RenderPipeline,
+    texture_bgl: wgpu::BindGroupLayout,
+    vertex_buffer: wgpu::Buffer,
+    /// vertices the buffer can hold
+    capacity: usize,
+    /// bind groups of the billboards textures, kept while the textures are drawn
+    bind_groups: Vec<(Rc<Texture>, wgpu::BindGroup)>,
+    /// bind group index of the billboards to draw, with the vertices range of their quad
+    draws: Vec<(usize, std::ops::Range<u32>)>,
+}
+
+impl BillboardPass {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("billboard.texture_bind_group_layout"),
+        });
+        let pipeline = create_billboard_pipeline(
+            device,
+            config,
+            camera_bgl,
+            &texture_bgl,
+            depth_format,
+            sample_count,
+        );
+        let capacity = 6;
+        Self {
+            pipeline,
+            vertex_buffer: Self::create_vertex_buffer(device, capacity),
+            texture_bgl,
+            capacity,
+            bind_groups: vec![],
+            draws: vec![],
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("billboard.vertex_buffer"),
+            size: (capacity * std::mem::size_of::<BillboardVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Set the billboards drawn by the next draws, facing the camera of the `view` matrix
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        billboards: &[Billboard],
+        view: cgmath::Matrix4<f32>,
+    ) {
+        let (right, up) = camera_axes(view);
+        let vertices: Vec<BillboardVertex> = billboards
+            .iter()
+            .flat_map(|billboard| {
+                let [bl, br, tr, tl] =
+                    billboard_corners(billboard.position, billboard.size, right, up);
+                let vertex = |position, tex_coords| BillboardVertex {
+                    position,
+                    tex_coords,
+                };
+                [
+                    vertex(bl, [0.0, 1.0]),
+                    vertex(br, [1.0, 1.0]),
+                    vertex(tr, [1.0, 0.0]),
+                    vertex(bl, [0.0, 1.0]),
+                    vertex(tr, [1.0, 0.0]),
+                    vertex(tl, [0.0, 0.0]),
+                ]
+            })
+            .collect();
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        let mut bind_groups = std::mem::take(&mut self.bind_groups);
+        self.draws = billboards
+            .iter()
+            .enumerate()
+            .map(|(index, billboard)| {
+                let first = index as u32 * 6;
+                let texture = &billboard.texture;
+                if let Some(bind_group) = self
+                    .bind_groups
+                    .iter()
+                    .position(|(t, _)| Rc::ptr_eq(t, texture))
+                {
+                    return (bind_group, first..first + 6);
+                }
+                let bind_group = match bind_groups.iter().position(|(t, _)| Rc::ptr_eq(t, texture))
+                {
+                    Some(index) => bind_groups.swap_remove(index).1,
+                    None => self.create_bind_group(device, texture),
+                };
+                self.bind_groups.push((texture.clone(), bind_group));
+                (self.bind_groups.len() - 1, first..first + 6)
+            })
+            .collect();
+    }
+
+    fn create_bind_group(&self, device: &wgpu::Device, texture: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.texture_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(texture.sampler()),
+                },
+            ],
+            label: Some("billboard.texture_bind_group"),
+        })
+    }
+
+    /// Draw the billboards, after the opaque models of the same pass
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bg: &'a wgpu::BindGroup,
+    ) {
+        if self.draws.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for (bind_group, vertices) in &self.draws {
+            render_pass.set_bind_group(1, &self.bind_groups[*bind_group].1, &[]);
+            render_pass.draw(vertices.clone(), 0..1);
+        }
+    }
+}
+
+/// Rasterize a single line of text (white over a translucent background), `font_size` pixels
+/// high
+pub fn label_image(text: &str, font_size: f32) -> Result<image::RgbaImage> {
+    let fonts = egui::FontDefinitions::default();
+    let font_data = fonts
+        .font_data
+        .get(LABEL_FONT)
+        .ok_or_else(|| anyhow!("Label font {} not found", LABEL_FONT))?;
+    let font = FontRef::try_from_slice_and_index(&font_data.font, font_data.index)?;
+    let scale = PxScale::from(font_size);
+    let scaled = font.as_scaled(scale);
+
+    let padding = LABEL_PADDING as f32;
+    let mut caret = padding;
+    let mut previous = None;
+    let mut glyphs = vec![];
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(
+            id.with_scale_and_position(scale, ab_glyph::point(caret, padding + scaled.ascent())),
+        );
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    let width = (caret + padding).ceil() as u32;
+    let height = (scaled.ascent() - scaled.descent() + 2.0 * padding).ceil() as u32;
+
+    let mut image = image::RgbaImage::from_pixel(width, height, image::Rgba(LABEL_BACKGROUND));
+    for glyph in glyphs {
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                let x = bounds.min.x as i32 + x as i32;
+                let y = bounds.min.y as i32 + y as i32;
+                if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+                    let pixel = image.get_pixel_mut(x as u32, y as u32);
+                    let blend = |background: u8| {
+                        (background as f32 * (1.0 - coverage) + 255.0 * coverage) as u8
+                    };
+                    *pixel = image::Rgba([
+                        blend(pixel[0]),
+                        blend(pixel[1]),
+                        blend(pixel[2]),
+                        blend(pixel[3]),
+                    ]);
+                }
+            });
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_are_offset_along_the_camera_axes() {
+        let view = cgmath::Matrix4::look_at_rh(
+            cgmath::Point3::new(0.0, 0.0, 5.0),
+            cgmath::Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+        );
+        let (right, up) = camera_axes(view);
+        assert_eq!(right, Vector3::unit_x());
+        assert_eq!(up, Vector3::unit_y());
+        assert_eq!(
+            billboard_corners([1.0, 2.0, 3.0], [4.0, 2.0], right, up),
+            [
+                [-1.0, 1.0, 3.0],
+                [3.0, 1.0, 3.0],
+                [3.0, 3.0, 3.0],
+                [-1.0, 3.0, 3.0]
+            ]
+        );
+
+        // looking along -x, the quad spans the z axis
+        let view = cgmath::Matrix4::look_at_rh(
+            cgmath::Point3::new(5.0, 0.0, 0.0),
+            cgmath::Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+        );
+        let (right, up) = camera_axes(view);
+        assert!((right - -Vector3::unit_z()).magnitude() < 1e-6);
+        let [bottom_left, bottom_right, ..] = billboard_corners([0.0; 3], [2.0, 2.0], right, up);
+        assert!((bottom_left[2] - 1.0).abs() < 1e-6 && (bottom_right[2] + 1.0).abs() < 1e-6);
+        assert!(bottom_left[0].abs() < 1e-6 && bottom_right[0].abs() < 1e-6);
+    }
+}
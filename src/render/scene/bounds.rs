@@ -0,0 +1,256 @@
+use cgmath::{EuclideanSpace, InnerSpace, MetricSpace, Rotation};
+
+/// Sphere enclosing a geometry, for the cheap visibility tests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: cgmath::Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere centered on the points bounding box, the empty set gives a zero radius sphere at
+    /// the origin
+    pub fn from_points<I: IntoIterator<Item = cgmath::Point3<f32>> + Clone>(points: I) -> Self {
+        let center = BoundingBox::from_points(points.clone()).center();
+        let radius = points
+            .into_iter()
+            .map(|p| p.distance(center))
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// Smallest sphere enclosing both spheres
+    pub fn union(&self, other: &Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        Self {
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    /// The sphere of an instance: scaled, rotated then translated (see `InstanceRaw`)
+    pub fn transformed(
+        &self,
+        position: cgmath::Vector3<f32>,
+        orientation: cgmath::Quaternion<f32>,
+        scale: f32,
+    ) -> Self {
+        Self {
+            center: cgmath::Point3::from_vec(
+                position + orientation.rotate_vector(self.center.to_vec() * scale),
+            ),
+            radius: self.radius * scale.abs(),
+        }
+    }
+
+    /// Distance along the ray to the sphere surface, `None` if the ray misses it. A ray
+    /// starting inside the sphere hits its far side.
+    pub fn ray_distance(&self, ray: &Ray) -> Option<f32> {
+        let offset = ray.origin - self.center;
+        let b = offset.dot(ray.direction);
+        let discriminant = b * b - (offset.magnitude2() - self.radius * self.radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [-b - root, -b + root]
+            .into_iter()
+            .find(|distance| *distance >= 0.0)
+    }
+}
+
+/// Axis aligned box enclosing a geometry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: cgmath::Point3<f32>,
+    pub max: cgmath::Point3<f32>,
+}
+
+impl BoundingBox {
+    /// Corners indices (see `corners`) of the box edges
+    const EDGES: [(usize, usize); 12] = [
+        // min z face
+        (0, 1),
+        (1, 3),
+        (3, 2),
+        (2, 0),
+        // max z face
+        (4, 5),
+        (5, 7),
+        (7, 6),
+        (6, 4),
+        // along z
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+
+    /// Box of the points, the empty set gives an empty box at the origin
+    pub fn from_points<I: IntoIterator<Item = cgmath::Point3<f32>>>(points: I) -> Self {
+        let mut points = points.into_iter();
+        let first = match points.next() {
+            Some(first) => first,
+            None => return Self::empty(),
+        };
+        points.fold(Self::new(first, first), |b, p| {
+            Self::new(
+                cgmath::Point3::new(b.min.x.min(p.x), b.min.y.min(p.y), b.min.z.min(p.z)),
+                cgmath::Point3::new(b.max.x.max(p.x), b.max.y.max(p.y), b.max.z.max(p.z)),
+            )
+        })
+    }
+
+    pub fn new(min: cgmath::Point3<f32>, max: cgmath::Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Zero sized box at the origin
+    pub fn empty() -> Self {
+        Self::new(cgmath::Point3::origin(), cgmath::Point3::origin())
+    }
+
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// Smallest box enclosing both boxes
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_points(self.corners().into_iter().chain(other.corners()))
+    }
+
+    /// The 8 corners, the bits 0, 1 and 2 of the index selecting the max x, y and z
+    pub fn corners(&self) -> [cgmath::Point3<f32>; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            cgmath::Point3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
+
+    /// The 12 edges, as pairs of points
+    pub fn edges(&self) -> [[cgmath::Point3<f32>; 2]; 12] {
+        let corners = self.corners();
+        Self::EDGES.map(|(a, b)| [corners[a], corners[b]])
+    }
+
+    /// The box enclosing the transformed box of an instance: scaled, rotated then translated
+    /// (see `InstanceRaw`)
+    pub fn transformed(
+        &self,
+        position: cgmath::Vector3<f32>,
+        orientation: cgmath::Quaternion<f32>,
+        scale: f32,
+    ) -> Self {
+        Self::from_points(self.corners().map(|corner| {
+            cgmath::Point3::from_vec(position + orientation.rotate_vector(corner.to_vec() * scale))
+        }))
+    }
+}
+
+/// The 6 planes of the camera view volume, facing inward
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// (normal, distance) of the planes: `normal . p + distance >= 0` inside
+    planes: [cgmath::Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Planes of a view projection matrix with a [0, 1] clip depth (wgpu)
+    pub fn from_view_proj(view_proj: cgmath::Matrix4<f32>) -> Self {
+        let row = |i: usize| {
+            cgmath::Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            // normalized so that the distance to the plane is in world units
+            let length = plane.truncate().magnitude();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Self { planes }
+    }
+
+    /// Whether the sphere is at least partly in the view volume
+    pub fn intersects(&self, sphere: &BoundingSphere) -> bool {
+        let center = sphere.center.to_homogeneous();
+        self.planes
+            .iter()
+            .all(|plane| plane.dot(center) >= -sphere.radius)
+    }
+}
+
+/// Half line from a point, the direction is normalized
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>,
+}
+
+impl Ray {
+    /// Ray through a point of the screen (normalized device coordinates), from the near plane to
+    /// the far one of the inverse view projection (wgpu [0, 1] clip depth)
+    pub fn from_ndc(inv_view_proj: cgmath::Matrix4<f32>, (x, y): (f32, f32)) -> Self {
+        let unproject = |depth| {
+            let clip = inv_view_proj * cgmath::Vector4::new(x, y, depth, 1.0);
+            cgmath::Point3::from_homogeneous(clip)
+        };
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::SquareMatrix;
+
+    use super::*;
+    use crate::render::scene::camera::OPENGL_TO_WGPU_MATRIX;
+
+    #[test]
+    fn screen_center_ray_hits_the_sphere_in_front() {
+        let view_proj = OPENGL_TO_WGPU_MATRIX
+            * cgmath::perspective(cgmath::Deg(45.0), 1.0, 1.0, 100.0)
+            * cgmath::Matrix4::look_at_rh(
+                cgmath::Point3::new(0.0, 0.0, 10.0),
+                cgmath::Point3::origin(),
+                cgmath::Vector3::unit_y(),
+            );
+        let ray = Ray::from_ndc(view_proj.invert().unwrap(), (0.0, 0.0));
+        assert!((ray.direction - -cgmath::Vector3::unit_z()).magnitude() < 1e-4);
+
+        let sphere = BoundingSphere {
+            center: cgmath::Point3::origin(),
+            radius: 2.0,
+        };
+        // from the near plane, one unit in front of the camera
+        let distance = sphere.ray_distance(&ray).unwrap();
+        assert!((distance - 7.0).abs() < 1e-3, "hit at {}", distance);
+        let aside = BoundingSphere {
+            center: cgmath::Point3::new(5.0, 0.0, 0.0),
+            radius: 2.0,
+        };
+        assert_eq!(aside.ray_distance(&ray), None);
+    }
+}
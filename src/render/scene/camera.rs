@@ -1,4 +1,11 @@
-use std::time::Duration;
+use std::{collections::VecDeque, time::Duration};
+
+use cgmath::SquareMatrix;
+use serde::Deserialize;
+
+use crate::{app::config::CameraConfig, render::error::CameraError};
+
+use super::bounds::{Frustum, Ray};
 
 // We need this for Rust to store our data correctly for the shaders
 #[repr(C)]
@@ -9,6 +16,11 @@ pub struct CameraUniform {
     // to convert the Matrix4 into a 4x4 f32 array
     view_position: [f32; 4],
     view_proj: [[f32; 4]; 4],
+    /// near and far planes distances (x, y)
+    clip_planes: [f32; 4],
+    /// clip to world space, to reconstruct the world positions from the depth (screen space
+    /// effects). Last field: the shaders not using it can leave it out of their declaration
+    inv_view_proj: [[f32; 4]; 4],
 }
 
 impl Default for CameraUniform {
@@ -17,19 +29,29 @@ impl Default for CameraUniform {
         let far: f32 = 1000.0;
         let near: f32 = 1.0;
         let aspect: f32 = 16.0 / 9.0;
-        Self {
-            view_position: [0.0; 4],
-            #[rustfmt::skip]
-            view_proj: [
+        #[rustfmt::skip]
+        let view_proj = [
                 [1.0/((fov/2.0).tan()*aspect), 0.0                , 0.0           , 0.0                  ],
                 [0.0                         , 1.0/(fov/2.0).tan(), 0.0           , 0.0                  ],
                 [0.0                         , 0.0                , far/(far-near), (far*near)/(near-far)],
                 [0.0                         , 0.0                , 1.0           , 0.0                  ],
-            ],
+            ];
+        Self {
+            view_position: [0.0; 4],
+            view_proj,
+            clip_planes: [near, far, 0.0, 0.0],
+            inv_view_proj: inverse(view_proj.into()).into(),
         }
     }
 }
 
+/// Inverse of the matrix, the identity if it is not invertible (degenerate projection)
+fn inverse(matrix: cgmath::Matrix4<f32>) -> cgmath::Matrix4<f32> {
+    matrix
+        .invert()
+        .unwrap_or_else(cgmath::Matrix4::<f32>::identity)
+}
+
 impl CameraUniform {
     fn update<T>(self, mut updater: T) -> Self
     where
@@ -41,23 +63,162 @@ impl CameraUniform {
 
 pub trait CameraUpdater {
     fn update(&mut self, camera_uniform: CameraUniform) -> CameraUniform;
+
+    /// World to view space transform
+    fn view(&self) -> cgmath::Matrix4<f32>;
+
+    /// The render surface size changed (the projection aspect ratio follows it)
+    fn resize(&mut self, _width: u32, _height: u32) {}
+
+    /// Go back to the initial view
+    fn reset(&mut self) {}
+
+    /// Current framing, None if the controller cannot be animated
+    fn pose(&self) -> Option<CameraPose> {
+        None
+    }
+
+    /// Move to the framing (animation step)
+    fn set_pose(&mut self, _pose: CameraPose) {}
+}
+
+/// Camera framing, interpolated by the camera tweens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    /// vertical field of view (degrees)
+    pub fovy: f32,
+}
+
+impl CameraPose {
+    /// Linear interpolation, `t` in [0, 1]
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        let mix3 = |a: [f32; 3], b: [f32; 3]| [mix(a[0], b[0]), mix(a[1], b[1]), mix(a[2], b[2])];
+        Self {
+            position: mix3(self.position, other.position),
+            target: mix3(self.target, other.target),
+            fovy: mix(self.fovy, other.fovy),
+        }
+    }
+}
+
+/// Progress curve of a camera animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Easing {
+    Linear,
+    /// slow start, slow end (smoothstep)
+    #[default]
+    EaseInOut,
+    /// fast start, slow end
+    EaseOut,
+}
+
+impl Easing {
+    /// Eased progress of the linear progress `t` in [0, 1]
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+/// Camera animation from a framing to another over a duration
+#[derive(Debug, Clone)]
+pub struct CameraTween {
+    start: CameraPose,
+    goal: CameraPose,
+    duration: Duration,
+    elapsed: Duration,
+    easing: Easing,
+}
+
+impl CameraTween {
+    pub fn new(start: CameraPose, goal: CameraPose, duration: Duration, easing: Easing) -> Self {
+        Self {
+            start,
+            goal,
+            duration,
+            elapsed: Duration::ZERO,
+            easing,
+        }
+    }
+
+    /// Advance the animation by `dt` and return the interpolated framing
+    pub fn advance(&mut self, dt: Duration) -> CameraPose {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        if self.duration.is_zero() {
+            return self.goal;
+        }
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.start.lerp(&self.goal, self.easing.apply(t))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Put `next` in place of the current controller, giving it the render surface size, and return
+/// the replaced one
+fn swap_updater(
+    current: &mut Box<dyn CameraUpdater>,
+    mut next: Box<dyn CameraUpdater>,
+    size: Option<(u32, u32)>,
+) -> Box<dyn CameraUpdater> {
+    if let Some((width, height)) = size {
+        next.resize(width, height);
+    }
+    std::mem::replace(current, next)
+}
+
+/// Replace the current controller by the first of the standby ones, keeping the current framing
+/// if both controllers have one
+fn cycle_controller(
+    current: &mut Box<dyn CameraUpdater>,
+    standby: &mut VecDeque<Box<dyn CameraUpdater>>,
+    size: Option<(u32, u32)>,
+) -> bool {
+    let mut next = match standby.pop_front() {
+        Some(next) => next,
+        None => return false,
+    };
+    if let Some(pose) = current.pose() {
+        next.set_pose(pose);
+    }
+    let previous = swap_updater(current, next, size);
+    standby.push_back(previous);
+    true
 }
 
-pub struct CameraSystem<T>
-where
-    T: CameraUpdater,
-{
-    updater: T,
+/// Duration of the camera glide back to its initial view
+const RESET_DURATION: Duration = Duration::from_millis(600);
+
+/// Camera uniform buffer and bind group, updated each frame by a camera controller.
+///
+/// The controller is a trait object so that it can be swapped at runtime (orbit, fly...).
+pub struct CameraSystem {
+    updater: Box<dyn CameraUpdater>,
+    /// registered controllers waiting for their turn, in the cycling order
+    standby: VecDeque<Box<dyn CameraUpdater>>,
+    /// render surface size, given to the controllers taking over
+    size: Option<(u32, u32)>,
+    /// running animation of the controller framing
+    tween: Option<CameraTween>,
     uniform: CameraUniform,
     pub buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
-impl<T> CameraSystem<T>
-where
-    T: CameraUpdater,
-{
-    pub fn init(device: &wgpu::Device, updater: T) -> (wgpu::BindGroupLayout, CameraSystem<T>) {
+impl CameraSystem {
+    pub fn init(
+        device: &wgpu::Device,
+        updater: Box<dyn CameraUpdater>,
+    ) -> (wgpu::BindGroupLayout, CameraSystem) {
         let uniform = CameraUniform::default();
 
         use wgpu::util::DeviceExt;
@@ -67,19 +228,7 @@ where
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: Some("camera.bind_group_layout"),
-        });
+        let bind_group_layout = Self::bind_group_layout(device);
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
@@ -94,6 +243,9 @@ where
             bind_group_layout,
             Self {
                 updater,
+                standby: VecDeque::new(),
+                size: None,
+                tween: None,
                 uniform,
                 buffer,
                 bind_group,
@@ -101,7 +253,97 @@ where
         )
     }
 
-    pub fn update(&mut self, queue: &wgpu::Queue, _dt: Duration) {
+    /// Layout of the camera group (0) of the scene pipelines
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("camera.bind_group_layout"),
+        })
+    }
+
+    /// Replace the camera controller, used from the next update, and return the replaced one
+    pub fn set_updater(&mut self, updater: Box<dyn CameraUpdater>) -> Box<dyn CameraUpdater> {
+        self.tween = None;
+        swap_updater(&mut self.updater, updater, self.size)
+    }
+
+    pub fn view(&self) -> cgmath::Matrix4<f32> {
+        self.updater.view()
+    }
+
+    /// Camera position of the last update
+    pub fn position(&self) -> [f32; 3] {
+        let [x, y, z, _] = self.uniform.view_position;
+        [x, y, z]
+    }
+
+    /// View volume of the last update
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_proj(self.uniform.view_proj.into())
+    }
+
+    /// Ray from the camera through a point of the screen (normalized device coordinates), as
+    /// of the last update
+    pub fn ray(&self, ndc: (f32, f32)) -> Ray {
+        Ray::from_ndc(self.uniform.inv_view_proj.into(), ndc)
+    }
+
+    /// Give the controller the render surface size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = Some((width, height));
+        self.updater.resize(width, height);
+    }
+
+    /// Add a controller to the ones `cycle_controller()` switches to
+    pub fn register_controller(&mut self, mut updater: Box<dyn CameraUpdater>) {
+        if let Some((width, height)) = self.size {
+            updater.resize(width, height);
+        }
+        self.standby.push_back(updater);
+    }
+
+    /// Switch to the next registered controller, the current one goes last.
+    /// Return false if no other controller is registered.
+    pub fn cycle_controller(&mut self) -> bool {
+        self.tween = None;
+        cycle_controller(&mut self.updater, &mut self.standby, self.size)
+    }
+
+    /// Glide back to the controller initial view
+    pub fn reset(&mut self, easing: Easing) {
+        let current = self.updater.pose();
+        self.updater.reset();
+        if let (Some(current), Some(initial)) = (current, self.updater.pose()) {
+            self.updater.set_pose(current);
+            self.animate_to(initial, RESET_DURATION, easing);
+        }
+    }
+
+    /// Animate the controller framing from the current one to `goal`, over the next updates.
+    /// Replaces the running animation; ignored by the controllers without framing.
+    pub fn animate_to(&mut self, goal: CameraPose, duration: Duration, easing: Easing) {
+        match self.updater.pose() {
+            Some(start) => self.tween = Some(CameraTween::new(start, goal, duration, easing)),
+            None => log::warn!("The camera controller cannot be animated"),
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: Duration) {
+        if let Some(tween) = &mut self.tween {
+            self.updater.set_pose(tween.advance(dt));
+            if tween.is_done() {
+                self.tween = None;
+            }
+        }
         self.uniform = self.updater.update(self.uniform);
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]))
     }
@@ -127,6 +369,7 @@ impl Default for InputState {
     }
 }
 
+#[derive(Clone)]
 pub struct ViewState {
     width: u32,
     height: u32,
@@ -153,21 +396,103 @@ impl Default for ViewState {
     }
 }
 
+/// The configured view, the size is set on resize.
+/// An invalid field of view or clip planes is replaced by the default one.
+impl From<&CameraConfig> for ViewState {
+    fn from(config: &CameraConfig) -> Self {
+        let mut view = Self {
+            position: config.position,
+            target: config.target,
+            up: config.up,
+            ..Default::default()
+        };
+        if let Err(e) = view.set_fovy(config.fovy) {
+            log::warn!("{}", e);
+        }
+        if let Err(e) = view.set_clip_planes(config.znear, config.zfar) {
+            log::warn!("{}", e);
+        }
+        view
+    }
+}
+
+impl ViewState {
+    /// Set the viewport size (pixels), defining the aspect ratio
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Set the vertical field of view (degrees)
+    pub fn set_fovy(&mut self, fovy: f32) -> Result<(), CameraError> {
+        if !(fovy > 0.0 && fovy < 180.0) {
+            return Err(CameraError::InvalidFov { fovy });
+        }
+        self.fovy = fovy;
+        Ok(())
+    }
+
+    /// Set the near and far clip planes distances
+    pub fn set_clip_planes(&mut self, znear: f32, zfar: f32) -> Result<(), CameraError> {
+        if !(znear > 0.0 && zfar > znear) {
+            return Err(CameraError::InvalidClipPlanes { znear, zfar });
+        }
+        self.znear = znear;
+        self.zfar = zfar;
+        Ok(())
+    }
+}
+
 pub struct OrbitController {
     _input: InputState,
     view: ViewState,
+    /// view restored on reset
+    initial_view: ViewState,
+    /// lowest camera height above the ground
+    min_height: Option<f32>,
+    /// ground height at a (x, z) position, the ground is the y = 0 plane if not set
+    ground_height: Option<Box<dyn Fn(f32, f32) -> f32>>,
 }
 
 impl Default for OrbitController {
     fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl OrbitController {
+    pub fn new(view: ViewState) -> Self {
         Self {
             _input: Default::default(),
-            view: Default::default(),
+            initial_view: view.clone(),
+            view,
+            min_height: None,
+            ground_height: None,
+        }
+    }
+
+    /// Keep the camera at least `min_height` above the ground (None to disable)
+    pub fn set_min_height(&mut self, min_height: Option<f32>) {
+        self.min_height = min_height;
+    }
+
+    /// Sample the ground (terrain) height under the camera instead of using the y = 0 plane
+    pub fn set_ground_height(&mut self, ground_height: Box<dyn Fn(f32, f32) -> f32>) {
+        self.ground_height = Some(ground_height);
+    }
+
+    /// Raise the camera back over its minimum height, if set
+    fn clamp_to_ground(&mut self) {
+        if let Some(min_height) = self.min_height {
+            let [x, y, z] = self.view.position;
+            let ground = self
+                .ground_height
+                .as_ref()
+                .map_or(0.0, |height| height(x, z));
+            self.view.position[1] = y.max(ground + min_height);
         }
     }
-}
 
-impl OrbitController {
     fn pos(&self) -> cgmath::Point3<f32> {
         let p = self.view.position;
         cgmath::Point3::new(p[0], p[1], p[2])
@@ -183,21 +508,33 @@ impl OrbitController {
         cgmath::Vector3::new(u[0], u[1], u[2])
     }
 
+    /// Width / height ratio.
+    /// A zero sized view (minimized window) falls back to 1.0 to keep the projection finite.
     fn aspect(&self) -> f32 {
+        if self.view.width == 0 || self.view.height == 0 {
+            return 1.0;
+        }
         self.view.width as f32 / self.view.height as f32
     }
 
+    fn view_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::look_at_rh(self.pos(), self.tar(), self.up())
+    }
+
     pub fn uniform(&self) -> CameraUniform {
         let ViewState {
             znear, zfar, fovy, ..
         } = self.view;
         let fovy = cgmath::Deg(fovy);
-        let vm = cgmath::Matrix4::look_at_rh(self.pos(), self.tar(), self.up());
+        let vm = self.view_matrix();
         let pm = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(fovy, self.aspect(), znear, zfar);
 
+        let view_proj = pm * vm;
         CameraUniform {
             view_position: self.pos().to_homogeneous().into(),
-            view_proj: (pm * vm).into(),
+            view_proj: view_proj.into(),
+            clip_planes: [znear, zfar, 0.0, 0.0],
+            inv_view_proj: inverse(view_proj).into(),
         }
     }
 
@@ -214,6 +551,256 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 
 impl CameraUpdater for OrbitController {
     fn update(&mut self, _camera_uniform: CameraUniform) -> CameraUniform {
+        self.clamp_to_ground();
         self.uniform()
     }
+
+    fn view(&self) -> cgmath::Matrix4<f32> {
+        self.view_matrix()
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.view.set_size(width, height);
+    }
+
+    /// Restore the initial position, target, up and projection, keeping the current size
+    fn reset(&mut self) {
+        let (width, height) = (self.view.width, self.view.height);
+        self.view = self.initial_view.clone();
+        self.view.set_size(width, height);
+    }
+
+    fn pose(&self) -> Option<CameraPose> {
+        Some(CameraPose {
+            position: self.view.position,
+            target: self.view.target,
+            fovy: self.view.fovy,
+        })
+    }
+
+    fn set_pose(&mut self, pose: CameraPose) {
+        self.view.position = pose.position;
+        self.view.target = pose.target;
+        if let Err(e) = self.view.set_fovy(pose.fovy) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// The view transform without its translation: the camera orientation only
+pub fn rotation_only(view: cgmath::Matrix4<f32>) -> cgmath::Matrix4<f32> {
+    let mut rotation = view;
+    rotation.w = cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0);
+    rotation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(uniform: CameraUniform) {
+        let product =
+            cgmath::Matrix4::from(uniform.inv_view_proj) * cgmath::Matrix4::from(uniform.view_proj);
+        let product: [[f32; 4]; 4] = product.into();
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::identity().into();
+        for (column, expected) in product.iter().zip(&identity) {
+            for (value, expected) in column.iter().zip(expected) {
+                assert!(
+                    (value - expected).abs() < 1e-4,
+                    "{:?} is not the identity",
+                    product
+                );
+            }
+        }
+    }
+
+    /// Fly camera stand-in, at a fixed position
+    struct Fly;
+
+    impl CameraUpdater for Fly {
+        fn update(&mut self, camera_uniform: CameraUniform) -> CameraUniform {
+            CameraUniform {
+                view_position: [0.0, 7.0, 0.0, 1.0],
+                ..camera_uniform
+            }
+        }
+
+        fn view(&self) -> cgmath::Matrix4<f32> {
+            cgmath::Matrix4::identity()
+        }
+    }
+
+    #[test]
+    fn swapped_updater_drives_the_next_update() {
+        let mut current: Box<dyn CameraUpdater> = Box::new(OrbitController::default());
+        let orbit_position = current.update(CameraUniform::default()).view_position;
+
+        let previous = swap_updater(&mut current, Box::new(Fly), Some((800, 600)));
+        let uniform = current.update(CameraUniform::default());
+        assert_eq!(uniform.view_position, [0.0, 7.0, 0.0, 1.0]);
+        assert_ne!(uniform.view_position, orbit_position);
+        // the replaced orbit is handed back, as it was
+        assert_eq!(
+            previous.pose().map(|pose| pose.position),
+            Some([100.0, 100.0, 50.0])
+        );
+    }
+
+    #[test]
+    fn camera_below_the_min_height_is_raised() {
+        let view = ViewState {
+            position: [5.0, -3.0, 5.0],
+            ..Default::default()
+        };
+        let mut controller = OrbitController::new(view.clone());
+        controller.set_min_height(Some(2.0));
+        let uniform = controller.update(CameraUniform::default());
+        assert_eq!(uniform.view_position, [5.0, 2.0, 5.0, 1.0]);
+
+        // over a terrain, sampled under the camera
+        let mut controller = OrbitController::new(view.clone());
+        controller.set_min_height(Some(2.0));
+        controller.set_ground_height(Box::new(|x, _z| x));
+        let uniform = controller.update(CameraUniform::default());
+        assert_eq!(uniform.view_position, [5.0, 7.0, 5.0, 1.0]);
+
+        // not clamped by default
+        let uniform = OrbitController::new(view).update(CameraUniform::default());
+        assert_eq!(uniform.view_position, [5.0, -3.0, 5.0, 1.0]);
+    }
+
+    #[test]
+    fn halfway_tween_is_between_start_and_goal() {
+        let start = CameraPose {
+            position: [0.0, 10.0, 0.0],
+            target: [0.0; 3],
+            fovy: 45.0,
+        };
+        let goal = CameraPose {
+            position: [20.0, 10.0, -8.0],
+            target: [4.0, 0.0, 0.0],
+            fovy: 60.0,
+        };
+        for easing in [Easing::Linear, Easing::EaseInOut, Easing::EaseOut] {
+            let mut tween = CameraTween::new(start, goal, Duration::from_secs(2), easing);
+            let halfway = tween.advance(Duration::from_secs(1));
+            assert!(!tween.is_done());
+            assert!(halfway.position[0] > 0.0 && halfway.position[0] < 20.0);
+            assert!(halfway.position[2] < 0.0 && halfway.position[2] > -8.0);
+            assert!(halfway.fovy > 45.0 && halfway.fovy < 60.0);
+            assert_eq!(halfway.position[1], 10.0);
+
+            assert_eq!(tween.advance(Duration::from_secs(2)), goal);
+            assert!(tween.is_done());
+        }
+        let mut linear = CameraTween::new(start, goal, Duration::from_secs(2), Easing::Linear);
+        assert_eq!(
+            linear.advance(Duration::from_secs(1)).position,
+            [10.0, 10.0, -4.0]
+        );
+    }
+
+    #[test]
+    fn inverse_view_projection_inverts_the_view_projection() {
+        assert_identity(CameraUniform::default());
+        // near the origin: the f32 inversion loses precision with the translation
+        let view = ViewState {
+            width: 800,
+            height: 600,
+            position: [3.0, 2.0, 5.0],
+            target: [0.0, 1.0, 0.0],
+            ..Default::default()
+        };
+        assert_identity(OrbitController::new(view).uniform());
+    }
+
+    #[test]
+    fn resized_view_follows_the_surface_aspect() {
+        let mut controller = OrbitController::default();
+        controller.resize(800, 600);
+        assert!((controller.aspect() - 4.0 / 3.0).abs() < 1e-6);
+        controller.resize(600, 800);
+        assert!((controller.aspect() - 3.0 / 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reset_restores_the_initial_view() {
+        let view = ViewState {
+            width: 800,
+            height: 600,
+            position: [3.0, 2.0, 5.0],
+            target: [0.0, 1.0, 0.0],
+            ..Default::default()
+        };
+        let mut controller = OrbitController::new(view);
+        let initial = controller.uniform();
+        controller.set_pose(CameraPose {
+            position: [-10.0, 4.0, 2.0],
+            target: [1.0, 0.0, 1.0],
+            fovy: 70.0,
+        });
+        assert_ne!(controller.uniform().view_proj, initial.view_proj);
+
+        controller.reset();
+        let reset = controller.uniform();
+        assert_eq!(reset.view_position, initial.view_position);
+        assert_eq!(reset.view_proj, initial.view_proj);
+    }
+
+    #[test]
+    fn zero_height_projection_stays_finite() {
+        let mut controller = OrbitController::default();
+        controller.resize(800, 0);
+        assert_eq!(controller.aspect(), 1.0);
+        let uniform = controller.uniform();
+        let matrices = [uniform.view_proj, uniform.inv_view_proj];
+        assert!(matrices.iter().flatten().flatten().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn cycling_twice_returns_to_the_first_controller() {
+        let mut current: Box<dyn CameraUpdater> = Box::new(OrbitController::default());
+        let mut standby: VecDeque<Box<dyn CameraUpdater>> =
+            VecDeque::from([Box::new(Fly) as Box<dyn CameraUpdater>]);
+        let orbit_position = current.update(CameraUniform::default()).view_position;
+
+        assert!(cycle_controller(
+            &mut current,
+            &mut standby,
+            Some((800, 600))
+        ));
+        let uniform = current.update(CameraUniform::default());
+        assert_eq!(uniform.view_position, [0.0, 7.0, 0.0, 1.0]);
+
+        assert!(cycle_controller(
+            &mut current,
+            &mut standby,
+            Some((800, 600))
+        ));
+        let uniform = current.update(CameraUniform::default());
+        assert_eq!(uniform.view_position, orbit_position);
+        assert_eq!(standby.len(), 1);
+
+        // nothing to cycle to
+        assert!(!cycle_controller(&mut current, &mut VecDeque::new(), None));
+    }
+
+    #[test]
+    fn rotation_only_drops_the_camera_position() {
+        let at = |position: [f32; 3]| {
+            let position = cgmath::Point3::from(position);
+            cgmath::Matrix4::look_at_rh(
+                position,
+                position + cgmath::Vector3::new(-1.0, -1.0, -1.0),
+                cgmath::Vector3::unit_y(),
+            )
+        };
+        let near = rotation_only(at([1.0, 1.0, 1.0]));
+        let far = rotation_only(at([100.0, 40.0, -7.0]));
+        assert_eq!(near.w, cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0));
+        // same orientation, same transform wherever the camera is
+        let x_axis = cgmath::Vector4::unit_x();
+        assert!(cgmath::InnerSpace::magnitude(near * x_axis - far * x_axis) < 1e-6);
+        assert_eq!(near.x, at([1.0, 1.0, 1.0]).x);
+    }
 }
@@ -0,0 +1,65 @@
+/// Encode a linear color component with the sRGB transfer function
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Clear color to give to a render pass writing a target of the given format.
+///
+/// wgpu expects linear values and encodes them itself for the sRGB formats: only the colors
+/// cleared on a non sRGB target have to be encoded (the alpha is always linear).
+pub fn clear_color(linear: [f32; 4], format: wgpu::TextureFormat) -> wgpu::Color {
+    let [r, g, b, a] = linear;
+    let encode = |c: f32| {
+        if format.describe().srgb {
+            c as f64
+        } else {
+            linear_to_srgb(c) as f64
+        }
+    };
+    wgpu::Color {
+        r: encode(r),
+        g: encode(g),
+        b: encode(b),
+        a: a as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn linear_to_srgb_known_values() {
+        assert_near(linear_to_srgb(0.0), 0.0);
+        assert_near(linear_to_srgb(1.0), 1.0);
+        // both sides of the linear segment end
+        assert_near(linear_to_srgb(0.002), 0.02584);
+        assert_near(linear_to_srgb(0.003_130_8), 0.04045);
+        // middle grey and half intensity
+        assert_near(linear_to_srgb(0.18), 0.46135);
+        assert_near(linear_to_srgb(0.5), 0.73536);
+    }
+
+    #[test]
+    fn clear_color_is_encoded_for_linear_targets_only() {
+        let linear = [0.5, 0.5, 0.5, 0.5];
+        let srgb_target = clear_color(linear, wgpu::TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(srgb_target.r, 0.5);
+        let linear_target = clear_color(linear, wgpu::TextureFormat::Bgra8Unorm);
+        assert_near(linear_target.r as f32, 0.73536);
+        assert_eq!(linear_target.a, 0.5);
+    }
+}
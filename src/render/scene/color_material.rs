@@ -2,7 +2,10 @@ use std::ops::Deref;
 
 use wgpu::util::DeviceExt;
 
-use super::material::{Material, MaterialKind};
+use super::{
+    color_materials::{ColorMaterials, PackedMaterial},
+    material::{Material, MaterialKind},
+};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -10,7 +13,19 @@ pub struct ColorUniform {
     ambient: [f32; 3],
     specular: f32,
     diffuse: [f32; 3],
-    _pad: f32,
+    shininess: f32,
+}
+
+impl ColorUniform {
+    /// The specular color is averaged, the shaders scale the lights colors
+    pub fn new(ambient: [f32; 3], diffuse: [f32; 3], specular: [f32; 3], shininess: f32) -> Self {
+        Self {
+            ambient,
+            specular: (specular[0] + specular[1] + specular[2]) / 3.0,
+            diffuse,
+            shininess,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -18,6 +33,8 @@ pub struct ColorMaterial {
     kind: MaterialKind,
     name: String,
     bind_group: wgpu::BindGroup,
+    /// slot in the packed color materials, None if they were full
+    packed: Option<PackedMaterial>,
 }
 
 impl Deref for ColorMaterial {
@@ -36,23 +53,21 @@ impl Material for ColorMaterial {
     fn name(&self) -> String {
         self.name.clone()
     }
+
+    fn packed(&self) -> Option<&PackedMaterial> {
+        self.packed.as_ref()
+    }
 }
 
 impl ColorMaterial {
+    /// Create the material bind group and pack the material into `color_materials`
     pub(crate) fn new<S: AsRef<str>>(
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_materials: &ColorMaterials,
         name: S,
-        ambient: [f32; 3],
-        diffuse: [f32; 3],
-        specular: [f32; 3],
+        uniform: ColorUniform,
     ) -> Self {
-        let uniform = ColorUniform {
-            ambient,
-            specular: (specular[0] + specular[1] + specular[2]) / 3.0,
-            diffuse,
-            _pad: 0.0,
-        };
-
         let name = name.as_ref().to_string();
 
         let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -73,6 +88,7 @@ impl ColorMaterial {
             kind: MaterialKind::Color,
             name,
             bind_group,
+            packed: color_materials.pack(queue, uniform),
         }
     }
 
@@ -0,0 +1,160 @@
+use std::{cell::Cell, rc::Rc};
+
+use wgpu::{util::DeviceExt, BufferAddress};
+
+use super::color_material::ColorUniform;
+
+/// Capacity of the packed color materials storage buffer
+pub const MAX_COLOR_MATERIALS: u32 = 256;
+
+/// All the color materials uniforms packed into a single storage buffer.
+///
+/// The indexed colored pipeline binds the buffer once and reads the material of each geometry
+/// at the index given by a per instance vertex attribute (location 12). The attribute has a
+/// zero stride: all the instances of a draw read the index of the bound `indices` slice.
+pub struct ColorMaterials {
+    uniforms: wgpu::Buffer,
+    indices: Rc<wgpu::Buffer>,
+    bind_group: Rc<wgpu::BindGroup>,
+    count: Cell<u32>,
+}
+
+/// Slot of a color material in the packed buffer
+#[derive(Debug)]
+pub struct PackedMaterial {
+    pub index: u32,
+    bind_group: Rc<wgpu::BindGroup>,
+    indices: Rc<wgpu::Buffer>,
+}
+
+impl PackedMaterial {
+    /// Bind group of the whole packed buffer
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Vertex buffer slice holding the material index
+    pub fn index_slice(&self) -> wgpu::BufferSlice<'_> {
+        let start = self.index as BufferAddress * std::mem::size_of::<u32>() as BufferAddress;
+        self.indices
+            .slice(start..start + std::mem::size_of::<u32>() as BufferAddress)
+    }
+}
+
+impl ColorMaterials {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("packed color materials buffer"),
+            size: Self::offset(MAX_COLOR_MATERIALS),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let indices: Vec<u32> = (0..MAX_COLOR_MATERIALS).collect();
+        let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("packed color materials indices buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::bind_group_layout(device),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniforms.as_entire_binding(),
+            }],
+            label: Some("packed color materials bind group"),
+        });
+        Self {
+            uniforms,
+            indices: Rc::new(indices),
+            bind_group: Rc::new(bind_group),
+            count: Cell::new(0),
+        }
+    }
+
+    /// Byte offset of the material at `index` in the storage buffer
+    pub fn offset(index: u32) -> BufferAddress {
+        index as BufferAddress * std::mem::size_of::<ColorUniform>() as BufferAddress
+    }
+
+    /// Write the material uniform in the next free slot, None if the buffer is full
+    pub fn pack(&self, queue: &wgpu::Queue, uniform: ColorUniform) -> Option<PackedMaterial> {
+        let index = self.count.get();
+        if index >= MAX_COLOR_MATERIALS {
+            log::warn!(
+                "Packed color materials buffer full ({} materials)",
+                MAX_COLOR_MATERIALS
+            );
+            return None;
+        }
+        queue.write_buffer(
+            &self.uniforms,
+            Self::offset(index),
+            bytemuck::cast_slice(&[uniform]),
+        );
+        self.count.set(index + 1);
+        Some(PackedMaterial {
+            index,
+            bind_group: self.bind_group.clone(),
+            indices: self.indices.clone(),
+        })
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("packed color materials bind group layout"),
+        })
+    }
+
+    /// Layout of the material index vertex buffer (slot 2)
+    pub fn index_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: 0,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 12,
+                format: wgpu::VertexFormat::Uint32,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn materials_are_packed_one_after_the_other() {
+        // the stride of the storage array of the shaders: two vec4
+        assert_eq!(std::mem::size_of::<ColorUniform>(), 32);
+        assert_eq!(ColorMaterials::offset(0), 0);
+        assert_eq!(ColorMaterials::offset(3), 96);
+        assert_eq!(
+            ColorMaterials::offset(MAX_COLOR_MATERIALS),
+            MAX_COLOR_MATERIALS as BufferAddress * 32
+        );
+
+        let (device, queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let materials = ColorMaterials::new(&device);
+        let uniform = ColorUniform::new([0.1; 3], [0.5; 3], [1.0; 3], 32.0);
+        let indices: Vec<u32> = (0..MAX_COLOR_MATERIALS)
+            .map(|_| materials.pack(&queue, uniform).unwrap().index)
+            .collect();
+        assert_eq!(indices, (0..MAX_COLOR_MATERIALS).collect::<Vec<_>>());
+        // full
+        assert!(materials.pack(&queue, uniform).is_none());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::render::names::ModelName;
+
+use super::{draw_ext::GeometryFilter, object::Object};
+
+/// Object placement in the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: cgmath::Vector3<f32>,
+    pub orientation: cgmath::Quaternion<f32>,
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            orientation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    pub fn apply_to(&self, object: &mut Object) {
+        object.position = self.position;
+        object.orientation = self.orientation;
+        object.mesh_scale = self.scale;
+    }
+}
+
+/// Scene control commands.
+///
+/// Unlike `PomarinEvent`, the commands are not required to be `Copy` and do not go through the
+/// winit event loop: they are sent over a `std::sync::mpsc` channel (see
+/// `AppRender::get_scene_commands_sender()`) and drained by the `ScenePass` at the start of each
+/// render.
+#[derive(Debug, Clone)]
+pub enum SceneCommand {
+    /// Add an object displaying the given model
+    Spawn {
+        name: String,
+        model: ModelName,
+        transform: Transform,
+    },
+    /// Remove the objects with the given name
+    Despawn(String),
+    /// Move the objects with the given name
+    SetTransform { name: String, transform: Transform },
+    /// Rotate the objects with the given name, keeping their position and scale
+    SetOrientation {
+        name: String,
+        orientation: cgmath::Quaternion<f32>,
+    },
+    /// Remove the objects displaying the model, then release the model resources
+    UnloadModel(ModelName),
+    /// Draw the vertices normals of the model, or stop drawing them
+    ShowNormals(Option<ModelName>),
+    /// Draw the bounding box of the objects with the given name, or stop drawing it
+    ShowBounds { name: String, show: bool },
+    /// Draw only the given geometry of its model (with its material), or draw the models whole
+    /// again
+    IsolateGeometry(Option<GeometryFilter>),
+    /// Draw the meshes at a generated level of detail (0 for the source geometries)
+    ShowLod(usize),
+    /// Outline the object with the given name, or stop outlining
+    Select(Option<String>),
+}
@@ -0,0 +1,221 @@
+//! GPU compressed images (`.dds` and `.ktx2` files), uploaded without decoding.
+
+use anyhow::Result;
+
+use crate::render::error::TextureError;
+
+/// Mip levels of a block compressed image, the largest first
+#[derive(Debug)]
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Parse a `.dds` or `.ktx2` file content, according to the file extension
+    pub fn parse(extension: &str, bytes: &[u8]) -> Result<Self> {
+        match extension {
+            "dds" => Self::parse_dds(bytes),
+            "ktx2" => Self::parse_ktx2(bytes),
+            _ => Err(TextureError::InvalidCompressedFile {
+                reason: format!("unknown compressed image extension {}", extension),
+            }
+            .into()),
+        }
+    }
+
+    /// Size in bytes of a mip level, and its bytes per blocks row
+    pub fn level_size(format: wgpu::TextureFormat, width: u32, height: u32) -> (usize, u32) {
+        let info = format.describe();
+        let (block_width, block_height) = info.block_dimensions;
+        let blocks_x = width.max(1).div_ceil(block_width as u32);
+        let blocks_y = height.max(1).div_ceil(block_height as u32);
+        let bytes_per_row = blocks_x * info.block_size as u32;
+        ((bytes_per_row * blocks_y) as usize, bytes_per_row)
+    }
+
+    /// Split the consecutive mip levels data
+    fn split_levels(
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels_count: u32,
+        data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut levels = vec![];
+        let mut offset = 0;
+        for level in 0..levels_count.max(1) {
+            let (size, _) = Self::level_size(format, width >> level, height >> level);
+            let bytes = data.get(offset..offset + size).ok_or_else(|| {
+                TextureError::InvalidCompressedFile {
+                    reason: format!("mip level {} is truncated", level),
+                }
+            })?;
+            levels.push(bytes.to_vec());
+            offset += size;
+        }
+        Ok(levels)
+    }
+
+    fn parse_dds(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 128 || &bytes[0..4] != b"DDS " {
+            return Err(invalid("not a DDS file"));
+        }
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let mip_map_count = read_u32(bytes, 28)?;
+        let four_cc = &bytes[84..88];
+        let (format, data_offset) = match four_cc {
+            b"DXT1" => (wgpu::TextureFormat::Bc1RgbaUnormSrgb, 128),
+            b"DXT5" => (wgpu::TextureFormat::Bc3RgbaUnormSrgb, 128),
+            b"ATI2" | b"BC5U" => (wgpu::TextureFormat::Bc5RgUnorm, 128),
+            b"DX10" => (dxgi_format(read_u32(bytes, 128)?)?, 148),
+            _ => {
+                return Err(invalid(&format!(
+                    "unsupported DDS pixel format {:?}",
+                    String::from_utf8_lossy(four_cc)
+                )))
+            }
+        };
+        let data = bytes
+            .get(data_offset..)
+            .ok_or_else(|| invalid("missing DDS data"))?;
+        let levels = Self::split_levels(format, width, height, mip_map_count, data)?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    fn parse_ktx2(bytes: &[u8]) -> Result<Self> {
+        const IDENTIFIER: [u8; 12] = [
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        if bytes.len() < 80 || bytes[0..12] != IDENTIFIER {
+            return Err(invalid("not a KTX2 file"));
+        }
+        let format = vk_format(read_u32(bytes, 12)?)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let level_count = read_u32(bytes, 40)?.max(1);
+        if read_u32(bytes, 44)? != 0 {
+            return Err(invalid("supercompressed KTX2 files are not supported"));
+        }
+        // levels index, after the header (48 bytes) and the data format, key/values and
+        // supercompression global data offsets (32 bytes)
+        let levels = (0..level_count)
+            .map(|level| {
+                let index = 80 + level as usize * 24;
+                let offset = read_u64(bytes, index)? as usize;
+                let length = read_u64(bytes, index + 8)? as usize;
+                let (size, _) = Self::level_size(format, width >> level, height >> level);
+                if length != size {
+                    return Err(invalid(&format!(
+                        "mip level {} has {} bytes, {} expected",
+                        level, length, size
+                    )));
+                }
+                bytes
+                    .get(offset..offset + length)
+                    .map(|data| data.to_vec())
+                    .ok_or_else(|| invalid(&format!("mip level {} is truncated", level)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+}
+
+fn invalid(reason: &str) -> anyhow::Error {
+    TextureError::InvalidCompressedFile {
+        reason: reason.to_string(),
+    }
+    .into()
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("truncated header"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+/// `DXGI_FORMAT` of the DDS DX10 header extension
+fn dxgi_format(format: u32) -> Result<wgpu::TextureFormat> {
+    match format {
+        71 => Ok(wgpu::TextureFormat::Bc1RgbaUnorm),
+        72 => Ok(wgpu::TextureFormat::Bc1RgbaUnormSrgb),
+        77 => Ok(wgpu::TextureFormat::Bc3RgbaUnorm),
+        78 => Ok(wgpu::TextureFormat::Bc3RgbaUnormSrgb),
+        83 => Ok(wgpu::TextureFormat::Bc5RgUnorm),
+        98 => Ok(wgpu::TextureFormat::Bc7RgbaUnorm),
+        99 => Ok(wgpu::TextureFormat::Bc7RgbaUnormSrgb),
+        _ => Err(invalid(&format!("unsupported DXGI format {}", format))),
+    }
+}
+
+/// `VkFormat` of the KTX2 header
+fn vk_format(format: u32) -> Result<wgpu::TextureFormat> {
+    match format {
+        133 => Ok(wgpu::TextureFormat::Bc1RgbaUnorm),
+        134 => Ok(wgpu::TextureFormat::Bc1RgbaUnormSrgb),
+        137 => Ok(wgpu::TextureFormat::Bc3RgbaUnorm),
+        138 => Ok(wgpu::TextureFormat::Bc3RgbaUnormSrgb),
+        141 => Ok(wgpu::TextureFormat::Bc5RgUnorm),
+        145 => Ok(wgpu::TextureFormat::Bc7RgbaUnorm),
+        146 => Ok(wgpu::TextureFormat::Bc7RgbaUnormSrgb),
+        157 => Ok(wgpu::TextureFormat::Astc4x4RgbaUnorm),
+        158 => Ok(wgpu::TextureFormat::Astc4x4RgbaUnormSrgb),
+        _ => Err(invalid(&format!("unsupported Vulkan format {}", format))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DDS file with a DX10 header, of `data` at the given DXGI format
+    fn dds(dxgi_format: u32, width: u32, height: u32, mip_map_count: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 148];
+        bytes[0..4].copy_from_slice(b"DDS ");
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&mip_map_count.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DX10");
+        bytes[128..132].copy_from_slice(&dxgi_format.to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn bc7_levels_are_copied_by_rows_of_blocks() {
+        // 4x4 texels blocks of 16 bytes: a 10x6 level has 3x2 blocks, its 5x3 mip 2x1 blocks
+        let format = wgpu::TextureFormat::Bc7RgbaUnormSrgb;
+        assert_eq!(CompressedImage::level_size(format, 10, 6), (96, 48));
+        assert_eq!(CompressedImage::level_size(format, 5, 3), (32, 32));
+        assert_eq!(CompressedImage::level_size(format, 1, 1), (16, 16));
+
+        let data: Vec<u8> = (0..128).collect();
+        let image = CompressedImage::parse("dds", &dds(99, 10, 6, 2, &data)).unwrap();
+        assert_eq!(image.format, format);
+        assert_eq!((image.width, image.height), (10, 6));
+        assert_eq!(image.levels, vec![data[..96].to_vec(), data[96..].to_vec()]);
+
+        let linear = CompressedImage::parse("dds", &dds(98, 10, 6, 1, &data)).unwrap();
+        assert_eq!(linear.format, wgpu::TextureFormat::Bc7RgbaUnorm);
+        // the second level is missing
+        assert!(CompressedImage::parse("dds", &dds(99, 10, 6, 2, &data[..96])).is_err());
+    }
+}
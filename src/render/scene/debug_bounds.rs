@@ -0,0 +1,142 @@
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+
+use crate::app::config::LinesConfig;
+
+use super::{
+    bounds::BoundingBox,
+    debug_normals::DebugLineVertex,
+    instance::InstanceRaw,
+    thick_line::{expand_lines, ThickLineVertex, ThickLines},
+};
+
+/// Boxes edges color (linear rgb)
+const BOUNDS_COLOR: [f32; 3] = [1.0, 0.8, 0.0];
+
+/// Lines list of the box edges: 12 segments, 24 vertices
+pub fn box_lines(bounds: &BoundingBox, color: [f32; 3]) -> Vec<DebugLineVertex> {
+    bounds
+        .edges()
+        .iter()
+        .flatten()
+        .map(|&corner| DebugLineVertex::new(corner.into(), color))
+        .collect()
+}
+
+/// Bounding boxes of the flagged objects (see `Object::show_bounds`), drawn as thick lines.
+///
+/// The boxes are given in world space: the lines are drawn with a single identity instance.
+pub struct DebugBoundsPass {
+    lines: ThickLines,
+    vertex_buffer: wgpu::Buffer,
+    /// vertices the buffer can hold
+    capacity: usize,
+    vertex_count: u32,
+    identity: wgpu::Buffer,
+}
+
+impl DebugBoundsPass {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        lines_config: &LinesConfig,
+    ) -> Self {
+        // one box: 12 quads
+        let capacity = 72;
+        let identity = InstanceRaw {
+            model: cgmath::Matrix4::identity().into(),
+            normal: cgmath::Matrix3::identity().into(),
+        };
+        Self {
+            lines: ThickLines::new(
+                device,
+                config,
+                camera_bgl,
+                depth_format,
+                sample_count,
+                lines_config,
+            ),
+            vertex_buffer: Self::create_vertex_buffer(device, capacity),
+            capacity,
+            vertex_count: 0,
+            identity: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("debug_bounds.identity_instance"),
+                contents: bytemuck::cast_slice(&[identity]),
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug_bounds.vertex_buffer"),
+            size: (capacity * std::mem::size_of::<ThickLineVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Set the boxes (world space) drawn by the next draws
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, boxes: &[BoundingBox]) {
+        let lines: Vec<DebugLineVertex> = boxes
+            .iter()
+            .flat_map(|bounds| box_lines(bounds, BOUNDS_COLOR))
+            .collect();
+        let vertices = expand_lines(&lines);
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// The lines pixels widths depend on the surface size
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.lines.resize(queue, width, height);
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bg: &'a wgpu::BindGroup,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        self.lines.bind(render_pass, camera_bg);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.identity.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_is_drawn_by_its_twelve_edges() {
+        let bounds = BoundingBox::new((-1.0, 0.0, -2.0).into(), (1.0, 3.0, 2.0).into());
+        let lines = box_lines(&bounds, BOUNDS_COLOR);
+        assert_eq!(lines.len(), 24);
+        assert!(lines.iter().all(|v| v.color == BOUNDS_COLOR));
+
+        let size = [2.0, 3.0, 4.0];
+        for edge in lines.chunks_exact(2) {
+            let (start, end) = (edge[0].position, edge[1].position);
+            // along a single axis, the whole size of the box on this axis
+            let changed: Vec<usize> = (0..3).filter(|&axis| start[axis] != end[axis]).collect();
+            assert_eq!(changed.len(), 1, "{:?} is not an edge", edge);
+            let axis = changed[0];
+            assert_eq!((end[axis] - start[axis]).abs(), size[axis]);
+        }
+        // each corner ends 3 edges
+        for corner in lines.iter().map(|v| v.position) {
+            assert_eq!(lines.iter().filter(|v| v.position == corner).count(), 3);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    app::config::{DebugNormalsConfig, LinesConfig},
+    render::{
+        config::{assets::TryAsRef, mesh::MeshDescriptor},
+        names::MeshName,
+        state::WgpuState,
+    },
+};
+
+use super::{
+    instance::{InstanceRaw, InstancesSystem},
+    model::Model,
+    thick_line::{expand_lines, ThickLines},
+    vertex::ModelVertex,
+};
+
+/// Segments colors (linear rgb)
+const NORMAL_COLOR: [f32; 3] = [0.0, 0.3, 1.0];
+const TANGENT_COLOR: [f32; 3] = [1.0, 0.1, 0.1];
+const BITANGENT_COLOR: [f32; 3] = [0.1, 1.0, 0.1];
+
+/// A lines list vertex, expanded to quads before the upload (see `expand_lines`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugLineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl DebugLineVertex {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self { position, color }
+    }
+}
+
+/// Lines list of the vertices normals (and tangents and bitangents if enabled), from the vertex
+/// position along the direction, `length` long
+pub fn normal_segments(
+    vertices: &[ModelVertex],
+    length: f32,
+    tangents: bool,
+) -> Vec<DebugLineVertex> {
+    let segment = |origin: [f32; 3], direction: [f32; 3], color: [f32; 3]| {
+        let end = [
+            origin[0] + direction[0] * length,
+            origin[1] + direction[1] * length,
+            origin[2] + direction[2] * length,
+        ];
+        [
+            DebugLineVertex {
+                position: origin,
+                color,
+            },
+            DebugLineVertex {
+                position: end,
+                color,
+            },
+        ]
+    };
+    vertices
+        .iter()
+        .flat_map(|v| {
+            let mut segments = vec![segment(v.position, v.normal, NORMAL_COLOR)];
+            if tangents {
+                segments.push(segment(v.position, v.tangent, TANGENT_COLOR));
+                segments.push(segment(v.position, v.bitangent, BITANGENT_COLOR));
+            }
+            segments
+        })
+        .flatten()
+        .collect()
+}
+
+/// Segments of the debugged model
+struct ModelSegments {
+    model: Rc<Model>,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+/// Vertices normals, tangents and bitangents of one model drawn as colored lines (lighting
+/// inspection).
+///
+/// The geometries vertices are not kept once uploaded: they are loaded again from the mesh
+/// source when a model is selected.
+pub struct DebugNormalsPass {
+    lines: ThickLines,
+    length: f32,
+    tangents: bool,
+    segments: Option<ModelSegments>,
+}
+
+impl DebugNormalsPass {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        debug_config: &DebugNormalsConfig,
+        lines_config: &LinesConfig,
+    ) -> Self {
+        Self {
+            lines: ThickLines::new(
+                device,
+                config,
+                camera_bgl,
+                depth_format,
+                sample_count,
+                lines_config,
+            ),
+            length: debug_config.length,
+            tangents: debug_config.tangents,
+            segments: None,
+        }
+    }
+
+    /// The debugged model, if any
+    pub fn model(&self) -> Option<&Rc<Model>> {
+        self.segments.as_ref().map(|segments| &segments.model)
+    }
+
+    /// Show the segments of the model, or hide them
+    pub fn set_model(&mut self, wgpu: &WgpuState, model: Option<Rc<Model>>) -> Result<()> {
+        self.segments = match model {
+            Some(model) => {
+                let mesh_descriptor: &MeshDescriptor = wgpu
+                    .assets
+                    .get(MeshName::from(model.mesh.name.as_str()))
+                    .and_then(|desc| desc.try_as_ref())?;
+                let segments: Vec<DebugLineVertex> = mesh_descriptor
+                    .load_vertices(wgpu)?
+                    .iter()
+                    .flat_map(|gv| normal_segments(&gv.vertices, self.length, self.tangents))
+                    .collect();
+                let vertices = expand_lines(&segments);
+                let vertex_buffer =
+                    wgpu.device
+                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some(&format!("debug_normals.{}.vertex_buffer", model.name)),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        });
+                log::info!(
+                    "Debug normals of {}: {} segments",
+                    model.name,
+                    segments.len() / 2
+                );
+                Some(ModelSegments {
+                    model,
+                    vertex_buffer,
+                    vertex_count: vertices.len() as u32,
+                })
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// The lines pixels widths depend on the surface size
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.lines.resize(queue, width, height);
+    }
+
+    /// Draw the segments of the debugged model instances
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        instances: &'a InstancesSystem<InstanceRaw>,
+        camera_bg: &'a wgpu::BindGroup,
+    ) {
+        if let Some(segments) = self.segments.as_ref().filter(|s| s.vertex_count > 0) {
+            self.lines.bind(render_pass, camera_bg);
+            render_pass.set_vertex_buffer(0, segments.vertex_buffer.slice(..));
+            for (slice, count) in instances.model_slices(&segments.model) {
+                render_pass.set_vertex_buffer(1, slice);
+                render_pass.draw(0..segments.vertex_count, 0..count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> ModelVertex {
+        ModelVertex {
+            position,
+            tex_coords: [0.0; 2],
+            normal: [0.0, 1.0, 0.0],
+            tangent: [1.0, 0.0, 0.0],
+            bitangent: [0.0, 0.0, 1.0],
+        }
+    }
+
+    #[test]
+    fn one_segment_per_vertex_and_direction() {
+        let vertices = [vertex([0.0; 3]), vertex([2.0, 0.0, 0.0])];
+
+        let normals = normal_segments(&vertices, 0.5, false);
+        assert_eq!(normals.len(), 2 * 2);
+        assert_eq!(normals[0], DebugLineVertex::new([0.0; 3], NORMAL_COLOR));
+        assert_eq!(
+            normals[1],
+            DebugLineVertex::new([0.0, 0.5, 0.0], NORMAL_COLOR)
+        );
+        assert_eq!(normals[3].position, [2.0, 0.5, 0.0]);
+
+        let all = normal_segments(&vertices, 0.5, true);
+        assert_eq!(all.len(), 2 * 3 * 2);
+        let colors: Vec<[f32; 3]> = all[..6].iter().map(|v| v.color).collect();
+        assert_eq!(
+            colors,
+            [
+                NORMAL_COLOR,
+                NORMAL_COLOR,
+                TANGENT_COLOR,
+                TANGENT_COLOR,
+                BITANGENT_COLOR,
+                BITANGENT_COLOR
+            ]
+        );
+        assert_eq!(all[3].position, [0.5, 0.0, 0.0]);
+        assert_eq!(all[5].position, [0.0, 0.0, 0.5]);
+    }
+}
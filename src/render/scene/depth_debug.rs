@@ -0,0 +1,107 @@
+use super::{pipeline::create_depth_debug_pipeline, texture::Texture};
+
+/// Fullscreen visualization of the scene depth buffer.
+///
+/// The depth texture is bound as a regular texture, so it must be drawn in a render pass that
+/// does not use it as its depth attachment. It must be created with a
+/// `DepthSamplerKind::NonFiltering` sampler.
+pub struct DepthDebugView {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl DepthDebugView {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_texture: &Texture,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+            label: Some("depth_debug.bind_group_layout"),
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, depth_texture);
+        let pipeline = create_depth_debug_pipeline(device, config, camera_bgl, &bind_group_layout);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth_texture: &Texture,
+    ) -> wgpu::BindGroup {
+        // the stencil aspect of the depth stencil formats cannot be sampled with the depth
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(depth_texture.sampler()),
+                },
+            ],
+            label: Some("depth_debug.bind_group"),
+        })
+    }
+
+    /// To be called when the depth texture is recreated
+    pub fn set_depth_texture(&mut self, device: &wgpu::Device, depth_texture: &Texture) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, depth_texture);
+    }
+
+    /// Draw the depth values over the whole output view
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        camera_bg: &wgpu::BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth debug pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
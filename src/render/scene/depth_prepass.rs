@@ -0,0 +1,74 @@
+use std::rc::Rc;
+
+use super::{
+    draw_ext::DrawSelection,
+    instance::{InstanceRaw, InstancesSystem},
+    model::Model,
+    pipeline::create_depth_prepass_pipeline,
+    texture::Texture,
+};
+
+/// Depth only pass over the opaque models, drawn before the main pass.
+///
+/// The main pass pipelines then test for equal depths without writing them (see
+/// `PipelineOptions::depth_prepassed()`): each pixel is shaded once, for its front most
+/// fragment. It only pays off when the fragment shading is expensive.
+pub struct DepthPrepass {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl DepthPrepass {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            pipeline: create_depth_prepass_pipeline(device, camera_bgl, depth_format, sample_count),
+        }
+    }
+
+    /// Clear the depth texture and write the depth of the geometries drawn by depth prepassed
+    /// pipelines
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_texture: &Texture,
+        models: &[Rc<Model>],
+        instances: &InstancesSystem<InstanceRaw>,
+        camera_bg: &wgpu::BindGroup,
+        selection: &DrawSelection,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth prepass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        for model in models {
+            for (slice, count) in instances.model_slices(model) {
+                render_pass.set_vertex_buffer(1, slice);
+                for (index, geometry) in selection.geometries(model).iter().enumerate() {
+                    if !model.geometry_pipeline(index).is_depth_prepassed()
+                        || !selection.includes(model, geometry)
+                    {
+                        continue;
+                    }
+                    render_pass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(geometry.index_buffer.slice(..), geometry.index_format);
+                    render_pass.draw_indexed(0..geometry.num_elements, 0, 0..count);
+                }
+            }
+        }
+    }
+}
@@ -1,30 +1,130 @@
-use std::ops::Range;
+use std::{fmt::Debug, ops::Range, rc::Rc};
 
-use super::model::Model;
+use crate::render::names::{GeometryName, ModelName, NamedHandle};
+
+use super::{
+    geometry::GeometryBuf,
+    instance::{InstanceRaw, InstancesSystem, ModelInstances, RawInstanceTrait},
+    model::Model,
+    pipeline::NamedPipeline,
+};
+
+/// Geometries drawn by a draw call, by the kind of their pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawPhase {
+    All,
+    /// geometries drawn by the opaque pipelines, writing the depth
+    Opaque,
+    /// geometries drawn by the transparent pipelines, after the opaque ones
+    Transparent,
+}
+
+impl DrawPhase {
+    fn includes(&self, pipeline: &NamedPipeline) -> bool {
+        match self {
+            DrawPhase::All => true,
+            DrawPhase::Opaque => !pipeline.is_transparent(),
+            DrawPhase::Transparent => pipeline.is_transparent(),
+        }
+    }
+}
+
+/// Isolates a geometry of a model (content debugging): the other geometries of the model are
+/// not drawn, the other models are drawn whole
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeometryFilter {
+    pub model: ModelName,
+    pub geometry: GeometryName,
+}
+
+impl GeometryFilter {
+    pub fn includes(&self, model: &Model, geometry: &GeometryBuf) -> bool {
+        self.includes_name(&model.name, &geometry.name())
+    }
+
+    fn includes_name(&self, model: &str, geometry: &GeometryName) -> bool {
+        *self.model != model || self.geometry == *geometry
+    }
+}
+
+/// Geometries drawn of the models (debugging): all the source geometries by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DrawSelection {
+    /// the only geometry drawn of its model
+    pub isolated: Option<GeometryFilter>,
+    /// level of detail drawn by the meshes having generated ones (see `MeshBuf::lod_geometries`)
+    pub lod: usize,
+}
+
+impl DrawSelection {
+    /// The geometries of the model at the selected level of detail, in the mesh order (the
+    /// order of their materials and pipelines)
+    pub fn geometries<'m>(&self, model: &'m Model) -> &'m [GeometryBuf] {
+        model.mesh.lod_geometries(self.lod)
+    }
+
+    /// Whether the geometry of the model is drawn
+    pub fn includes(&self, model: &Model, geometry: &GeometryBuf) -> bool {
+        self.isolated
+            .as_ref()
+            .is_none_or(|filter| filter.includes(model, geometry))
+    }
+}
+
+/// The geometries of the model drawn in the phase, with their index in the mesh (the index of
+/// their material and pipeline): one `draw_indexed` each
+fn drawn_geometries<'m: 's, 's>(
+    model: &'m Model,
+    phase: DrawPhase,
+    selection: &'s DrawSelection,
+) -> impl Iterator<Item = (usize, &'m GeometryBuf)> + 's {
+    selection
+        .geometries(model)
+        .iter()
+        .enumerate()
+        .filter(move |(mesh_index, mesh)| {
+            phase.includes(model.geometry_pipeline(*mesh_index)) && selection.includes(model, mesh)
+        })
+}
 
 pub trait DrawModel<'m> {
-    fn draw_meshes(&mut self, model: &'m Model, instances_range: Range<u32>);
-
-    /// draw an entity and all its instances using an instance buffer previously set.
-    /// The offset parameter refers to the offset in the instance buffer
-    /// Example: if the instance buffer has 4 values
-    /// if the entity has two instances
-    /// if the entity instances are linked to the instance buffer from the index 1 in the instance
-    /// buffer (2nd plce)
-    /// then we call this function with offset 1 and the range given to draw will be 1..3
-    fn draw_model<M: AsRef<Model>>(
+    /// draw the selected model geometries of the phase, the light group is bound for the lit
+    /// pipelines only
+    fn draw_meshes(
         &mut self,
-        model: &'m M,
-        offset: u32,
+        model: &'m Model,
+        instances_range: Range<u32>,
+        light_bg: &'m wgpu::BindGroup,
+        phase: DrawPhase,
+        selection: &DrawSelection,
+    );
+
+    /// draw a single instance of the model apart from the instances system (overlays drawing a
+    /// model on demand): the instance is uploaded to `buffer`, e.g.
+    /// `ModelInstances::new(device, name, 1)`.
+    ///
+    /// The upload is queued and lands before the submission of the pass: a buffer holds a
+    /// single instance per frame
+    fn draw_single(
+        &mut self,
+        model: &'m Model,
+        instance: &InstanceRaw,
+        buffer: &'m ModelInstances,
+        queue: &wgpu::Queue,
         camera_bg: &'m wgpu::BindGroup,
         light_bg: &'m wgpu::BindGroup,
     );
 
-    fn draw_models<M: AsRef<Model>>(
+    /// draw the models with their instances ranges (named after the model) of the instances
+    /// system, once per range, the selected geometries of the phase
+    fn draw_models<M: AsRef<Model>, I: RawInstanceTrait + Debug>(
         &mut self,
         models: Vec<&'m M>,
+        instances: &'m InstancesSystem<I>,
         camera_bg: &'m wgpu::BindGroup,
         light_bg: &'m wgpu::BindGroup,
+        phase: DrawPhase,
+        selection: &DrawSelection,
     );
 }
 
@@ -32,52 +132,288 @@ impl<'m, 'p> DrawModel<'m> for wgpu::RenderPass<'p>
 where
     'm: 'p,
 {
-    fn draw_models<M: AsRef<Model>>(
+    fn draw_models<M: AsRef<Model>, I: RawInstanceTrait + Debug>(
         &mut self,
         models: Vec<&'m M>,
+        instances: &'m InstancesSystem<I>,
         camera_bg: &'m wgpu::BindGroup,
         light_bg: &'m wgpu::BindGroup,
+        phase: DrawPhase,
+        selection: &DrawSelection,
     ) {
-        let mut offset = 0;
         for model in models {
-            self.draw_model(model, offset, camera_bg, light_bg);
-            offset += 1; //model.as_ref().instances_count();
+            let slices = instances.model_slices(model.as_ref());
+            if slices.is_empty() {
+                log::debug!("No instance of model {}", model.as_ref().name);
+            }
+            for (slice, count) in slices {
+                self.set_bind_group(0, camera_bg, &[]);
+                self.set_vertex_buffer(1, slice);
+                self.draw_meshes(model.as_ref(), 0..count, light_bg, phase, selection);
+            }
         }
     }
 
-    fn draw_meshes(&mut self, model: &'m Model, instances_range: Range<u32>) {
-        let mut mesh_index = 0;
-
-        for mesh in &model.mesh.geometries {
-            if model.pipeline.needs_material() {
-                self.set_bind_group(
-                    2,
-                    model
-                        .materials
-                        .get(mesh_index)
-                        .expect("mesh material not present during render"),
-                    &[],
+    fn draw_single(
+        &mut self,
+        model: &'m Model,
+        instance: &InstanceRaw,
+        buffer: &'m ModelInstances,
+        queue: &wgpu::Queue,
+        camera_bg: &'m wgpu::BindGroup,
+        light_bg: &'m wgpu::BindGroup,
+    ) {
+        buffer.set_instances(std::slice::from_ref(instance), queue);
+        match buffer.slice() {
+            Some((slice, _)) => {
+                self.set_bind_group(0, camera_bg, &[]);
+                self.set_vertex_buffer(1, slice);
+                self.draw_meshes(
+                    model,
+                    0..1,
+                    light_bg,
+                    DrawPhase::All,
+                    &DrawSelection::default(),
                 );
             }
-            self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-            self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            self.draw_indexed(0..mesh.num_elements, 0, instances_range.clone());
-            mesh_index += 1;
+            None => log::debug!("No room for the instance of model {}", model.name),
         }
     }
 
-    fn draw_model<M: AsRef<Model>>(
+    fn draw_meshes(
         &mut self,
-        model: &'m M,
-        instances_offset: u32,
-        camera_bg: &'m wgpu::BindGroup,
+        model: &'m Model,
+        instances_range: Range<u32>,
         light_bg: &'m wgpu::BindGroup,
+        phase: DrawPhase,
+        selection: &DrawSelection,
     ) {
-        let model = model.as_ref();
-        let instances_end = instances_offset + 1; //model.instances_count();
-        self.set_pipeline(&model.pipeline);
-        self.set_bind_group(0, &camera_bg, &[]);
-        self.set_bind_group(1, &light_bg, &[]);
-        self.draw_meshes(model, instances_offset..instances_end);
+        let mut current_pipeline: Option<&Rc<NamedPipeline>> = None;
+        let mut packed_bound = false;
+
+        for (mesh_index, mesh) in drawn_geometries(model, phase, selection) {
+            let pipeline = model.geometry_pipeline(mesh_index);
+            // the pipelines share the camera bind group layout: group 0 stays bound when
+            // switching, the group 1 is either the light or the material (unlit pipelines). The
+            // model overrides follow the material group
+            if !current_pipeline.is_some_and(|current| Rc::ptr_eq(current, pipeline)) {
+                self.set_pipeline(pipeline);
+                packed_bound = false;
+                if pipeline.is_lit() {
+                    self.set_bind_group(1, light_bg, &[]);
+                }
+                if pipeline.needs_material() {
+                    let overrides = model
+                        .overrides
+                        .as_ref()
+                        .expect("model overrides not present during render");
+                    self.set_bind_group(pipeline.overrides_group(), overrides.bind_group(), &[]);
+                }
+                current_pipeline = Some(pipeline);
+            }
+            if pipeline.needs_material() {
+                let material = model
+                    .materials
+                    .get(mesh_index)
+                    .expect("mesh material not present during render");
+                if pipeline.uses_packed_materials() {
+                    // the packed buffer stays bound, only the material index changes
+                    let packed = material
+                        .packed()
+                        .expect("packed material not present during render");
+                    if !packed_bound {
+                        self.set_bind_group(pipeline.material_group(), packed.bind_group(), &[]);
+                        packed_bound = true;
+                    }
+                    self.set_vertex_buffer(2, packed.index_slice());
+                } else {
+                    self.set_bind_group(pipeline.material_group(), material, &[]);
+                }
+            }
+            self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            self.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+            self.draw_indexed(0..mesh.num_elements, 0, instances_range.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::util::DeviceExt;
+
+    use crate::render::{
+        config::geometry::GeometryVertices,
+        scene::{
+            camera::{CameraSystem, CameraUniform},
+            mesh::MeshBuf,
+            pipeline::create_outline_pipeline,
+            vertex::ModelVertex,
+        },
+        state::tests::headless_device,
+    };
+
+    use super::*;
+
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    fn target(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Model of a triangle geometry per name, drawn by an unlit pipeline without material: the
+    /// light group is not bound
+    fn triangles_model(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        geometries: &[&str],
+    ) -> Model {
+        let pipeline = NamedPipeline::new(
+            "single",
+            create_outline_pipeline(device, config, camera_bgl, DEPTH_FORMAT, 1),
+            vec![],
+        )
+        .unlit();
+        let vertex = |position| ModelVertex {
+            position,
+            tex_coords: [0.0; 2],
+            normal: [0.0, 0.0, 1.0],
+            tangent: [1.0, 0.0, 0.0],
+            bitangent: [0.0, 1.0, 0.0],
+        };
+        let geometries = geometries
+            .iter()
+            .map(|name| {
+                GeometryVertices::new(
+                    name,
+                    vec![
+                        vertex([0.0, 0.0, 0.0]),
+                        vertex([1.0, 0.0, 0.0]),
+                        vertex([0.0, 1.0, 0.0]),
+                    ],
+                    vec![0, 1, 2],
+                )
+                .to_wgpu_geometry_buffer(device)
+                .unwrap()
+            })
+            .collect();
+        let mesh = MeshBuf {
+            name: "triangles".to_string(),
+            geometries,
+            lods: vec![],
+            source_modified: None,
+        };
+        Model::new("triangles".to_string(), Rc::new(pipeline), Rc::new(mesh))
+    }
+
+    #[test]
+    fn draw_single_model() {
+        let (device, queue) = match headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: FORMAT,
+            width: 4,
+            height: 4,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &[0; std::mem::size_of::<CameraUniform>()],
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: None,
+        });
+        let model = triangles_model(&device, &config, &camera_bgl, &["triangle"]);
+        let buffer = ModelInstances::new(&device, "triangle", 1);
+        let instance = InstanceRaw {
+            model: cgmath::Matrix4::from_scale(1.0).into(),
+            normal: cgmath::Matrix3::from_scale(1.0).into(),
+        };
+
+        let color = target(&device, FORMAT);
+        let depth = target(&device, DEPTH_FORMAT);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &color,
+                    resolve_target: None,
+                    ops: wgpu::Operations::default(),
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            render_pass.draw_single(&model, &instance, &buffer, &queue, &camera_bg, &camera_bg);
+        }
+        queue.submit(Some(encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+        assert_eq!(buffer.count(), 1);
+    }
+
+    #[test]
+    fn isolated_geometry_is_drawn_alone() {
+        let (device, _queue) = match headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: FORMAT,
+            width: 4,
+            height: 4,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        let model = triangles_model(&device, &config, &camera_bgl, &["hull", "deck", "mast"]);
+        let draws = |selection: &DrawSelection| -> Vec<(usize, String)> {
+            drawn_geometries(&model, DrawPhase::All, selection)
+                .map(|(index, geometry)| (index, geometry.name.clone()))
+                .collect()
+        };
+        assert_eq!(draws(&DrawSelection::default()).len(), 3);
+
+        let isolated = |model: &str| DrawSelection {
+            isolated: Some(GeometryFilter {
+                model: ModelName::from(model),
+                geometry: GeometryName::from("deck"),
+            }),
+            ..Default::default()
+        };
+        // a single draw, with the material and pipeline of the geometry index
+        assert_eq!(draws(&isolated("triangles")), [(1, "deck".to_string())]);
+        // the other models are drawn whole
+        assert_eq!(draws(&isolated("zodiac")).len(), 3);
     }
 }
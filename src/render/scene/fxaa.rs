@@ -0,0 +1,215 @@
+use wgpu::util::DeviceExt;
+
+use super::{
+    pipeline::create_fxaa_pipeline,
+    texture::{RenderTargetFormat, Texture},
+};
+
+/// Minimum local contrast, relative to the brightest neighbour, of the smoothed pixels
+const EDGE_THRESHOLD: f32 = 0.125;
+/// Maximum blur length along the edges (texels)
+const SPAN_MAX: f32 = 8.0;
+/// Contributions of the red, green and blue channels to the luma the edges are detected on
+/// (Rec. 601): summing to 1, the green one weighing most as the eye is most sensitive to it
+const LUMA_WEIGHTS: [f32; 3] = [0.299, 0.587, 0.114];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FxaaUniform {
+    texel_size: [f32; 2],
+    edge_threshold: f32,
+    span_max: f32,
+    /// w unused
+    luma_weights: [f32; 4],
+}
+
+impl FxaaUniform {
+    fn new(config: &wgpu::SurfaceConfiguration) -> Self {
+        Self {
+            texel_size: [
+                1.0 / config.width.max(1) as f32,
+                1.0 / config.height.max(1) as f32,
+            ],
+            edge_threshold: EDGE_THRESHOLD,
+            span_max: SPAN_MAX,
+            luma_weights: [LUMA_WEIGHTS[0], LUMA_WEIGHTS[1], LUMA_WEIGHTS[2], 0.0],
+        }
+    }
+}
+
+/// Screen space anti-aliasing, cheaper than multisampling.
+///
+/// The scene is drawn into the pass offscreen target (see `target_view()`), then smoothed into
+/// the output view by a fullscreen triangle.
+pub struct FxaaPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    target: Texture,
+}
+
+impl FxaaPass {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fxaa.uniform_buffer"),
+            contents: bytemuck::cast_slice(&[FxaaUniform::new(config)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("fxaa.bind_group_layout"),
+        });
+        let target = Self::create_target(device, config);
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, &uniform_buffer, &target);
+        let pipeline = create_fxaa_pipeline(device, config, &bind_group_layout);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            target,
+        }
+    }
+
+    fn create_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Texture {
+        Texture::create_render_target(
+            device,
+            "fxaa.target",
+            config.width,
+            config.height,
+            // the scene pipelines draw into it with the surface format
+            RenderTargetFormat::Output.format(config.format),
+        )
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        target: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(target.sampler()),
+                },
+            ],
+            label: Some("fxaa.bind_group"),
+        })
+    }
+
+    /// The view the scene has to be drawn into
+    pub fn target_view(&self) -> &wgpu::TextureView {
+        &self.target.view
+    }
+
+    /// Recreate the offscreen target at the new surface size
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+    ) {
+        self.target = Self::create_target(device, config);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.target,
+        );
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[FxaaUniform::new(config)]),
+        );
+    }
+
+    /// Draw the smoothed scene over the whole output view
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, output_view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("FXAA pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Luma of a linear color, as computed by the shader
+    fn luma(uniform: &FxaaUniform, color: [f32; 3]) -> f32 {
+        let w = uniform.luma_weights;
+        (color[0] * w[0] + color[1] * w[1] + color[2] * w[2]).sqrt()
+    }
+
+    #[test]
+    fn luma_weights_favour_green() {
+        let uniform = FxaaUniform::new(&wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 800,
+            height: 600,
+            present_mode: wgpu::PresentMode::Fifo,
+        });
+        // white is the brightest, black the darkest
+        assert!((luma(&uniform, [1.0, 1.0, 1.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(luma(&uniform, [0.0, 0.0, 0.0]), 0.0);
+        let [red, green, blue] =
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].map(|c| luma(&uniform, c));
+        assert!(green > red && red > blue);
+        // a pure red and pure blue edge is contrasted enough to be smoothed
+        assert!(red - blue >= red * EDGE_THRESHOLD);
+    }
+}
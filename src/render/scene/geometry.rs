@@ -1,15 +1,23 @@
+use super::bounds::{BoundingBox, BoundingSphere};
+
 /// # Grouped buffers for indexed vertices of a geometry (simple mesh raw buffers)
 ///
 /// - *name*: a convenient value to identify this geometry's buffers. Only accessible from name()
 /// function as a typed GeometryName.
 /// - *vertex_buffer*: wgpu::Buffer of vertices
 /// - *index_buffer*: wgpu::Buffer of indexes to make faces
+/// - *index_format*: 16 bits indexes for the small geometries, 32 bits otherwise
 /// - *num_elements*: vertices count
+/// - *bounding_sphere*: enclosing the vertices (model space)
+/// - *bounding_box*: axis aligned, enclosing the vertices (model space)
 ///.
 #[derive(Debug)]
 pub struct GeometryBuf {
     pub(crate) name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
     pub num_elements: u32,
+    pub bounding_sphere: BoundingSphere,
+    pub bounding_box: BoundingBox,
 }
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use wgpu::util::DeviceExt;
+
+/// Frame timing shared by the shaders
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlobalUniform {
+    /// scene time in seconds, the sum of the frames `delta_time`
+    pub time: f32,
+    pub delta_time: f32,
+    /// count of the frames rendered, the current one included
+    pub frame: u32,
+    _padding: u32,
+}
+
+impl GlobalUniform {
+    /// Move on to the next frame, rendered `dt` after the previous one
+    pub fn advance(&mut self, dt: Duration) {
+        self.frame = self.frame.wrapping_add(1);
+        self.delta_time = dt.as_secs_f32();
+        self.time += self.delta_time;
+    }
+}
+
+/// Globals bind group, the single time source of the scene.
+///
+/// The pipelines reading it add its layout after their own groups: the group 3 for the models
+/// pipelines (after the material), the group 1 for the particles update.
+pub struct GlobalSystem {
+    uniform: GlobalUniform,
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl GlobalSystem {
+    pub fn init(device: &wgpu::Device) -> (wgpu::BindGroupLayout, GlobalSystem) {
+        let uniform = GlobalUniform::default();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("globals.buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX
+                    | wgpu::ShaderStages::FRAGMENT
+                    | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("globals.bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("globals.bind_group"),
+        });
+
+        (
+            bind_group_layout,
+            Self {
+                uniform,
+                buffer,
+                bind_group,
+            },
+        )
+    }
+
+    /// Scene time in seconds
+    pub fn time(&self) -> f32 {
+        self.uniform.time
+    }
+
+    /// Advance the time of `dt` and upload it
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: Duration) {
+        self.uniform.advance(dt);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_is_the_sum_of_the_frames_durations() {
+        let mut uniform = GlobalUniform::default();
+        let frames = [250, 500, 125, 0].map(Duration::from_millis);
+        for dt in frames {
+            uniform.advance(dt);
+        }
+        let total: Duration = frames.iter().sum();
+        assert_eq!(uniform.time, total.as_secs_f32());
+        assert_eq!(uniform.delta_time, 0.0);
+        assert_eq!(uniform.frame, 4);
+    }
+}
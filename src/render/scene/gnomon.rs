@@ -0,0 +1,158 @@
+use wgpu::util::DeviceExt;
+
+use crate::app::config::{Corner, GnomonConfig};
+
+use super::{
+    camera::{rotation_only, OPENGL_TO_WGPU_MATRIX},
+    pipeline::create_gnomon_pipeline,
+};
+
+/// Distance between the indicator and the window borders (pixels)
+const MARGIN: u32 = 10;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GnomonVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl GnomonVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GnomonVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// X (red), Y (green) and Z (blue) axes, as a lines list
+#[rustfmt::skip]
+const AXES: [GnomonVertex; 6] = [
+    GnomonVertex { position: [0.0, 0.0, 0.0], color: [1.0, 0.0, 0.0] },
+    GnomonVertex { position: [0.8, 0.0, 0.0], color: [1.0, 0.0, 0.0] },
+    GnomonVertex { position: [0.0, 0.0, 0.0], color: [0.0, 1.0, 0.0] },
+    GnomonVertex { position: [0.0, 0.8, 0.0], color: [0.0, 1.0, 0.0] },
+    GnomonVertex { position: [0.0, 0.0, 0.0], color: [0.0, 0.0, 1.0] },
+    GnomonVertex { position: [0.0, 0.0, 0.8], color: [0.0, 0.0, 1.0] },
+];
+
+/// Axes indicator drawn in a window corner, rotating with the camera
+pub struct GnomonPass {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// indicator viewport side (pixels)
+    size: u32,
+    corner: Corner,
+}
+
+impl GnomonPass {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_config: &wgpu::SurfaceConfiguration,
+        config: &GnomonConfig,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("gnomon.vertex_buffer"),
+            contents: bytemuck::cast_slice(&AXES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let transform: [[f32; 4]; 4] = cgmath::Matrix4::from_scale(1.0).into();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("gnomon.uniform_buffer"),
+            contents: bytemuck::cast_slice(&[transform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("gnomon.bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("gnomon.bind_group"),
+        });
+
+        Self {
+            pipeline: create_gnomon_pipeline(device, surface_config, &bind_group_layout),
+            vertex_buffer,
+            uniform_buffer,
+            bind_group,
+            size: config.size,
+            corner: config.corner,
+        }
+    }
+
+    /// Orient the axes with the camera view
+    pub fn update(&self, queue: &wgpu::Queue, view: cgmath::Matrix4<f32>) {
+        // the axes fit in the unit cube: no perspective, nor clipping
+        let projection = OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let transform: [[f32; 4]; 4] = (projection * rotation_only(view)).into();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[transform]));
+    }
+
+    /// Draw the axes over the output view, in the configured corner
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        config: &wgpu::SurfaceConfiguration,
+    ) {
+        let size = self.size.min(config.width).min(config.height);
+        if size == 0 {
+            return;
+        }
+        let right = config.width.saturating_sub(size + MARGIN);
+        let bottom = config.height.saturating_sub(size + MARGIN);
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (MARGIN.min(right), MARGIN.min(bottom)),
+            Corner::TopRight => (right, MARGIN.min(bottom)),
+            Corner::BottomLeft => (MARGIN.min(right), bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gnomon pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_viewport(x as f32, y as f32, size as f32, size as f32, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..AXES.len() as u32, 0..1);
+    }
+}
@@ -1,7 +1,10 @@
-use std::marker::PhantomData;
+use std::{cell::Cell, marker::PhantomData, ops::Range};
 
+use serde::{Deserialize, Serialize};
 use wgpu::BufferAddress;
 
+use super::model::Model;
+
 pub trait RawInstanceTrait: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable {}
 
 #[repr(C)]
@@ -63,24 +66,122 @@ impl InstanceRaw {
     }
 }
 
-pub struct InstancesSystem<I>
+/// How often the instances of an object are written
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstanceUsage {
+    /// written when the static objects change (spawned, moved, hidden...), never culled
+    Static,
+    /// written every frame, culled out of the camera view
+    #[default]
+    Dynamic,
+}
+
+/// An object drawn as an instance of a model
+pub trait Instanced<I> {
+    fn model_name(&self) -> &str;
+    fn usage(&self) -> InstanceUsage;
+    fn instance(&self) -> I;
+}
+
+/// Instances of the objects grouped by model name, to write on an update
+#[derive(Debug)]
+pub struct InstancesRanges<I> {
+    /// `None` if the static objects did not change since the last update
+    pub statics: Option<Vec<(String, Vec<I>)>>,
+    pub dynamics: Vec<(String, Vec<I>)>,
+}
+
+/// Tracks the changes of the static objects, so that their instances are gathered (and
+/// written) only after they changed
+#[derive(Debug)]
+pub struct StaticsTracker {
+    dirty: bool,
+}
+
+impl Default for StaticsTracker {
+    fn default() -> Self {
+        Self { dirty: true }
+    }
+}
+
+impl StaticsTracker {
+    /// The static objects changed (spawned, moved, hidden...)
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Some objects changed (spawned, moved, removed...): only the static ones invalidate
+    pub fn invalidate_for<'o, T, I>(&mut self, changed: impl IntoIterator<Item = &'o T>)
+    where
+        T: Instanced<I> + 'o,
+    {
+        if changed
+            .into_iter()
+            .any(|object| object.usage() == InstanceUsage::Static)
+        {
+            self.invalidate();
+        }
+    }
+
+    /// Group the instances of the objects by model. The static objects are gathered if they
+    /// changed since the last call, all of them; the dynamic ones if `visible`.
+    pub fn gather<'o, T, I>(
+        &mut self,
+        objects: impl IntoIterator<Item = &'o T>,
+        visible: impl Fn(&T) -> bool,
+    ) -> InstancesRanges<I>
+    where
+        T: Instanced<I> + 'o,
+    {
+        let dirty = std::mem::take(&mut self.dirty);
+        let mut statics = vec![];
+        let mut dynamics = vec![];
+        for object in objects {
+            let ranges = match object.usage() {
+                InstanceUsage::Static if dirty => &mut statics,
+                InstanceUsage::Static => continue,
+                InstanceUsage::Dynamic if visible(object) => &mut dynamics,
+                InstanceUsage::Dynamic => continue,
+            };
+            push_instance(ranges, object.model_name(), object.instance());
+        }
+        InstancesRanges {
+            statics: dirty.then_some(statics),
+            dynamics,
+        }
+    }
+}
+
+fn push_instance<I>(ranges: &mut Vec<(String, Vec<I>)>, model_name: &str, instance: I) {
+    match ranges.iter_mut().find(|(name, _)| name == model_name) {
+        Some((_, instances)) => instances.push(instance),
+        None => ranges.push((model_name.to_string(), vec![instance])),
+    }
+}
+
+/// Instances buffer shared by the models.
+///
+/// Each model owns a named range of contiguous instances in the buffer, bound as its own
+/// slice so that the models instances counts are independent.
+struct SharedInstances<I>
 where
     I: RawInstanceTrait,
 {
     buffer: wgpu::Buffer,
     instances_count: u32,
+    ranges: Vec<(String, Range<u32>)>,
     _phamtom: PhantomData<I>,
 }
 
 const MAX_INSTANCES: u64 = 10;
 
-impl<I> InstancesSystem<I>
+impl<I> SharedInstances<I>
 where
     I: RawInstanceTrait + std::fmt::Debug,
 {
-    pub fn new(device: &wgpu::Device) -> Self {
+    fn new(device: &wgpu::Device, label: &str) -> Self {
         let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("instances buffer"),
+            label: Some(label),
             size: MAX_INSTANCES * std::mem::size_of::<I>() as BufferAddress,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
@@ -91,21 +192,266 @@ where
         Self {
             buffer,
             instances_count,
+            ranges: vec![],
             _phamtom: PhantomData,
         }
     }
 
-    pub fn buffer(&self) -> &wgpu::Buffer {
-        &self.buffer
+    /// Write the instances of each range one after the other.
+    /// The instances exceeding the buffer capacity are dropped.
+    fn set_ranges(&mut self, ranges: Vec<(String, Vec<I>)>, queue: &wgpu::Queue) {
+        let (instances, ranges) = pack_ranges(ranges, MAX_INSTANCES as usize);
+        self.ranges = ranges;
+        self.instances_count = instances.len() as u32;
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    /// Instances indices of the named range
+    fn range(&self, name: &str) -> Option<Range<u32>> {
+        self.ranges
+            .iter()
+            .find(|(range_name, _)| range_name == name)
+            .map(|(_, range)| range.clone())
+    }
+
+    /// Buffer slice holding the instances of the named range and their count
+    fn slice(&self, name: &str) -> Option<(wgpu::BufferSlice<'_>, u32)> {
+        let stride = std::mem::size_of::<I>() as BufferAddress;
+        self.range(name)
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                let bytes =
+                    range.start as BufferAddress * stride..range.end as BufferAddress * stride;
+                (self.buffer.slice(bytes), range.end - range.start)
+            })
+    }
+}
+
+/// Lay the instances of each range one after the other, up to `capacity` instances.
+/// Returns the instances and the indices range of each name.
+fn pack_ranges<I>(
+    ranges: Vec<(String, Vec<I>)>,
+    capacity: usize,
+) -> (Vec<I>, Vec<(String, Range<u32>)>) {
+    let mut instances = vec![];
+    let mut packed = vec![];
+    for (name, range_instances) in ranges {
+        let start = instances.len();
+        let available = capacity - start;
+        if range_instances.len() > available {
+            log::warn!("Instances buffer full, dropping instances of {}", name);
+        }
+        instances.extend(range_instances.into_iter().take(available));
+        packed.push((name, start as u32..instances.len() as u32));
+    }
+    (instances, packed)
+}
+
+/// Instances buffers shared by the models: one written when the static objects change, one
+/// rewritten every frame (see `InstanceUsage`).
+///
+/// A model may have instances in both buffers: it is drawn once per buffer.
+pub struct InstancesSystem<I>
+where
+    I: RawInstanceTrait,
+{
+    statics: SharedInstances<I>,
+    dynamics: SharedInstances<I>,
+}
+
+impl<I> InstancesSystem<I>
+where
+    I: RawInstanceTrait + std::fmt::Debug,
+{
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            statics: SharedInstances::new(device, "static instances buffer"),
+            dynamics: SharedInstances::new(device, "instances buffer"),
+        }
     }
 
+    /// Count of the static and dynamic instances
     pub fn count(&self) -> u32 {
-        self.instances_count
+        self.statics.instances_count + self.dynamics.instances_count
     }
 
-    pub fn set_instances_raw(&mut self, mut instances: Vec<I>, queue: &wgpu::Queue) {
-        instances.truncate(MAX_INSTANCES as usize);
-        self.instances_count = instances.len() as u32;
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&instances));
+    /// Write the dynamic instances of each range one after the other.
+    /// The instances exceeding the buffer capacity are dropped.
+    pub fn set_instances_ranges(&mut self, ranges: Vec<(String, Vec<I>)>, queue: &wgpu::Queue) {
+        self.dynamics.set_ranges(ranges, queue);
+    }
+
+    /// Write the static instances of each range one after the other, they are kept until the
+    /// next call.
+    /// The instances exceeding the buffer capacity are dropped.
+    pub fn set_static_instances_ranges(
+        &mut self,
+        ranges: Vec<(String, Vec<I>)>,
+        queue: &wgpu::Queue,
+    ) {
+        self.statics.set_ranges(ranges, queue);
+    }
+
+    /// Buffer slices holding the instances of the model and their counts: its dedicated
+    /// buffer if it has one, else its static then dynamic ranges of the shared buffers
+    pub fn model_slices<'a>(&'a self, model: &'a Model) -> Vec<(wgpu::BufferSlice<'a>, u32)> {
+        match &model.instances {
+            Some(instances) => instances.slice().into_iter().collect(),
+            None => self
+                .statics
+                .slice(&model.name)
+                .into_iter()
+                .chain(self.dynamics.slice(&model.name))
+                .collect(),
+        }
+    }
+}
+
+/// Instances buffer dedicated to a single model, sized by the `max_instances` of its descriptor.
+#[derive(Debug)]
+pub struct ModelInstances {
+    buffer: wgpu::Buffer,
+    capacity: u32,
+    count: Cell<u32>,
+}
+
+impl ModelInstances {
+    pub fn new(device: &wgpu::Device, model_name: &str, capacity: u32) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} instances buffer", model_name)),
+            size: capacity as BufferAddress * std::mem::size_of::<InstanceRaw>() as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            capacity,
+            count: Cell::new(0),
+        }
+    }
+
+    /// Maximum count of instances of the buffer
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count.get()
+    }
+
+    /// Write the model instances, the ones exceeding the buffer capacity are dropped
+    pub fn set_instances(&self, instances: &[InstanceRaw], queue: &wgpu::Queue) {
+        if instances.len() > self.capacity as usize {
+            log::warn!(
+                "Model instances buffer full, dropping {} instances",
+                instances.len() - self.capacity as usize
+            );
+        }
+        let instances = &instances[..instances.len().min(self.capacity as usize)];
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        self.count.set(instances.len() as u32);
+    }
+
+    /// Buffer slice holding the written instances and their count
+    pub fn slice(&self) -> Option<(wgpu::BufferSlice<'_>, u32)> {
+        let count = self.count();
+        let size = count as BufferAddress * std::mem::size_of::<InstanceRaw>() as BufferAddress;
+        (count > 0).then(|| (self.buffer.slice(..size), count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dummy {
+        model: &'static str,
+        usage: InstanceUsage,
+        id: u32,
+    }
+
+    impl Instanced<u32> for Dummy {
+        fn model_name(&self) -> &str {
+            self.model
+        }
+
+        fn usage(&self) -> InstanceUsage {
+            self.usage
+        }
+
+        fn instance(&self) -> u32 {
+            self.id
+        }
+    }
+
+    fn objects() -> Vec<Dummy> {
+        let dummy = |model, usage, id| Dummy { model, usage, id };
+        vec![
+            dummy("rock", InstanceUsage::Static, 0),
+            dummy("ship", InstanceUsage::Dynamic, 1),
+            dummy("rock", InstanceUsage::Static, 2),
+            dummy("ship", InstanceUsage::Dynamic, 3),
+        ]
+    }
+
+    #[test]
+    fn static_instances_are_gathered_once() {
+        let objects = objects();
+        let mut tracker = StaticsTracker::default();
+
+        let first = tracker.gather(&objects, |_| true);
+        assert_eq!(first.statics, Some(vec![("rock".to_string(), vec![0, 2])]));
+        assert_eq!(first.dynamics, vec![("ship".to_string(), vec![1, 3])]);
+
+        let second = tracker.gather(&objects, |_| true);
+        assert_eq!(second.statics, None);
+        assert_eq!(second.dynamics, first.dynamics);
+
+        tracker.invalidate();
+        let invalidated = tracker.gather(&objects, |_| true);
+        assert_eq!(invalidated.statics, first.statics);
+    }
+
+    #[test]
+    fn only_static_changes_invalidate() {
+        let objects = objects();
+        let mut tracker = StaticsTracker::default();
+        tracker.gather(&objects, |_| true);
+
+        tracker.invalidate_for(objects.iter().filter(|object| object.model == "ship"));
+        assert_eq!(tracker.gather(&objects, |_| true).statics, None);
+
+        tracker.invalidate_for(objects.iter().filter(|object| object.id == 2));
+        assert!(tracker.gather(&objects, |_| true).statics.is_some());
+    }
+
+    #[test]
+    fn only_dynamic_instances_are_culled() {
+        let objects = objects();
+        let ranges = StaticsTracker::default().gather(&objects, |object| object.id == 3);
+        assert_eq!(ranges.statics, Some(vec![("rock".to_string(), vec![0, 2])]));
+        assert_eq!(ranges.dynamics, vec![("ship".to_string(), vec![3])]);
+    }
+
+    #[test]
+    fn models_get_distinct_ranges() {
+        let ranges = vec![
+            ("rock".to_string(), vec![0, 2]),
+            ("ship".to_string(), vec![1, 3, 5]),
+        ];
+        let (instances, packed) = pack_ranges(ranges.clone(), 10);
+        assert_eq!(instances, vec![0, 2, 1, 3, 5]);
+        assert_eq!(
+            packed,
+            vec![("rock".to_string(), 0..2), ("ship".to_string(), 2..5)]
+        );
+
+        // the instances over the capacity are dropped, from the last ranges
+        let (instances, packed) = pack_ranges(ranges, 3);
+        assert_eq!(instances, vec![0, 2, 1]);
+        assert_eq!(
+            packed,
+            vec![("rock".to_string(), 0..2), ("ship".to_string(), 2..3)]
+        );
     }
 }
@@ -9,9 +9,31 @@ pub struct LightUniform {
     _padding: u32,
     color: [f32; 3],
     _padding2: u32,
+    /// constant, linear and quadratic terms: `1 / (c + l*d + q*d*d)`
+    attenuation: [f32; 3],
+    _padding3: u32,
+}
+
+impl LightUniform {
+    /// Set the distance attenuation terms.
+    /// (1, 0, 0) means no attenuation.
+    pub fn set_attenuation(&mut self, constant: f32, linear: f32, quadratic: f32) {
+        self.attenuation = [constant, linear, quadratic];
+    }
 }
 
 impl LightUniformTrait for LightUniform {
+    fn source(&self) -> LightSource {
+        LightSource {
+            position: self.position,
+            color: self.color,
+        }
+    }
+
+    fn at(self, position: [f32; 3]) -> Self {
+        Self { position, ..self }
+    }
+
     fn on_update(self, dt: Duration) -> Self {
         let position: cgmath::Vector3<_> = self.position.into();
         Self {
@@ -23,15 +45,38 @@ impl LightUniformTrait for LightUniform {
             _padding: 0,
             color: self.color,
             _padding2: 0,
+            attenuation: self.attenuation,
+            _padding3: 0,
         }
     }
 }
 
 /// has to be #[repr(C)]
 pub trait LightUniformTrait: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable {
+    fn source(&self) -> LightSource;
+    /// The same light moved to `position`
+    fn at(self, position: [f32; 3]) -> Self;
     fn on_update(self, dt: Duration) -> Self;
 }
 
+/// Placement of the light at each update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightMode {
+    /// circles around the vertical axis
+    #[default]
+    Orbit,
+    /// follows the camera, lighting what it looks at (dark models inspection). The orbit is
+    /// suspended meanwhile
+    Headlight,
+}
+
+/// Position and color of a light (debug gizmos)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSource {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
 impl Default for LightUniform {
     fn default() -> Self {
         Self {
@@ -39,17 +84,158 @@ impl Default for LightUniform {
             _padding: 0,
             color: [1.0, 1.0, 1.0],
             _padding2: 0,
+            attenuation: [1.0, 0.0, 0.0],
+            _padding3: 0,
+        }
+    }
+}
+
+/// Maximum spot lights count the shaders are able to process
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+/// A light emitting in a cone (dock floodlight...)
+///
+/// The light is full between the direction and the inner angle,
+/// fades out between the inner and the outer angle and is null beyond.
+/// It is also attenuated with the distance until reaching zero at `range`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpotLightUniform {
+    position: [f32; 3],
+    inner_cos: f32,
+    direction: [f32; 3],
+    outer_cos: f32,
+    color: [f32; 3],
+    range: f32,
+}
+
+impl SpotLightUniform {
+    /// Build a spot light from its cone half angles (inner <= outer)
+    pub fn new<A: Into<cgmath::Rad<f32>>>(
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        inner_angle: A,
+        outer_angle: A,
+        range: f32,
+    ) -> Self {
+        let inner_angle: cgmath::Rad<f32> = inner_angle.into();
+        let outer_angle: cgmath::Rad<f32> = outer_angle.into();
+        let inner_cos = inner_angle.cos();
+        let outer_cos = outer_angle.cos();
+        Self {
+            position,
+            inner_cos,
+            direction: cgmath::Vector3::from(direction).normalize().into(),
+            // keep the fade interval valid even if angles are given in the wrong order
+            outer_cos: outer_cos.min(inner_cos),
+            color,
+            range,
+        }
+    }
+
+    /// The same spot light, emitting no light
+    pub fn switched_off(self) -> Self {
+        Self {
+            color: [0.0; 3],
+            ..self
+        }
+    }
+}
+
+/// Fixed size spot lights array as expected by the shaders
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpotLightsUniform {
+    lights: [SpotLightUniform; MAX_SPOT_LIGHTS],
+    count: u32,
+    _padding: [u32; 3],
+}
+
+impl SpotLightsUniform {
+    fn add(&mut self, spot_light: SpotLightUniform) -> Option<usize> {
+        let index = self.count as usize;
+        if index >= MAX_SPOT_LIGHTS {
+            log::warn!(
+                "Cannot add spot light: max count ({}) reached",
+                MAX_SPOT_LIGHTS
+            );
+            return None;
+        }
+        self.lights[index] = spot_light;
+        self.count += 1;
+        Some(index)
+    }
+
+    fn set(&mut self, index: usize, spot_light: SpotLightUniform) -> bool {
+        match self.lights[..self.count as usize].get_mut(index) {
+            Some(spot) => {
+                *spot = spot_light;
+                true
+            }
+            None => false,
         }
     }
 }
 
+/// The light of the mode: the orbiting light advanced, or moved to the camera position
+fn place_light<U: LightUniformTrait>(
+    mode: LightMode,
+    orbit: &mut U,
+    dt: Duration,
+    camera_position: [f32; 3],
+) -> U {
+    match mode {
+        LightMode::Orbit => {
+            *orbit = orbit.on_update(dt);
+            *orbit
+        }
+        LightMode::Headlight => orbit.at(camera_position),
+    }
+}
+
 // TODO: Sun light system
 pub struct LightSystem<U: LightUniformTrait> {
+    /// the orbiting light
     light_uniform: U,
+    mode: LightMode,
+    /// the light of the last update, as written to the buffer
+    current: U,
     light_buffer: wgpu::Buffer,
+    spot_lights: SpotLightsUniform,
+    spot_lights_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
+/// Layout of the light group (1) of the lit scene pipelines: the light and the spot lights
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+        label: None,
+    })
+}
+
 impl<U> LightSystem<U>
 where
     U: LightUniformTrait,
@@ -63,26 +249,27 @@ where
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: None,
+        let spot_lights: SpotLightsUniform = bytemuck::Zeroable::zeroed();
+        let spot_lights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Spot lights uniform buffer"),
+            contents: bytemuck::cast_slice(&[spot_lights]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let bind_group_layout = bind_group_layout(device);
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: spot_lights_buffer.as_entire_binding(),
+                },
+            ],
             label: None,
         });
 
@@ -90,19 +277,145 @@ where
             bind_group_layout,
             Self {
                 light_uniform,
+                mode: LightMode::default(),
+                current: light_uniform,
                 light_buffer,
+                spot_lights,
+                spot_lights_buffer,
                 bind_group,
             },
         )
     }
 
-    pub fn update(&mut self, queue: &wgpu::Queue, dt: Duration) {
-        self.light_uniform = self.light_uniform.on_update(dt);
+    /// Add a spot light and return its index.
+    /// Return None if the shaders spot lights capacity (MAX_SPOT_LIGHTS) is reached.
+    pub fn add_spot_light(&mut self, spot_light: SpotLightUniform) -> Option<usize> {
+        self.spot_lights.add(spot_light)
+    }
 
+    /// Replace a previously added spot light.
+    /// Return false if no spot light exists at this index.
+    pub fn set_spot_light(&mut self, index: usize, spot_light: SpotLightUniform) -> bool {
+        self.spot_lights.set(index, spot_light)
+    }
+
+    /// The light then the spot lights, at their current position
+    pub fn sources(&self) -> Vec<LightSource> {
+        let spot_lights = self.spot_lights.lights[..self.spot_lights.count as usize]
+            .iter()
+            .map(|spot| LightSource {
+                position: spot.position,
+                color: spot.color,
+            });
+        std::iter::once(self.current.source())
+            .chain(spot_lights)
+            .collect()
+    }
+
+    pub fn toggle_headlight(&mut self) {
+        self.mode = match self.mode {
+            LightMode::Orbit => LightMode::Headlight,
+            LightMode::Headlight => LightMode::Orbit,
+        };
+    }
+
+    /// Place the light for the mode, `camera_position` being the one of this frame
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: Duration, camera_position: [f32; 3]) {
+        self.current = place_light(self.mode, &mut self.light_uniform, dt, camera_position);
+
+        queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.current]));
         queue.write_buffer(
-            &self.light_buffer,
+            &self.spot_lights_buffer,
             0,
-            bytemuck::cast_slice(&[self.light_uniform]),
+            bytemuck::cast_slice(&[self.spot_lights]),
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spot light intensity at `point`, as computed by the shaders (cone then range falloff)
+    fn spot_intensity(spot: &SpotLightUniform, point: [f32; 3]) -> f32 {
+        let to_spot = cgmath::Vector3::from(spot.position) - cgmath::Vector3::from(point);
+        let distance = to_spot.magnitude();
+        let spot_dir = to_spot / distance;
+        let theta = (-spot_dir).dot(spot.direction.into());
+        let cone = ((theta - spot.outer_cos) / (spot.inner_cos - spot.outer_cos).max(0.0001))
+            .clamp(0.0, 1.0);
+        let range = (1.0 - distance / spot.range).clamp(0.0, 1.0);
+        cone * range
+    }
+
+    fn floodlight() -> SpotLightUniform {
+        SpotLightUniform::new(
+            [0.0, 30.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [1.0, 0.9, 0.7],
+            cgmath::Deg(15.0),
+            cgmath::Deg(25.0),
+            60.0,
+        )
+    }
+
+    #[test]
+    fn point_outside_the_cone_gets_no_spot_light() {
+        let spot = floodlight();
+        // right below, then within the fade interval
+        assert!(spot_intensity(&spot, [0.0, 0.0, 0.0]) > 0.0);
+        let fading = spot_intensity(&spot, [30.0 * 20f32.to_radians().tan(), 0.0, 0.0]);
+        assert!(fading > 0.0 && fading < spot_intensity(&spot, [0.0, 0.0, 0.0]));
+        // 45° off the direction, beyond the outer angle
+        assert_eq!(spot_intensity(&spot, [30.0, 0.0, 0.0]), 0.0);
+        // above the spot
+        assert_eq!(spot_intensity(&spot, [0.0, 40.0, 0.0]), 0.0);
+        // in the cone but out of range
+        assert_eq!(spot_intensity(&spot, [0.0, -40.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn only_added_spot_lights_are_replaced() {
+        let mut spot_lights: SpotLightsUniform = bytemuck::Zeroable::zeroed();
+        assert!(!spot_lights.set(0, floodlight()));
+        assert_eq!(spot_lights.add(floodlight()), Some(0));
+        assert!(spot_lights.set(0, floodlight().switched_off()));
+        assert_eq!(spot_lights.lights[0].color, [0.0; 3]);
+        assert_eq!(spot_lights.lights[0].position, floodlight().position);
+        assert!(!spot_lights.set(1, floodlight()));
+        for _ in 1..MAX_SPOT_LIGHTS {
+            assert!(spot_lights.add(floodlight()).is_some());
+        }
+        assert_eq!(spot_lights.add(floodlight()), None);
+    }
+
+    #[test]
+    fn headlight_is_at_the_camera_and_suspends_the_orbit() {
+        let mut orbit = LightUniform::default();
+        let camera_position = [3.0, 4.0, 5.0];
+        let dt = Duration::from_secs(1);
+
+        let light = place_light(LightMode::Headlight, &mut orbit, dt, camera_position);
+        assert_eq!(light.source().position, camera_position);
+        assert_eq!(
+            orbit.source().position,
+            LightUniform::default().source().position
+        );
+
+        let light = place_light(LightMode::Orbit, &mut orbit, dt, camera_position);
+        assert_ne!(light.source().position, camera_position);
+        assert_eq!(orbit.source().position, light.source().position);
+    }
+
+    #[test]
+    fn uniform_carries_the_attenuation_terms() {
+        let mut light = LightUniform::default();
+        light.set_attenuation(1.0, 0.09, 0.032);
+        let floats: &[f32] = bytemuck::cast_slice(std::slice::from_ref(&light));
+        // after the position and the color, each padded to 16 bytes
+        assert_eq!(&floats[8..11], &[1.0, 0.09, 0.032]);
+        // kept when the light moves
+        let moved = light.on_update(Duration::from_secs(1)).at([0.0, 5.0, 0.0]);
+        assert_eq!(moved.attenuation, [1.0, 0.09, 0.032]);
+    }
+}
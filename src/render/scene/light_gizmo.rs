@@ -0,0 +1,171 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use wgpu::util::DeviceExt;
+
+use crate::render::{
+    config::{assets::AssetDescriptor, assets::TryAsRef, mesh::MeshDescriptor, WgpuResourceLoader},
+    names::MeshName,
+    state::WgpuState,
+};
+
+use super::{
+    light::{LightSource, MAX_SPOT_LIGHTS},
+    mesh::MeshBuf,
+    pipeline::NamedPipeline,
+};
+
+/// Primitive mesh drawn at the lights positions
+const GIZMO_MESH: &str = "cube";
+/// The cube primitive is 2 units wide
+const GIZMO_SCALE: f32 = 0.25;
+/// The point light and the spot lights
+const MAX_GIZMOS: usize = 1 + MAX_SPOT_LIGHTS;
+
+/// A light gizmo instance: the transform of the mesh and the light color
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GizmoInstance {
+    model: [[f32; 4]; 4],
+    color: [f32; 4],
+}
+
+impl GizmoInstance {
+    pub fn new(source: &LightSource) -> Self {
+        let transform = cgmath::Matrix4::from_translation(source.position.into())
+            * cgmath::Matrix4::from_scale(GIZMO_SCALE);
+        let [r, g, b] = source.color;
+        Self {
+            model: transform.into(),
+            color: [r, g, b, 1.0],
+        }
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<GizmoInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            // after the ModelVertex locations
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Small cubes drawn at the lights positions, with their color, by the light pipeline
+pub struct LightGizmos {
+    pipeline: Rc<NamedPipeline>,
+    mesh: Rc<MeshBuf>,
+    instances: wgpu::Buffer,
+    count: u32,
+}
+
+impl LightGizmos {
+    /// Use the stored light pipeline and the cube primitive mesh
+    pub fn new(wgpu: &WgpuState) -> Result<Self> {
+        let pipeline = wgpu
+            .store
+            .get_pipeline("light_pipeline")?
+            .ok_or_else(|| anyhow!("light pipeline not found in store"))?;
+        let mesh = wgpu
+            .assets
+            .find(MeshName::from(GIZMO_MESH))
+            .ok_or_else(|| anyhow!("gizmo mesh {} not found in assets", GIZMO_MESH))
+            .and_then(|mesh: &AssetDescriptor| mesh.try_as_ref())
+            .and_then(|mesh: &MeshDescriptor| mesh.load(wgpu))?;
+        let empty: [GizmoInstance; MAX_GIZMOS] = bytemuck::Zeroable::zeroed();
+        let instances = wgpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("light_gizmos.instances"),
+                contents: bytemuck::cast_slice(&empty),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+        Ok(Self {
+            pipeline,
+            mesh,
+            instances,
+            count: 0,
+        })
+    }
+
+    /// Move the gizmos to the lights current positions
+    pub fn update(&mut self, queue: &wgpu::Queue, sources: &[LightSource]) {
+        let gizmos: Vec<GizmoInstance> = sources
+            .iter()
+            .take(MAX_GIZMOS)
+            .map(GizmoInstance::new)
+            .collect();
+        queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(&gizmos));
+        self.count = gizmos.len() as u32;
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bg: &'a wgpu::BindGroup,
+        light_bg: &'a wgpu::BindGroup,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass.set_bind_group(1, light_bg, &[]);
+        render_pass.set_vertex_buffer(1, self.instances.slice(..));
+        for geometry in &self.mesh.geometries {
+            render_pass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(geometry.index_buffer.slice(..), geometry.index_format);
+            render_pass.draw_indexed(0..geometry.num_elements, 0, 0..self.count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Transform;
+
+    use super::*;
+
+    #[test]
+    fn gizmo_is_a_small_cube_on_the_light() {
+        let gizmo = GizmoInstance::new(&LightSource {
+            position: [4.0, 10.0, -2.0],
+            color: [1.0, 0.5, 0.0],
+        });
+        assert_eq!(gizmo.color, [1.0, 0.5, 0.0, 1.0]);
+
+        let transform = cgmath::Matrix4::from(gizmo.model);
+        let center = transform.transform_point(cgmath::Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(center, cgmath::Point3::new(4.0, 10.0, -2.0));
+        // a corner of the 2 units wide cube primitive
+        let corner = transform.transform_point(cgmath::Point3::new(1.0, 1.0, 1.0));
+        assert_eq!(corner, cgmath::Point3::new(4.25, 10.25, -1.75));
+    }
+}
@@ -1,13 +1,17 @@
 use std::{fmt::Debug, ops::Deref};
 
 use anyhow::Result;
+use serde::Deserialize;
 
 use crate::render::error::MaterialError;
 
-#[derive(Clone, Copy, Hash, PartialEq, std::cmp::Eq, Debug)]
+use super::color_materials::PackedMaterial;
+
+#[derive(Clone, Copy, Hash, PartialEq, std::cmp::Eq, Debug, Deserialize)]
 pub enum MaterialKind {
     Texture,
     Color,
+    Unlit,
 }
 
 impl From<MaterialKind> for String {
@@ -15,6 +19,7 @@ impl From<MaterialKind> for String {
         match mk {
             MaterialKind::Texture => "MaterialKind::Texture".to_string(),
             MaterialKind::Color => "MaterialKind::Color".to_string(),
+            MaterialKind::Unlit => "MaterialKind::Unlit".to_string(),
         }
     }
 }
@@ -26,6 +31,7 @@ impl TryFrom<&str> for MaterialKind {
         match value {
             "MaterialKind::Texture" => Ok(MaterialKind::Texture),
             "MaterialKind::Color" => Ok(MaterialKind::Color),
+            "MaterialKind::Unlit" => Ok(MaterialKind::Unlit),
             input => Err(MaterialError::DeserialisationError {
                 type_to_deser: "MaterialKind".to_string(),
                 input: input.to_string(),
@@ -34,8 +40,39 @@ impl TryFrom<&str> for MaterialKind {
     }
 }
 
+/// How a material covers what is behind it
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    /// alpha tested: the fragments less opaque than the threshold are discarded (cutouts)
+    Mask(f32),
+    /// alpha blended: drawn after the opaque geometries, back to front, with a transparent
+    /// pipeline
+    Blend,
+}
+
+impl BlendMode {
+    /// Alpha under which the fragments are discarded, 0 when nothing is
+    pub fn alpha_cutoff(&self) -> f32 {
+        match self {
+            BlendMode::Mask(cutoff) => *cutoff,
+            BlendMode::Opaque | BlendMode::Blend => 0.0,
+        }
+    }
+}
+
 /// In the end a material is just a bind group
 pub trait Material: Debug + Deref<Target = wgpu::BindGroup> {
     fn name(&self) -> String;
     fn kind(&self) -> MaterialKind;
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Opaque
+    }
+    /// Update the animated material parameters, `time` is the scene time in seconds
+    fn animate(&self, _queue: &wgpu::Queue, _time: f32) {}
+    /// Slot of the material in a packed materials buffer, read by the packed pipelines
+    fn packed(&self) -> Option<&PackedMaterial> {
+        None
+    }
 }
@@ -1,8 +1,63 @@
-use super::geometry::GeometryBuf;
+use std::time::SystemTime;
+
+use crate::render::names::{GeometryName, NamedHandle};
+
+use super::{
+    bounds::{BoundingBox, BoundingSphere},
+    geometry::GeometryBuf,
+};
 
 /// # Wgpu named geometries buffers
 #[derive(Debug)]
 pub struct MeshBuf {
     pub name: String,
     pub geometries: Vec<GeometryBuf>,
+    /// simplified copies of the geometries, in the same order, from the finest level of detail
+    /// to the coarsest (see `MeshDescriptor::lod_ratios`)
+    pub lods: Vec<Vec<GeometryBuf>>,
+    /// modification time of the source file the geometries were loaded from
+    pub source_modified: Option<SystemTime>,
+}
+
+impl MeshBuf {
+    /// The geometries of a level of detail: the source ones at 0, the coarsest ones beyond the
+    /// generated levels
+    pub fn lod_geometries(&self, level: usize) -> &[GeometryBuf] {
+        match level.checked_sub(1) {
+            None => &self.geometries,
+            Some(index) => self
+                .lods
+                .get(index)
+                .or_else(|| self.lods.last())
+                .unwrap_or(&self.geometries),
+        }
+    }
+
+    /// The geometry named `name`, if the mesh has one
+    pub fn geometry(&self, name: &GeometryName) -> Option<&GeometryBuf> {
+        self.geometries.iter().find(|g| &g.name() == name)
+    }
+
+    /// Sphere enclosing all the geometries (the empty ones aside)
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        self.geometries
+            .iter()
+            .filter(|g| g.num_elements > 0)
+            .map(|g| g.bounding_sphere)
+            .reduce(|union, sphere| union.union(&sphere))
+            .unwrap_or(BoundingSphere {
+                center: cgmath::Point3::new(0.0, 0.0, 0.0),
+                radius: 0.0,
+            })
+    }
+
+    /// Box enclosing all the geometries (the empty ones aside)
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.geometries
+            .iter()
+            .filter(|g| g.num_elements > 0)
+            .map(|g| g.bounding_box)
+            .reduce(|union, bounds| union.union(&bounds))
+            .unwrap_or_else(BoundingBox::empty)
+    }
 }
@@ -1,16 +1,40 @@
+pub mod billboard;
+pub mod bounds;
 pub mod camera;
+pub mod color;
 pub mod color_material;
+pub mod color_materials;
+pub mod command;
+pub mod compressed;
+pub mod debug_bounds;
+pub mod debug_normals;
+pub mod depth_debug;
+pub mod depth_prepass;
 pub mod draw_ext;
+pub mod fxaa;
 pub mod geometry;
+pub mod global;
+pub mod gnomon;
 pub mod instance;
 pub mod light;
+pub mod light_gizmo;
 pub mod material;
 pub mod mesh;
 pub mod model;
 pub mod object;
+pub mod outline;
+pub mod overrides;
+pub mod particles;
 pub mod pass;
 pub mod pipeline;
+pub mod pipeline_color;
+pub mod stats;
 pub mod store;
 pub mod texture;
 pub mod texture_material;
+pub mod texture_stream;
+pub mod thick_line;
+pub mod unlit_material;
 pub mod vertex;
+pub mod vignette;
+pub mod wireframe;
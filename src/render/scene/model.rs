@@ -1,12 +1,16 @@
 use std::rc::Rc;
 
-use super::{material::Material, mesh::MeshBuf, pipeline::NamedPipeline};
+use super::{
+    instance::ModelInstances, material::Material, mesh::MeshBuf, overrides::ModelOverridesBuf,
+    pipeline::NamedPipeline,
+};
 
 /// A Wgpu-ready model
 ///
 /// This struct points to the wgpu pipeline to use,
 /// the mesh buffers to be used,
 /// the materials (as bind groups) to apply to eac of the mesh's geometries
+/// the pipelines to use for each of the mesh's geometries (the model pipeline if not set)
 ///
 #[derive(Debug)]
 pub struct Model {
@@ -14,6 +18,16 @@ pub struct Model {
     pub pipeline: Rc<NamedPipeline>,
     pub mesh: Rc<MeshBuf>,
     pub materials: Vec<Rc<dyn Material>>,
+    pub pipelines: Vec<Rc<NamedPipeline>>,
+    /// Reserved for the shadow pass, which does not exist yet: `casts_shadow` will select the
+    /// models drawn in the shadow maps, `receives_shadow` the models shaded by them. Nothing
+    /// reads either flag today.
+    pub casts_shadow: bool,
+    pub receives_shadow: bool,
+    /// dedicated instances buffer, the model instances are in the shared one if not set
+    pub instances: Option<ModelInstances>,
+    /// bound with the materials, `None` if the model has no material
+    pub overrides: Option<ModelOverridesBuf>,
 }
 
 impl Model {
@@ -23,8 +37,23 @@ impl Model {
             pipeline,
             mesh,
             materials: vec![],
+            pipelines: vec![],
+            casts_shadow: true,
+            receives_shadow: true,
+            instances: None,
+            overrides: None,
         }
     }
+
+    /// Pipeline used to draw the geometry at the given index of the mesh
+    pub fn geometry_pipeline(&self, geometry_index: usize) -> &Rc<NamedPipeline> {
+        self.pipelines.get(geometry_index).unwrap_or(&self.pipeline)
+    }
+
+    /// Whether some geometries are drawn by a transparent pipeline (blended materials)
+    pub fn has_transparent(&self) -> bool {
+        (0..self.mesh.geometries.len()).any(|i| self.geometry_pipeline(i).is_transparent())
+    }
 }
 
 impl Ord for Model {
@@ -1,7 +1,12 @@
-use super::instance::InstanceRaw;
+use super::instance::{InstanceRaw, InstanceUsage};
 use crate::render::names::ModelName;
 use cgmath::Zero;
 
+/// Layers of a new object (the first one)
+pub const DEFAULT_LAYERS: u32 = 1;
+/// Layer mask showing every object
+pub const ALL_LAYERS: u32 = u32::MAX;
+
 #[derive(PartialEq, Debug)]
 pub struct Object {
     name: String,
@@ -10,6 +15,12 @@ pub struct Object {
     pub orientation: cgmath::Quaternion<f32>,
     pub mesh_scale: f32,
     pub opacity: f32,
+    /// bitmask of the layers the object belongs to (see `ScenePass::set_layer_mask`)
+    pub layers: u32,
+    /// draw the object bounding box (debug)
+    pub show_bounds: bool,
+    /// static objects instances are only written when the static objects change
+    pub usage: InstanceUsage,
 }
 
 impl Object {
@@ -21,6 +32,9 @@ impl Object {
             orientation: cgmath::Quaternion::zero(),
             mesh_scale: 1.0,
             opacity: 1.0,
+            layers: DEFAULT_LAYERS,
+            show_bounds: false,
+            usage: InstanceUsage::Dynamic,
         }
     }
 
@@ -35,6 +49,11 @@ impl Object {
     pub fn model(&self) -> ModelName {
         self.model.clone()
     }
+
+    /// Whether the object belongs to one of the masked layers
+    pub fn in_layers(&self, mask: u32) -> bool {
+        self.layers & mask != 0
+    }
 }
 
 impl PartialOrd for Object {
@@ -67,14 +86,8 @@ impl From<&Object> for InstanceRaw {
     }
 }
 
-impl Into<InstanceRaw> for Object {
-    fn into(self) -> InstanceRaw {
-        InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.orientation)
-                * cgmath::Matrix4::from_scale(self.mesh_scale))
-            .into(),
-            normal: cgmath::Matrix3::from(self.orientation).into(),
-        }
+impl From<Object> for InstanceRaw {
+    fn from(o: Object) -> Self {
+        InstanceRaw::from(&o)
     }
 }
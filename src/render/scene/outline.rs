@@ -0,0 +1,89 @@
+use std::rc::Rc;
+
+use super::{
+    draw_ext::DrawModel,
+    instance::{InstanceRaw, ModelInstances},
+    model::Model,
+    object::Object,
+    pipeline::{create_outline_pipeline, NamedPipeline},
+};
+
+/// Name of the outline models and pipeline
+const OUTLINE: &str = "selection_outline";
+
+/// Outline of the selected object (see `SceneCommand::Select` and `ScenePass::pick`), drawn
+/// after the opaque models.
+///
+/// The object mesh is drawn once more by the outline pipeline, as a single instance apart from
+/// the instances system (`DrawModel::draw_single`).
+pub struct SelectionOutline {
+    pipeline: Rc<NamedPipeline>,
+    buffer: ModelInstances,
+    /// name of the selected object
+    selected: Option<String>,
+    /// the selected object mesh drawn by the outline pipeline, and the object instance. `None`
+    /// if no object has the selected name
+    target: Option<(Model, InstanceRaw)>,
+}
+
+impl SelectionOutline {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let pipeline = NamedPipeline::new(
+            OUTLINE,
+            create_outline_pipeline(device, config, camera_bgl, depth_format, sample_count),
+            vec![],
+        )
+        .unlit();
+        Self {
+            pipeline: Rc::new(pipeline),
+            buffer: ModelInstances::new(device, OUTLINE, 1),
+            selected: None,
+            target: None,
+        }
+    }
+
+    /// Outline the object with the given name (the first one if several have it), or none
+    pub fn select(&mut self, name: Option<String>) {
+        self.selected = name;
+    }
+
+    /// Follow the selected object: its current placement and model
+    pub fn update<'o>(&mut self, objects: impl IntoIterator<Item = (&'o Object, &'o Rc<Model>)>) {
+        let selected = match &self.selected {
+            Some(selected) => objects
+                .into_iter()
+                .find(|(object, _)| &object.name() == selected),
+            None => None,
+        };
+        self.target = selected.map(|(object, model)| {
+            let outline = match self.target.take() {
+                // the outline model is rebuilt when the selected mesh changes only
+                Some((outline, _)) if Rc::ptr_eq(&outline.mesh, &model.mesh) => outline,
+                _ => Model::new(
+                    OUTLINE.to_string(),
+                    self.pipeline.clone(),
+                    model.mesh.clone(),
+                ),
+            };
+            (outline, InstanceRaw::from(object))
+        });
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        queue: &wgpu::Queue,
+        camera_bg: &'a wgpu::BindGroup,
+        light_bg: &'a wgpu::BindGroup,
+    ) {
+        if let Some((outline, instance)) = &self.target {
+            render_pass.draw_single(outline, instance, &self.buffer, queue, camera_bg, light_bg);
+        }
+    }
+}
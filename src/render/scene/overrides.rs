@@ -0,0 +1,114 @@
+use serde::Deserialize;
+use wgpu::util::DeviceExt;
+
+/// Adjustments of the materials output of a model, so that models sharing their materials can
+/// still differ (e.g. boats of several colors). Neutral by default.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ModelOverrides {
+    /// multiplies the shaded color (linear rgba)
+    pub tint: [f32; 4],
+    /// divides the specular exponent: above 1, wider and dimmer highlights
+    pub roughness_mul: f32,
+}
+
+impl Default for ModelOverrides {
+    fn default() -> Self {
+        Self {
+            tint: [1.0, 1.0, 1.0, 1.0],
+            roughness_mul: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelOverridesUniform {
+    tint: [f32; 4],
+    roughness_mul: f32,
+    // the struct is 16 bytes aligned in the shaders
+    _padding: [f32; 3],
+}
+
+impl From<ModelOverrides> for ModelOverridesUniform {
+    fn from(overrides: ModelOverrides) -> Self {
+        Self {
+            tint: overrides.tint,
+            roughness_mul: overrides.roughness_mul,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// The overrides of a model, bound after the material by the pipelines drawing materials (see
+/// `NamedPipeline::overrides_group`)
+#[derive(Debug)]
+pub struct ModelOverridesBuf {
+    _buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ModelOverridesBuf {
+    pub fn new(device: &wgpu::Device, model_name: &str, overrides: ModelOverrides) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} overrides uniform buffer", model_name)),
+            contents: bytemuck::cast_slice(&[ModelOverridesUniform::from(overrides)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::bind_group_layout(device),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some(&format!("{} overrides bind group", model_name)),
+        });
+        Self {
+            _buffer: buffer,
+            bind_group,
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("model overrides bind group layout"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_overrides_are_neutral() {
+        let overrides: ModelOverrides = ron::from_str("(roughness_mul:2.0)").unwrap();
+        assert_eq!(overrides.tint, [1.0; 4]);
+        assert_eq!(overrides.roughness_mul, 2.0);
+        assert_eq!(
+            ron::from_str::<ModelOverrides>("()").unwrap(),
+            ModelOverrides::default()
+        );
+
+        let overrides: ModelOverrides =
+            ron::from_str("(tint:(1.0, 0.2, 0.2, 1.0), roughness_mul:1.5)").unwrap();
+        let uniform = ModelOverridesUniform::from(overrides);
+        assert_eq!(std::mem::size_of::<ModelOverridesUniform>(), 32);
+        let floats: &[f32] = bytemuck::cast_slice(std::slice::from_ref(&uniform));
+        assert_eq!(floats, [1.0, 0.2, 0.2, 1.0, 1.5, 0.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,266 @@
+use wgpu::util::DeviceExt;
+
+use super::pipeline::{create_particles_draw_pipeline, create_particles_update_pipeline};
+
+/// Particles buffer capacity
+pub const MAX_PARTICLES: u32 = 1024;
+/// Compute shader workgroup size (see particles_update.wgsl)
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Particle state, advanced by the compute shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Particle {
+    position: [f32; 3],
+    age: f32,
+    velocity: [f32; 3],
+    lifetime: f32,
+}
+
+impl Particle {
+    /// The particles are drawn as instances
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Particles emission parameters
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleEmitter {
+    pub position: [f32; 3],
+    /// velocity of the emitted particles
+    pub velocity: [f32; 3],
+    /// random variation of the emitted particles velocity (on each axis)
+    pub spread: f32,
+    pub gravity: [f32; 3],
+    /// particles emitted per second
+    pub spawn_rate: f32,
+    /// particles life duration (seconds)
+    pub lifetime: f32,
+    /// particles radius in clip space units
+    pub size: f32,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            velocity: [0.0, 4.0, 0.0],
+            spread: 1.5,
+            gravity: [0.0, -9.81, 0.0],
+            spawn_rate: 100.0,
+            lifetime: 1.5,
+            size: 0.3,
+        }
+    }
+}
+
+impl ParticleEmitter {
+    /// Particles alive at once: the emission must not outpace the particles recycling
+    pub fn count(&self) -> u32 {
+        ((self.spawn_rate * self.lifetime).ceil() as u32).clamp(1, MAX_PARTICLES)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticlesParams {
+    emitter_position: [f32; 3],
+    spawn_rate: f32,
+    initial_velocity: [f32; 3],
+    lifetime: f32,
+    gravity: [f32; 3],
+    spread: f32,
+    count: u32,
+    size: f32,
+    aspect: f32,
+    _padding: f32,
+}
+
+/// GPU particles (spray, foam...).
+///
+/// A compute pass advances the particles state stored in a storage buffer, which is then used
+/// as an instance buffer to draw camera facing quads.
+pub struct ParticleSystem {
+    emitter: ParticleEmitter,
+    particles_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    update_bind_group: wgpu::BindGroup,
+    draw_bind_group: wgpu::BindGroup,
+    update_pipeline: wgpu::ComputePipeline,
+    draw_pipeline: wgpu::RenderPipeline,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        global_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        emitter: ParticleEmitter,
+    ) -> Self {
+        let particles_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("particles.buffer"),
+            contents: bytemuck::cast_slice(
+                &[<Particle as bytemuck::Zeroable>::zeroed(); MAX_PARTICLES as usize],
+            ),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particles.params_buffer"),
+            size: std::mem::size_of::<ParticlesParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let params_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // the particles buffer is written by the compute pass and read as vertices by the draw:
+        // both usages can't be in the same bind group
+        let update_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(wgpu::ShaderStages::COMPUTE),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("particles.update_bind_group_layout"),
+        });
+        let draw_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[params_entry(wgpu::ShaderStages::VERTEX)],
+            label: Some("particles.draw_bind_group_layout"),
+        });
+
+        let update_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &update_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("particles.update_bind_group"),
+        });
+        let draw_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &draw_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("particles.draw_bind_group"),
+        });
+
+        Self {
+            emitter,
+            particles_buffer,
+            params_buffer,
+            update_bind_group,
+            draw_bind_group,
+            update_pipeline: create_particles_update_pipeline(device, &update_bgl, global_bgl),
+            draw_pipeline: create_particles_draw_pipeline(
+                device,
+                config,
+                camera_bgl,
+                &draw_bgl,
+                depth_format,
+                sample_count,
+            ),
+        }
+    }
+
+    /// Set the particles emitted per second (negative values are clamped to 0)
+    pub fn set_spawn_rate(&mut self, spawn_rate: f32) {
+        self.emitter.spawn_rate = spawn_rate.max(0.0);
+    }
+
+    /// Set the particles lifetime in seconds (negative values are clamped to 0)
+    pub fn set_lifetime(&mut self, lifetime: f32) {
+        self.emitter.lifetime = lifetime.max(0.0);
+    }
+
+    /// Record the compute pass advancing the particles to the globals time
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        global_bg: &wgpu::BindGroup,
+        aspect: f32,
+    ) {
+        let emitter = &self.emitter;
+        let params = ParticlesParams {
+            emitter_position: emitter.position,
+            spawn_rate: emitter.spawn_rate,
+            initial_velocity: emitter.velocity,
+            lifetime: emitter.lifetime,
+            gravity: emitter.gravity,
+            spread: emitter.spread,
+            // nothing is emitted at a null rate
+            count: if emitter.spawn_rate > 0.0 {
+                emitter.count()
+            } else {
+                0
+            },
+            size: emitter.size,
+            aspect,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particles update pass"),
+        });
+        compute_pass.set_pipeline(&self.update_pipeline);
+        compute_pass.set_bind_group(0, &self.update_bind_group, &[]);
+        compute_pass.set_bind_group(1, global_bg, &[]);
+        compute_pass.dispatch(MAX_PARTICLES.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Draw the particles in the scene render pass
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bg: &'a wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.draw_pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass.set_bind_group(1, &self.draw_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.particles_buffer.slice(..));
+        render_pass.draw(0..6, 0..MAX_PARTICLES);
+    }
+}
@@ -1,30 +1,56 @@
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::mpsc::Receiver;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Result};
 use winit::event_loop::EventLoop;
 use winit::window::Window;
 
+use crate::app::config::{AntiAliasing, LabelsConfig, RenderSettings};
 use crate::app::event::{Emitter, PomarinEvent};
 use crate::render::config::assets::{AssetDescriptor, TryAsRef};
 use crate::render::config::model::ModelDescriptor;
+use crate::render::config::scene::{ObjectDescriptor, ScatterDescriptor, SceneDescriptor};
 use crate::render::config::WgpuResourceLoader;
+use crate::render::error::{SceneError, StoreError};
 use crate::render::names::ModelName;
+use crate::render::rpass::WgpuRpass;
 use crate::render::state::WgpuState;
 
-use super::camera::{CameraSystem, OrbitController};
-use super::draw_ext::DrawModel;
-use super::instance::{InstanceRaw, InstancesSystem};
-use super::light::{self, LightSystem, LightUniform};
+use super::billboard::{self, Billboard, BillboardPass};
+use super::bounds::BoundingBox;
+use super::camera::{CameraSystem, Easing, OrbitController, ViewState};
+use super::color;
+use super::command::SceneCommand;
+use super::debug_bounds::DebugBoundsPass;
+use super::debug_normals::DebugNormalsPass;
+use super::depth_debug::DepthDebugView;
+use super::depth_prepass::DepthPrepass;
+use super::draw_ext::{DrawModel, DrawPhase, DrawSelection, GeometryFilter};
+use super::fxaa::FxaaPass;
+use super::global::GlobalSystem;
+use super::gnomon::GnomonPass;
+use super::instance::{
+    InstanceRaw, InstanceUsage, Instanced, InstancesRanges, InstancesSystem, StaticsTracker,
+};
+use super::light::{self, LightSystem, LightUniform, SpotLightUniform};
+use super::light_gizmo::LightGizmos;
 use super::material::MaterialKind;
 use super::model::Model;
-use super::object::Object;
+use super::object::{Object, ALL_LAYERS, DEFAULT_LAYERS};
+use super::outline::SelectionOutline;
+use super::particles::{ParticleEmitter, ParticleSystem};
 use super::pipeline::{
-    create_colored_model_pipeline, create_light_pipeline, create_textured_model_pipeline,
-    NamedPipeline,
+    create_colored_model_pipeline, create_flat_shaded_pipeline, create_light_pipeline,
+    create_packed_colored_model_pipeline, create_textured_model_pipeline, create_unlit_pipeline,
+    NamedPipeline, PipelineOptions,
 };
-use super::texture::{self, Texture};
+use super::pipeline_color::PipelineColorOverlay;
+use super::stats::{FrameStats, FrameTimes, StatsLog};
+use super::texture::{self, DepthSamplerKind, Texture};
+use super::wireframe::{WireframeOverlay, WIREFRAME_COLOR, WIREFRAME_DEPTH_BIAS};
 
 /// A struct mapping the object and the model
 /// The model is displayed based on the object data
@@ -39,26 +65,469 @@ impl LinkedObject {
     }
 }
 
+/// An object of the scene with its model
+trait SceneObject: Instanced<InstanceRaw> {
+    fn object(&self) -> &Object;
+    fn object_mut(&mut self) -> &mut Object;
+}
+
+impl SceneObject for LinkedObject {
+    fn object(&self) -> &Object {
+        &self.object
+    }
+
+    fn object_mut(&mut self) -> &mut Object {
+        &mut self.object
+    }
+}
+
+/// Apply the command to the scene objects, the static instances are invalidated when static
+/// objects change. `link` loads the model of a spawned object.
+///
+/// Returns the command if the pass has more to apply: the commands not about the objects, and
+/// the models unloading once their objects are removed.
+fn apply_to_objects<T: SceneObject>(
+    objects: &mut Vec<T>,
+    statics: &mut StaticsTracker,
+    command: SceneCommand,
+    link: impl FnOnce(Object) -> Result<T>,
+) -> Option<SceneCommand> {
+    let named = |name: &str, o: &T| o.object().name() == name;
+    match command {
+        SceneCommand::Spawn {
+            name,
+            model,
+            transform,
+        } => {
+            let mut object = Object::new(name, model);
+            transform.apply_to(&mut object);
+            match link(object) {
+                Ok(linked) => {
+                    statics.invalidate_for([&linked]);
+                    objects.push(linked);
+                }
+                Err(e) => log::warn!("Failed while trying to spawn object: {}", e),
+            }
+        }
+        SceneCommand::Despawn(name) => {
+            statics.invalidate_for(objects.iter().filter(|o| named(&name, o)));
+            objects.retain(|o| !named(&name, o));
+        }
+        SceneCommand::SetTransform { name, transform } => {
+            statics.invalidate_for(objects.iter().filter(|o| named(&name, o)));
+            objects
+                .iter_mut()
+                .filter(|o| named(&name, o))
+                .for_each(|o| transform.apply_to(o.object_mut()));
+        }
+        SceneCommand::SetOrientation { name, orientation } => {
+            statics.invalidate_for(objects.iter().filter(|o| named(&name, o)));
+            objects
+                .iter_mut()
+                .filter(|o| named(&name, o))
+                .for_each(|o| o.object_mut().orientation = orientation);
+        }
+        SceneCommand::ShowBounds { name, show } => objects
+            .iter_mut()
+            .filter(|o| named(&name, o))
+            .for_each(|o| o.object_mut().show_bounds = show),
+        SceneCommand::UnloadModel(ref model_name) => {
+            let displays = |o: &T| o.object().model() == *model_name;
+            statics.invalidate_for(objects.iter().filter(|o| displays(o)));
+            objects.retain(|o| !displays(o));
+            return Some(command);
+        }
+        command => return Some(command),
+    }
+    None
+}
+
+/// Height of the ground at (x, z): the highest top of the boxes over this point, the y = 0 plane
+/// elsewhere
+fn ground_height(boxes: &[BoundingBox], x: f32, z: f32) -> f32 {
+    boxes
+        .iter()
+        .filter(|b| (b.min.x..=b.max.x).contains(&x) && (b.min.z..=b.max.z).contains(&z))
+        .map(|b| b.max.y)
+        .fold(0.0, f32::max)
+}
+
+/// Frame duration as seen by the animated systems: scaled by the animation speed, none while
+/// paused
+fn scaled_dt(dt: Duration, time_scale: f32, paused: bool) -> Duration {
+    if paused {
+        Duration::ZERO
+    } else {
+        dt.mul_f32(time_scale)
+    }
+}
+
+/// Instances of the objects of the masked layers grouped by model (see `StaticsTracker::gather`)
+fn gather_in_layers<T: SceneObject>(
+    statics: &mut StaticsTracker,
+    objects: &[T],
+    layer_mask: u32,
+    visible: impl Fn(&T) -> bool,
+) -> InstancesRanges<InstanceRaw> {
+    statics.gather(
+        objects.iter().filter(|o| o.object().in_layers(layer_mask)),
+        visible,
+    )
+}
+
+/// Link each object with `link`, the objects failing are left out.
+/// Returns the linked objects, and the aggregated failures if any.
+fn link_all<T>(
+    objects: Vec<Object>,
+    mut link: impl FnMut(Object) -> Result<T>,
+) -> (Vec<T>, Option<SceneError>) {
+    let total = objects.len();
+    let mut linked = vec![];
+    let mut failures = vec![];
+    for object in objects {
+        let name = object.name();
+        match link(object) {
+            Ok(object) => linked.push(object),
+            Err(e) => failures.push((name, e.to_string())),
+        }
+    }
+    let error = (!failures.is_empty()).then_some(SceneError::ObjectsNotLoaded { total, failures });
+    (linked, error)
+}
+
+impl Instanced<InstanceRaw> for LinkedObject {
+    fn model_name(&self) -> &str {
+        &self.model.name
+    }
+
+    /// the models with a dedicated buffer have all their instances written every frame
+    fn usage(&self) -> InstanceUsage {
+        match self.model.instances {
+            Some(_) => InstanceUsage::Dynamic,
+            None => self.object.usage,
+        }
+    }
+
+    fn instance(&self) -> InstanceRaw {
+        InstanceRaw::from(&self.object)
+    }
+}
+
 /// Scene initialisation and redrawing
 pub struct ScenePass {
     _emitter: Arc<Emitter<PomarinEvent>>,
     depth_texture: Texture,
     objects: Vec<LinkedObject>,
     instances_system: InstancesSystem<InstanceRaw>,
-    camera_system: CameraSystem<OrbitController>,
+    statics: StaticsTracker,
+    camera_system: CameraSystem,
+    /// progress curve of the camera glide back to its initial view
+    reset_easing: Easing,
     light_system: LightSystem<LightUniform>, //TODO: rm useless trait/generic
+    /// `None` if the gizmo mesh or pipeline is not available
+    light_gizmos: Option<LightGizmos>,
+    /// draw the lights positions
+    show_light_gizmos: bool,
+    /// spot light index and lit uniform of the dock floodlight, `None` if it could not be added
+    floodlight: Option<(usize, SpotLightUniform)>,
+    floodlight_on: bool,
     last_render_time: Instant,
+    /// animation speed factor applied to the frames duration
+    time_scale: f32,
+    /// stops the animations (the frames keep being rendered)
+    paused: bool,
+    /// layers drawn, the objects of the other layers are hidden
+    layer_mask: u32,
+    /// animations time, accumulated from the scaled frames durations, shared with the shaders
+    globals: GlobalSystem,
+    frame_times: FrameTimes,
+    /// periodic statistics summary, `None` if disabled (see `RenderConfig::stats_log`)
+    stats_log: Option<StatsLog>,
+    commands: Receiver<SceneCommand>,
+    /// multisampled color target resolved into the output view, `None` without MSAA
+    msaa_target: Option<Texture>,
+    /// `None` with MSAA: the view reads a single sampled depth texture
+    depth_debug: Option<DepthDebugView>,
+    /// draw the depth buffer instead of the scene
+    show_depth: bool,
+    /// spray behind the boats
+    particles: ParticleSystem,
+    /// camera orientation indicator, `None` if disabled
+    gnomon: Option<GnomonPass>,
+    /// `None` unless FXAA is the applied anti-aliasing
+    fxaa: Option<FxaaPass>,
+    /// background color (linear rgb)
+    clear_color: [f32; 3],
+    /// `None` if disabled (see `RenderConfig::depth_prepass`)
+    depth_prepass: Option<DepthPrepass>,
+    /// `None` if the device does not support the line polygon mode
+    wireframe: Option<WireframeOverlay>,
+    /// draw the models edges over the shaded models
+    show_wireframe: bool,
+    pipeline_colors: PipelineColorOverlay,
+    /// draw the models in a flat color of their pipeline, in place of their blended pass
+    show_pipeline_colors: bool,
+    debug_normals: DebugNormalsPass,
+    /// select the next model to show the normals of, on the next render
+    cycle_debug_normals: bool,
+    /// bounding boxes of the objects flagged with `show_bounds`
+    debug_bounds: DebugBoundsPass,
+    /// geometries drawn of the models
+    selection: DrawSelection,
+    /// outline of the selected object
+    outline: SelectionOutline,
+    billboards: BillboardPass,
+    labels_config: LabelsConfig,
+    show_labels: bool,
+    /// objects names labels (with their aspect ratio) by object name, `None` if the label could
+    /// not be rasterized
+    labels: HashMap<String, Option<(Rc<Texture>, f32)>>,
 }
 
 impl ScenePass {
-    pub fn new(wgpu: &WgpuState, _window: &Window, event_loop: &EventLoop<PomarinEvent>) -> Self {
+    pub fn new(
+        wgpu: &WgpuState,
+        _window: &Window,
+        event_loop: &EventLoop<PomarinEvent>,
+        commands: Receiver<SceneCommand>,
+        saved_scene: Option<SceneDescriptor>,
+    ) -> Result<(Self, Option<SceneError>), SceneError> {
         let _emitter = Arc::new(Emitter::new(event_loop));
 
+        let objects_desc = match saved_scene {
+            Some(scene) => scene
+                .objects
+                .iter()
+                .map(ObjectDescriptor::to_object)
+                .collect(),
+            None => Self::demo_objects(),
+        };
+
+        let instances_system: InstancesSystem<InstanceRaw> = InstancesSystem::new(&wgpu.device);
+        let mut light_uniform = LightUniform::default();
+        let [constant, linear, quadratic] = wgpu.render_config.light.attenuation;
+        light_uniform.set_attenuation(constant, linear, quadratic);
+        let (light_bgl, mut light_system) = light::LightSystem::init(light_uniform, &wgpu.device);
+        // dock floodlight
+        let floodlight = SpotLightUniform::new(
+            [0.0, 30.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [1.0, 0.9, 0.7],
+            cgmath::Deg(15.0),
+            cgmath::Deg(25.0),
+            60.0,
+        );
+        let floodlight = light_system
+            .add_spot_light(floodlight)
+            .map(|index| (index, floodlight));
+
+        // free orbit, until the ground is known from the linked objects
+        let (camera_bgl, mut camera_system) = CameraSystem::init(
+            &wgpu.device,
+            Box::new(OrbitController::new(ViewState::from(
+                &wgpu.render_config.camera,
+            ))),
+        );
+        camera_system.resize(wgpu.config.width, wgpu.config.height);
+
+        let (depth_texture, msaa_target) = Self::create_targets(wgpu);
+        let depth_debug = (wgpu.sample_count == 1)
+            .then(|| DepthDebugView::new(&wgpu.device, &wgpu.config, &camera_bgl, &depth_texture));
+        let (global_bgl, globals) = GlobalSystem::init(&wgpu.device);
+        // spray behind z2
+        let particles = ParticleSystem::new(
+            &wgpu.device,
+            &wgpu.config,
+            &camera_bgl,
+            &global_bgl,
+            wgpu.depth_format,
+            wgpu.sample_count,
+            ParticleEmitter {
+                position: [10.0, 0.5, 7.0],
+                ..Default::default()
+            },
+        );
+
+        let wireframe = if wgpu
+            .device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+        {
+            Some(WireframeOverlay::new(
+                &wgpu.device,
+                &wgpu.config,
+                &camera_bgl,
+                WIREFRAME_COLOR,
+                wgpu.depth_format,
+                wgpu.sample_count,
+                WIREFRAME_DEPTH_BIAS,
+            ))
+        } else {
+            log::warn!("Line polygon mode not supported, the wireframe overlay is disabled");
+            None
+        };
+
+        let pipeline_colors = PipelineColorOverlay::new(
+            &wgpu.device,
+            &wgpu.config,
+            &camera_bgl,
+            wgpu.depth_format,
+            wgpu.sample_count,
+        );
+
+        let debug_normals = DebugNormalsPass::new(
+            &wgpu.device,
+            &wgpu.config,
+            &camera_bgl,
+            wgpu.depth_format,
+            wgpu.sample_count,
+            &wgpu.render_config.debug_normals,
+            &wgpu.render_config.lines,
+        );
+
+        let debug_bounds = DebugBoundsPass::new(
+            &wgpu.device,
+            &wgpu.config,
+            &camera_bgl,
+            wgpu.depth_format,
+            wgpu.sample_count,
+            &wgpu.render_config.lines,
+        );
+
+        let outline = SelectionOutline::new(
+            &wgpu.device,
+            &wgpu.config,
+            &camera_bgl,
+            wgpu.depth_format,
+            wgpu.sample_count,
+        );
+
+        let billboards = BillboardPass::new(
+            &wgpu.device,
+            &wgpu.config,
+            &camera_bgl,
+            wgpu.depth_format,
+            wgpu.sample_count,
+        );
+
+        let (fxaa, _) = Self::anti_aliasing(wgpu, wgpu.render_config.anti_aliasing);
+
+        let gnomon_config = &wgpu.render_config.gnomon;
+        let gnomon = gnomon_config
+            .enabled
+            .then(|| GnomonPass::new(&wgpu.device, &wgpu.config, gnomon_config));
+
+        let prepassed = wgpu.render_config.depth_prepass;
+        let depth_prepass = prepassed.then(|| {
+            DepthPrepass::new(
+                &wgpu.device,
+                &camera_bgl,
+                wgpu.depth_format,
+                wgpu.sample_count,
+            )
+        });
+        Self::add_pipelines(wgpu, &camera_bgl, &light_bgl, prepassed)?;
+        // TODO: terrain pipeline to colr according to height
+        if wgpu.settings.preload_models {
+            for (name, result) in wgpu.assets.load_models(wgpu) {
+                if let Err(e) = result {
+                    log::warn!("Cannot preload model {}: {}", name, e);
+                }
+            }
+        }
+        let light_gizmos = LightGizmos::new(wgpu)
+            .map_err(|e| log::warn!("Light gizmos disabled: {}", e))
+            .ok();
+
+        let (objects, failures) = link_all(objects_desc, |object| Self::link_object(wgpu, object));
+
+        // kept over the static objects as linked (terrain, moored boats)
+        let ground: Vec<BoundingBox> = objects
+            .iter()
+            .filter(|o| o.object.usage == InstanceUsage::Static)
+            .map(|o| {
+                o.model.mesh.bounding_box().transformed(
+                    o.object.position,
+                    o.object.orientation,
+                    o.object.mesh_scale,
+                )
+            })
+            .collect();
+        let mut camera_controler =
+            OrbitController::new(ViewState::from(&wgpu.render_config.camera));
+        camera_controler.set_min_height(wgpu.render_config.camera.min_height);
+        camera_controler.set_ground_height(Box::new(move |x, z| ground_height(&ground, x, z)));
+        let free = camera_system.set_updater(Box::new(camera_controler));
+        // free orbit, allowed under the minimum height (`PomarinEvent::CycleCamera` switches)
+        camera_system.register_controller(free);
+
+        let pass = Self {
+            _emitter,
+            instances_system,
+            statics: StaticsTracker::default(),
+            depth_texture,
+            objects,
+            camera_system,
+            reset_easing: wgpu.render_config.camera.reset_easing,
+            light_system,
+            light_gizmos,
+            show_light_gizmos: false,
+            floodlight,
+            floodlight_on: true,
+            last_render_time: Instant::now(),
+            time_scale: 1.0,
+            paused: false,
+            layer_mask: ALL_LAYERS,
+            globals,
+            frame_times: FrameTimes::default(),
+            stats_log: wgpu.render_config.stats_log.enabled.then(|| {
+                StatsLog::new(
+                    Duration::from_secs_f32(wgpu.render_config.stats_log.interval.max(0.0)),
+                    Instant::now(),
+                )
+            }),
+            commands,
+            msaa_target,
+            depth_debug,
+            show_depth: false,
+            particles,
+            gnomon,
+            fxaa,
+            clear_color: wgpu.render_config.clear_color,
+            depth_prepass,
+            wireframe,
+            show_wireframe: false,
+            pipeline_colors,
+            show_pipeline_colors: false,
+            debug_normals,
+            cycle_debug_normals: false,
+            debug_bounds,
+            selection: DrawSelection::default(),
+            outline,
+            billboards,
+            labels_config: wgpu.render_config.labels.clone(),
+            show_labels: wgpu.render_config.labels.enabled,
+            labels: HashMap::new(),
+        };
+        Ok((pass, failures))
+    }
+
+    /// The demo layout, when no scene was saved
+    fn demo_objects() -> Vec<Object> {
         let mut z2 = Object::new("z2".to_string(), ModelName::from("texture_zod"));
         z2.set_position((10.0, 0.0, 10.0));
 
-        let sea = Object::new("sea".to_string(), ModelName::from("sea_square"));
-        let terrian = Object::new("surface".to_string(), ModelName::from("fake_terrain"));
+        // textured hull with a colored inflatable
+        let mut z3 = Object::new("z3".to_string(), ModelName::from("mixed_zod"));
+        z3.set_position((-10.0, 0.0, 10.0));
+
+        // environment on the second layer
+        let mut sea = Object::new("sea".to_string(), ModelName::from("sea_square"));
+        sea.layers = 1 << 1;
+        sea.usage = InstanceUsage::Static;
+        let mut terrian = Object::new("surface".to_string(), ModelName::from("fake_terrain"));
+        terrian.layers = 1 << 1;
+        terrian.usage = InstanceUsage::Static;
 
         let mut objects_desc = vec![];
         objects_desc.push(Object::new(
@@ -68,88 +537,647 @@ impl ScenePass {
         objects_desc.push(terrian);
         objects_desc.push(sea);
         objects_desc.push(z2);
+        objects_desc.push(z3);
+        // moored fleet
+        objects_desc.extend(
+            ScatterDescriptor {
+                name: "moored".to_string(),
+                model: ModelName::from("toon_zod"),
+                count: 6,
+                area: ([-40.0, 0.0, -40.0], [-20.0, 0.0, -25.0]),
+                seed: 7,
+                scale_range: (0.8, 1.2),
+                layers: DEFAULT_LAYERS,
+                usage: InstanceUsage::Static,
+            }
+            .to_objects(),
+        );
+        objects_desc
+    }
+
+    /// Build the models pipelines missing from the store
+    fn add_pipelines(
+        wgpu: &WgpuState,
+        camera_bgl: &wgpu::BindGroupLayout,
+        light_bgl: &wgpu::BindGroupLayout,
+        prepassed: bool,
+    ) -> Result<(), StoreError> {
+        // the opaque models pipelines test for the prepass depth
+        let options = if prepassed {
+            PipelineOptions::new(wgpu.depth_format, wgpu.sample_count).depth_prepassed()
+        } else {
+            PipelineOptions::new(wgpu.depth_format, wgpu.sample_count)
+        };
 
-        let mut instances_system: InstancesSystem<InstanceRaw> = InstancesSystem::new(&wgpu.device);
-        let (light_bgl, light_system) =
-            light::LightSystem::init(LightUniform::default(), &wgpu.device);
+        // pipelines are kept in the store: only build the missing ones
+        wgpu.store.add_pipeline_if_absent("textures_pipeline", || {
+            NamedPipeline::new(
+                "textures_pipeline",
+                create_textured_model_pipeline(
+                    &wgpu.device,
+                    &wgpu.config,
+                    camera_bgl,
+                    light_bgl,
+                    options,
+                ),
+                vec![MaterialKind::Texture],
+            )
+            .depth_prepassed(prepassed)
+            .with_transparent_variant("textures_transparent_pipeline")
+        })?;
+        // the blended materials are drawn after the opaque ones, they can't test for the
+        // prepass depth
+        wgpu.store
+            .add_pipeline_if_absent("textures_transparent_pipeline", || {
+                NamedPipeline::new(
+                    "textures_transparent_pipeline",
+                    create_textured_model_pipeline(
+                        &wgpu.device,
+                        &wgpu.config,
+                        camera_bgl,
+                        light_bgl,
+                        PipelineOptions::new(wgpu.depth_format, wgpu.sample_count).transparent(),
+                    ),
+                    vec![MaterialKind::Texture],
+                )
+                .transparent()
+            })?;
+        // the depth prepass culls the back faces: the double sided geometries can't test for
+        // its depth
+        wgpu.store
+            .add_pipeline_if_absent("textures_double_sided_pipeline", || {
+                NamedPipeline::new(
+                    "textures_double_sided_pipeline",
+                    create_textured_model_pipeline(
+                        &wgpu.device,
+                        &wgpu.config,
+                        camera_bgl,
+                        light_bgl,
+                        PipelineOptions::new(wgpu.depth_format, wgpu.sample_count).double_sided(),
+                    ),
+                    vec![MaterialKind::Texture],
+                )
+            })?;
+        // drawn over the coplanar textured surfaces (e.g. hulls markings), in any order
+        wgpu.store.add_pipeline_if_absent("decals_pipeline", || {
+            NamedPipeline::new(
+                "decals_pipeline",
+                create_textured_model_pipeline(
+                    &wgpu.device,
+                    &wgpu.config,
+                    camera_bgl,
+                    light_bgl,
+                    PipelineOptions::new(wgpu.depth_format, wgpu.sample_count)
+                        .decal(wgpu.render_config.decal_bias.into()),
+                ),
+                vec![MaterialKind::Texture],
+            )
+        })?;
+        wgpu.store.add_pipeline_if_absent("colors_pipeline", || {
+            NamedPipeline::new(
+                "colors_pipeline",
+                create_colored_model_pipeline(
+                    &wgpu.device,
+                    &wgpu.config,
+                    camera_bgl,
+                    light_bgl,
+                    options,
+                ),
+                vec![MaterialKind::Color],
+            )
+            .depth_prepassed(prepassed)
+        })?;
+        wgpu.store.add_pipeline_if_absent("flat_pipeline", || {
+            NamedPipeline::new(
+                "flat_pipeline",
+                create_flat_shaded_pipeline(
+                    &wgpu.device,
+                    &wgpu.config,
+                    camera_bgl,
+                    light_bgl,
+                    options,
+                ),
+                vec![MaterialKind::Color],
+            )
+            .depth_prepassed(prepassed)
+        })?;
+        wgpu.store
+            .add_pipeline_if_absent("colors_packed_pipeline", || {
+                NamedPipeline::new(
+                    "colors_packed_pipeline",
+                    create_packed_colored_model_pipeline(
+                        &wgpu.device,
+                        &wgpu.config,
+                        camera_bgl,
+                        light_bgl,
+                        options,
+                    ),
+                    vec![MaterialKind::Color],
+                )
+                .packed_materials()
+                .depth_prepassed(prepassed)
+            })?;
+        wgpu.store.add_pipeline_if_absent("unlit_pipeline", || {
+            NamedPipeline::new(
+                "unlit_pipeline",
+                create_unlit_pipeline(&wgpu.device, &wgpu.config, camera_bgl, options),
+                vec![MaterialKind::Unlit],
+            )
+            .unlit()
+            .depth_prepassed(prepassed)
+            .with_transparent_variant("unlit_transparent_pipeline")
+        })?;
+        wgpu.store
+            .add_pipeline_if_absent("unlit_transparent_pipeline", || {
+                NamedPipeline::new(
+                    "unlit_transparent_pipeline",
+                    create_unlit_pipeline(
+                        &wgpu.device,
+                        &wgpu.config,
+                        camera_bgl,
+                        PipelineOptions::new(wgpu.depth_format, wgpu.sample_count).transparent(),
+                    ),
+                    vec![MaterialKind::Unlit],
+                )
+                .unlit()
+                .transparent()
+            })?;
+        wgpu.store.add_pipeline_if_absent("light_pipeline", || {
+            NamedPipeline::new(
+                "light_pipeline",
+                create_light_pipeline(
+                    &wgpu.device,
+                    &wgpu.config,
+                    camera_bgl,
+                    light_bgl,
+                    wgpu.depth_format,
+                    wgpu.sample_count,
+                ),
+                vec![],
+            )
+        })?;
+        Ok(())
+    }
 
-        let camera_controler = OrbitController::default();
+    /// The models drawing blended materials, the farthest from the camera first.
+    ///
+    /// A model is as far as its farthest object: the instances of a model are drawn together,
+    /// in their buffer order.
+    fn back_to_front<'m>(&self, models: &'m [Rc<Model>]) -> Vec<&'m Rc<Model>> {
+        let view = self.camera_system.view();
+        let distance = |model: &Rc<Model>| {
+            self.objects
+                .iter()
+                .filter(|o| Rc::ptr_eq(&o.model, model))
+                // the camera looks toward -z in view space
+                .map(|o| -(view * o.object.position.extend(1.0)).z)
+                .fold(f32::NEG_INFINITY, f32::max)
+        };
+        let mut transparent: Vec<(f32, &Rc<Model>)> = models
+            .iter()
+            .filter(|model| model.has_transparent())
+            .map(|model| (distance(model), model))
+            .collect();
+        transparent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        transparent.into_iter().map(|(_, model)| model).collect()
+    }
 
-        let (camera_bgl, camera_system) = CameraSystem::init(&wgpu.device, camera_controler);
+    /// Load the model of the object
+    fn link_object(wgpu: &WgpuState, object: Object) -> Result<LinkedObject> {
+        let model = wgpu
+            .assets
+            .find(object.model())
+            .ok_or_else(|| anyhow!("model {} not found in assets", object.model()))
+            .and_then(|model: &AssetDescriptor| model.try_as_ref())
+            .and_then(|zd: &ModelDescriptor| zd.load(wgpu))?;
+        Ok(LinkedObject { object, model })
+    }
 
-        let depth_texture =
-            texture::Texture::create_depth_texture(&wgpu.device, &wgpu.config, "depth_texture");
+    /// Apply the scene commands received since the last render
+    fn process_commands(&mut self, wgpu: &WgpuState) {
+        while let Ok(command) = self.commands.try_recv() {
+            self.apply_command(wgpu, command);
+        }
+    }
 
-        let textured_model_pipeline = NamedPipeline::new(
-            "textures_pipeline",
-            create_textured_model_pipeline(&wgpu.device, &wgpu.config, &camera_bgl, &light_bgl),
-            vec![MaterialKind::Texture],
-        );
-        let colored_model_pipeline = NamedPipeline::new(
-            "colors_pipeline",
-            create_colored_model_pipeline(&wgpu.device, &wgpu.config, &camera_bgl, &light_bgl),
-            vec![MaterialKind::Color],
-        );
-        let light_pipeline = NamedPipeline::new(
-            "light_pipeline",
-            create_light_pipeline(&wgpu.device, &wgpu.config, &camera_bgl, &light_bgl),
-            vec![],
+    fn apply_command(&mut self, wgpu: &WgpuState, command: SceneCommand) {
+        log::debug!(target: "event", "Scene command {:?}", command);
+        let command = match apply_to_objects(&mut self.objects, &mut self.statics, command, |o| {
+            Self::link_object(wgpu, o)
+        }) {
+            Some(command) => command,
+            None => return,
+        };
+        match command {
+            SceneCommand::UnloadModel(model_name) => {
+                if self
+                    .debug_normals
+                    .model()
+                    .is_some_and(|model| model.name == *model_name)
+                {
+                    self.show_normals(wgpu, None);
+                }
+                match wgpu
+                    .assets
+                    .get(model_name.clone())
+                    .and_then(|desc| TryAsRef::<ModelDescriptor>::try_as_ref(desc))
+                {
+                    Ok(descriptor) => {
+                        if let Err(e) = descriptor.unload(wgpu) {
+                            log::warn!("Cannot unload model {}: {}", model_name, e);
+                        }
+                    }
+                    Err(e) => log::warn!("Cannot unload model {}: {}", model_name, e),
+                }
+            }
+            SceneCommand::ShowNormals(model_name) => {
+                let model = model_name.and_then(|name| match wgpu.store.get_model(name.as_str()) {
+                    Ok(Some(model)) => Some(model),
+                    Ok(None) => {
+                        log::warn!("Cannot show the normals of {}: model not loaded", name);
+                        None
+                    }
+                    Err(e) => {
+                        log::warn!("Cannot show the normals of {}: {}", name, e);
+                        None
+                    }
+                });
+                self.show_normals(wgpu, model);
+            }
+            SceneCommand::IsolateGeometry(filter) => self.isolate_geometry(wgpu, filter),
+            SceneCommand::ShowLod(lod) => self.selection.lod = lod,
+            SceneCommand::Select(name) => self.outline.select(name),
+            // applied to the objects
+            _ => {}
+        }
+    }
+
+    fn isolate_geometry(&mut self, wgpu: &WgpuState, filter: Option<GeometryFilter>) {
+        if let Some(filter) = &filter {
+            let model = match wgpu.store.get_model(filter.model.as_str()) {
+                Ok(Some(model)) => model,
+                Ok(None) => {
+                    log::warn!("Cannot isolate {}: model not loaded", filter.geometry);
+                    return;
+                }
+                Err(e) => {
+                    log::warn!("Cannot isolate {}: {}", filter.geometry, e);
+                    return;
+                }
+            };
+            if model.mesh.geometry(&filter.geometry).is_none() {
+                log::warn!(
+                    "Cannot isolate {}: not a geometry of {}",
+                    filter.geometry,
+                    filter.model
+                );
+                return;
+            }
+        }
+        self.selection.isolated = filter;
+    }
+
+    fn show_normals(&mut self, wgpu: &WgpuState, model: Option<Rc<Model>>) {
+        if let Err(e) = self.debug_normals.set_model(wgpu, model) {
+            log::warn!("Cannot show the model normals: {}", e);
+        }
+    }
+
+    /// Show the normals of the loaded model following the current one (by name), none after
+    /// the last one
+    fn show_next_normals(&mut self, wgpu: &WgpuState) {
+        let mut models = match wgpu.store.models() {
+            Ok(models) => models,
+            Err(e) => {
+                log::warn!("Cannot cycle the models normals: {}", e);
+                return;
+            }
+        };
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        let next = match self.debug_normals.model() {
+            Some(current) => models.into_iter().find(|m| m.name > current.name),
+            None => models.into_iter().next(),
+        };
+        self.show_normals(wgpu, next);
+    }
+
+    /// Label billboards above the visible objects, the labels textures are rasterized once
+    fn update_labels(&mut self, wgpu: &WgpuState) {
+        let mut billboards = vec![];
+        if self.show_labels {
+            let config = &self.labels_config;
+            for o in self
+                .objects
+                .iter()
+                .filter(|o| o.object.in_layers(self.layer_mask))
+            {
+                let name = o.name();
+                let texture = self.labels.entry(name.clone()).or_insert_with(|| {
+                    billboard::label_image(&name, config.font_size)
+                        .and_then(|image| {
+                            let aspect = image.width() as f32 / image.height() as f32;
+                            let texture = Texture::from_image(
+                                &wgpu.device,
+                                &wgpu.queue,
+                                &image::DynamicImage::ImageRgba8(image),
+                                Some(&format!("label.{}", name)),
+                                false,
+                            )?;
+                            Ok((Rc::new(texture), aspect))
+                        })
+                        .map_err(|e| log::warn!("Cannot rasterize the label of {}: {}", name, e))
+                        .ok()
+                });
+                if let Some((texture, aspect)) = texture {
+                    let position = o.object.position + cgmath::Vector3::unit_y() * config.offset;
+                    billboards.push(Billboard {
+                        position: position.into(),
+                        size: [config.height * *aspect, config.height],
+                        texture: texture.clone(),
+                    });
+                }
+            }
+        }
+        self.billboards.update(
+            &wgpu.device,
+            &wgpu.queue,
+            &billboards,
+            self.camera_system.view(),
         );
-        wgpu.store.add_pipeline(Rc::new(textured_model_pipeline));
-        wgpu.store.add_pipeline(Rc::new(colored_model_pipeline));
-        wgpu.store.add_pipeline(Rc::new(light_pipeline));
-        // TODO: terrain pipeline to colr according to height
+    }
 
-        let mut objects = vec![];
+    /// World space boxes of the visible objects flagged with `show_bounds`
+    fn update_debug_bounds(&mut self, wgpu: &WgpuState) {
+        let boxes: Vec<BoundingBox> = self
+            .objects
+            .iter()
+            .filter(|o| o.object.show_bounds && o.object.in_layers(self.layer_mask))
+            .map(|o| {
+                o.model.mesh.bounding_box().transformed(
+                    o.object.position,
+                    o.object.orientation,
+                    o.object.mesh_scale,
+                )
+            })
+            .collect();
+        self.debug_bounds.update(&wgpu.device, &wgpu.queue, &boxes);
+    }
 
-        objects_desc.into_iter().for_each(|object| {
-            wgpu.assets
-                .find(object.model())
-                .ok_or(anyhow!("obj asset model not found"))
-                .and_then(|model: &AssetDescriptor| model.try_as_ref())
-                .and_then(|zd: &ModelDescriptor| zd.load(wgpu))
-                .and_then(|model| {
-                    instances_system
-                        .set_instances_raw(vec![InstanceRaw::from(&object)], &wgpu.queue);
-                    objects.push(LinkedObject { object, model });
-                    Ok(())
-                })
-                .err()
+    /// The current scene layout
+    pub fn to_descriptor(&self) -> SceneDescriptor {
+        SceneDescriptor {
+            objects: self
+                .objects
                 .iter()
-                .for_each(|e| log::warn!("Failed while trying to load object: {}", e));
-        });
+                .map(|o| ObjectDescriptor::from(&o.object))
+                .collect(),
+        }
+    }
 
-        Self {
-            _emitter,
-            instances_system,
-            depth_texture,
-            objects,
-            camera_system,
-            light_system,
-            last_render_time: Instant::now(),
+    /// Frame times statistics over the last rendered frames
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_times.stats()
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Only draw the objects belonging to one of the masked layers
+    pub fn set_layer_mask(&mut self, layer_mask: u32) {
+        if self.layer_mask != layer_mask {
+            self.statics.invalidate();
         }
+        self.layer_mask = layer_mask;
+    }
+
+    /// Select the nearest object under a point of the screen (normalized device coordinates),
+    /// by its mesh bounding sphere; none if no object is there
+    pub fn pick(&mut self, ndc: (f32, f32)) {
+        let ray = self.camera_system.ray(ndc);
+        let picked = self
+            .objects
+            .iter()
+            .filter(|o| o.object.in_layers(self.layer_mask))
+            .filter_map(|o| {
+                let object = &o.object;
+                let sphere = o.model.mesh.bounding_sphere().transformed(
+                    object.position,
+                    object.orientation,
+                    object.mesh_scale,
+                );
+                sphere.ray_distance(&ray).map(|distance| (distance, o))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, o)| o.name());
+        log::debug!(target: "event", "Picked {:?}", picked);
+        self.outline.select(picked);
     }
 
+    /// Set the animation speed factor (negative values are clamped to 0)
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    /// Write the visible objects instances grouped by model (one instances range per model, or
+    /// the model dedicated instances buffer).
+    ///
+    /// The dynamic objects out of the camera view volume (by their mesh bounding sphere) are
+    /// skipped. The static objects instances are only written when they changed, and are not
+    /// culled (the models with a dedicated buffer have all their instances written every
+    /// frame).
     fn update_instance_system(&mut self, wgpu: &WgpuState) {
-        let mut instances = vec![];
-        //let mut i = 0;
-        for o in &self.objects {
-            log::debug!("Object: {:?}", o.name());
-            instances.push(InstanceRaw::from(&o.object));
-            //i += 1;
+        let frustum = self.camera_system.frustum();
+        let InstancesRanges {
+            statics,
+            dynamics: mut ranges,
+        } = gather_in_layers(&mut self.statics, &self.objects, self.layer_mask, |o| {
+            let object = &o.object;
+            frustum.intersects(&o.model.mesh.bounding_sphere().transformed(
+                object.position,
+                object.orientation,
+                object.mesh_scale,
+            ))
+        });
+        let models = wgpu.store.models().unwrap_or_else(|e| {
+            log::error!("Cannot update the models instances: {}", e);
+            vec![]
+        });
+        for model in models {
+            if let Some(model_instances) = &model.instances {
+                let instances = ranges
+                    .iter()
+                    .position(|(name, _)| name == &model.name)
+                    .map(|index| ranges.remove(index).1)
+                    .unwrap_or_default();
+                model_instances.set_instances(&instances, &wgpu.queue);
+            }
+        }
+        if let Some(static_ranges) = statics {
+            self.instances_system
+                .set_static_instances_ranges(static_ranges, &wgpu.queue);
         }
         self.instances_system
-            .set_instances_raw(instances, &wgpu.queue);
-        log::debug!("total instances count : {}", self.instances_system.count());
+            .set_instances_ranges(ranges, &wgpu.queue);
+        log::trace!("total instances count : {}", self.instances_system.count());
+    }
+
+    /// Log the statistics summary if its interval elapsed
+    fn log_stats(&mut self, wgpu: &WgpuState, now: Instant, models: &[Rc<Model>]) {
+        if !self.stats_log.as_mut().is_some_and(|log| log.due(now)) {
+            return;
+        }
+        // one draw per geometry and instances range of the models
+        let draws: usize = models
+            .iter()
+            .map(|model| {
+                model.mesh.geometries.len() * self.instances_system.model_slices(model).len()
+            })
+            .sum();
+        let instances = self.instances_system.count()
+            + models
+                .iter()
+                .filter_map(|model| model.instances.as_ref())
+                .map(|instances| instances.count())
+                .sum::<u32>();
+        match wgpu.store.counts() {
+            Ok(counts) => log::info!(
+                "Scene: {} models, {} meshes, {} materials, {} textures, {} pipelines resident",
+                counts.models,
+                counts.meshes,
+                counts.materials,
+                counts.textures,
+                counts.pipelines
+            ),
+            Err(e) => log::error!("Cannot count the stored resources: {}", e),
+        }
+        let frames = self.frame_stats();
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        log::info!(
+            "Scene: {} objects, {} instances, {} draws, {:.2}ms average frame time \
+            ({:.2}ms 1% low, {:.2}ms max)",
+            self.objects.len(),
+            instances,
+            draws,
+            millis(frames.avg),
+            millis(frames.low_1_percent),
+            millis(frames.max)
+        );
     }
 
     pub(crate) fn resize(&mut self, wgpu_state: &WgpuState) {
-        // self.projection.resize(size);
-        self.depth_texture = texture::Texture::create_depth_texture(
-            &wgpu_state.device,
-            &wgpu_state.config,
+        self.camera_system
+            .resize(wgpu_state.config.width, wgpu_state.config.height);
+        let (depth_texture, msaa_target) = Self::create_targets(wgpu_state);
+        self.depth_texture = depth_texture;
+        self.msaa_target = msaa_target;
+        if let Some(depth_debug) = &mut self.depth_debug {
+            depth_debug.set_depth_texture(&wgpu_state.device, &self.depth_texture);
+        }
+        if let Some(fxaa) = &mut self.fxaa {
+            fxaa.resize(&wgpu_state.device, &wgpu_state.queue, &wgpu_state.config);
+        }
+        let (width, height) = (wgpu_state.config.width, wgpu_state.config.height);
+        self.debug_normals.resize(&wgpu_state.queue, width, height);
+        self.debug_bounds.resize(&wgpu_state.queue, width, height);
+    }
+
+    /// Depth texture and, with MSAA, multisampled color target of the scene, of the surface
+    /// size
+    fn create_targets(wgpu: &WgpuState) -> (Texture, Option<Texture>) {
+        let multisampled = wgpu.sample_count > 1;
+        let depth_texture = texture::Texture::create_depth_texture(
+            &wgpu.device,
+            &wgpu.config,
+            wgpu.depth_format,
+            wgpu.sample_count,
             "depth_texture",
+            // read by the depth debug view, at any time (see `PomarinEvent::ToggleDepthView`)
+            if multisampled {
+                DepthSamplerKind::None
+            } else {
+                DepthSamplerKind::NonFiltering
+            },
         );
+        let msaa_target = multisampled.then(|| {
+            Texture::create_multisampled_target(
+                &wgpu.device,
+                &wgpu.config,
+                wgpu.sample_count,
+                "msaa_target",
+            )
+        });
+        (depth_texture, msaa_target)
+    }
+
+    /// The anti-aliasing pass for the requested mode, with the mode actually applied.
+    ///
+    /// MSAA is set at startup (see `WgpuState::sample_count`): a multisampled scene keeps it
+    /// whatever the request, a single sampled one can't switch to it.
+    fn anti_aliasing(
+        wgpu: &WgpuState,
+        requested: AntiAliasing,
+    ) -> (Option<FxaaPass>, AntiAliasing) {
+        if wgpu.sample_count > 1 {
+            if !matches!(requested, AntiAliasing::Msaa(_)) {
+                log::warn!(
+                    "MSAA x{} is set at startup, {:?} not applied",
+                    wgpu.sample_count,
+                    requested
+                );
+            }
+            return (None, AntiAliasing::Msaa(wgpu.sample_count));
+        }
+        match requested {
+            AntiAliasing::Fxaa => (
+                Some(FxaaPass::new(&wgpu.device, &wgpu.config)),
+                AntiAliasing::Fxaa,
+            ),
+            AntiAliasing::Msaa(samples) if samples > 1 => {
+                log::warn!("MSAA is set at startup, restart to apply it");
+                (None, AntiAliasing::None)
+            }
+            AntiAliasing::Msaa(_) | AntiAliasing::None => (None, AntiAliasing::None),
+        }
+    }
+
+    /// Apply the scene related settings (anti-aliasing, background color).
+    /// Returns the settings as applied, an unsupported anti-aliasing is disabled.
+    pub(crate) fn apply_settings(
+        &mut self,
+        wgpu: &WgpuState,
+        settings: RenderSettings,
+    ) -> RenderSettings {
+        let current = match self.fxaa {
+            Some(_) => AntiAliasing::Fxaa,
+            None if wgpu.sample_count > 1 => AntiAliasing::Msaa(wgpu.sample_count),
+            None => AntiAliasing::None,
+        };
+        let anti_aliasing = if settings.anti_aliasing == current {
+            current
+        } else {
+            let (fxaa, applied) = Self::anti_aliasing(wgpu, settings.anti_aliasing);
+            self.fxaa = fxaa;
+            applied
+        };
+        self.clear_color = settings.clear_color;
+        RenderSettings {
+            anti_aliasing,
+            ..settings
+        }
+    }
+
+    /// Switch the dock floodlight off (black) or back on
+    fn toggle_floodlight(&mut self) {
+        if let Some((index, spot_light)) = self.floodlight {
+            self.floodlight_on = !self.floodlight_on;
+            let spot_light = if self.floodlight_on {
+                spot_light
+            } else {
+                spot_light.switched_off()
+            };
+            self.light_system.set_spot_light(index, spot_light);
+        }
     }
 
     pub(crate) fn handle_event(&mut self, ref event: PomarinEvent) {
@@ -158,11 +1186,44 @@ impl ScenePass {
             PomarinEvent::SomeEvent => {
                 log::info!(target: "event", "some user event");
             }
+            PomarinEvent::SetPaused(paused) => self.set_paused(*paused),
+            PomarinEvent::SetLayerMask(layer_mask) => self.set_layer_mask(*layer_mask),
+            PomarinEvent::SetTimeScale(time_scale) => self.set_time_scale(*time_scale),
+            PomarinEvent::SetSpray {
+                spawn_rate,
+                lifetime,
+            } => {
+                self.particles.set_spawn_rate(*spawn_rate);
+                self.particles.set_lifetime(*lifetime);
+            }
+            PomarinEvent::ToggleDepthView if self.depth_debug.is_none() => {
+                log::warn!("The depth view can't read the multisampled depth texture (MSAA)")
+            }
+            PomarinEvent::ToggleDepthView => self.show_depth = !self.show_depth,
+            PomarinEvent::ToggleWireframe => self.show_wireframe = !self.show_wireframe,
+            PomarinEvent::TogglePipelineColors => {
+                self.show_pipeline_colors = !self.show_pipeline_colors
+            }
+            PomarinEvent::ResetCamera => self.camera_system.reset(self.reset_easing),
+            PomarinEvent::CycleCamera if !self.camera_system.cycle_controller() => {
+                log::info!("No other camera controller registered");
+            }
+            PomarinEvent::CycleDebugNormals => self.cycle_debug_normals = true,
+            PomarinEvent::ToggleLabels => self.show_labels = !self.show_labels,
+            PomarinEvent::ToggleLightGizmos => self.show_light_gizmos = !self.show_light_gizmos,
+            PomarinEvent::ToggleHeadlight => self.light_system.toggle_headlight(),
+            PomarinEvent::ToggleFloodlight => self.toggle_floodlight(),
             _ => {}
         };
     }
+}
 
-    pub(crate) fn render(
+impl WgpuRpass for ScenePass {
+    /// Clear `output_view` and draw the scene into it.
+    ///
+    /// `output_view` is the final single sampled target: a multisampled scene must resolve into
+    /// it, the ui is composited over it afterwards.
+    fn render(
         &mut self,
         wgpu: &WgpuState,
         _window: &winit::window::Window,
@@ -170,33 +1231,106 @@ impl ScenePass {
         mut encoder: wgpu::CommandEncoder,
     ) -> wgpu::CommandEncoder {
         let now = std::time::Instant::now();
-        let dt = now - self.last_render_time;
+        let frame_time = now - self.last_render_time;
+        self.frame_times.push(frame_time);
+        // always advance the render time so that resuming does not jump
+        let dt = scaled_dt(frame_time, self.time_scale, self.paused);
         self.last_render_time = now;
-        self.update_instance_system(wgpu);
+        self.process_commands(wgpu);
+        if std::mem::take(&mut self.cycle_debug_normals) {
+            self.show_next_normals(wgpu);
+        }
+        wgpu.store
+            .texture_stream
+            .upload_decoded(&wgpu.store, &wgpu.queue);
+        self.globals.update(&wgpu.queue, dt);
+        let scene_time = self.globals.time();
+        self.objects
+            .iter()
+            .flat_map(|o| o.model.materials.iter())
+            .for_each(|material| material.animate(&wgpu.queue, scene_time));
         self.camera_system.update(&wgpu.queue, dt);
-        self.light_system.update(&wgpu.queue, dt);
-        let objects = self.objects.iter().map(|o| &o.model).collect();
-        //
+        self.light_system
+            .update(&wgpu.queue, dt, self.camera_system.position());
+        if let Some(gizmos) = self
+            .light_gizmos
+            .as_mut()
+            .filter(|_| self.show_light_gizmos)
+        {
+            gizmos.update(&wgpu.queue, &self.light_system.sources());
+        }
+        self.update_instance_system(wgpu);
+        self.update_debug_bounds(wgpu);
+        // the hidden objects are not outlined
+        let layer_mask = self.layer_mask;
+        self.outline.update(
+            self.objects
+                .iter()
+                .filter(|o| o.object.in_layers(layer_mask))
+                .map(|o| (&o.object, &o.model)),
+        );
+        self.update_labels(wgpu);
+        // the particles are advanced before being drawn in the scene pass
+        let aspect = wgpu.config.width as f32 / wgpu.config.height.max(1) as f32;
+        self.particles
+            .update(&wgpu.queue, &mut encoder, &self.globals.bind_group, aspect);
+        // the models without instances are skipped when drawing
+        let models = wgpu.store.models_sorted().unwrap_or_else(|e| {
+            log::error!("Cannot draw the models: {}", e);
+            vec![]
+        });
+        self.log_stats(wgpu, now, &models);
+        if self.show_pipeline_colors {
+            self.pipeline_colors.update(&wgpu.device, &models);
+        }
+        if let Some(depth_prepass) = &self.depth_prepass {
+            depth_prepass.draw(
+                &mut encoder,
+                &self.depth_texture,
+                &models,
+                &self.instances_system,
+                &self.camera_system.bind_group,
+                &self.selection,
+            );
+        }
+        // with FXAA, the scene is drawn offscreen then smoothed into the output view
+        let scene_view = self
+            .fxaa
+            .as_ref()
+            .map_or(output_view, |fxaa| fxaa.target_view());
+        // with MSAA, the scene is drawn multisampled then resolved into the scene view
+        let (view, resolve_target) = match &self.msaa_target {
+            Some(msaa_target) => (&msaa_target.view, Some(scene_view)),
+            None => (scene_view, None),
+        };
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &output_view,
-                    resolve_target: None,
+                    view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.05,
-                            b: 0.1,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(color::clear_color(
+                            [
+                                self.clear_color[0],
+                                self.clear_color[1],
+                                self.clear_color[2],
+                                1.0,
+                            ],
+                            wgpu.config.format,
+                        )),
                         store: true,
                     },
                 }],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        // keep the depth written by the prepass
+                        load: if self.depth_prepass.is_some() {
+                            wgpu::LoadOp::Load
+                        } else {
+                            wgpu::LoadOp::Clear(1.0)
+                        },
                         store: true,
                     }),
                     stencil_ops: None,
@@ -204,15 +1338,292 @@ impl ScenePass {
             });
 
             {
-                render_pass.set_vertex_buffer(1, self.instances_system.buffer().slice(..));
-
                 render_pass.draw_models(
-                    objects,
+                    models.iter().collect(),
+                    &self.instances_system,
                     &self.camera_system.bind_group,
                     &self.light_system.bind_group,
+                    DrawPhase::Opaque,
+                    &self.selection,
                 );
+                if self.show_pipeline_colors {
+                    self.pipeline_colors.draw(
+                        &mut render_pass,
+                        &models,
+                        &self.instances_system,
+                        &self.camera_system.bind_group,
+                        &self.selection,
+                    );
+                }
+                if let Some(wireframe) = self.wireframe.as_ref().filter(|_| self.show_wireframe) {
+                    wireframe.draw(
+                        &mut render_pass,
+                        &models,
+                        &self.instances_system,
+                        &self.camera_system.bind_group,
+                        &self.selection,
+                    );
+                }
+                self.debug_normals.draw(
+                    &mut render_pass,
+                    &self.instances_system,
+                    &self.camera_system.bind_group,
+                );
+                self.debug_bounds
+                    .draw(&mut render_pass, &self.camera_system.bind_group);
+                self.outline.draw(
+                    &mut render_pass,
+                    &wgpu.queue,
+                    &self.camera_system.bind_group,
+                    &self.light_system.bind_group,
+                );
+                if let Some(gizmos) = self
+                    .light_gizmos
+                    .as_ref()
+                    .filter(|_| self.show_light_gizmos)
+                {
+                    gizmos.draw(
+                        &mut render_pass,
+                        &self.camera_system.bind_group,
+                        &self.light_system.bind_group,
+                    );
+                }
+                // blended: drawn after the opaque models, already flat colored with the pipeline
+                // colors
+                if !self.show_pipeline_colors {
+                    render_pass.draw_models(
+                        self.back_to_front(&models),
+                        &self.instances_system,
+                        &self.camera_system.bind_group,
+                        &self.light_system.bind_group,
+                        DrawPhase::Transparent,
+                        &self.selection,
+                    );
+                }
+                self.particles
+                    .draw(&mut render_pass, &self.camera_system.bind_group);
+                self.billboards
+                    .draw(&mut render_pass, &self.camera_system.bind_group);
             }
         }
+        if let Some(fxaa) = &self.fxaa {
+            fxaa.draw(&mut encoder, output_view);
+        }
+        if let Some(gnomon) = &self.gnomon {
+            gnomon.update(&wgpu.queue, self.camera_system.view());
+            gnomon.draw(&mut encoder, output_view, &wgpu.config);
+        }
+        if let Some(depth_debug) = self.depth_debug.as_ref().filter(|_| self.show_depth) {
+            // the scene pass filled the depth texture, show it over the scene
+            depth_debug.draw(&mut encoder, output_view, &self.camera_system.bind_group);
+        }
         encoder
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Rotation3;
+
+    use super::super::command::Transform;
+    use super::*;
+
+    #[test]
+    fn paused_frames_do_not_advance_the_animations() {
+        let frame = Duration::from_secs(1);
+        assert_eq!(scaled_dt(frame, 2.0, true), Duration::ZERO);
+        assert_eq!(scaled_dt(frame, 2.0, false), Duration::from_secs(2));
+        assert_eq!(scaled_dt(frame, 1.0, false), frame);
+    }
+
+    /// An object linked to its model name only
+    #[derive(Debug)]
+    struct Placed {
+        object: Object,
+        model: String,
+    }
+
+    impl Instanced<InstanceRaw> for Placed {
+        fn model_name(&self) -> &str {
+            &self.model
+        }
+
+        fn usage(&self) -> InstanceUsage {
+            self.object.usage
+        }
+
+        fn instance(&self) -> InstanceRaw {
+            InstanceRaw::from(&self.object)
+        }
+    }
+
+    impl SceneObject for Placed {
+        fn object(&self) -> &Object {
+            &self.object
+        }
+
+        fn object_mut(&mut self) -> &mut Object {
+            &mut self.object
+        }
+    }
+
+    fn place(object: Object) -> Result<Placed> {
+        let model = object.model().as_str().to_string();
+        Ok(Placed { object, model })
+    }
+
+    /// Apply the commands sent, as the pass does before rendering
+    fn apply_sent(
+        objects: &mut Vec<Placed>,
+        statics: &mut StaticsTracker,
+        commands: Vec<SceneCommand>,
+    ) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        commands
+            .into_iter()
+            .for_each(|command| sender.send(command).unwrap());
+        while let Ok(command) = receiver.try_recv() {
+            apply_to_objects(objects, statics, command, place);
+        }
+    }
+
+    #[test]
+    fn ground_is_the_highest_box_top_over_the_point() {
+        let boxes = [
+            BoundingBox::new((-10.0, -5.0, -10.0).into(), (10.0, 2.0, 10.0).into()),
+            BoundingBox::new((0.0, 0.0, 0.0).into(), (4.0, 6.0, 4.0).into()),
+        ];
+        assert_eq!(ground_height(&boxes, -5.0, -5.0), 2.0);
+        assert_eq!(ground_height(&boxes, 2.0, 2.0), 6.0);
+        // the sea plane around
+        assert_eq!(ground_height(&boxes, 20.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn spawned_object_is_linked() {
+        let mut objects = vec![];
+        let transform = Transform {
+            position: cgmath::Vector3::new(1.0, 2.0, 3.0),
+            ..Default::default()
+        };
+        apply_sent(
+            &mut objects,
+            &mut StaticsTracker::default(),
+            vec![SceneCommand::Spawn {
+                name: "rock".to_string(),
+                model: ModelName::from("color_zod"),
+                transform,
+            }],
+        );
+
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].object.name(), "rock");
+        assert_eq!(objects[0].model, "color_zod");
+        assert_eq!(objects[0].object.position, transform.position);
+    }
+
+    #[test]
+    fn orientation_keeps_the_position_and_scale() {
+        let mut object = Object::new("zodiac".to_string(), ModelName::from("color_zod"));
+        object.set_position((10.0, 0.0, 10.0));
+        object.mesh_scale = 2.0;
+        let mut objects = vec![place(object).unwrap()];
+        let orientation = cgmath::Quaternion::from_angle_z(cgmath::Deg(5.0));
+        apply_sent(
+            &mut objects,
+            &mut StaticsTracker::default(),
+            vec![SceneCommand::SetOrientation {
+                name: "zodiac".to_string(),
+                orientation,
+            }],
+        );
+
+        let object = &objects[0].object;
+        assert_eq!(object.orientation, orientation);
+        assert_eq!(object.position, cgmath::Vector3::new(10.0, 0.0, 10.0));
+        assert_eq!(object.mesh_scale, 2.0);
+    }
+
+    #[test]
+    fn moving_a_dynamic_object_keeps_the_static_instances() {
+        let mut sea = Object::new("sea".to_string(), ModelName::from("sea_square"));
+        sea.usage = InstanceUsage::Static;
+        let zodiac = Object::new("zodiac".to_string(), ModelName::from("color_zod"));
+        let mut objects = vec![place(sea).unwrap(), place(zodiac).unwrap()];
+        let mut statics = StaticsTracker::default();
+        statics.gather(&objects, |_| true);
+        let moved = |name: &str| SceneCommand::SetTransform {
+            name: name.to_string(),
+            transform: Transform::default(),
+        };
+
+        apply_sent(&mut objects, &mut statics, vec![moved("zodiac")]);
+        let ranges = statics.gather(&objects, |_| true);
+        assert_eq!(ranges.statics.map(|statics| statics.len()), None);
+        assert_eq!(ranges.dynamics.len(), 1);
+
+        apply_sent(&mut objects, &mut statics, vec![moved("sea")]);
+        let ranges = statics.gather(&objects, |_| true);
+        assert_eq!(ranges.statics.map(|statics| statics.len()), Some(1));
+    }
+
+    #[test]
+    fn failing_objects_are_reported_and_the_others_linked() {
+        let objects = vec![
+            Object::new("bad".to_string(), ModelName::from("missing")),
+            Object::new("good".to_string(), ModelName::from("color_zod")),
+        ];
+        let (linked, error) = link_all(objects, |object| {
+            if object.name() == "bad" {
+                Err(anyhow!("model missing not found in assets"))
+            } else {
+                Ok(object.name())
+            }
+        });
+
+        assert_eq!(linked, vec!["good".to_string()]);
+        match error {
+            Some(SceneError::ObjectsNotLoaded { total, failures }) => {
+                assert_eq!(total, 2);
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].0, "bad");
+            }
+            other => panic!("expected the objects failures, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn masked_out_layers_are_not_instanced() {
+        let mut rock = Object::new("rock".to_string(), ModelName::from("rock"));
+        rock.usage = InstanceUsage::Static;
+        rock.layers = 1 << 1;
+        let mut debug = Object::new("debug".to_string(), ModelName::from("marker"));
+        debug.layers = 1 << 1;
+        let zodiac = Object::new("zodiac".to_string(), ModelName::from("color_zod"));
+        let objects = vec![
+            place(rock).unwrap(),
+            place(debug).unwrap(),
+            place(zodiac).unwrap(),
+        ];
+        let model_names = |ranges: &[(String, Vec<InstanceRaw>)]| {
+            ranges
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>()
+        };
+        let mut statics = StaticsTracker::default();
+
+        let all = gather_in_layers(&mut statics, &objects, ALL_LAYERS, |_| true);
+        assert_eq!(
+            all.statics.as_deref().map(model_names),
+            Some(vec!["rock".to_string()])
+        );
+        assert_eq!(model_names(&all.dynamics), ["marker", "color_zod"]);
+
+        // a mask change invalidates the static instances (see `ScenePass::set_layer_mask`)
+        statics.invalidate();
+        let masked = gather_in_layers(&mut statics, &objects, DEFAULT_LAYERS, |_| true);
+        assert_eq!(masked.statics.as_deref().map(model_names), Some(vec![]));
+        assert_eq!(model_names(&masked.dynamics), ["color_zod"]);
+    }
+}
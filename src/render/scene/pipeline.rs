@@ -1,8 +1,13 @@
-use std::ops::Deref;
+use std::{ops::Deref, path::Path};
+
+use crate::render::error::PipelineError;
 
 use super::{
-    color_material::ColorMaterial, instance::InstanceRaw, material::MaterialKind, texture,
-    texture_material::TextureMaterial, vertex::ModelVertex,
+    billboard::BillboardVertex, color_material::ColorMaterial, color_materials::ColorMaterials,
+    gnomon::GnomonVertex, instance::InstanceRaw, light_gizmo::GizmoInstance,
+    material::MaterialKind, overrides::ModelOverridesBuf, particles::Particle,
+    texture_material::TextureMaterial, thick_line::ThickLineVertex, unlit_material::UnlitMaterial,
+    vertex::ModelVertex,
 };
 
 #[derive(Debug)]
@@ -10,6 +15,17 @@ pub struct NamedPipeline {
     name: String,
     pipeline: wgpu::RenderPipeline,
     supported_material_kind: Vec<MaterialKind>,
+    /// whether the layout has the light group (1), the material group follows it
+    lit: bool,
+    /// whether the pipeline tests for the depth written by the depth prepass
+    depth_prepassed: bool,
+    /// whether the materials are read from a packed buffer at the per draw index (slot 2)
+    packed_materials: bool,
+    /// whether it draws the blended materials: after the opaque geometries, without writing
+    /// the depth
+    transparent: bool,
+    /// pipeline drawing the blended materials in place of this one
+    transparent_variant: Option<String>,
 }
 
 // TODO: PipelineName
@@ -24,15 +40,87 @@ impl NamedPipeline {
             name: name.as_ref().to_string(),
             pipeline,
             supported_material_kind: materials,
+            lit: true,
+            depth_prepassed: false,
+            packed_materials: false,
+            transparent: false,
+            transparent_variant: None,
+        }
+    }
+
+    /// The pipeline draws the blended materials (see `PipelineOptions::transparent()`)
+    pub fn transparent(mut self) -> Self {
+        self.transparent = true;
+        self
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Name of the pipeline drawing the blended materials instead of this one
+    pub fn with_transparent_variant<S: AsRef<str>>(mut self, name: S) -> Self {
+        self.transparent_variant = Some(name.as_ref().to_string());
+        self
+    }
+
+    pub fn transparent_variant(&self) -> Option<&str> {
+        self.transparent_variant.as_deref()
+    }
+
+    /// The material group is the packed materials buffer, the material index is bound to the
+    /// vertex buffer slot 2
+    pub fn packed_materials(mut self) -> Self {
+        self.packed_materials = true;
+        self
+    }
+
+    pub fn uses_packed_materials(&self) -> bool {
+        self.packed_materials
+    }
+
+    /// The pipeline was created with `PipelineOptions::depth_prepassed()`: its geometries are
+    /// drawn by the depth prepass
+    pub fn depth_prepassed(mut self, depth_prepassed: bool) -> Self {
+        self.depth_prepassed = depth_prepassed;
+        self
+    }
+
+    pub fn is_depth_prepassed(&self) -> bool {
+        self.depth_prepassed
+    }
+
+    /// The pipeline layout has no light group: the material is bound to the group 1
+    pub fn unlit(mut self) -> Self {
+        self.lit = false;
+        self
+    }
+
+    pub fn is_lit(&self) -> bool {
+        self.lit
+    }
+
+    /// Index of the material bind group in the pipeline layout
+    pub fn material_group(&self) -> u32 {
+        if self.lit {
+            2
+        } else {
+            1
         }
     }
 
+    /// Index of the model overrides bind group (see `ModelOverridesBuf`), following the material
+    /// one in the layouts of the pipelines drawing materials
+    pub fn overrides_group(&self) -> u32 {
+        self.material_group() + 1
+    }
+
     pub fn name(&self) -> String {
         self.name.to_string()
     }
 
-    // TODO; to be deleted after having a good way to check pipeline compat
-    pub fn _can_use(&self, material_kind: MaterialKind) -> bool {
+    /// Whether the pipeline bind group layouts accept this material kind
+    pub fn can_use(&self, material_kind: MaterialKind) -> bool {
         self.supported_material_kind
             .iter()
             .find(|mk| *mk == &material_kind)
@@ -52,14 +140,99 @@ impl Deref for NamedPipeline {
     }
 }
 
+/// Rasterization and depth test settings of a render pipeline
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineOptions {
+    /// `None` for the pipelines drawing without depth attachment
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub topology: wgpu::PrimitiveTopology,
+    /// `Line` and `Point` require the device feature `POLYGON_MODE_LINE`/`POLYGON_MODE_POINT`
+    pub polygon_mode: wgpu::PolygonMode,
+    pub cull_mode: Option<wgpu::Face>,
+    pub depth_write_enabled: bool,
+    pub depth_compare: wgpu::CompareFunction,
+    pub depth_bias: wgpu::DepthBiasState,
+    /// `None` replaces the target colors
+    pub blend: Option<wgpu::BlendState>,
+    /// samples count of the color and depth targets
+    pub sample_count: u32,
+}
+
+impl PipelineOptions {
+    /// Default options, testing a depth texture of the given format and samples count.
+    ///
+    /// All the pipelines drawing with the scene depth texture must use its format and samples
+    /// count (`WgpuState::depth_format`, `WgpuState::sample_count`) or the render passes fail
+    /// validation.
+    pub fn new(depth_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        Self {
+            depth_format: Some(depth_format),
+            sample_count,
+            ..Self::without_depth()
+        }
+    }
+
+    /// Default options of the pipelines drawing without depth attachment
+    pub fn without_depth() -> Self {
+        Self {
+            depth_format: None,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            cull_mode: Some(wgpu::Face::Back),
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_bias: wgpu::DepthBiasState::default(),
+            blend: Some(wgpu::BlendState {
+                alpha: wgpu::BlendComponent::REPLACE,
+                color: wgpu::BlendComponent::OVER,
+            }),
+            sample_count: 1,
+        }
+    }
+
+    /// Options of the pipelines drawing the blended materials: the depth is tested but not
+    /// written, so that the geometries drawn behind still show
+    pub fn transparent(self) -> Self {
+        Self {
+            depth_write_enabled: false,
+            ..self
+        }
+    }
+
+    /// Draw the back faces too (planes seen from both sides)
+    pub fn double_sided(self) -> Self {
+        Self {
+            cull_mode: None,
+            ..self
+        }
+    }
+
+    /// Options of the pipelines drawing over coplanar geometries (decals): the bias pulls them
+    /// towards the camera, without it the two surfaces z-fight.
+    ///
+    /// The biased depth can't match a prepass depth: a decal pipeline is never depth prepassed.
+    pub fn decal(self, depth_bias: wgpu::DepthBiasState) -> Self {
+        Self { depth_bias, ..self }
+    }
+
+    /// Options of the main pass pipelines when the depth was written by a depth prepass
+    pub fn depth_prepassed(self) -> Self {
+        Self {
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Equal,
+            ..self
+        }
+    }
+}
+
 pub fn create_render_pipeline<S: ToString>(
     name: S,
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
     color_format: wgpu::TextureFormat,
-    depth_format: Option<wgpu::TextureFormat>,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
+    options: PipelineOptions,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(&shader);
 
@@ -76,31 +249,28 @@ pub fn create_render_pipeline<S: ToString>(
             entry_point: "fs_main",
             targets: &[wgpu::ColorTargetState {
                 format: color_format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::OVER,
-                }),
+                blend: options.blend,
                 write_mask: wgpu::ColorWrites::ALL,
             }],
         }),
         primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
+            topology: options.topology,
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Fill,
+            cull_mode: options.cull_mode,
+            polygon_mode: options.polygon_mode,
             unclipped_depth: false,
             conservative: false,
         },
-        depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+        depth_stencil: options.depth_format.map(|format| wgpu::DepthStencilState {
             format,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
+            depth_write_enabled: options.depth_write_enabled,
+            depth_compare: options.depth_compare,
             stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
+            bias: options.depth_bias,
         }),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: options.sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -108,16 +278,68 @@ pub fn create_render_pipeline<S: ToString>(
     })
 }
 
+/// Pipeline drawing with a WGSL shader read from disk at runtime, so that it can be edited
+/// without rebuilding. The entry points are `vs_main` and `fs_main`.
+///
+/// The shader and pipeline validation errors are returned instead of reaching the device
+/// uncaptured errors handler (which panics).
+pub fn create_custom_pipeline<P: AsRef<Path>>(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    name: &str,
+    wgsl_path: P,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    options: PipelineOptions,
+) -> Result<wgpu::RenderPipeline, PipelineError> {
+    let wgsl_path = wgsl_path.as_ref();
+    let source =
+        std::fs::read_to_string(wgsl_path).map_err(|e| PipelineError::ShaderNotReadable {
+            pipeline: name.to_string(),
+            path: wgsl_path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{} layout", name)),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some(name),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    };
+    let pipeline = create_render_pipeline(
+        name,
+        device,
+        &layout,
+        config.format,
+        vertex_layouts,
+        shader,
+        options,
+    );
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(e) => Err(PipelineError::InvalidPipeline {
+            pipeline: name.to_string(),
+            reason: e.to_string(),
+        }),
+        None => Ok(pipeline),
+    }
+}
+
 pub fn create_textured_model_pipeline(
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
     camera_bgl: &wgpu::BindGroupLayout,
     light_bgl: &wgpu::BindGroupLayout,
+    options: PipelineOptions,
 ) -> wgpu::RenderPipeline {
     let textures_bgl = TextureMaterial::bind_group_layout(device);
+    let overrides_bgl = ModelOverridesBuf::bind_group_layout(device);
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Textured model render pipeline layout"),
-        bind_group_layouts: &[&camera_bgl, &light_bgl, &textures_bgl],
+        bind_group_layouts: &[&camera_bgl, &light_bgl, &textures_bgl, &overrides_bgl],
         push_constant_ranges: &[],
     });
     let shader = wgpu::ShaderModuleDescriptor {
@@ -129,9 +351,9 @@ pub fn create_textured_model_pipeline(
         &device,
         &render_pipeline_layout,
         config.format,
-        Some(texture::Texture::DEPTH_FORMAT),
         &[ModelVertex::desc(), InstanceRaw::desc()],
         shader,
+        options,
     )
 }
 
@@ -140,11 +362,13 @@ pub fn create_colored_model_pipeline(
     config: &wgpu::SurfaceConfiguration,
     camera_bgl: &wgpu::BindGroupLayout,
     light_bgl: &wgpu::BindGroupLayout,
+    options: PipelineOptions,
 ) -> wgpu::RenderPipeline {
     let colors_bgl = ColorMaterial::bind_group_layout(device);
+    let overrides_bgl = ModelOverridesBuf::bind_group_layout(device);
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Colored model render pipeline layout"),
-        bind_group_layouts: &[&camera_bgl, &light_bgl, &colors_bgl],
+        bind_group_layouts: &[&camera_bgl, &light_bgl, &colors_bgl, &overrides_bgl],
         push_constant_ranges: &[],
     });
     let shader = wgpu::ShaderModuleDescriptor {
@@ -156,16 +380,83 @@ pub fn create_colored_model_pipeline(
         &device,
         &render_pipeline_layout,
         config.format,
-        Some(texture::Texture::DEPTH_FORMAT),
         &[ModelVertex::desc(), InstanceRaw::desc()],
         shader,
+        options,
+    )
+}
+/// Colored pipeline shading with the faces normals, derived in the fragment shader from the
+/// world position derivatives (the vertex normals are ignored)
+pub fn create_flat_shaded_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    light_bgl: &wgpu::BindGroupLayout,
+    options: PipelineOptions,
+) -> wgpu::RenderPipeline {
+    let colors_bgl = ColorMaterial::bind_group_layout(device);
+    let overrides_bgl = ModelOverridesBuf::bind_group_layout(device);
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Flat shaded model render pipeline layout"),
+        bind_group_layouts: &[camera_bgl, light_bgl, &colors_bgl, &overrides_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Flat shaded model shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/flat.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Flat shaded render pipeline",
+        device,
+        &render_pipeline_layout,
+        config.format,
+        &[ModelVertex::desc(), InstanceRaw::desc()],
+        shader,
+        options,
     )
 }
+
+/// Colored pipeline reading the materials from the packed color materials
+pub fn create_packed_colored_model_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    light_bgl: &wgpu::BindGroupLayout,
+    options: PipelineOptions,
+) -> wgpu::RenderPipeline {
+    let colors_bgl = ColorMaterials::bind_group_layout(device);
+    let overrides_bgl = ModelOverridesBuf::bind_group_layout(device);
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Packed colored model render pipeline layout"),
+        bind_group_layouts: &[&camera_bgl, &light_bgl, &colors_bgl, &overrides_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Packed colored model shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/color_packed.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Packed colored render pipeline",
+        &device,
+        &render_pipeline_layout,
+        config.format,
+        &[
+            ModelVertex::desc(),
+            InstanceRaw::desc(),
+            ColorMaterials::index_desc(),
+        ],
+        shader,
+        options,
+    )
+}
+
 pub fn create_light_pipeline(
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
     camera_bgl: &wgpu::BindGroupLayout,
     light_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
 ) -> wgpu::RenderPipeline {
     let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Light Pipeline Layout"),
@@ -181,8 +472,611 @@ pub fn create_light_pipeline(
         &device,
         &layout,
         config.format,
-        Some(texture::Texture::DEPTH_FORMAT),
-        &[ModelVertex::desc()],
+        &[ModelVertex::desc(), GizmoInstance::desc()],
+        shader,
+        PipelineOptions::new(depth_format, sample_count),
+    )
+}
+
+/// Flat colored models: the layout has no light group, the material is the group 1
+pub fn create_unlit_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    options: PipelineOptions,
+) -> wgpu::RenderPipeline {
+    let unlit_bgl = UnlitMaterial::bind_group_layout(device);
+    let overrides_bgl = ModelOverridesBuf::bind_group_layout(device);
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Unlit model render pipeline layout"),
+        bind_group_layouts: &[camera_bgl, &unlit_bgl, &overrides_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Unlit model shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/unlit.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Unlit render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[ModelVertex::desc(), InstanceRaw::desc()],
         shader,
+        options,
     )
 }
+
+/// Axes indicator lines, drawn over the scene without depth test
+pub fn create_gnomon_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    gnomon_bgl: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Gnomon pipeline layout"),
+        bind_group_layouts: &[gnomon_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Gnomon shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/gnomon.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Gnomon render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[GnomonVertex::desc()],
+        shader,
+        PipelineOptions {
+            topology: wgpu::PrimitiveTopology::LineList,
+            cull_mode: None,
+            ..PipelineOptions::without_depth()
+        },
+    )
+}
+
+/// Fullscreen anti-aliasing of the scene image
+pub fn create_fxaa_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    fxaa_bgl: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("FXAA pipeline layout"),
+        bind_group_layouts: &[fxaa_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("FXAA shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/fxaa.wgsl").into()),
+    };
+    create_render_pipeline(
+        "FXAA render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[],
+        shader,
+        PipelineOptions::without_depth(),
+    )
+}
+
+/// Fullscreen triangle darkening the frame edges, blended over the output view
+pub fn create_vignette_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    vignette_bgl: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Vignette pipeline layout"),
+        bind_group_layouts: &[vignette_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Vignette shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/vignette.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Vignette render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[],
+        shader,
+        PipelineOptions::without_depth(),
+    )
+}
+
+/// Fullscreen view of the depth texture, the camera uniform gives the clip planes used to
+/// linearize the depth values
+pub fn create_depth_debug_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    depth_bgl: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Depth debug pipeline layout"),
+        bind_group_layouts: &[camera_bgl, depth_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Depth debug shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/depth_debug.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Depth debug render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[],
+        shader,
+        PipelineOptions::without_depth(),
+    )
+}
+
+/// Compute pipeline advancing the particles state
+pub fn create_particles_update_pipeline(
+    device: &wgpu::Device,
+    particles_bgl: &wgpu::BindGroupLayout,
+    global_bgl: &wgpu::BindGroupLayout,
+) -> wgpu::ComputePipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Particles update pipeline layout"),
+        bind_group_layouts: &[particles_bgl, global_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("Particles update shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/particles_update.wgsl").into()),
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Particles update pipeline"),
+        layout: Some(&layout),
+        module: &shader,
+        entry_point: "cs_main",
+    })
+}
+
+/// Particles drawn as blended camera facing quads: depth tested, but not written
+pub fn create_particles_draw_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    particles_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Particles draw pipeline layout"),
+        bind_group_layouts: &[camera_bgl, particles_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("Particles draw shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/particles_draw.wgsl").into()),
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Particles draw pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[Particle::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: config.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// Edges of the geometries drawn over their shaded surface: the depth bias moves the lines in
+/// front of the surface, the depth is not written
+pub fn create_wireframe_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    wireframe_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_bias: wgpu::DepthBiasState,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Wireframe pipeline layout"),
+        bind_group_layouts: &[camera_bgl, wireframe_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Wireframe shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/wireframe.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Wireframe render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[ModelVertex::desc(), InstanceRaw::desc()],
+        shader,
+        PipelineOptions {
+            polygon_mode: wgpu::PolygonMode::Line,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            depth_bias,
+            ..PipelineOptions::new(depth_format, sample_count)
+        },
+    )
+}
+
+/// Geometries filled with a flat color over their shaded surface (see `PipelineColorOverlay`),
+/// with the wireframe shader: the depth bias moves the surface in front of the shaded one
+pub fn create_pipeline_color_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    color_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_bias: wgpu::DepthBiasState,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Pipeline color pipeline layout"),
+        bind_group_layouts: &[camera_bgl, color_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Pipeline color shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/wireframe.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Pipeline color render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[ModelVertex::desc(), InstanceRaw::desc()],
+        shader,
+        PipelineOptions {
+            cull_mode: None,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            depth_bias,
+            blend: None,
+            ..PipelineOptions::new(depth_format, sample_count)
+        },
+    )
+}
+
+/// Outline of a model (see `SelectionOutline`): the back faces of the geometries pushed out
+/// along their normals, tested against the scene depth without writing it. Unlit and without
+/// material, the camera is the only group
+pub fn create_outline_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Outline pipeline layout"),
+        bind_group_layouts: &[camera_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Outline shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/outline.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Outline render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[ModelVertex::desc(), InstanceRaw::desc()],
+        shader,
+        PipelineOptions {
+            cull_mode: Some(wgpu::Face::Front),
+            depth_write_enabled: false,
+            blend: None,
+            ..PipelineOptions::new(depth_format, sample_count)
+        },
+    )
+}
+
+/// Lines drawn as camera facing quads (see `ThickLineVertex`), transformed by the models
+/// instances and blended over the scene for the antialiased edges
+pub fn create_thick_line_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    line_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Thick line pipeline layout"),
+        bind_group_layouts: &[camera_bgl, line_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Thick line shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/thick_line.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Thick line render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[ThickLineVertex::desc(), InstanceRaw::desc()],
+        shader,
+        PipelineOptions {
+            cull_mode: None,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            ..PipelineOptions::new(depth_format, sample_count)
+        },
+    )
+}
+
+/// Camera facing textured quads, blended over the scene
+pub fn create_billboard_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bgl: &wgpu::BindGroupLayout,
+    texture_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Billboard pipeline layout"),
+        bind_group_layouts: &[camera_bgl, texture_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Billboard shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/billboard.wgsl").into()),
+    };
+    create_render_pipeline(
+        "Billboard render pipeline",
+        device,
+        &layout,
+        config.format,
+        &[BillboardVertex::desc()],
+        shader,
+        PipelineOptions {
+            cull_mode: None,
+            depth_write_enabled: false,
+            ..PipelineOptions::new(depth_format, sample_count)
+        },
+    )
+}
+
+/// Depth only pipeline of the depth prepass (no fragment stage), same vertex layouts as the
+/// models pipelines
+pub fn create_depth_prepass_pipeline(
+    device: &wgpu::Device,
+    camera_bgl: &wgpu::BindGroupLayout,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Depth prepass pipeline layout"),
+        bind_group_layouts: &[camera_bgl],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("Depth prepass shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/depth_prepass.wgsl").into()),
+    });
+    let options = PipelineOptions::new(depth_format, sample_count);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Depth prepass pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[ModelVertex::desc(), InstanceRaw::desc()],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: options.cull_mode,
+            polygon_mode: options.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        camera::CameraSystem,
+        light,
+        texture::{DepthSamplerKind, Texture},
+    };
+    use super::*;
+
+    fn surface_config() -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 4,
+            height: 4,
+            present_mode: wgpu::PresentMode::Fifo,
+        }
+    }
+
+    /// The value built, or the validation errors raised while building it
+    fn validated<T>(device: &wgpu::Device, build: impl FnOnce() -> T) -> Result<T, String> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let built = build();
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(e) => Err(e.to_string()),
+            None => Ok(built),
+        }
+    }
+
+    #[test]
+    fn shader_read_at_runtime_builds_a_pipeline() {
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = surface_config();
+        let tests_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/tests");
+        let build = |shader: &str| {
+            create_custom_pipeline(
+                &device,
+                &config,
+                "plain_pipeline",
+                tests_directory.join(shader),
+                &[],
+                &[],
+                PipelineOptions::without_depth(),
+            )
+        };
+        assert!(build("plain.wgsl").is_ok());
+        assert!(matches!(
+            build("missing.wgsl"),
+            Err(PipelineError::ShaderNotReadable { pipeline, .. }) if pipeline == "plain_pipeline"
+        ));
+    }
+
+    #[test]
+    fn prepass_and_prepassed_pipelines_share_the_vertex_layouts() {
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = surface_config();
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        let light_bgl = light::bind_group_layout(&device);
+        let depth_format = wgpu::TextureFormat::Depth32Float;
+        let options = PipelineOptions::new(depth_format, 1).depth_prepassed();
+        assert!(!options.depth_write_enabled);
+        assert_eq!(options.depth_compare, wgpu::CompareFunction::Equal);
+
+        // the shaders inputs are checked against the same models vertex buffers
+        validated(&device, || {
+            create_depth_prepass_pipeline(&device, &camera_bgl, depth_format, 1);
+            create_textured_model_pipeline(&device, &config, &camera_bgl, &light_bgl, options);
+            create_colored_model_pipeline(&device, &config, &camera_bgl, &light_bgl, options);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn scene_pipelines_use_the_configured_depth_format() {
+        let (device, queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = surface_config();
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        let light_bgl = light::bind_group_layout(&device);
+        // not the default one
+        let depth_format = wgpu::TextureFormat::Depth24Plus;
+        let options = PipelineOptions::new(depth_format, 1);
+        let pipelines = [
+            create_textured_model_pipeline(&device, &config, &camera_bgl, &light_bgl, options),
+            create_colored_model_pipeline(&device, &config, &camera_bgl, &light_bgl, options),
+            create_unlit_pipeline(&device, &config, &camera_bgl, options),
+            create_light_pipeline(&device, &config, &camera_bgl, &light_bgl, depth_format, 1),
+            create_outline_pipeline(&device, &config, &camera_bgl, depth_format, 1),
+            create_depth_prepass_pipeline(&device, &camera_bgl, depth_format, 1),
+        ];
+        let depth = Texture::create_depth_texture(
+            &device,
+            &config,
+            depth_format,
+            1,
+            "depth",
+            DepthSamplerKind::None,
+        );
+        let output = Texture::create_render_target(&device, "output", 4, 4, config.format);
+
+        // a pipeline depth format differing from the depth attachment one fails validation
+        validated(&device, || {
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &output.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations::default(),
+                    }],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth.view,
+                        depth_ops: Some(wgpu::Operations::default()),
+                        stencil_ops: None,
+                    }),
+                });
+                for pipeline in &pipelines {
+                    render_pass.set_pipeline(pipeline);
+                }
+            }
+            queue.submit(Some(encoder.finish()));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn flat_shaded_pipeline_builds_on_the_models_vertex_layouts() {
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = surface_config();
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        let light_bgl = light::bind_group_layout(&device);
+        let options = PipelineOptions::new(wgpu::TextureFormat::Depth32Float, 1);
+        validated(&device, || {
+            create_flat_shaded_pipeline(&device, &config, &camera_bgl, &light_bgl, options)
+        })
+        .unwrap();
+    }
+}
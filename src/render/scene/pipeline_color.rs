@@ -0,0 +1,187 @@
+use std::{collections::HashMap, rc::Rc};
+
+use wgpu::util::DeviceExt;
+
+use super::{
+    draw_ext::DrawSelection,
+    instance::{InstanceRaw, InstancesSystem},
+    model::Model,
+    pipeline::create_pipeline_color_pipeline,
+};
+
+/// Bias pulling the flat surfaces towards the camera, so that they pass the depth test against
+/// the shaded surface they were drawn from
+const PIPELINE_COLOR_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: -4,
+    slope_scale: -1.0,
+    clamp: 0.0,
+};
+
+/// FNV-1a hash of the pipeline name: unlike the std hashers, stable across builds and runs
+pub fn pipeline_name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Saturated color (linear rgba) of a pipeline, its hue given by the name hash
+pub fn pipeline_color(name: &str) -> [f32; 4] {
+    let hue = (pipeline_name_hash(name) % 360) as f32 / 60.0;
+    let (saturation, value) = (0.8, 0.9);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r + m, g + m, b + m, 1.0]
+}
+
+/// The color of a pipeline, bound at the wireframe color slot
+struct PipelineColor {
+    _buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Models drawn over their shaded surface in a flat color of their pipeline (pipelines
+/// diagnosis): a model drawn by an unexpected pipeline stands out.
+///
+/// Same shader as the wireframe overlay, filling the triangles.
+pub struct PipelineColorOverlay {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// by pipeline name, created on the first draw of a pipeline models
+    colors: HashMap<String, PipelineColor>,
+}
+
+impl PipelineColorOverlay {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("pipeline_color.bind_group_layout"),
+        });
+        let pipeline = create_pipeline_color_pipeline(
+            device,
+            config,
+            camera_bgl,
+            &bind_group_layout,
+            depth_format,
+            sample_count,
+            PIPELINE_COLOR_DEPTH_BIAS,
+        );
+        Self {
+            pipeline,
+            bind_group_layout,
+            colors: HashMap::new(),
+        }
+    }
+
+    /// Create the colors of the models pipelines not drawn yet
+    pub fn update(&mut self, device: &wgpu::Device, models: &[Rc<Model>]) {
+        for model in models {
+            let name = model.pipeline.name();
+            if self.colors.contains_key(&name) {
+                continue;
+            }
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("pipeline_color.{}.buffer", name)),
+                contents: bytemuck::cast_slice(&[pipeline_color(&name)]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some(&format!("pipeline_color.{}.bind_group", name)),
+            });
+            self.colors.insert(
+                name,
+                PipelineColor {
+                    _buffer: buffer,
+                    bind_group,
+                },
+            );
+        }
+    }
+
+    /// Draw the models instances in their pipeline color, after `update` with the same models
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        models: &'a [Rc<Model>],
+        instances: &'a InstancesSystem<InstanceRaw>,
+        camera_bg: &'a wgpu::BindGroup,
+        selection: &DrawSelection,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        for model in models {
+            let color = match self.colors.get(&model.pipeline.name()) {
+                Some(color) => color,
+                None => continue,
+            };
+            render_pass.set_bind_group(1, &color.bind_group, &[]);
+            for (slice, count) in instances.model_slices(model) {
+                render_pass.set_vertex_buffer(1, slice);
+                for geometry in selection.geometries(model) {
+                    if !selection.includes(model, geometry) {
+                        continue;
+                    }
+                    render_pass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(geometry.index_buffer.slice(..), geometry.index_format);
+                    render_pass.draw_indexed(0..geometry.num_elements, 0, 0..count);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_name_hash_is_stable() {
+        // FNV-1a reference values
+        assert_eq!(pipeline_name_hash(""), 0x811c_9dc5);
+        assert_eq!(pipeline_name_hash("a"), 0xe40c_292c);
+        assert_eq!(pipeline_name_hash("foobar"), 0xbf9c_f968);
+
+        assert_eq!(
+            pipeline_color("colors_pipeline"),
+            pipeline_color("colors_pipeline")
+        );
+        assert_ne!(
+            pipeline_color("colors_pipeline"),
+            pipeline_color("textures_pipeline")
+        );
+        for name in ["colors_pipeline", "textures_pipeline", "light_pipeline"] {
+            let color = pipeline_color(name);
+            assert!(color.iter().all(|c| (0.0..=1.0).contains(c)));
+            assert_eq!(color[3], 1.0);
+        }
+    }
+}
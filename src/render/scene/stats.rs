@@ -0,0 +1,133 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Frames count the statistics are computed over
+const FRAMES_WINDOW: usize = 300;
+
+/// Frame times statistics over the last frames
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub frames: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub avg: Duration,
+    /// frame time exceeded by the 1% slowest frames (the "1% low" frame rate)
+    pub low_1_percent: Duration,
+}
+
+/// Rolling window of frame durations
+pub struct FrameTimes {
+    durations: VecDeque<Duration>,
+    capacity: usize,
+}
+
+impl Default for FrameTimes {
+    fn default() -> Self {
+        Self::with_capacity(FRAMES_WINDOW)
+    }
+}
+
+impl FrameTimes {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            durations: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record a frame duration, forgetting the oldest one if the window is full
+    pub fn push(&mut self, duration: Duration) {
+        if self.durations.len() == self.capacity {
+            self.durations.pop_front();
+        }
+        self.durations.push_back(duration);
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        let frames = self.durations.len();
+        if frames == 0 {
+            return FrameStats::default();
+        }
+        let mut sorted: Vec<Duration> = self.durations.iter().copied().collect();
+        sorted.sort();
+        // index of the 99th percentile (nearest rank)
+        let percentile_index = ((frames as f64 * 0.99).ceil() as usize).clamp(1, frames) - 1;
+        FrameStats {
+            frames,
+            min: sorted[0],
+            max: sorted[frames - 1],
+            avg: sorted.iter().sum::<Duration>() / frames as u32,
+            low_1_percent: sorted[percentile_index],
+        }
+    }
+}
+
+/// Trigger of the periodic statistics summary
+pub struct StatsLog {
+    interval: Duration,
+    last: Instant,
+}
+
+impl StatsLog {
+    /// The first summary is due `interval` after `start`
+    pub fn new(interval: Duration, start: Instant) -> Self {
+        Self {
+            interval,
+            last: start,
+        }
+    }
+
+    /// Whether the interval elapsed since the last summary, restarting it if so
+    pub fn due(&mut self, now: Instant) -> bool {
+        if now.saturating_duration_since(self.last) < self.interval {
+            return false;
+        }
+        self.last = now;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(durations: &[u64]) -> FrameTimes {
+        let mut times = FrameTimes::with_capacity(durations.len());
+        durations
+            .iter()
+            .for_each(|&ms| times.push(Duration::from_millis(ms)));
+        times
+    }
+
+    #[test]
+    fn stats_of_synthetic_frames() {
+        // 99 frames of 10ms and a spike of 50ms
+        let mut durations = vec![10; 99];
+        durations.push(50);
+        let stats = millis(&durations).stats();
+        assert_eq!(stats.frames, 100);
+        assert_eq!(stats.min, Duration::from_millis(10));
+        assert_eq!(stats.max, Duration::from_millis(50));
+        assert_eq!(stats.avg, Duration::from_micros(10_400));
+        assert_eq!(stats.low_1_percent, Duration::from_millis(10));
+
+        // two spikes: the 99th percentile frame is the first of them
+        durations[0] = 40;
+        assert_eq!(
+            millis(&durations).stats().low_1_percent,
+            Duration::from_millis(40)
+        );
+    }
+
+    #[test]
+    fn window_forgets_the_oldest_frames() {
+        let mut times = millis(&[100, 1, 2]);
+        times.push(Duration::from_millis(3));
+        let stats = times.stats();
+        assert_eq!(stats.frames, 3);
+        assert_eq!(stats.max, Duration::from_millis(3));
+        assert_eq!(FrameTimes::default().stats(), FrameStats::default());
+    }
+}
@@ -1,118 +1,349 @@
 use super::{
     material::Material, mesh::MeshBuf, model::Model, pipeline::NamedPipeline, texture::Texture,
+    texture_stream::TextureStream,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use crate::render::error::StoreError;
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+/// Image file texture, as loaded: the same file gives distinct textures for different formats
+/// or sizes
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct TextureFile {
+    /// canonicalized when the file exists
+    path: PathBuf,
+    is_normal_map: bool,
+    max_dimension: u32,
+}
 
+impl TextureFile {
+    pub fn new(path: &Path, is_normal_map: bool, max_dimension: u32) -> Self {
+        Self {
+            path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            is_normal_map,
+            max_dimension,
+        }
+    }
+}
+
+/// Count of the resources resident in the store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreCounts {
+    pub textures: usize,
+    pub materials: usize,
+    pub meshes: usize,
+    pub pipelines: usize,
+    pub models: usize,
+}
+
+/// Wgpu resources cache, by resource name.
+///
+/// The collections are borrowed for the duration of a method call only (the resources are
+/// returned as `Rc` clones), so that a loader may use the store while loading its dependencies.
+/// A borrow conflict is reported as a `StoreError` rather than a panic.
 pub struct Store {
     /// wgpu textures (with sampler and view)
-    pub textures: RefCell<HashMap<String, Rc<Texture>>>,
+    textures: RefCell<HashMap<String, Rc<Texture>>>,
+    /// the textures loaded from an image file, shared by the descriptors of the same file
+    texture_files: RefCell<HashMap<TextureFile, Weak<Texture>>>,
     /// materials dereferencing to bing group
-    pub materials: RefCell<HashMap<String, Rc<dyn Material>>>,
+    materials: RefCell<HashMap<String, Rc<dyn Material>>>,
     /// meshes made of geometries
-    pub meshes: RefCell<HashMap<String, Rc<MeshBuf>>>,
+    meshes: RefCell<HashMap<String, Rc<MeshBuf>>>,
     /// render unit using a shader and able to process a model with its mesh and material
-    pub pipelines: RefCell<HashMap<String, Rc<NamedPipeline>>>,
+    pipelines: RefCell<HashMap<String, Rc<NamedPipeline>>>,
     /// an aggregation of material and geometries (via mesh)
-    pub models: RefCell<HashMap<String, Rc<Model>>>,
+    models: RefCell<HashMap<String, Rc<Model>>>,
+    /// images being decoded for the streamed textures
+    pub texture_stream: TextureStream,
+}
+
+fn read<'a, K, T>(
+    cell: &'a RefCell<HashMap<K, T>>,
+    collection: &'static str,
+) -> Result<Ref<'a, HashMap<K, T>>, StoreError> {
+    cell.try_borrow()
+        .map_err(|_| StoreError::AlreadyBorrowed { collection })
+}
+
+fn write<'a, K, T>(
+    cell: &'a RefCell<HashMap<K, T>>,
+    collection: &'static str,
+) -> Result<RefMut<'a, HashMap<K, T>>, StoreError> {
+    cell.try_borrow_mut()
+        .map_err(|_| StoreError::AlreadyBorrowed { collection })
 }
 
 impl Store {
     pub fn new() -> Self {
         Self {
             textures: RefCell::new(HashMap::new()),
+            texture_files: RefCell::new(HashMap::new()),
             materials: RefCell::new(HashMap::new()),
             meshes: RefCell::new(HashMap::new()),
             models: RefCell::new(HashMap::new()), // RefCell::new(BinaryHeap::new()),
             pipelines: RefCell::new(HashMap::new()),
+            texture_stream: TextureStream::new(),
+        }
+    }
+
+    pub fn add_pipeline(&self, pipeline: Rc<NamedPipeline>) -> Result<(), StoreError> {
+        let mut pipelines = write(&self.pipelines, "pipelines")?;
+        if pipelines.contains_key(&pipeline.name()) {
+            log::warn!("Replacing pipeline {} in store", pipeline.name());
         }
+        pipelines.insert(pipeline.as_ref().name().clone(), pipeline);
+        Ok(())
+    }
+
+    /// Add the pipeline built by `build` unless a pipeline with the same name is already stored.
+    /// The pipeline is not built at all when present: the stored one is returned.
+    pub fn add_pipeline_if_absent<S, F>(
+        &self,
+        name: S,
+        build: F,
+    ) -> Result<Rc<NamedPipeline>, StoreError>
+    where
+        S: AsRef<str>,
+        F: FnOnce() -> NamedPipeline,
+    {
+        if let Some(pipeline) = self.get_pipeline(name.as_ref())? {
+            log::debug!("Pipeline {} already in store", name.as_ref());
+            return Ok(pipeline);
+        }
+        let pipeline = Rc::new(build());
+        self.add_pipeline(pipeline.clone())?;
+        Ok(pipeline)
+    }
+
+    pub fn get_pipeline<S: AsRef<str>>(
+        &self,
+        name: S,
+    ) -> Result<Option<Rc<NamedPipeline>>, StoreError> {
+        Ok(read(&self.pipelines, "pipelines")?
+            .get(name.as_ref())
+            .cloned())
     }
 
-    pub fn add_pipeline(&self, pipeline: Rc<NamedPipeline>) {
-        self.pipelines
-            .borrow_mut()
-            .insert(pipeline.as_ref().name().clone(), pipeline);
+    pub fn add_mesh(&self, mesh: Rc<MeshBuf>) -> Result<(), StoreError> {
+        write(&self.meshes, "meshes")?.insert(mesh.as_ref().name.clone(), mesh);
+        Ok(())
     }
 
-    pub fn get_pipeline<S: AsRef<str>>(&self, name: S) -> Option<Rc<NamedPipeline>> {
-        self.pipelines
-            .borrow()
-            .get(&name.as_ref().to_string())
-            .map(|m| m.clone())
+    pub fn remove_pipeline(&self, pipeline: &str) -> Result<Option<Rc<NamedPipeline>>, StoreError> {
+        Ok(write(&self.pipelines, "pipelines")?.remove(pipeline))
     }
 
-    pub fn add_mesh(&self, mesh: Rc<MeshBuf>) {
-        self.meshes
-            .borrow_mut()
-            .insert(mesh.as_ref().name.clone(), mesh);
+    pub fn remove_mesh(&self, mesh: &str) -> Result<Option<Rc<MeshBuf>>, StoreError> {
+        Ok(write(&self.meshes, "meshes")?.remove(mesh))
     }
 
-    pub fn contains_mesh(&self, mesh: &str) -> bool {
-        self.meshes.borrow().contains_key(mesh)
+    pub fn get_mesh<S: AsRef<str>>(&self, name: S) -> Result<Option<Rc<MeshBuf>>, StoreError> {
+        Ok(read(&self.meshes, "meshes")?.get(name.as_ref()).cloned())
     }
 
-    pub fn get_mesh<S: AsRef<str>>(&self, name: S) -> Option<Rc<MeshBuf>> {
-        self.meshes
-            .borrow()
-            .get(&name.as_ref().to_string())
-            .map(|m| m.clone())
+    pub fn add_model(&self, entity: Rc<Model>) -> Result<(), StoreError> {
+        write(&self.models, "models")?.insert(entity.as_ref().name.clone(), entity);
+        Ok(())
     }
 
-    pub fn add_model(&self, entity: Rc<Model>) {
-        self.models
-            .borrow_mut()
-            .insert(entity.as_ref().name.clone(), entity);
+    pub fn remove_model(&self, model: &str) -> Result<Option<Rc<Model>>, StoreError> {
+        Ok(write(&self.models, "models")?.remove(model))
     }
 
-    pub fn contains_model(&self, model: &str) -> bool {
-        self.models.borrow().contains_key(model)
+    pub fn add_material(&self, material: Rc<dyn Material>) -> Result<(), StoreError> {
+        write(&self.materials, "materials")?.insert(material.name(), material);
+        Ok(())
     }
 
-    pub fn add_material(&self, material: Rc<dyn Material>) {
-        self.materials
-            .borrow_mut()
-            .insert(material.name(), material);
+    pub fn remove_material(&self, material: &str) -> Result<Option<Rc<dyn Material>>, StoreError> {
+        Ok(write(&self.materials, "materials")?.remove(material))
     }
 
-    pub fn contains_material(&self, material: &str) -> bool {
-        self.materials.borrow().contains_key(material)
+    pub fn get_material<S: AsRef<str>>(
+        &self,
+        name: S,
+    ) -> Result<Option<Rc<dyn Material>>, StoreError> {
+        Ok(read(&self.materials, "materials")?
+            .get(name.as_ref())
+            .cloned())
     }
 
-    pub fn get_material<S: AsRef<str>>(&self, name: S) -> Option<Rc<dyn Material>> {
-        self.materials
-            .borrow()
-            .get(&name.as_ref().to_string())
-            .map(|m| m.clone())
+    pub fn add_texture<S: AsRef<str>>(
+        &self,
+        name: S,
+        texture: Rc<Texture>,
+    ) -> Result<(), StoreError> {
+        write(&self.textures, "textures")?.insert(name.as_ref().to_string(), texture);
+        Ok(())
     }
 
-    pub fn add_texture<S: AsRef<str>>(&self, name: S, texture: Rc<Texture>) {
-        self.textures
-            .borrow_mut()
-            .insert(name.as_ref().to_string(), texture);
+    pub fn remove_texture(&self, texture: &str) -> Result<Option<Rc<Texture>>, StoreError> {
+        Ok(write(&self.textures, "textures")?.remove(texture))
     }
 
-    pub fn contains_texture(&self, texture: &str) -> bool {
-        self.textures.borrow().contains_key(texture)
+    pub fn get_texture<S: AsRef<str>>(&self, name: S) -> Result<Option<Rc<Texture>>, StoreError> {
+        Ok(read(&self.textures, "textures")?
+            .get(name.as_ref())
+            .cloned())
     }
 
-    pub fn get_texture<S: AsRef<str>>(&self, name: S) -> Option<Rc<Texture>> {
-        self.textures
-            .borrow()
-            .get(&name.as_ref().to_string())
-            .map(|m| m.clone())
+    /// Texture loaded from the file and still in use
+    pub fn get_texture_file(&self, file: &TextureFile) -> Result<Option<Rc<Texture>>, StoreError> {
+        Ok(read(&self.texture_files, "texture files")?
+            .get(file)
+            .and_then(Weak::upgrade))
     }
 
-    pub fn get_model<S: AsRef<str>>(&self, name: S) -> Option<Rc<Model>> {
-        self.models
-            .borrow()
-            .get(&name.as_ref().to_string())
-            .map(|m| m.clone())
+    /// Share the texture with the next loads of the file. The file is only weakly referenced:
+    /// the texture is freed once unloaded by all its names.
+    pub fn add_texture_file(
+        &self,
+        file: TextureFile,
+        texture: &Rc<Texture>,
+    ) -> Result<(), StoreError> {
+        let mut files = write(&self.texture_files, "texture files")?;
+        files.retain(|_, texture| texture.strong_count() > 0);
+        files.insert(file, Rc::downgrade(texture));
+        Ok(())
     }
 
-    pub fn _models(&self) -> Vec<Rc<Model>> {
-        self.models
-            .borrow()
+    /// All stored textures with their name
+    pub fn textures(&self) -> Result<Vec<(String, Rc<Texture>)>, StoreError> {
+        Ok(read(&self.textures, "textures")?
+            .iter()
+            .map(|(name, texture)| (name.clone(), texture.clone()))
+            .collect())
+    }
+
+    pub fn get_model<S: AsRef<str>>(&self, name: S) -> Result<Option<Rc<Model>>, StoreError> {
+        Ok(read(&self.models, "models")?.get(name.as_ref()).cloned())
+    }
+
+    pub fn models(&self) -> Result<Vec<Rc<Model>>, StoreError> {
+        Ok(read(&self.models, "models")?.values().cloned().collect())
+    }
+
+    /// Count of the stored resources of each kind
+    pub fn counts(&self) -> Result<StoreCounts, StoreError> {
+        Ok(StoreCounts {
+            textures: read(&self.textures, "textures")?.len(),
+            materials: read(&self.materials, "materials")?.len(),
+            meshes: read(&self.meshes, "meshes")?.len(),
+            pipelines: read(&self.pipelines, "pipelines")?.len(),
+            models: read(&self.models, "models")?.len(),
+        })
+    }
+
+    /// All stored models sorted by pipeline then mesh, the drawing order minimizing the
+    /// pipelines switches
+    pub fn models_sorted(&self) -> Result<Vec<Rc<Model>>, StoreError> {
+        let mut models = self.models()?;
+        models.sort();
+        Ok(models)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{
+        scene::{camera::CameraSystem, pipeline::create_outline_pipeline},
+        state::tests::headless_device,
+    };
+
+    use super::*;
+
+    #[test]
+    fn reentrant_access_is_an_error() {
+        let store = Store::new();
+        {
+            // a caller still iterating the models, calling back into the store
+            let _models = write(&store.models, "models").unwrap();
+            assert!(matches!(
+                store.get_model("zodiac"),
+                Err(StoreError::AlreadyBorrowed {
+                    collection: "models"
+                })
+            ));
+            assert!(matches!(
+                store.counts(),
+                Err(StoreError::AlreadyBorrowed {
+                    collection: "models"
+                })
+            ));
+            // the other collections stay available
+            assert!(store.get_texture("flat").unwrap().is_none());
+        }
+        assert_eq!(store.counts().unwrap().models, 0);
+    }
+
+    #[test]
+    fn removed_texture_is_released() {
+        let (device, queue) = match headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let store = Store::new();
+        let texture = Rc::new(Texture::flat_normal(&device, &queue).unwrap());
+        store.add_texture("flat", texture.clone()).unwrap();
+        assert!(store.get_texture("flat").unwrap().is_some());
+
+        assert!(store.remove_texture("flat").unwrap().is_some());
+        assert!(store.get_texture("flat").unwrap().is_none());
+        assert_eq!(Rc::strong_count(&texture), 1);
+    }
+
+    #[test]
+    fn models_are_sorted_by_pipeline_name() {
+        let (device, _queue) = match headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 4,
+            height: 4,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        let model = |name: &str, pipeline: &str, mesh: &str| {
+            let render_pipeline = create_outline_pipeline(
+                &device,
+                &config,
+                &camera_bgl,
+                wgpu::TextureFormat::Depth32Float,
+                1,
+            );
+            let mesh = MeshBuf {
+                name: mesh.to_string(),
+                geometries: vec![],
+                lods: vec![],
+                source_modified: None,
+            };
+            Rc::new(Model::new(
+                name.to_string(),
+                Rc::new(NamedPipeline::new(pipeline, render_pipeline, vec![])),
+                Rc::new(mesh),
+            ))
+        };
+        let store = Store::new();
+        // the mesh names are in the opposite order: the pipeline name comes first
+        store
+            .add_model(model("zodiac", "wireframe", "a_zodiac"))
+            .unwrap();
+        store.add_model(model("hull", "colored", "z_hull")).unwrap();
+
+        let names: Vec<_> = store
+            .models_sorted()
+            .unwrap()
             .iter()
-            .map(|(_name, model)| model.clone())
-            .collect()
+            .map(|model| model.pipeline.name())
+            .collect();
+        assert_eq!(names, ["colored", "wireframe"]);
     }
 }
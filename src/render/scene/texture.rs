@@ -5,11 +5,31 @@ use std::ops::Deref;
 use std::path::Path;
 
 use crate::render::error::TextureError;
+use crate::render::scene::compressed::CompressedImage;
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    /// always set for the images textures, depends on the `DepthSamplerKind` for depth textures
+    pub sampler: Option<wgpu::Sampler>,
+}
+
+/// Sampler to create along with a depth texture, according to how it is read.
+///
+/// The depth textures used to get a comparison sampler whatever their use. The scene depth
+/// texture now gets a `NonFiltering` one: its only reader is the depth debug view, whose
+/// layout binds a non filtering sampler (a comparison one fails its validation). The shadow
+/// maps keep the comparison one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthSamplerKind {
+    /// compares the depth values with a reference (shadow mapping)
+    Comparison,
+    /// reads the raw depth values (debug views), without interpolation: depth textures are not
+    /// filterable
+    NonFiltering,
+    /// the texture is only used as a depth attachment
+    #[default]
+    None,
 }
 
 impl Deref for Texture {
@@ -21,22 +41,137 @@ impl Deref for Texture {
 }
 
 impl Texture {
+    /// Load a texture from an image file.
+    ///
+    /// The image is downscaled (preserving its aspect ratio) if one of its dimensions exceeds
+    /// `max_dimension`. The `.dds` and `.ktx2` files are uploaded block compressed, their
+    /// largest mip levels being dropped instead.
     pub fn load<P: AsRef<Path>>(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         path: P,
         is_normal_map: bool,
+        max_dimension: u32,
     ) -> Result<Self> {
         // use load & texture as target ?
         log::info!("Loading texture from file {:?}", path.as_ref().to_str());
         let path_copy = path.as_ref().to_path_buf();
         let label = path_copy.to_str();
 
-        let img = image::open(path.as_ref())
-            .context(format!("cannot open texture {:?}", path.as_ref()))?;
+        if let Some(extension) = Self::compressed_extension(path.as_ref()) {
+            let bytes = std::fs::read(path.as_ref())
+                .context(format!("cannot open texture {:?}", path.as_ref()))?;
+            let image = CompressedImage::parse(&extension, &bytes)
+                .context(format!("cannot parse texture {:?}", path.as_ref()))?;
+            return Self::from_compressed(device, queue, image, label, max_dimension);
+        }
+
+        let img = Self::decode(path.as_ref(), max_dimension)?;
         Self::from_image(device, queue, &img, label, is_normal_map)
     }
 
+    /// Extension (lower case) of the compressed image files, uploaded without decoding
+    pub fn compressed_extension(path: &Path) -> Option<String> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .filter(|e| e == "dds" || e == "ktx2")
+    }
+
+    /// Open an image file, downscaled (preserving its aspect ratio) if one of its dimensions
+    /// exceeds `max_dimension`.
+    ///
+    /// Does not need the device: the streamed textures are decoded by a worker thread.
+    pub fn decode(path: &Path, max_dimension: u32) -> Result<image::DynamicImage> {
+        let mut img = image::open(path).context(format!("cannot open texture {:?}", path))?;
+        if let Some((width, height)) = Self::downscaled_dimensions(img.dimensions(), max_dimension)
+        {
+            log::info!(
+                "Downscale texture {:?} from {:?} to {:?} (max dimension is {})",
+                path,
+                img.dimensions(),
+                (width, height),
+                max_dimension
+            );
+            img = image::DynamicImage::ImageRgba8(image::imageops::resize(
+                &img,
+                width,
+                height,
+                image::imageops::FilterType::Triangle,
+            ));
+        }
+        Ok(img)
+    }
+
+    /// Texture of the image file final size (after downscaling), filled with a placeholder
+    /// color until the decoded image is written with `write_image`.
+    ///
+    /// Only the image header is read: the texture, its view and the bind groups using it are
+    /// created right away and stay valid when the image is written.
+    pub fn placeholder(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        is_normal_map: bool,
+        max_dimension: u32,
+    ) -> Result<Self> {
+        let dimensions = image::image_dimensions(path)
+            .context(format!("cannot read texture {:?} dimensions", path))?;
+        let (width, height) =
+            Self::downscaled_dimensions(dimensions, max_dimension).unwrap_or(dimensions);
+        // flat normal for the normal maps, mid gray for the colors
+        let color = if is_normal_map {
+            [128, 128, 255, 255]
+        } else {
+            [128, 128, 128, 255]
+        };
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            width,
+            height,
+            image::Rgba(color),
+        ));
+        Self::from_image(device, queue, &img, path.to_str(), is_normal_map)
+    }
+
+    /// Replace the texture content by the image, which must have the texture size
+    pub fn write_image(&self, queue: &wgpu::Queue, img: &image::DynamicImage) {
+        let rgba = img.to_rgba8();
+        let (width, height) = img.dimensions();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Compute the dimensions fitting in `max_dimension` while preserving the aspect ratio.
+    /// Return None if the dimensions already fit.
+    pub fn downscaled_dimensions(
+        (width, height): (u32, u32),
+        max_dimension: u32,
+    ) -> Option<(u32, u32)> {
+        if width <= max_dimension && height <= max_dimension {
+            return None;
+        }
+        let ratio = max_dimension as f64 / width.max(height) as f64;
+        let scale = |d: u32| ((d as f64 * ratio).round() as u32).clamp(1, max_dimension);
+        Some((scale(width), scale(height)))
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -96,48 +231,280 @@ impl Texture {
         Ok(Self {
             texture,
             view,
-            sampler,
+            sampler: Some(sampler),
         })
     }
 
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-
-    pub fn create_depth_texture(
+    /// Upload a block compressed image with its mip levels.
+    ///
+    /// The levels larger than `max_dimension` are skipped (the smallest level is kept anyway).
+    pub fn from_compressed(
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-        label: &str,
-    ) -> Self {
-        log::debug!("Creating depth texture");
+        queue: &wgpu::Queue,
+        image: CompressedImage,
+        label: Option<&str>,
+        max_dimension: u32,
+    ) -> Result<Self> {
+        let required_features = image.format.describe().required_features;
+        if !device.features().contains(required_features) {
+            return Err(TextureError::UnsupportedCompressedFormat {
+                format: image.format,
+                features: required_features,
+            }
+            .into());
+        }
+
+        let skipped = (0..image.levels.len() - 1)
+            .take_while(|level| (image.width.max(image.height) >> level) > max_dimension)
+            .count();
+        let levels = &image.levels[skipped..];
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width: (image.width >> skipped).max(1),
+            height: (image.height >> skipped).max(1),
             depth_or_array_layers: 1,
         };
-        let desc = wgpu::TextureDescriptor {
-            label: Some(label),
+        if skipped > 0 {
+            log::info!(
+                "Skip {} mip levels of texture {:?} (max dimension is {})",
+                skipped,
+                label,
+                max_dimension
+            );
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
             size,
-            mip_level_count: 1,
+            mip_level_count: levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-        };
-        let texture = device.create_texture(&desc);
+            format: image.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+
+        for (level, data) in levels.iter().enumerate() {
+            let level_size = size.mip_level_size(level as u32, false);
+            let (_, bytes_per_row) =
+                CompressedImage::level_size(image.format, level_size.width, level_size.height);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                    rows_per_image: None,
+                },
+                level_size.physical_size(image.format),
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler: Some(sampler),
+        })
+    }
+
+    /// Store name of the flat normal map used by texture materials without normal texture
+    pub const FLAT_NORMAL_NAME: &'static str = "__flat_normal";
+
+    /// A 1x1 normal map pointing along the surface normal (no perturbation)
+    pub fn flat_normal(device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([128, 128, 255, 255]),
+        ));
+        Self::from_image(device, queue, &img, Some(Self::FLAT_NORMAL_NAME), true)
+    }
+
+    /// An empty texture, drawn into by a render pass and sampled by the materials
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        log::debug!("Creating render target {} ({}x{})", label, width, height);
+        let texture = device.create_texture(&Self::render_target_descriptor(
+            label, width, height, format,
+        ));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
-            compare: Some(wgpu::CompareFunction::LessEqual),
-            lod_min_clamp: -100.0,
-            lod_max_clamp: 100.0,
             ..Default::default()
         });
 
+        Self {
+            texture,
+            view,
+            sampler: Some(sampler),
+        }
+    }
+
+    /// Texture of a render target (color or depth): drawn into, then sampled
+    pub fn render_target_descriptor(
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::TextureDescriptor<'_> {
+        wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    /// An empty square depth texture, drawn into by a light pass and compared when sampled
+    pub fn create_shadow_map(
+        device: &wgpu::Device,
+        label: &str,
+        size: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        log::debug!("Creating shadow map {} ({}x{})", label, size, size);
+        Self::create_depth(
+            device,
+            label,
+            size,
+            size,
+            format,
+            1,
+            DepthSamplerKind::Comparison,
+        )
+    }
+
+    /// Sampler of the texture.
+    ///
+    /// # Panics
+    ///
+    /// If the texture was created without sampler (see `DepthSamplerKind::None`)
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        self.sampler
+            .as_ref()
+            .expect("texture created without sampler")
+    }
+
+    /// Depth texture of the surface size, `format` and `sample_count` must be the ones of the
+    /// pipelines testing it (`WgpuState::depth_format`, `WgpuState::sample_count`)
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+        sampler_kind: DepthSamplerKind,
+    ) -> Self {
+        log::debug!("Creating depth texture");
+        Self::create_depth(
+            device,
+            label,
+            config.width,
+            config.height,
+            format,
+            sample_count,
+            sampler_kind,
+        )
+    }
+
+    /// Multisampled color target of the surface size and format, drawn into and resolved into
+    /// a single sampled view (MSAA)
+    pub fn create_multisampled_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        log::debug!("Creating multisampled target {} (x{})", label, sample_count);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            sample_count,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ..Self::render_target_descriptor(label, config.width, config.height, config.format)
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            sampler: None,
+        }
+    }
+
+    fn create_depth(
+        device: &wgpu::Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        sampler_kind: DepthSamplerKind,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            sample_count,
+            ..Self::render_target_descriptor(label, width, height, format)
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = match sampler_kind {
+            DepthSamplerKind::Comparison => Some(device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some(label),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            })),
+            DepthSamplerKind::NonFiltering => {
+                Some(device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some(label),
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: wgpu::FilterMode::Nearest,
+                    min_filter: wgpu::FilterMode::Nearest,
+                    mipmap_filter: wgpu::FilterMode::Nearest,
+                    ..Default::default()
+                }))
+            }
+            DepthSamplerKind::None => None,
+        };
+
         Self {
             texture,
             view,
@@ -150,6 +517,41 @@ impl Texture {
 pub enum TextureKind {
     Diffuse,
     Normal,
+    /// empty texture written by a render pass (mirrors, minimaps...)
+    RenderTarget {
+        width: u32,
+        height: u32,
+        format: RenderTargetFormat,
+    },
+    /// empty depth texture written by a light pass, of the pipelines depth format
+    ShadowMap {
+        size: u32,
+    },
+}
+
+/// Purpose of an offscreen render target, deciding its format from the surface one
+#[derive(Debug, Clone, Copy, Hash, PartialEq, PartialOrd, Ord, Eq, Deserialize)]
+pub enum RenderTargetFormat {
+    /// intermediate buffer of the lighting math: linear float, values above 1 are kept
+    Hdr,
+    /// intermediate 8 bits buffer, sRGB encoded like the surface
+    Ldr,
+    /// buffer presented or copied as is to the surface
+    Output,
+}
+
+impl RenderTargetFormat {
+    /// Texture format of a target with this purpose when rendering to a `surface` format
+    pub fn format(self, surface: wgpu::TextureFormat) -> wgpu::TextureFormat {
+        match self {
+            RenderTargetFormat::Hdr => wgpu::TextureFormat::Rgba16Float,
+            RenderTargetFormat::Ldr if surface.describe().srgb => {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            }
+            RenderTargetFormat::Ldr => wgpu::TextureFormat::Rgba8Unorm,
+            RenderTargetFormat::Output => surface,
+        }
+    }
 }
 
 impl From<TextureKind> for String {
@@ -157,6 +559,8 @@ impl From<TextureKind> for String {
         match tk {
             TextureKind::Diffuse => "TextureKind::Diffuse".to_string(),
             TextureKind::Normal => "TextureKind::Normal".to_string(),
+            TextureKind::RenderTarget { .. } => "TextureKind::RenderTarget".to_string(),
+            TextureKind::ShadowMap { .. } => "TextureKind::ShadowMap".to_string(),
         }
     }
 }
@@ -175,3 +579,141 @@ impl TryFrom<&str> for TextureKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::render::scene::{camera::CameraSystem, pipeline::create_outline_pipeline};
+
+    use super::*;
+
+    #[test]
+    fn downscaled_dimensions_keep_the_aspect_ratio() {
+        assert_eq!(Texture::downscaled_dimensions((1024, 512), 1024), None);
+        assert_eq!(
+            Texture::downscaled_dimensions((4096, 2048), 1024),
+            Some((1024, 512))
+        );
+        assert_eq!(
+            Texture::downscaled_dimensions((1000, 3000), 300),
+            Some((100, 300))
+        );
+        // the thin side keeps a pixel at least
+        assert_eq!(
+            Texture::downscaled_dimensions((4096, 1), 256),
+            Some((256, 1))
+        );
+    }
+
+    #[test]
+    fn render_target_formats_follow_the_surface() {
+        let srgb = wgpu::TextureFormat::Bgra8UnormSrgb;
+        let linear = wgpu::TextureFormat::Bgra8Unorm;
+        assert_eq!(
+            RenderTargetFormat::Hdr.format(srgb),
+            wgpu::TextureFormat::Rgba16Float
+        );
+        assert_eq!(
+            RenderTargetFormat::Ldr.format(srgb),
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            RenderTargetFormat::Ldr.format(linear),
+            wgpu::TextureFormat::Rgba8Unorm
+        );
+        assert_eq!(RenderTargetFormat::Output.format(srgb), srgb);
+        assert_eq!(RenderTargetFormat::Output.format(linear), linear);
+    }
+
+    #[test]
+    fn render_targets_have_the_requested_size_and_usage() {
+        let format = RenderTargetFormat::Ldr.format(wgpu::TextureFormat::Bgra8UnormSrgb);
+        let desc = Texture::render_target_descriptor("mirror", 512, 256, format);
+        assert_eq!(
+            desc.size,
+            wgpu::Extent3d {
+                width: 512,
+                height: 256,
+                depth_or_array_layers: 1,
+            }
+        );
+        assert_eq!(desc.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert!(desc.usage.contains(wgpu::TextureUsages::RENDER_ATTACHMENT));
+        assert!(desc.usage.contains(wgpu::TextureUsages::TEXTURE_BINDING));
+        assert!(!desc.usage.contains(wgpu::TextureUsages::COPY_DST));
+
+        let (device, _queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let target = Texture::create_render_target(&device, "mirror", 512, 256, format);
+        assert!(target.sampler.is_some());
+        let shadow_map =
+            Texture::create_shadow_map(&device, "shadow", 1024, wgpu::TextureFormat::Depth32Float);
+        assert!(shadow_map.sampler.is_some());
+    }
+
+    #[test]
+    fn render_target_kinds_deserialize() {
+        let kind: TextureKind =
+            ron::from_str("RenderTarget(width: 256, height: 128, format: Hdr)").unwrap();
+        assert_eq!(
+            kind,
+            TextureKind::RenderTarget {
+                width: 256,
+                height: 128,
+                format: RenderTargetFormat::Hdr,
+            }
+        );
+        let kind: TextureKind = ron::from_str("ShadowMap(size: 2048)").unwrap();
+        assert_eq!(kind, TextureKind::ShadowMap { size: 2048 });
+    }
+
+    #[test]
+    fn multisampled_scene_resolves_into_a_single_sampled_view() {
+        let (device, queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 4,
+            height: 4,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let depth_format = wgpu::TextureFormat::Depth32Float;
+        let camera_bgl = CameraSystem::bind_group_layout(&device);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create_outline_pipeline(&device, &config, &camera_bgl, depth_format, 4);
+        let msaa_target = Texture::create_multisampled_target(&device, &config, 4, "msaa");
+        let depth = Texture::create_depth_texture(
+            &device,
+            &config,
+            depth_format,
+            4,
+            "depth",
+            DepthSamplerKind::None,
+        );
+        let output = Texture::create_render_target(&device, "output", 4, 4, config.format);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &msaa_target.view,
+                    resolve_target: Some(&output.view),
+                    ops: wgpu::Operations::default(),
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(wgpu::Operations::default()),
+                    stencil_ops: None,
+                }),
+            });
+            // fails if the pipeline samples count differs from the targets one
+            render_pass.set_pipeline(&pipeline);
+        }
+        queue.submit(Some(encoder.finish()));
+        assert!(pollster::block_on(device.pop_error_scope()).is_none());
+    }
+}
@@ -1,25 +1,117 @@
 use std::ops::Deref;
 
+use wgpu::util::DeviceExt;
+
+use crate::render::{error::LimitError, state::check_limit};
+
 use super::{
-    material::{Material, MaterialKind},
+    material::{BlendMode, Material, MaterialKind},
     texture::Texture,
 };
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextureParamsUniform {
+    /// uv scroll offset
+    offset: [f32; 2],
+    alpha_cutoff: f32,
+    /// 1 if the shader derives the tangent frame (see `TextureMaterial::derive_tangents`)
+    derive_tangents: u32,
+}
+
+impl TextureParamsUniform {
+    fn new(offset: [f32; 2], blend_mode: BlendMode, derive_tangents: bool) -> Self {
+        Self {
+            offset,
+            alpha_cutoff: blend_mode.alpha_cutoff(),
+            derive_tangents: derive_tangents as u32,
+        }
+    }
+}
+
+/// Texture coordinates offset after scrolling for `time` seconds at `speed` (uv per second),
+/// wrapped in [0, 1[ (the scrolled textures are sampled with a repeat address mode)
+pub fn uv_scroll_offset(speed: [f32; 2], time: f32) -> [f32; 2] {
+    [
+        (speed[0] * time).rem_euclid(1.0),
+        (speed[1] * time).rem_euclid(1.0),
+    ]
+}
+
 #[derive(Debug)]
 pub struct TextureMaterial {
     kind: MaterialKind,
     name: String,
     bind_group: wgpu::BindGroup,
+    /// texture coordinates scrolling speed (uv per second)
+    uv_scroll: Option<[f32; 2]>,
+    blend_mode: BlendMode,
+    /// the normal map tangent frame is built from the screen space derivatives of the position
+    /// and texture coordinates, the vertices tangents are ignored (meshes with broken tangents)
+    derive_tangents: bool,
+    params_buffer: wgpu::Buffer,
 }
 
 impl TextureMaterial {
+    /// Textures (diffuse and normal), each with its sampler, bound by the fragment stage
+    pub const TEXTURES_COUNT: u32 = 2;
+
+    /// Check the material bindings against the device limits
+    pub fn check_limits(name: &str, limits: &wgpu::Limits) -> Result<(), LimitError> {
+        let resource = format!("material {}", name);
+        check_limit(
+            &resource,
+            "max_sampled_textures_per_shader_stage",
+            Self::TEXTURES_COUNT as u64,
+            limits.max_sampled_textures_per_shader_stage as u64,
+        )?;
+        check_limit(
+            &resource,
+            "max_samplers_per_shader_stage",
+            Self::TEXTURES_COUNT as u64,
+            limits.max_samplers_per_shader_stage as u64,
+        )
+    }
+
     // create a new textured material using loaded textures
     pub(crate) fn new<S: AsRef<str>>(
         device: &wgpu::Device,
         name: S,
         diffuse_texture: &Texture,
         normal_texture: &Texture,
+        uv_scroll: Option<[f32; 2]>,
+        blend_mode: BlendMode,
+        derive_tangents: bool,
     ) -> Self {
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} material params uniform buffer", name.as_ref())),
+            contents: bytemuck::cast_slice(&[TextureParamsUniform::new(
+                [0.0; 2],
+                blend_mode,
+                derive_tangents,
+            )]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // the textures samplers clamp to edge, scrolling needs to wrap the coordinates
+        let repeat_sampler = uv_scroll.map(|_| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some(&format!("{} material repeat sampler", name.as_ref())),
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            })
+        });
+        let diffuse_sampler = repeat_sampler
+            .as_ref()
+            .unwrap_or_else(|| diffuse_texture.sampler());
+        let normal_sampler = repeat_sampler
+            .as_ref()
+            .unwrap_or_else(|| normal_texture.sampler());
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &Self::bind_group_layout(device),
             entries: &[
@@ -29,7 +121,7 @@ impl TextureMaterial {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(diffuse_sampler),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -37,7 +129,11 @@ impl TextureMaterial {
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(normal_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: params_buffer.as_entire_binding(),
                 },
             ],
             label: Some(&name.as_ref().to_string()),
@@ -47,6 +143,10 @@ impl TextureMaterial {
             kind: MaterialKind::Texture,
             name: name.as_ref().to_string(),
             bind_group,
+            uv_scroll,
+            blend_mode,
+            derive_tangents,
+            params_buffer,
         }
     }
 
@@ -85,6 +185,16 @@ impl TextureMaterial {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("texture material bind group layout"),
         })
@@ -107,4 +217,61 @@ impl Material for TextureMaterial {
     fn name(&self) -> String {
         self.name.clone()
     }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn animate(&self, queue: &wgpu::Queue, time: f32) {
+        if let Some(speed) = self.uv_scroll {
+            let uniform = TextureParamsUniform::new(
+                uv_scroll_offset(speed, time),
+                self.blend_mode,
+                self.derive_tangents,
+            );
+            queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_offset(actual: [f32; 2], expected: [f32; 2]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} is not {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn uv_scroll_offset_wraps_in_the_unit_square() {
+        assert_offset(uv_scroll_offset([0.1, 0.0], 0.0), [0.0, 0.0]);
+        assert_offset(uv_scroll_offset([0.1, 0.25], 2.0), [0.2, 0.5]);
+        // a full turn and a quarter
+        assert_offset(uv_scroll_offset([0.5, 0.0], 2.5), [0.25, 0.0]);
+        // backward scrolling stays positive
+        assert_offset(uv_scroll_offset([-0.1, 0.0], 2.0), [0.8, 0.0]);
+    }
+
+    #[test]
+    fn derive_tangents_flag_reaches_the_shader_params() {
+        // `TextureParams` of the texture shader: uv_offset, alpha_cutoff, derive_tangents
+        assert!(include_str!("../shaders/texture.wgsl").contains("derive_tangents: u32;"));
+        let words = |uniform: TextureParamsUniform| -> [u32; 4] { bytemuck::cast(uniform) };
+
+        let derived = words(TextureParamsUniform::new(
+            [0.0; 2],
+            BlendMode::Mask(0.5),
+            true,
+        ));
+        assert_eq!(derived[3], 1);
+        assert_eq!(f32::from_bits(derived[2]), 0.5);
+        let vertices_tangents = words(TextureParamsUniform::new(
+            [0.0; 2],
+            BlendMode::Opaque,
+            false,
+        ));
+        assert_eq!(vertices_tangents[3], 0);
+    }
 }
@@ -0,0 +1,130 @@
+use std::{
+    cell::Cell,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+use anyhow::Result;
+
+use super::{store::Store, texture::Texture};
+
+/// Image decoded by a worker thread, to be written into the named stored texture
+struct DecodedImage {
+    name: String,
+    image: Result<image::DynamicImage>,
+}
+
+/// Textures decoded in the background.
+///
+/// A streamed texture is stored as a placeholder of its final size (see
+/// `Texture::placeholder`) while a thread decodes its image. The decoded images are written
+/// into the placeholders by `upload_decoded`, called once per frame: the textures keep their
+/// view, so the materials bind groups created with the placeholders show the images without
+/// being rebuilt. Replacing a stored texture by another one would require rebuilding the
+/// materials (and the models holding them), as the bind groups capture the texture views.
+pub struct TextureStream {
+    sender: Sender<DecodedImage>,
+    receiver: Receiver<DecodedImage>,
+    /// images being decoded
+    pending: Cell<usize>,
+}
+
+impl TextureStream {
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver,
+            pending: Cell::new(0),
+        }
+    }
+
+    /// Decode the image file in a new thread, for the stored texture `name`
+    pub fn spawn(&self, name: &str, path: PathBuf, max_dimension: u32) {
+        let sender = self.sender.clone();
+        let name = name.to_string();
+        self.pending.set(self.pending.get() + 1);
+        std::thread::spawn(move || {
+            let image = Texture::decode(&path, max_dimension);
+            // the receiver is gone if the app closed meanwhile
+            sender.send(DecodedImage { name, image }).ok();
+        });
+    }
+
+    /// Count of the images still being decoded
+    pub fn pending(&self) -> usize {
+        self.pending.get()
+    }
+
+    /// Write the images decoded since the last call into their stored textures.
+    /// Return the names of the updated textures.
+    pub fn upload_decoded(&self, store: &Store, queue: &wgpu::Queue) -> Vec<String> {
+        let mut uploaded = vec![];
+        while let Ok(DecodedImage { name, image }) = self.receiver.try_recv() {
+            self.pending.set(self.pending.get() - 1);
+            match (image, store.get_texture(&name)) {
+                (Ok(image), Ok(Some(texture))) => {
+                    log::info!("Streamed texture {} decoded", name);
+                    texture.write_image(queue, &image);
+                    uploaded.push(name);
+                }
+                (Err(e), _) => log::error!("Cannot decode streamed texture {}: {:?}", name, e),
+                (_, Ok(None)) => log::warn!("Streamed texture {} is not in the store", name),
+                (_, Err(e)) => log::error!("Cannot upload streamed texture {}: {}", name, e),
+            }
+        }
+        if !uploaded.is_empty() && self.pending() == 0 {
+            log::info!("All the streamed textures are decoded");
+        }
+        uploaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Upload the decoded images until none is pending, the names of the updated textures
+    fn upload_all(stream: &TextureStream, store: &Store, queue: &wgpu::Queue) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut uploaded = vec![];
+        while stream.pending() > 0 {
+            assert!(Instant::now() < deadline, "the images are not decoded");
+            uploaded.extend(stream.upload_decoded(store, queue));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        uploaded
+    }
+
+    #[test]
+    fn placeholder_is_written_once_decoded() {
+        let (device, queue) = match crate::render::state::tests::headless_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let directory = std::env::temp_dir().join(format!("pomarin_stream_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("red.png");
+        image::RgbaImage::from_pixel(8, 4, image::Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+
+        let store = Store::new();
+        let placeholder =
+            Rc::new(Texture::placeholder(&device, &queue, &path, false, 1024).unwrap());
+        store.add_texture("red", placeholder.clone()).unwrap();
+        let stream = TextureStream::new();
+        stream.spawn("red", path, 1024);
+        stream.spawn("missing", directory.join("missing.png"), 1024);
+        assert_eq!(stream.pending(), 2);
+
+        // the placeholder stays stored: the materials using it show the image
+        assert_eq!(upload_all(&stream, &store, &queue), ["red"]);
+        let stored = store.get_texture("red").unwrap().unwrap();
+        assert!(Rc::ptr_eq(&stored, &placeholder));
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,210 @@
+use wgpu::util::DeviceExt;
+
+use crate::app::config::{LineWidth, LinesConfig};
+
+use super::{debug_normals::DebugLineVertex, pipeline::create_thick_line_pipeline};
+
+/// Corners of the quad of a segment, two triangles: (position along the segment, side)
+const QUAD_CORNERS: [[f32; 2]; 6] = [
+    [0.0, -1.0],
+    [1.0, -1.0],
+    [1.0, 1.0],
+    [0.0, -1.0],
+    [1.0, 1.0],
+    [0.0, 1.0],
+];
+
+/// A corner of a segment quad: the whole segment is given to each corner, the vertex shader
+/// widens it facing the camera
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ThickLineVertex {
+    start: [f32; 3],
+    end: [f32; 3],
+    color: [f32; 3],
+    corner: [f32; 2],
+}
+
+impl ThickLineVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ThickLineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}
+
+/// Triangles list of the quads of a lines list: 6 vertices per segment, the segment color is
+/// the color of its start. A trailing unpaired vertex is ignored.
+pub fn expand_lines(lines: &[DebugLineVertex]) -> Vec<ThickLineVertex> {
+    lines
+        .chunks_exact(2)
+        .flat_map(|segment| {
+            let (start, end) = (&segment[0], &segment[1]);
+            QUAD_CORNERS.map(|corner| ThickLineVertex {
+                start: start.position,
+                end: end.position,
+                color: start.color,
+                corner,
+            })
+        })
+        .collect()
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineParamsUniform {
+    /// surface size, pixels
+    viewport: [f32; 2],
+    width: f32,
+    /// 1 if the width is in world units
+    world_space: u32,
+}
+
+impl LineParamsUniform {
+    fn new(width: LineWidth, viewport_width: u32, viewport_height: u32) -> Self {
+        let (width, world_space) = match width {
+            LineWidth::Pixels(width) => (width, 0),
+            LineWidth::World(width) => (width, 1),
+        };
+        Self {
+            viewport: [viewport_width as f32, viewport_height as f32],
+            width,
+            world_space,
+        }
+    }
+}
+
+/// Pipeline and parameters drawing lines as antialiased quads of a configured width (see
+/// `expand_lines`), with the models instances layout
+pub struct ThickLines {
+    pipeline: wgpu::RenderPipeline,
+    width: LineWidth,
+    params_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ThickLines {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        lines_config: &LinesConfig,
+    ) -> Self {
+        let params = LineParamsUniform::new(lines_config.width, config.width, config.height);
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("thick_lines.params_buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("thick_lines.bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("thick_lines.bind_group"),
+        });
+        let pipeline = create_thick_line_pipeline(
+            device,
+            config,
+            camera_bgl,
+            &bind_group_layout,
+            depth_format,
+            sample_count,
+        );
+        Self {
+            pipeline,
+            width: lines_config.width,
+            params_buffer,
+            bind_group,
+        }
+    }
+
+    /// The pixels widths depend on the surface size
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        let params = LineParamsUniform::new(self.width, width, height);
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    /// Set the pipeline and its groups, the vertex buffers are left to the caller
+    pub fn bind<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bg: &'a wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_segment_expands_to_a_quad() {
+        let red = [1.0, 0.0, 0.0];
+        let blue = [0.0, 0.0, 1.0];
+        let lines = [
+            DebugLineVertex::new([0.0; 3], red),
+            DebugLineVertex::new([1.0, 0.0, 0.0], blue),
+            DebugLineVertex::new([0.0, 2.0, 0.0], blue),
+            DebugLineVertex::new([0.0, 2.0, 3.0], red),
+            // unpaired
+            DebugLineVertex::new([9.0; 3], red),
+        ];
+        let vertices = expand_lines(&lines);
+        assert_eq!(vertices.len(), 2 * 6);
+
+        let (first, second) = vertices.split_at(6);
+        assert!(first
+            .iter()
+            .all(|v| v.start == [0.0; 3] && v.end == [1.0, 0.0, 0.0] && v.color == red));
+        assert!(second
+            .iter()
+            .all(|v| v.end == [0.0, 2.0, 3.0] && v.color == blue));
+        let corners: Vec<[f32; 2]> = first.iter().map(|v| v.corner).collect();
+        assert_eq!(corners, QUAD_CORNERS);
+        assert!(expand_lines(&[]).is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+use std::ops::Deref;
+
+use wgpu::util::DeviceExt;
+
+use super::material::{BlendMode, Material, MaterialKind};
+
+/// Flat color, not affected by the lights
+#[derive(Debug)]
+pub struct UnlitMaterial {
+    kind: MaterialKind,
+    name: String,
+    bind_group: wgpu::BindGroup,
+    /// blended when the color is translucent
+    blend_mode: BlendMode,
+}
+
+impl Deref for UnlitMaterial {
+    type Target = wgpu::BindGroup;
+
+    fn deref(&self) -> &Self::Target {
+        &self.bind_group
+    }
+}
+
+impl Material for UnlitMaterial {
+    fn kind(&self) -> MaterialKind {
+        self.kind
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+}
+
+impl UnlitMaterial {
+    pub(crate) fn new<S: AsRef<str>>(device: &wgpu::Device, name: S, color: [f32; 4]) -> Self {
+        let name = name.as_ref().to_string();
+
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} material unlit uniform buffer", name)),
+            contents: bytemuck::cast_slice(&[color]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::bind_group_layout(device),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: material_buffer.as_entire_binding(),
+            }],
+            label: Some(&format!("{} material buffer bind group", name)),
+        });
+
+        UnlitMaterial {
+            kind: MaterialKind::Unlit,
+            name,
+            bind_group,
+            blend_mode: if color[3] < 1.0 {
+                BlendMode::Blend
+            } else {
+                BlendMode::Opaque
+            },
+        }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("unlit material bind group layout"),
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use cgmath::InnerSpace;
+use cgmath::{InnerSpace, Rotation};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -47,6 +47,16 @@ impl ModelVertex {
         }
     }
 
+    /// Rotate the vertex position and its normal, tangent and bitangent
+    pub fn rotate(&mut self, rotation: cgmath::Quaternion<f32>) {
+        self.position = rotation
+            .rotate_point(cgmath::Point3::from(self.position))
+            .into();
+        self.normal = rotation.rotate_vector(self.normal.into()).into();
+        self.tangent = rotation.rotate_vector(self.tangent.into()).into();
+        self.bitangent = rotation.rotate_vector(self.bitangent.into()).into();
+    }
+
     pub fn fill_vertices_from_model(vertices: &mut Vec<Self>, model: &tobj::Model) {
         log::debug!("mesh : {}", model.name);
         let mesh = &model.mesh;
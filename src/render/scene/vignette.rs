@@ -0,0 +1,121 @@
+use wgpu::util::DeviceExt;
+
+use crate::render::{rpass::WgpuRpass, state::WgpuState};
+
+use super::pipeline::create_vignette_pipeline;
+
+/// Distance from the frame center (1 at the corners) where the darkening starts
+const RADIUS: f32 = 0.5;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VignetteUniform {
+    strength: f32,
+    radius: f32,
+    _padding: [f32; 2],
+}
+
+impl VignetteUniform {
+    fn new(strength: f32) -> Self {
+        Self {
+            strength: strength.clamp(0.0, 1.0),
+            radius: RADIUS,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+struct VignetteResources {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Darkened frame edges, a frame pass drawn over the previous ones (see
+/// `AppRender::insert_pass`).
+///
+/// The pass is created before the window: its pipeline is built on the first frame.
+pub struct VignettePass {
+    uniform: VignetteUniform,
+    /// `None` until the first frame
+    resources: Option<VignetteResources>,
+}
+
+impl VignettePass {
+    /// `strength` is the darkening of the corners, 0 (none) to 1 (black)
+    pub fn new(strength: f32) -> Self {
+        Self {
+            uniform: VignetteUniform::new(strength),
+            resources: None,
+        }
+    }
+
+    fn create_resources(wgpu: &WgpuState, uniform: VignetteUniform) -> VignetteResources {
+        let uniform_buffer = wgpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("vignette.uniform_buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let bind_group_layout =
+            wgpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                    label: Some("vignette.bind_group_layout"),
+                });
+        let bind_group = wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("vignette.bind_group"),
+        });
+        VignetteResources {
+            pipeline: create_vignette_pipeline(&wgpu.device, &wgpu.config, &bind_group_layout),
+            bind_group,
+        }
+    }
+}
+
+impl WgpuRpass for VignettePass {
+    fn render(
+        &mut self,
+        wgpu: &WgpuState,
+        _window: &winit::window::Window,
+        output_view: &wgpu::TextureView,
+        mut encoder: wgpu::CommandEncoder,
+    ) -> wgpu::CommandEncoder {
+        let uniform = self.uniform;
+        let resources = self
+            .resources
+            .get_or_insert_with(|| Self::create_resources(wgpu, uniform));
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Vignette pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&resources.pipeline);
+            render_pass.set_bind_group(0, &resources.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        encoder
+    }
+}
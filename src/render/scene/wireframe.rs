@@ -0,0 +1,113 @@
+use std::rc::Rc;
+
+use wgpu::util::DeviceExt;
+
+use super::{
+    draw_ext::DrawSelection,
+    instance::{InstanceRaw, InstancesSystem},
+    model::Model,
+    pipeline::create_wireframe_pipeline,
+};
+
+/// Default wireframe edges color (linear rgba)
+pub const WIREFRAME_COLOR: [f32; 4] = [0.0, 1.0, 0.3, 1.0];
+
+/// Default bias pulling the edges towards the camera, so that they pass the depth test against
+/// the shaded surface they were drawn from
+pub const WIREFRAME_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: -4,
+    slope_scale: -1.0,
+    clamp: 0.0,
+};
+
+/// Edges of the models drawn over their shaded surface (mesh inspection).
+///
+/// The geometries are redrawn from their own vertex and index buffers, with a line polygon mode:
+/// the device must be created with the `POLYGON_MODE_LINE` feature.
+pub struct WireframeOverlay {
+    pipeline: wgpu::RenderPipeline,
+    _color_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl WireframeOverlay {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bgl: &wgpu::BindGroupLayout,
+        color: [f32; 4],
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_bias: wgpu::DepthBiasState,
+    ) -> Self {
+        let color_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("wireframe.color_buffer"),
+            contents: bytemuck::cast_slice(&[color]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("wireframe.bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: color_buffer.as_entire_binding(),
+            }],
+            label: Some("wireframe.bind_group"),
+        });
+        let pipeline = create_wireframe_pipeline(
+            device,
+            config,
+            camera_bgl,
+            &bind_group_layout,
+            depth_format,
+            sample_count,
+            depth_bias,
+        );
+
+        Self {
+            pipeline,
+            _color_buffer: color_buffer,
+            bind_group,
+        }
+    }
+
+    /// Draw the edges of the models instances, after the models were drawn in the same pass
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        models: &'a [Rc<Model>],
+        instances: &'a InstancesSystem<InstanceRaw>,
+        camera_bg: &'a wgpu::BindGroup,
+        selection: &DrawSelection,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        for model in models {
+            for (slice, count) in instances.model_slices(model) {
+                render_pass.set_vertex_buffer(1, slice);
+                for geometry in selection.geometries(model) {
+                    if !selection.includes(model, geometry) {
+                        continue;
+                    }
+                    render_pass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(geometry.index_buffer.slice(..), geometry.index_format);
+                    render_pass.draw_indexed(0..geometry.num_elements, 0, 0..count);
+                }
+            }
+        }
+    }
+}
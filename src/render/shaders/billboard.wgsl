@@ -0,0 +1,43 @@
+// Camera facing textured quads, the corners are computed on the cpu
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+[[group(1), binding(0)]]
+var t_billboard: texture_2d<f32>;
+[[group(1), binding(1)]]
+var s_billboard: sampler;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_billboard, s_billboard, in.tex_coords);
+    if (color.a < 0.01) {
+        discard;
+    }
+    return color;
+}
@@ -5,6 +5,8 @@
 struct CameraUniform {
     view_pos: vec4<f32>;
     view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
 };
 
 [[group(0), binding(0)]]
@@ -15,11 +17,30 @@ var<uniform> camera: CameraUniform;
 struct Light {
     position: vec3<f32>;
     color: vec3<f32>;
+    // constant, linear, quadratic
+    attenuation: vec3<f32>;
 };
 
 [[group(1), binding(0)]]
 var<uniform> light: Light;
 
+struct SpotLight {
+    position: vec3<f32>;
+    inner_cos: f32;
+    direction: vec3<f32>;
+    outer_cos: f32;
+    color: vec3<f32>;
+    range: f32;
+};
+
+struct SpotLights {
+    lights: array<SpotLight, 4>;
+    count: u32;
+};
+
+[[group(1), binding(1)]]
+var<uniform> spot_lights: SpotLights;
+
 
 // -------------
 // Vertex shader
@@ -83,14 +104,26 @@ struct MaterialColor {
     ambient: vec3<f32>;
     specular: f32;
     diffuse: vec3<f32>;
-    _pad: u32;
+    // specular exponent
+    shininess: f32;
 };
 
 [[group(2), binding(0)]]
 var<uniform> material: MaterialColor; // materials to be used
 
+// per model adjustments, neutral by default
+struct ModelOverrides {
+    tint: vec4<f32>;
+    // divides the specular exponent
+    roughness_mul: f32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> overrides: ModelOverrides;
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let shininess = material.shininess / max(overrides.roughness_mul, 0.0001);
 
     let ambient_color = material.ambient * light.color;
 
@@ -102,11 +135,36 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let view_dir = normalize(camera.view_pos.xyz - in.world_position);
     let reflect_dir = reflect(-light_dir, in.world_normal);
 
-    let specular_strength = pow(max(dot(view_dir, reflect_dir), 0.0), 32.0);
+    let specular_strength = pow(max(dot(view_dir, reflect_dir), 0.0), shininess);
     let specular_color = specular_strength * material.specular * light.color;
 
-    let result = (ambient_color + diffuse_color + specular_color);
-
-    return vec4<f32>(result, 1.0);
+    var spot_color = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i: u32 = 0u; i < spot_lights.count; i = i + 1u) {
+        let spot = spot_lights.lights[i];
+        let to_spot = spot.position - in.world_position;
+        let distance = length(to_spot);
+        let spot_dir = to_spot / distance;
+
+        // cone falloff between inner and outer angles
+        let theta = dot(-spot_dir, spot.direction);
+        let cone = clamp((theta - spot.outer_cos) / max(spot.inner_cos - spot.outer_cos, 0.0001), 0.0, 1.0);
+        // fades out linearly until range
+        let range = clamp(1.0 - distance / spot.range, 0.0, 1.0);
+        let intensity = cone * range;
+
+        let spot_diffuse = max(dot(in.world_normal, spot_dir), 0.0) * material.diffuse;
+        let spot_reflect_dir = reflect(-spot_dir, in.world_normal);
+        let spot_specular = pow(max(dot(view_dir, spot_reflect_dir), 0.0), shininess) * material.specular;
+        spot_color = spot_color + (spot_diffuse + spot_specular) * spot.color * intensity;
+    }
+
+    let light_distance = length(light.position - in.world_position);
+    let attenuation = 1.0 / (light.attenuation.x
+        + light.attenuation.y * light_distance
+        + light.attenuation.z * light_distance * light_distance);
+
+    let result = (ambient_color + (diffuse_color + specular_color) * attenuation + spot_color);
+
+    return vec4<f32>(result, 1.0) * overrides.tint;
 }
 
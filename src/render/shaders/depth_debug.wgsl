@@ -0,0 +1,47 @@
+// -------------
+// Camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+// -------------
+// Depth
+
+[[group(1), binding(0)]]
+var t_depth: texture_depth_2d;
+[[group(1), binding(1)]]
+var s_depth: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// fullscreen triangle, no vertex buffer
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    // counter clockwise to pass the back face culling
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let near = camera.clip_planes.x;
+    let far = camera.clip_planes.y;
+    let depth = textureSample(t_depth, s_depth, in.tex_coords);
+    // back to the view space distance, then normalized between the clip planes
+    let linear = near * far / (far - depth * (far - near));
+    let value = clamp((linear - near) / (far - near), 0.0, 1.0);
+    return vec4<f32>(vec3<f32>(value), 1.0);
+}
@@ -0,0 +1,38 @@
+// Depth only pass: the clip positions must be computed exactly as in the models shaders
+// (same operations order), the main pass tests for equal depths.
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> [[builtin(position)]] vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    return camera.view_proj * world_position;
+}
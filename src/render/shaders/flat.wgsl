@@ -0,0 +1,170 @@
+
+// -------------
+// Camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+// todo: do lights in view space
+// https://sotrh.github.io/learn-wgpu/intermediate/tutorial10-lighting/#the-normal-matrix
+struct Light {
+    position: vec3<f32>;
+    color: vec3<f32>;
+    // constant, linear, quadratic
+    attenuation: vec3<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> light: Light;
+
+struct SpotLight {
+    position: vec3<f32>;
+    inner_cos: f32;
+    direction: vec3<f32>;
+    outer_cos: f32;
+    color: vec3<f32>;
+    range: f32;
+};
+
+struct SpotLights {
+    lights: array<SpotLight, 4>;
+    count: u32;
+};
+
+[[group(1), binding(1)]]
+var<uniform> spot_lights: SpotLights;
+
+
+// -------------
+// Vertex shader
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] uv: vec3<f32>;
+    [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] tan: vec3<f32>;
+    [[location(4)]] bt: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+
+    [[location(9)]] normal_matrix_0: vec3<f32>;
+    [[location(10)]] normal_matrix_1: vec3<f32>;
+    [[location(11)]] normal_matrix_2: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput { 
+
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    // the vertex normals are ignored: flat shading uses the faces normals
+    var out: VertexOutput;
+    var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+// ---------------
+// Fragment shader (flat shading)
+
+struct MaterialColor {
+    ambient: vec3<f32>;
+    specular: f32;
+    diffuse: vec3<f32>;
+    // specular exponent
+    shininess: f32;
+};
+
+[[group(2), binding(0)]]
+var<uniform> material: MaterialColor; // materials to be used
+
+// per model adjustments, neutral by default
+struct ModelOverrides {
+    tint: vec4<f32>;
+    // divides the specular exponent
+    roughness_mul: f32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> overrides: ModelOverrides;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let shininess = material.shininess / max(overrides.roughness_mul, 0.0001);
+
+    // face normal from the world position derivatives, constant over the triangle. Its sign
+    // depends on the framebuffer axes: the normal is turned toward the viewer
+    var normal = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    if (dot(normal, camera.view_pos.xyz - in.world_position) < 0.0) {
+        normal = -normal;
+    }
+
+    let ambient_color = material.ambient * light.color;
+
+    let light_dir = normalize(light.position - in.world_position);
+
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
+    let diffuse_color = diffuse_strength * material.diffuse * light.color;
+
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let reflect_dir = reflect(-light_dir, normal);
+
+    let specular_strength = pow(max(dot(view_dir, reflect_dir), 0.0), shininess);
+    let specular_color = specular_strength * material.specular * light.color;
+
+    var spot_color = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i: u32 = 0u; i < spot_lights.count; i = i + 1u) {
+        let spot = spot_lights.lights[i];
+        let to_spot = spot.position - in.world_position;
+        let distance = length(to_spot);
+        let spot_dir = to_spot / distance;
+
+        // cone falloff between inner and outer angles
+        let theta = dot(-spot_dir, spot.direction);
+        let cone = clamp((theta - spot.outer_cos) / max(spot.inner_cos - spot.outer_cos, 0.0001), 0.0, 1.0);
+        // fades out linearly until range
+        let range = clamp(1.0 - distance / spot.range, 0.0, 1.0);
+        let intensity = cone * range;
+
+        let spot_diffuse = max(dot(normal, spot_dir), 0.0) * material.diffuse;
+        let spot_reflect_dir = reflect(-spot_dir, normal);
+        let spot_specular = pow(max(dot(view_dir, spot_reflect_dir), 0.0), shininess) * material.specular;
+        spot_color = spot_color + (spot_diffuse + spot_specular) * spot.color * intensity;
+    }
+
+    let light_distance = length(light.position - in.world_position);
+    let attenuation = 1.0 / (light.attenuation.x
+        + light.attenuation.y * light_distance
+        + light.attenuation.z * light_distance * light_distance);
+
+    let result = (ambient_color + (diffuse_color + specular_color) * attenuation + spot_color);
+
+    return vec4<f32>(result, 1.0) * overrides.tint;
+}
+
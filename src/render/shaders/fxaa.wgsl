@@ -0,0 +1,93 @@
+// Fast approximate anti-aliasing of the scene image (FXAA, console version)
+
+struct Fxaa {
+    // 1 / target size
+    texel_size: vec2<f32>;
+    // minimum local contrast (relative to the brightest neighbour) to process a pixel
+    edge_threshold: f32;
+    // maximum blur length along the edge (texels)
+    span_max: f32;
+    // luma weights of the rgb channels (w unused)
+    luma_weights: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> fxaa: Fxaa;
+[[group(0), binding(1)]]
+var t_scene: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_scene: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// fullscreen triangle, no vertex buffer
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    // counter clockwise to pass the back face culling
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+let REDUCE_MIN: f32 = 0.0078125;
+let REDUCE_MUL: f32 = 0.125;
+// minimum contrast, avoids processing the dark areas
+let EDGE_THRESHOLD_MIN: f32 = 0.0312;
+
+// perceived brightness: weighted channels of the gamma encoded (approximated by sqrt) color
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, fxaa.luma_weights.rgb));
+}
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(t_scene, s_scene, uv).rgb;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let uv = in.tex_coords;
+    let texel = fxaa.texel_size;
+    let rgb_m = sample(uv);
+    let luma_nw = luma(sample(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample(uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample(uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = luma(rgb_m);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // blur direction: along the edge
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(
+        dir * rcp_dir_min,
+        vec2<f32>(-fxaa.span_max),
+        vec2<f32>(fxaa.span_max),
+    ) * texel;
+
+    // sampled in uniform control flow, selected afterwards
+    let rgb_a = 0.5 * (
+        sample(uv + dir * (1.0 / 3.0 - 0.5)) +
+        sample(uv + dir * (2.0 / 3.0 - 0.5))
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        sample(uv + dir * -0.5) +
+        sample(uv + dir * 0.5)
+    );
+    let luma_b = luma(rgb_b);
+    // the wider blur went over another edge: keep the narrow one
+    let blurred = select(rgb_b, rgb_a, luma_b < luma_min || luma_b > luma_max);
+
+    let contrast = luma_max - luma_min;
+    let is_edge = contrast >= max(EDGE_THRESHOLD_MIN, luma_max * fxaa.edge_threshold);
+    return vec4<f32>(select(rgb_m, blurred, is_edge), 1.0);
+}
@@ -0,0 +1,32 @@
+// Axes indicator: the camera orientation applied to the world axes
+
+struct Gnomon {
+    // projection * camera rotation (no translation)
+    transform: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> gnomon: Gnomon;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] color: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = gnomon.transform * vec4<f32>(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
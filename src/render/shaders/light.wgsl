@@ -4,6 +4,8 @@
 struct Camera {
     view_pos: vec4<f32>;
     view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
 };
 [[group(0), binding(0)]]
 var<uniform> camera: Camera;
@@ -11,6 +13,8 @@ var<uniform> camera: Camera;
 struct Light {
     position: vec3<f32>;
     color: vec3<f32>;
+    // constant, linear, quadratic
+    attenuation: vec3<f32>;
 };
 [[group(1), binding(0)]]
 var<uniform> light: Light;
@@ -19,6 +23,15 @@ struct VertexInput {
     [[location(0)]] position: vec3<f32>;
 };
 
+// a gizmo per light, at its position
+struct GizmoInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+    [[location(9)]] color: vec4<f32>;
+};
+
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] color: vec3<f32>;
@@ -27,11 +40,17 @@ struct VertexOutput {
 [[stage(vertex)]]
 fn vs_main(
     model: VertexInput,
+    gizmo: GizmoInput,
 ) -> VertexOutput {
-    let scale = 0.25;
+    let model_matrix = mat4x4<f32>(
+        gizmo.model_matrix_0,
+        gizmo.model_matrix_1,
+        gizmo.model_matrix_2,
+        gizmo.model_matrix_3,
+    );
     var out: VertexOutput;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position * scale + light.position, 1.0);
-    out.color = light.color;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    out.color = gizmo.color.rgb;
     return out;
 }
 
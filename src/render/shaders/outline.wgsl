@@ -0,0 +1,67 @@
+// Selection outline: the back faces of the model pushed out along its normals, in a flat color
+// (inverted hull). The front faces of the model hide the hull but along its silhouette.
+
+// -------------
+// Camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+// world units the hull extends beyond the model surface
+let OUTLINE_WIDTH: f32 = 0.04;
+// linear rgba
+let OUTLINE_COLOR: vec4<f32> = vec4<f32>(1.0, 0.55, 0.0, 1.0);
+
+// -------------
+// Vertex shader
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+    [[location(9)]] normal_matrix_0: vec3<f32>;
+    [[location(10)]] normal_matrix_1: vec3<f32>;
+    [[location(11)]] normal_matrix_2: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> [[builtin(position)]] vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    let world_normal = normalize(normal_matrix * model.normal);
+    return camera.view_proj * vec4<f32>(world_position.xyz + world_normal * OUTLINE_WIDTH, 1.0);
+}
+
+// ---------------
+// Fragment shader
+
+[[stage(fragment)]]
+fn fs_main() -> [[location(0)]] vec4<f32> {
+    return OUTLINE_COLOR;
+}
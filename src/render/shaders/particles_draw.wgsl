@@ -0,0 +1,81 @@
+// -------------
+// Camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+// -------------
+// Particles
+
+struct ParticlesParams {
+    emitter_position: vec3<f32>;
+    spawn_rate: f32;
+    initial_velocity: vec3<f32>;
+    lifetime: f32;
+    gravity: vec3<f32>;
+    spread: f32;
+    count: u32;
+    size: f32;
+    aspect: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> params: ParticlesParams;
+
+struct ParticleInput {
+    [[location(0)]] position_age: vec4<f32>;
+    [[location(1)]] velocity_lifetime: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] corner: vec2<f32>;
+    [[location(1)]] life: f32;
+};
+
+// camera facing quad (two counter clockwise triangles) per particle instance
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    particle: ParticleInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let age = particle.position_age.w;
+    let lifetime = particle.velocity_lifetime.w;
+
+    var out: VertexOutput;
+    out.corner = corner;
+    out.life = 1.0 - age / max(lifetime, 0.0001);
+    // dead particles collapse to a point
+    let size = select(0.0, params.size, age < lifetime);
+    let center = camera.view_proj * vec4<f32>(particle.position_age.xyz, 1.0);
+    // offset in clip space before the perspective division: the quad shrinks with the distance
+    out.clip_position = center + vec4<f32>(corner.x * size / params.aspect, corner.y * size, 0.0, 0.0);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let distance = length(in.corner);
+    if (distance > 1.0) {
+        discard;
+    }
+    // round and fading foam
+    let alpha = (1.0 - distance) * in.life;
+    return vec4<f32>(0.9, 0.95, 1.0, alpha);
+}
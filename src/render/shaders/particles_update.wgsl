@@ -0,0 +1,86 @@
+// -------------
+// Particles
+
+struct Particle {
+    position: vec3<f32>;
+    age: f32;
+    velocity: vec3<f32>;
+    lifetime: f32;
+};
+
+struct Particles {
+    particles: array<Particle>;
+};
+
+struct ParticlesParams {
+    emitter_position: vec3<f32>;
+    spawn_rate: f32;
+    initial_velocity: vec3<f32>;
+    lifetime: f32;
+    gravity: vec3<f32>;
+    spread: f32;
+    count: u32;
+    size: f32;
+    aspect: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: ParticlesParams;
+[[group(0), binding(1)]]
+var<storage, read_write> buffer: Particles;
+
+struct Globals {
+    time: f32;
+    delta_time: f32;
+    frame: u32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> globals: Globals;
+
+// integer hash to a float in [0, 1[
+fn random(seed: u32) -> f32 {
+    var x = seed;
+    x = (x ^ 61u) ^ (x >> 16u);
+    x = x * 9u;
+    x = x ^ (x >> 4u);
+    x = x * 668265261u;
+    x = x ^ (x >> 15u);
+    return f32(x & 16777215u) / 16777216.0;
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= arrayLength(&buffer.particles)) {
+        return;
+    }
+    var particle = buffer.particles[index];
+
+    // particle `index` is emitted at (index + k * count) / spawn_rate, for k = 0, 1...
+    let emitted = globals.time * params.spawn_rate - f32(index);
+    if (index >= params.count || emitted < 0.0) {
+        // not emitted (yet)
+        particle.age = particle.lifetime;
+        buffer.particles[index] = particle;
+        return;
+    }
+    let cycle = floor(emitted / f32(params.count));
+    let birth = (f32(index) + cycle * f32(params.count)) / params.spawn_rate;
+    let age = globals.time - birth;
+
+    if (age < particle.age || particle.age >= particle.lifetime) {
+        // (re)emitted since the last update
+        let seed = index * 3u + u32(cycle) * 7919u;
+        let jitter = vec3<f32>(random(seed), random(seed + 1u), random(seed + 2u)) * 2.0 - 1.0;
+        particle.velocity = params.initial_velocity + jitter * params.spread;
+        particle.position = params.emitter_position + particle.velocity * age;
+        particle.lifetime = params.lifetime;
+        particle.age = age;
+    } else {
+        particle.velocity = particle.velocity + params.gravity * globals.delta_time;
+        particle.position = particle.position + particle.velocity * globals.delta_time;
+        particle.age = particle.age + globals.delta_time;
+    }
+    buffer.particles[index] = particle;
+}
@@ -3,6 +3,8 @@
 struct CameraUniform {
     view_pos: vec4<f32>;
     view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
 };
 
 [[group(0), binding(0)]]
@@ -11,11 +13,30 @@ var<uniform> camera: CameraUniform;
 struct Light {
     position: vec3<f32>;
     color: vec3<f32>;
+    // constant, linear, quadratic
+    attenuation: vec3<f32>;
 };
 
 [[group(1), binding(0)]]
 var<uniform> light: Light;
 
+struct SpotLight {
+    position: vec3<f32>;
+    inner_cos: f32;
+    direction: vec3<f32>;
+    outer_cos: f32;
+    color: vec3<f32>;
+    range: f32;
+};
+
+struct SpotLights {
+    lights: array<SpotLight, 4>;
+    count: u32;
+};
+
+[[group(1), binding(1)]]
+var<uniform> spot_lights: SpotLights;
+
 // -------------
 // Vertex shader
 
@@ -46,6 +67,11 @@ struct VertexOutput {
     [[location(1)]] tangent_position: vec3<f32>;
     [[location(2)]] tangent_light_position: vec3<f32>;
     [[location(3)]] tangent_view_position: vec3<f32>;
+    // world space data for the spot lights
+    [[location(5)]] world_position: vec3<f32>;
+    [[location(6)]] world_tangent: vec3<f32>;
+    [[location(7)]] world_bitangent: vec3<f32>;
+    [[location(8)]] world_normal: vec3<f32>;
 };
 
 [[stage(vertex)]]
@@ -86,6 +112,10 @@ fn vs_main(
     out.tangent_position = tangent_matrix * world_position.xyz;
     out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
     out.tangent_light_position = tangent_matrix * light.position;
+    out.world_position = world_position.xyz;
+    out.world_tangent = world_tangent;
+    out.world_bitangent = world_bitangent;
+    out.world_normal = world_normal;
     return out;
 }
 
@@ -101,34 +131,129 @@ var t_normal: texture_2d<f32>;
 [[group(2), binding(3)]]
 var s_normal: sampler;
 
+struct TextureParams {
+    uv_offset: vec2<f32>;
+    // the less opaque fragments are discarded (masked materials)
+    alpha_cutoff: f32;
+    // 1 to build the tangent frame from the screen space derivatives, the vertices tangents
+    // being ignored
+    derive_tangents: u32;
+};
+[[group(2), binding(4)]]
+var<uniform> params: TextureParams;
+
+// per model adjustments, neutral by default
+struct ModelOverrides {
+    tint: vec4<f32>;
+    // divides the specular exponent
+    roughness_mul: f32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> overrides: ModelOverrides;
+
+// Tangent frame (world space) of the surface around the fragment, from the derivatives of its
+// position and texture coordinates
+fn derived_tangent_frame(
+    normal: vec3<f32>,
+    dp1: vec3<f32>,
+    dp2: vec3<f32>,
+    duv1: vec2<f32>,
+    duv2: vec2<f32>,
+) -> mat3x3<f32> {
+    let dp2_perp = cross(dp2, normal);
+    let dp1_perp = cross(normal, dp1);
+    let tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    let bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+    // degenerate texture coordinates give a null frame: the normal map is flattened
+    let scale = inverseSqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1.0e-12));
+    return mat3x3<f32>(tangent * scale, bitangent * scale, normal);
+}
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let tex_coords = in.tex_coords + params.uv_offset;
+    let shininess = 64.0 / max(overrides.roughness_mul, 0.0001);
+    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, tex_coords);
     
     // We don't need (or want) much ambient light, so 0.1 is fine
     let ambient_strength = 0.1;
     let ambient_color = light.color * ambient_strength;
 
     // Create the lighting vectors // texture or normals
-    let object_normal: vec4<f32> = textureSample(t_normal, s_normal, in.tex_coords);
+    let object_normal: vec4<f32> = textureSample(t_normal, s_normal, tex_coords);
+    let dp1 = dpdx(in.world_position);
+    let dp2 = dpdy(in.world_position);
+    let duv1 = dpdx(tex_coords);
+    let duv2 = dpdy(tex_coords);
+    // after the samplings and derivatives, which must stay in uniform control flow
+    if (object_color.a < params.alpha_cutoff) {
+        discard;
+    }
     let tangent_normal = object_normal.xyz * 2.0 - 1.0;
     // let tangent_normal = in.tangent_normal;
 
-    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
-    let view_dir = normalize(in.tangent_view_position - in.tangent_position);
+    // the point light is computed in tangent space, or in world space with the derived frame
+    var world_normal: vec3<f32>;
+    var normal: vec3<f32>;
+    var light_dir: vec3<f32>;
+    var view_dir: vec3<f32>;
+    if (params.derive_tangents != 0u) {
+        let frame = derived_tangent_frame(normalize(in.world_normal), dp1, dp2, duv1, duv2);
+        world_normal = normalize(frame * tangent_normal);
+        normal = world_normal;
+        light_dir = normalize(light.position - in.world_position);
+        view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    } else {
+        world_normal = normalize(mat3x3<f32>(
+            in.world_tangent,
+            in.world_bitangent,
+            in.world_normal,
+        ) * tangent_normal);
+        normal = tangent_normal;
+        light_dir = normalize(in.tangent_light_position - in.tangent_position);
+        view_dir = normalize(in.tangent_view_position - in.tangent_position);
+    }
     let half_dir = normalize(view_dir + light_dir);
 
     // let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
-    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
     let diffuse_color = light.color * diffuse_strength;
 
     // let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 2.0);
-    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 64.0);
+    let specular_strength = pow(max(dot(normal, half_dir), 0.0), shininess);
     let specular_color = specular_strength * light.color;
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
+    // spot lights are computed in world space
+    let world_view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    var spot_color = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i: u32 = 0u; i < spot_lights.count; i = i + 1u) {
+        let spot = spot_lights.lights[i];
+        let to_spot = spot.position - in.world_position;
+        let distance = length(to_spot);
+        let spot_dir = to_spot / distance;
+
+        // cone falloff between inner and outer angles
+        let theta = dot(-spot_dir, spot.direction);
+        let cone = clamp((theta - spot.outer_cos) / max(spot.inner_cos - spot.outer_cos, 0.0001), 0.0, 1.0);
+        // fades out linearly until range
+        let range = clamp(1.0 - distance / spot.range, 0.0, 1.0);
+        let intensity = cone * range;
+
+        let spot_half_dir = normalize(world_view_dir + spot_dir);
+        let spot_diffuse = max(dot(world_normal, spot_dir), 0.0);
+        let spot_specular = pow(max(dot(world_normal, spot_half_dir), 0.0), shininess);
+        spot_color = spot_color + (spot_diffuse + spot_specular) * spot.color * intensity;
+    }
+
+    let light_distance = length(light.position - in.world_position);
+    let attenuation = 1.0 / (light.attenuation.x
+        + light.attenuation.y * light_distance
+        + light.attenuation.z * light_distance * light_distance);
+
+    let result = (ambient_color + (diffuse_color + specular_color) * attenuation + spot_color) * object_color.xyz;
 
-    return vec4<f32>(result, object_color.a);
+    return vec4<f32>(result, object_color.a) * overrides.tint;
 }
 
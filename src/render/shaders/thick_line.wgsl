@@ -0,0 +1,118 @@
+// -------------
+// Camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct LineParams {
+    // surface size (pixels)
+    viewport: vec2<f32>;
+    width: f32;
+    // 1 if the width is in world units, 0 if it is in pixels
+    world_space: u32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> line: LineParams;
+
+// -------------
+// Vertex shader
+
+struct VertexInput {
+    [[location(0)]] start: vec3<f32>;
+    [[location(1)]] end: vec3<f32>;
+    [[location(2)]] color: vec3<f32>;
+    // x: 0 at the segment start, 1 at its end
+    // y: -1 or 1, the side of the segment
+    [[location(3)]] corner: vec2<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec3<f32>;
+    // -1 and 1 on the quad edges, 0 on the segment
+    [[location(1)]] side: f32;
+};
+
+// Unit vector, zero for a null vector
+fn safe_normalize2(v: vec2<f32>) -> vec2<f32> {
+    let length = length(v);
+    if (length > 0.0) {
+        return v / length;
+    }
+    return vec2<f32>(0.0, 0.0);
+}
+
+fn safe_normalize3(v: vec3<f32>) -> vec3<f32> {
+    let length = length(v);
+    if (length > 0.0) {
+        return v / length;
+    }
+    return vec3<f32>(0.0, 0.0, 0.0);
+}
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let start = model_matrix * vec4<f32>(model.start, 1.0);
+    let end = model_matrix * vec4<f32>(model.end, 1.0);
+    let side = model.corner.y;
+
+    var out: VertexOutput;
+    if (line.world_space != 0u) {
+        // the quad faces the camera: widened across the segment and the view direction
+        let position = mix(start.xyz, end.xyz, model.corner.x);
+        let across = safe_normalize3(cross(end.xyz - start.xyz, camera.view_pos.xyz - position));
+        let offset = across * side * line.width * 0.5;
+        out.clip_position = camera.view_proj * vec4<f32>(position + offset, 1.0);
+    } else {
+        let clip_start = camera.view_proj * start;
+        let clip_end = camera.view_proj * end;
+        let clip = mix(clip_start, clip_end, model.corner.x);
+        // segment direction on screen (pixels)
+        let half_viewport = line.viewport * 0.5;
+        let direction = safe_normalize2(
+            clip_end.xy / clip_end.w * half_viewport - clip_start.xy / clip_start.w * half_viewport
+        );
+        // one more pixel for the antialiased edges
+        let half_width = (line.width + 1.0) * 0.5;
+        let offset = vec2<f32>(-direction.y, direction.x) * side * half_width / half_viewport;
+        // scaled by w: the offset is applied after the perspective division
+        out.clip_position = vec4<f32>(clip.xy + offset * clip.w, clip.zw);
+    }
+    out.color = model.color;
+    out.side = side;
+    return out;
+}
+
+// ---------------
+// Fragment shader
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // fades out over the last pixel of the quad edges
+    let coverage = clamp((1.0 - abs(in.side)) / max(fwidth(in.side), 0.0001), 0.0, 1.0);
+    return vec4<f32>(in.color, coverage);
+}
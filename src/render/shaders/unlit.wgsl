@@ -0,0 +1,67 @@
+// -------------
+// Camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    // near, far
+    clip_planes: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+// -------------
+// Vertex shader
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> [[builtin(position)]] vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    return camera.view_proj * world_position;
+}
+
+// ---------------
+// Fragment shader
+
+struct UnlitColor {
+    color: vec4<f32>;
+};
+
+// no light group: the material is the second group
+[[group(1), binding(0)]]
+var<uniform> material: UnlitColor;
+
+// per model adjustments, neutral by default
+struct ModelOverrides {
+    tint: vec4<f32>;
+    // no specular highlight: unused
+    roughness_mul: f32;
+};
+
+[[group(2), binding(0)]]
+var<uniform> overrides: ModelOverrides;
+
+[[stage(fragment)]]
+fn fs_main() -> [[location(0)]] vec4<f32> {
+    return material.color * overrides.tint;
+}
@@ -0,0 +1,37 @@
+// Darkening of the frame edges, blended over the previous passes
+
+struct Vignette {
+    // darkening of the corners, 0 (none) to 1 (black)
+    strength: f32;
+    // distance from the center (1 at the corners) where the darkening starts
+    radius: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> vignette: Vignette;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// fullscreen triangle, no vertex buffer
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    // counter clockwise to pass the back face culling
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // 0 at the center, 1 at the corners
+    let distance = length(in.tex_coords - vec2<f32>(0.5, 0.5)) * 1.4142135;
+    // smoothstep from the radius to the corners
+    let t = clamp((distance - vignette.radius) / max(1.0 - vignette.radius, 0.0001), 0.0, 1.0);
+    let darkening = t * t * (3.0 - 2.0 * t) * vignette.strength;
+    return vec4<f32>(0.0, 0.0, 0.0, darkening);
+}
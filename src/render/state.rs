@@ -1,12 +1,94 @@
 use winit::dpi::PhysicalSize;
 
-use crate::app::config::ResourcesConfig;
+use crate::app::config::{RenderConfig, ResourcesConfig};
 
 use super::{
     config::{assets::AssetsDescriptors, utils::load_assets},
-    scene::store::Store,
+    error::{LimitError, SurfaceError},
+    scene::{color_materials::ColorMaterials, store::Store},
 };
 
+/// Largest buffer the geometries are uploaded to. This wgpu version has no buffer size limit:
+/// the WebGPU default one is checked (`max_buffer_size` of the later versions)
+pub const MAX_BUFFER_SIZE: u64 = 1 << 28;
+
+/// Fail with an error naming the exceeded device limit, rather than deep in wgpu
+pub fn check_limit(
+    resource: &str,
+    limit: &'static str,
+    requested: u64,
+    max: u64,
+) -> Result<(), LimitError> {
+    if requested <= max {
+        return Ok(());
+    }
+    Err(LimitError::Exceeded {
+        resource: resource.to_string(),
+        limit,
+        requested,
+        max,
+    })
+}
+
+/// Surface formats tried when the surface has no preferred format, sRGB first
+const FALLBACK_SURFACE_FORMATS: [wgpu::TextureFormat; 4] = [
+    wgpu::TextureFormat::Bgra8UnormSrgb,
+    wgpu::TextureFormat::Rgba8UnormSrgb,
+    wgpu::TextureFormat::Bgra8Unorm,
+    wgpu::TextureFormat::Rgba8Unorm,
+];
+
+/// The surface preferred format, or the first fallback format the adapter can render to.
+///
+/// Some adapters (seen on Linux with GL) report no preferred format; this wgpu version cannot
+/// list the surface formats, so the fallback formats are checked against the adapter only.
+fn surface_format(
+    surface: &wgpu::Surface,
+    adapter: &wgpu::Adapter,
+) -> Result<wgpu::TextureFormat, SurfaceError> {
+    if let Some(format) = surface.get_preferred_format(adapter) {
+        return Ok(format);
+    }
+    log::warn!("The surface has no preferred format, using a fallback one");
+    let supported: Vec<wgpu::TextureFormat> = FALLBACK_SURFACE_FORMATS
+        .into_iter()
+        .filter(|format| {
+            adapter
+                .get_texture_format_features(*format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        })
+        .collect();
+    pick_surface_format(&supported).ok_or_else(|| SurfaceError::NoSupportedFormat {
+        candidates: FALLBACK_SURFACE_FORMATS.to_vec(),
+    })
+}
+
+/// Pick the surface format among the supported ones: sRGB ones first, in the fallback order
+fn pick_surface_format(supported: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+    FALLBACK_SURFACE_FORMATS
+        .into_iter()
+        .find(|format| supported.contains(format))
+        .or_else(|| supported.first().copied())
+}
+
+/// The depth format if the adapter can render to it, else `Depth32Float`
+fn depth_format(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    let supported = adapter
+        .get_texture_format_features(format)
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING);
+    if supported {
+        format
+    } else {
+        log::warn!(
+            "Depth format {:?} not supported, using Depth32Float",
+            format
+        );
+        wgpu::TextureFormat::Depth32Float
+    }
+}
+
 pub struct WgpuState {
     pub instance: wgpu::Instance,
     pub surface: wgpu::Surface,
@@ -15,25 +97,55 @@ pub struct WgpuState {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_format: wgpu::TextureFormat,
+    /// format of the depth textures and of the pipelines depth tests, the configured one if
+    /// supported by the adapter
+    pub depth_format: wgpu::TextureFormat,
+    /// samples count of the scene color and depth targets and of the pipelines drawing into
+    /// them, more than 1 with MSAA (see `AntiAliasing::sample_count`)
+    pub sample_count: u32,
     pub assets: AssetsDescriptors,
     pub settings: ResourcesConfig,
+    pub render_config: RenderConfig,
     pub store: Store,
+    /// color materials packed for the packed colored pipeline
+    pub color_materials: ColorMaterials,
 }
 
 impl WgpuState {
-    pub(crate) fn init(window: &winit::window::Window, settings: &ResourcesConfig) -> Self {
-        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
+    pub(crate) fn init(
+        window: &winit::window::Window,
+        settings: &ResourcesConfig,
+        render_config: &RenderConfig,
+    ) -> Self {
+        // fail before creating the device rather than deep in the assets loading
+        settings
+            .validate()
+            .unwrap_or_else(|e| panic!("invalid resources configuration: {}", e));
+        let backends = render_config.backends();
+        let power_preference = render_config.power_preference();
+        log::info!(
+            "Requested backends: {:?}, power preference: {:?}",
+            backends,
+            power_preference
+        );
+        let instance = wgpu::Instance::new(backends);
         let surface = unsafe { instance.create_surface(&window) };
         let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
+            power_preference,
             compatible_surface: Some(&surface),
             force_fallback_adapter: false,
         }))
-        .unwrap();
+        .expect("no adapter available for the requested backends");
 
+        // optional debug and compressed textures features, enabled when available
+        let features = adapter.features()
+            & (wgpu::Features::POLYGON_MODE_LINE
+                | wgpu::Features::TIMESTAMP_QUERY
+                | wgpu::Features::TEXTURE_COMPRESSION_BC
+                | wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR);
         let (device, queue) = pollster::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                features,
                 limits: wgpu::Limits::default(),
                 label: None,
             },
@@ -42,20 +154,28 @@ impl WgpuState {
         .unwrap();
 
         let size = window.inner_size();
-        let surface_format = surface.get_preferred_format(&adapter).unwrap();
+        let surface_format = surface_format(&surface, &adapter)
+            .unwrap_or_else(|e| panic!("cannot configure the surface: {}", e));
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Immediate,
+            present_mode: render_config.present_mode.into(),
         };
         surface.configure(&device, &config);
+        let depth_format = depth_format(&adapter, render_config.depth_format.into());
+        let sample_count = render_config.anti_aliasing.sample_count();
 
         let store = Store::new();
+        let color_materials = ColorMaterials::new(&device);
         let assets = load_assets(settings).expect("asset loading failure");
+        assets
+            .validate()
+            .iter()
+            .for_each(|e| log::warn!("Invalid assets configuration: {}", e));
 
-        Self {
+        let state = Self {
             instance,
             surface,
             config,
@@ -63,9 +183,53 @@ impl WgpuState {
             device,
             queue,
             surface_format,
+            depth_format,
+            sample_count,
             assets,
             settings: settings.to_owned(),
+            render_config: render_config.to_owned(),
             store,
+            color_materials,
+        };
+        state
+            .diagnostics()
+            .iter()
+            .for_each(|(key, value)| log::info!("GPU {}: {}", key, value));
+        state
+    }
+
+    /// Limits of the device, the resources are checked against them before their creation
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.adapter.get_info()
+    }
+
+    /// Adapter and surface details, to be reported in "nothing renders" issues
+    /// (this wgpu version does not expose the driver name nor version)
+    pub fn diagnostics(&self) -> Vec<(&'static str, String)> {
+        let info = self.adapter_info();
+        vec![
+            ("backend", format!("{:?}", info.backend)),
+            ("device", info.name),
+            ("device type", format!("{:?}", info.device_type)),
+            (
+                "pci ids",
+                format!("vendor {:#06x}, device {:#06x}", info.vendor, info.device),
+            ),
+            ("surface format", format!("{:?}", self.surface_format)),
+            ("present mode", format!("{:?}", self.config.present_mode)),
+        ]
+    }
+
+    /// Reconfigure the surface with the presentation mode.
+    /// An unsupported mode falls back to `Fifo` (this wgpu version does not report it).
+    pub(crate) fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.config.present_mode = present_mode;
+        if self.config.width > 0 && self.config.height > 0 {
+            self.surface.configure(&self.device, &self.config)
         }
     }
 
@@ -82,3 +246,47 @@ impl WgpuState {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    /// Device of the default adapter, without surface, for the tests drawing offscreen. `None`
+    /// on the machines without adapter: the calling tests are then skipped
+    pub fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::LowPower,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }));
+        let adapter = match adapter {
+            Some(adapter) => adapter,
+            None => {
+                eprintln!("no adapter available, GPU test skipped");
+                return None;
+            }
+        };
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+    }
+
+    #[test]
+    fn srgb_surface_format_is_preferred() {
+        use super::pick_surface_format;
+        use wgpu::TextureFormat::*;
+
+        assert_eq!(
+            pick_surface_format(&[Rgba8Unorm, Rgba8UnormSrgb, Bgra8UnormSrgb]),
+            Some(Bgra8UnormSrgb)
+        );
+        assert_eq!(
+            pick_surface_format(&[Bgra8Unorm, Rgba8UnormSrgb]),
+            Some(Rgba8UnormSrgb)
+        );
+        // no sRGB format: the linear fallbacks, then the first supported one
+        assert_eq!(
+            pick_surface_format(&[Rgba16Float, Bgra8Unorm]),
+            Some(Bgra8Unorm)
+        );
+        assert_eq!(pick_surface_format(&[Rgba16Float]), Some(Rgba16Float));
+        assert_eq!(pick_surface_format(&[]), None);
+    }
+}